@@ -0,0 +1,35 @@
+package main
+
+import (
+	"fmt"
+	"io"
+	"text/tabwriter"
+	"time"
+	"vaportrail/internal/db"
+	"vaportrail/internal/scheduler"
+)
+
+// maxCheckExitCode caps the exit code of -check-once, as codes above 125 have
+// special meanings to shells.
+const maxCheckExitCode = 125
+
+// checkOnce probes every target once, writes a table of the outcomes to w and
+// returns the number of failed probes, capped at maxCheckExitCode, as the exit code.
+func checkOnce(w io.Writer, sched *scheduler.Scheduler, targets []db.Target) int {
+	results := sched.CheckOnce(targets)
+
+	tw := tabwriter.NewWriter(w, 0, 0, 2, ' ', 0)
+	fmt.Fprintln(tw, "NAME\tTYPE\tADDRESS\tSTATUS\tLATENCY")
+	failures := 0
+	for _, c := range results {
+		status, latency := "ok", time.Duration(c.Result.Latency).Round(time.Microsecond).String()
+		if c.Err != nil {
+			failures++
+			status, latency = "FAIL: "+c.Err.Error(), "-"
+		}
+		fmt.Fprintf(tw, "%s\t%s\t%s\t%s\t%s\n", c.Target.Name, c.Target.ProbeType, c.Target.Address, status, latency)
+	}
+	tw.Flush()
+	fmt.Fprintf(w, "%d of %d targets failed\n", failures, len(results))
+	return min(failures, maxCheckExitCode)
+}
@@ -1,10 +1,12 @@
 package main
 
 import (
+	"context"
 	"log"
 	"os"
 	"os/signal"
 	"syscall"
+	"time"
 	"vaportrail/internal/config"
 	"vaportrail/internal/db"
 	"vaportrail/internal/scheduler"
@@ -13,10 +15,21 @@ import (
 
 func main() {
 	cfg := config.Load()
-	log.Printf("Starting VaporTrail on port %d...", cfg.HTTPPort)
-	log.Printf("Using database at %s", cfg.DBPath)
+	if err := cfg.Validate(); err != nil {
+		log.Fatalf("Invalid configuration: %v", err)
+	}
+	if !cfg.CheckOnce {
+		log.Printf("Starting VaporTrail on %s port %d...", cfg.BindAddr, cfg.HTTPPort)
+	}
+	if cfg.DataDir != "" {
+		if err := os.MkdirAll(cfg.DataDir, 0o755); err != nil {
+			log.Fatalf("Failed to create data directory: %v", err)
+		}
+	}
+	dbPath := cfg.DatabasePath()
+	log.Printf("Using database at %s", dbPath)
 
-	dbConn, err := db.New(cfg.DBPath)
+	dbConn, err := openDB(cfg, dbPath)
 	if err != nil {
 		log.Fatalf("Failed to initialize database: %v", err)
 	}
@@ -24,10 +37,35 @@ func main() {
 	defer dbConn.Close()
 
 	sched := scheduler.New(dbConn)
+	sched.MaxJitter = cfg.ProbeJitter
+	sched.SpreadStart = cfg.SpreadProbeStart
+	sched.LegacyResults = cfg.LegacyResults
+	sched.TDigestCompression = uint32(cfg.TDigestCompression)
+	sched.SourceAddress = cfg.SourceAddress
+	sched.WriteBufferSize = cfg.WriteBufferSize
+	sched.WriteBufferPolicy = cfg.WriteBufferPolicy
+	sched.SetMaintenanceIntervals(cfg.RollupInterval, cfg.RetentionInterval)
+
+	if cfg.CheckOnce {
+		os.Exit(runCheckOnce(cfg, dbConn, sched))
+	}
+
+	// Start the web server right away, so health checks answer while the targets
+	// file is applied and the scheduler starts. API requests get a 503 until then.
+	ws := web.New(cfg, dbConn, sched)
+	go func() {
+		if err := ws.Start(); err != nil {
+			log.Fatalf("Web server failed: %v", err)
+		}
+	}()
+
+	if cfg.TargetsFile != "" {
+		applyTargetsFile(cfg, dbConn)
+	}
 
 	// Add a sample target if none exist
 	targets, _ := dbConn.GetTargets()
-	if len(targets) == 0 {
+	if len(targets) == 0 && cfg.TargetsFile == "" {
 		log.Println("Adding sample target: Google")
 		_, err := dbConn.AddTarget(&db.Target{
 			Name:        "Google",
@@ -44,17 +82,78 @@ func main() {
 		log.Fatalf("Failed to start scheduler: %v", err)
 	}
 
-	// Start Web Server
-	ws := web.New(cfg, dbConn, sched)
-	go func() {
-		if err := ws.Start(); err != nil {
-			log.Fatalf("Web server failed: %v", err)
-		}
-	}()
+	ws.SetReady(true)
 
 	sigCh := make(chan os.Signal, 1)
-	signal.Notify(sigCh, os.Interrupt, syscall.SIGTERM)
-	sig := <-sigCh
-	log.Printf("Received %s, shutting down...", sig)
+	signal.Notify(sigCh, os.Interrupt, syscall.SIGTERM, syscall.SIGHUP)
+	for sig := range sigCh {
+		if sig == syscall.SIGHUP {
+			reload(cfg, sched)
+			continue
+		}
+		log.Printf("Received %s, shutting down...", sig)
+		break
+	}
+
+	// Stop taking API requests first so no targets are added mid-shutdown
+	ctx, cancel := context.WithTimeout(context.Background(), 10*time.Second)
+	defer cancel()
+	if err := ws.Shutdown(ctx); err != nil {
+		log.Printf("Web server shutdown: %v", err)
+	}
+
+	// Stop probes and flush buffered raw results before the database is closed
 	sched.Stop()
+	log.Println("Shutdown complete")
+}
+
+// openDB opens the database at dbPath with the storage mode cfg selects.
+func openDB(cfg *config.ServerConfig, dbPath string) (*db.DB, error) {
+	if cfg.Storage == config.StorageSharded {
+		log.Printf("Storing each target's results under %s", cfg.ShardDir())
+		return db.NewSharded(dbPath, cfg.ShardDir())
+	}
+	return db.New(dbPath)
+}
+
+// applyTargetsFile syncs the targets table with cfg.TargetsFile, exiting when the
+// file is invalid.
+func applyTargetsFile(cfg *config.ServerConfig, dbConn *db.DB) {
+	specs, err := config.LoadTargetsFile(cfg.TargetsFile)
+	if err != nil {
+		log.Fatalf("Invalid targets file: %v", err)
+	}
+	report, err := scheduler.SyncTargetSpecs(dbConn, specs, cfg.PruneTargets)
+	if err != nil {
+		log.Fatalf("Failed to apply targets file %s: %v", cfg.TargetsFile, err)
+	}
+	log.Printf("Applied targets file %s: %d added, %d updated, %d deleted",
+		cfg.TargetsFile, report.Added, report.Updated, report.Deleted)
+}
+
+// runCheckOnce handles -check-once: it applies the targets file, probes every
+// target once and returns the exit code.
+func runCheckOnce(cfg *config.ServerConfig, dbConn *db.DB, sched *scheduler.Scheduler) int {
+	defer dbConn.Close()
+	if cfg.TargetsFile != "" {
+		applyTargetsFile(cfg, dbConn)
+	}
+	targets, err := dbConn.GetTargets()
+	if err != nil {
+		log.Printf("Failed to load targets: %v", err)
+		return 1
+	}
+	return checkOnce(os.Stdout, sched, targets)
+}
+
+// reload handles SIGHUP: it syncs the scheduler with the targets table and
+// warns about settings that only take effect after a restart.
+func reload(cfg *config.ServerConfig, sched *scheduler.Scheduler) {
+	log.Println("Received SIGHUP, reloading...")
+	for _, name := range cfg.RestartRequiredChanges(config.Load()) {
+		log.Printf("Config %s changed; restart to apply", name)
+	}
+	if err := sched.Reload(); err != nil {
+		log.Printf("Failed to reload targets: %v", err)
+	}
 }
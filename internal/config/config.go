@@ -2,24 +2,228 @@ package config
 
 import (
 	"flag"
+	"fmt"
+	"net"
 	"os"
+	"path/filepath"
+	"slices"
 	"strconv"
+	"strings"
+	"time"
 )
 
 // ServerConfig holds the global configuration for the VaporTrail server.
 // Probe-specific configurations are stored in the database.
+//
+// On SIGHUP the server re-reads the targets table and syncs the scheduler.
+// The fields below are read once at startup and need a restart to change;
+// a reload only logs that they differ.
 type ServerConfig struct {
-	// HTTPPort is the port the web server listens on.
+	// HTTPPort is the port the web server listens on. Requires restart.
 	HTTPPort int
-	// DBPath is the file path to the SQLite database.
+	// BindAddr is the IP address the web server listens on. Defaults to 0.0.0.0,
+	// every IPv4 interface. Requires restart.
+	BindAddr string
+	// DBPath is the file path to the SQLite database. Requires restart.
 	DBPath string
+	// DataDir is the directory a relative DBPath is kept in. It is created at
+	// startup if missing. Empty uses the working directory. Requires restart.
+	DataDir string
+	// Storage selects how results are stored: "single" keeps every target's results
+	// in the database at DBPath, "sharded" keeps each target's results in its own
+	// SQLite file under ShardDir. Switching modes doesn't move existing results.
+	// Requires restart.
+	Storage string
+	// TargetsFile is an optional TOML or JSON file of targets that is reconciled
+	// against the database at startup. Requires restart.
+	TargetsFile string
+	// PruneTargets deletes targets that are not in TargetsFile. Requires restart.
+	PruneTargets bool
+	// CheckOnce probes every target once and exits instead of serving. Only set by
+	// the -check-once flag.
+	CheckOnce bool
+	// AuthToken, when set, must be sent as "Authorization: Bearer <token>" on
+	// POST, PUT and DELETE requests. Only read from VAPORTRAIL_AUTH_TOKEN, so it
+	// doesn't show up in the process list. Requires restart.
+	AuthToken string
+	// CORSOrigins lists the origins, such as "https://grafana.example.com", that
+	// browsers may call the API from. Empty sends no CORS headers, so only
+	// same-origin pages can use the API. Requires restart.
+	CORSOrigins []string
+	// RollupInterval is how often raw results are rolled up into aggregated
+	// windows. Defaults to 10s. Requires restart.
+	RollupInterval time.Duration
+	// RetentionInterval is how often expired results are deleted. Defaults to 1h.
+	// Requires restart.
+	RetentionInterval time.Duration
+	// ProbeJitter is the longest random delay before a target's first probe, which
+	// spreads targets' probes apart. Zero starts every target immediately.
+	// Defaults to 100ms. Requires restart.
+	ProbeJitter time.Duration
+	// SpreadProbeStart delays each target's first probe by a random part of its
+	// interval instead of ProbeJitter, so a restart doesn't probe every target at
+	// once. Off by default. Requires restart.
+	SpreadProbeStart bool
+	// LegacyResults also writes the finest rollup window's min, avg, max, p50 and
+	// p99 to the legacy results table, for dashboards that still query it. Off by
+	// default. Requires restart.
+	LegacyResults bool
+	// MaxTargets caps how many targets the API will create, so a runaway client
+	// can't start an unbounded number of probe loops. Zero disables the limit.
+	// Defaults to 10000. Requires restart.
+	MaxTargets int
+	// WriteBufferSize is how many raw results the batch writer holds before
+	// WriteBufferPolicy applies. Defaults to 500. Requires restart.
+	WriteBufferSize int
+	// WriteBufferPolicy is what happens when the write buffer is full: "flush"
+	// writes it out immediately, "drop_oldest" discards the oldest results and
+	// counts them, so memory stays bounded when writes stall. Defaults to "flush".
+	// Requires restart.
+	WriteBufferPolicy string
+	// SourceAddress is the local IP address probes are sent from when their target
+	// doesn't set one, for hosts with several interfaces. Empty lets the system
+	// pick. Requires restart.
+	SourceAddress string
+	// TDigestCompression is the compression of rollups whose retention policy
+	// doesn't set one, between 10 and 1000. Higher values keep more centroids per
+	// digest: more accurate tails, more space. Defaults to 100. Requires restart.
+	TDigestCompression int
+}
+
+// RestartRequiredChanges returns the names of settings that differ between c and
+// next but can only take effect after a restart.
+func (c *ServerConfig) RestartRequiredChanges(next *ServerConfig) []string {
+	var changed []string
+	if c.HTTPPort != next.HTTPPort {
+		changed = append(changed, "HTTPPort")
+	}
+	if c.BindAddr != next.BindAddr {
+		changed = append(changed, "BindAddr")
+	}
+	if c.DBPath != next.DBPath {
+		changed = append(changed, "DBPath")
+	}
+	if c.DataDir != next.DataDir {
+		changed = append(changed, "DataDir")
+	}
+	if c.Storage != next.Storage {
+		changed = append(changed, "Storage")
+	}
+	if c.TargetsFile != next.TargetsFile {
+		changed = append(changed, "TargetsFile")
+	}
+	if c.PruneTargets != next.PruneTargets {
+		changed = append(changed, "PruneTargets")
+	}
+	if c.AuthToken != next.AuthToken {
+		changed = append(changed, "AuthToken")
+	}
+	if !slices.Equal(c.CORSOrigins, next.CORSOrigins) {
+		changed = append(changed, "CORSOrigins")
+	}
+	if c.RollupInterval != next.RollupInterval {
+		changed = append(changed, "RollupInterval")
+	}
+	if c.RetentionInterval != next.RetentionInterval {
+		changed = append(changed, "RetentionInterval")
+	}
+	if c.ProbeJitter != next.ProbeJitter {
+		changed = append(changed, "ProbeJitter")
+	}
+	if c.SpreadProbeStart != next.SpreadProbeStart {
+		changed = append(changed, "SpreadProbeStart")
+	}
+	if c.LegacyResults != next.LegacyResults {
+		changed = append(changed, "LegacyResults")
+	}
+	if c.MaxTargets != next.MaxTargets {
+		changed = append(changed, "MaxTargets")
+	}
+	if c.WriteBufferSize != next.WriteBufferSize {
+		changed = append(changed, "WriteBufferSize")
+	}
+	if c.WriteBufferPolicy != next.WriteBufferPolicy {
+		changed = append(changed, "WriteBufferPolicy")
+	}
+	if c.SourceAddress != next.SourceAddress {
+		changed = append(changed, "SourceAddress")
+	}
+	if c.TDigestCompression != next.TDigestCompression {
+		changed = append(changed, "TDigestCompression")
+	}
+	return changed
+}
+
+// Validate reports settings that can't be used. Unlike the other environment
+// variables, a bad bind address isn't ignored, as falling back to listening on
+// every interface could expose the server.
+func (c *ServerConfig) Validate() error {
+	if net.ParseIP(c.BindAddr) == nil {
+		return fmt.Errorf("invalid bind address %q: expected an IP address such as 127.0.0.1 or ::1", c.BindAddr)
+	}
+	// Only SQLite is supported. Without this check a connection string would be
+	// opened as a SQLite file of that name, silently splitting the data per instance.
+	if scheme, _, ok := strings.Cut(c.DBPath, "://"); ok && isServerDBScheme(scheme) {
+		return fmt.Errorf("unsupported database %q: only SQLite file paths are supported", scheme+"://...")
+	}
+	if c.Storage != "" && c.Storage != StorageSingle && c.Storage != StorageSharded {
+		return fmt.Errorf("unknown storage mode %q: expected %s or %s", c.Storage, StorageSingle, StorageSharded)
+	}
+	if c.WriteBufferPolicy != "" && c.WriteBufferPolicy != "flush" && c.WriteBufferPolicy != "drop_oldest" {
+		return fmt.Errorf("unknown write buffer policy %q: expected flush or drop_oldest", c.WriteBufferPolicy)
+	}
+	if c.SourceAddress != "" && net.ParseIP(c.SourceAddress) == nil {
+		return fmt.Errorf("invalid source address %q: expected an IP address", c.SourceAddress)
+	}
+	return nil
+}
+
+// Storage modes for ServerConfig.Storage.
+const (
+	// StorageSingle keeps every target's results in one SQLite database.
+	StorageSingle = "single"
+	// StorageSharded keeps each target's results in a SQLite database of its own,
+	// so a target's data can be archived or dropped as one file.
+	StorageSharded = "sharded"
+)
+
+// ShardDir returns the directory sharded storage keeps the per-target databases in.
+func (c *ServerConfig) ShardDir() string {
+	return filepath.Join(c.DataDir, "shards")
+}
+
+// DatabasePath returns the path to open the database at: DBPath, inside DataDir
+// when it is a relative file path. SQLite URIs and ":memory:" are left alone.
+func (c *ServerConfig) DatabasePath() string {
+	if c.DataDir == "" || filepath.IsAbs(c.DBPath) || c.DBPath == ":memory:" || strings.HasPrefix(c.DBPath, "file:") {
+		return c.DBPath
+	}
+	return filepath.Join(c.DataDir, c.DBPath)
+}
+
+// isServerDBScheme reports whether scheme names a database server rather than a file.
+func isServerDBScheme(scheme string) bool {
+	switch strings.ToLower(scheme) {
+	case "postgres", "postgresql", "mysql":
+		return true
+	}
+	return false
 }
 
 // DefaultConfig returns a default configuration.
 func DefaultConfig() *ServerConfig {
 	return &ServerConfig{
-		HTTPPort: 8080,
-		DBPath:   "vaportrail.db",
+		HTTPPort:           8080,
+		BindAddr:           "0.0.0.0",
+		DBPath:             "vaportrail.db",
+		Storage:            StorageSingle,
+		RollupInterval:     10 * time.Second,
+		RetentionInterval:  time.Hour,
+		ProbeJitter:        100 * time.Millisecond,
+		MaxTargets:         10000,
+		WriteBufferSize:    500,
+		WriteBufferPolicy:  "flush",
+		TDigestCompression: 100,
 	}
 }
 
@@ -37,23 +241,123 @@ func Load() *ServerConfig {
 		}
 	}
 
+	if bindAddr := os.Getenv("VAPORTRAIL_BIND_ADDR"); bindAddr != "" {
+		cfg.BindAddr = bindAddr
+	}
+
 	if dbPath := os.Getenv("VAPORTRAIL_DB_PATH"); dbPath != "" {
 		cfg.DBPath = dbPath
 	}
 
+	if dataDir := os.Getenv("VAPORTRAIL_DATA_DIR"); dataDir != "" {
+		cfg.DataDir = dataDir
+	}
+
+	if storage := os.Getenv("VAPORTRAIL_STORAGE"); storage != "" {
+		cfg.Storage = strings.ToLower(storage)
+	}
+
+	if targetsFile := os.Getenv("VAPORTRAIL_TARGETS_FILE"); targetsFile != "" {
+		cfg.TargetsFile = targetsFile
+	}
+
+	if pruneStr := os.Getenv("VAPORTRAIL_PRUNE_TARGETS"); pruneStr != "" {
+		if prune, err := strconv.ParseBool(pruneStr); err == nil {
+			cfg.PruneTargets = prune
+		}
+	}
+
+	cfg.AuthToken = os.Getenv("VAPORTRAIL_AUTH_TOKEN")
+
+	if originsStr := os.Getenv("VAPORTRAIL_CORS_ORIGINS"); originsStr != "" {
+		cfg.CORSOrigins = parseCORSOrigins(originsStr)
+	}
+
+	if rollupStr := os.Getenv("VAPORTRAIL_ROLLUP_INTERVAL"); rollupStr != "" {
+		if interval, err := time.ParseDuration(rollupStr); err == nil && interval > 0 {
+			cfg.RollupInterval = interval
+		}
+	}
+
+	if retentionStr := os.Getenv("VAPORTRAIL_RETENTION_INTERVAL"); retentionStr != "" {
+		if interval, err := time.ParseDuration(retentionStr); err == nil && interval > 0 {
+			cfg.RetentionInterval = interval
+		}
+	}
+
+	if jitterStr := os.Getenv("VAPORTRAIL_PROBE_JITTER"); jitterStr != "" {
+		if jitter, err := time.ParseDuration(jitterStr); err == nil && jitter >= 0 {
+			cfg.ProbeJitter = jitter
+		}
+	}
+
+	if spreadStr := os.Getenv("VAPORTRAIL_SPREAD_PROBE_START"); spreadStr != "" {
+		if spread, err := strconv.ParseBool(spreadStr); err == nil {
+			cfg.SpreadProbeStart = spread
+		}
+	}
+
+	if legacyStr := os.Getenv("VAPORTRAIL_LEGACY_RESULTS"); legacyStr != "" {
+		if legacy, err := strconv.ParseBool(legacyStr); err == nil {
+			cfg.LegacyResults = legacy
+		}
+	}
+
+	if maxStr := os.Getenv("VAPORTRAIL_MAX_TARGETS"); maxStr != "" {
+		if limit, err := strconv.Atoi(maxStr); err == nil && limit >= 0 {
+			cfg.MaxTargets = limit
+		}
+	}
+
+	if sizeStr := os.Getenv("VAPORTRAIL_WRITE_BUFFER_SIZE"); sizeStr != "" {
+		if size, err := strconv.Atoi(sizeStr); err == nil && size > 0 {
+			cfg.WriteBufferSize = size
+		}
+	}
+
+	if policy := os.Getenv("VAPORTRAIL_WRITE_BUFFER_POLICY"); policy != "" {
+		cfg.WriteBufferPolicy = strings.ToLower(policy)
+	}
+
+	if source := os.Getenv("VAPORTRAIL_SOURCE_ADDRESS"); source != "" {
+		cfg.SourceAddress = source
+	}
+
+	if compressionStr := os.Getenv("VAPORTRAIL_TDIGEST_COMPRESSION"); compressionStr != "" {
+		if compression, err := strconv.Atoi(compressionStr); err == nil && compression >= 10 && compression <= 1000 {
+			cfg.TDigestCompression = compression
+		}
+	}
+
 	// 3. Override with Flags
 	// We need to be careful with flags in tests to avoid "redefined" panics.
 	var portFlag int
+	var bindFlag string
 	var dbFlag string
+	var targetsFlag string
+	var pruneFlag bool
+	var checkOnceFlag bool
 
 	fs := flag.CommandLine
 
 	if fs.Lookup("port") == nil {
 		fs.IntVar(&portFlag, "port", 0, "HTTP server port (env: VAPORTRAIL_HTTP_PORT)")
 	}
+	if fs.Lookup("bind") == nil {
+		fs.StringVar(&bindFlag, "bind", "", "IP address to listen on (env: VAPORTRAIL_BIND_ADDR)")
+	}
 	if fs.Lookup("db") == nil {
 		fs.StringVar(&dbFlag, "db", "", "SQLite database path (env: VAPORTRAIL_DB_PATH)")
 	}
+	if fs.Lookup("targets") == nil {
+		fs.StringVar(&targetsFlag, "targets", "", "Targets file to reconcile at startup (env: VAPORTRAIL_TARGETS_FILE)")
+	}
+	if fs.Lookup("prune-targets") == nil {
+		fs.BoolVar(&pruneFlag, "prune-targets", false, "Delete targets not in the targets file (env: VAPORTRAIL_PRUNE_TARGETS)")
+	}
+	if fs.Lookup("check-once") == nil {
+		fs.BoolVar(&checkOnceFlag, "check-once", false, "Probe every target once, print the results and exit with the number of failures, without starting the web server or scheduler")
+	}
 
 	if !flag.Parsed() {
 		flag.Parse()
@@ -80,6 +384,19 @@ func Load() *ServerConfig {
 		}
 	}
 
+	if b := fs.Lookup("bind"); b != nil {
+		isSet := false
+		fs.Visit(func(f *flag.Flag) {
+			if f.Name == "bind" {
+				isSet = true
+			}
+		})
+
+		if isSet {
+			cfg.BindAddr = b.Value.String()
+		}
+	}
+
 	if d := fs.Lookup("db"); d != nil {
 		isSet := false
 		fs.Visit(func(f *flag.Flag) {
@@ -93,5 +410,52 @@ func Load() *ServerConfig {
 		}
 	}
 
+	if tf := fs.Lookup("targets"); tf != nil {
+		isSet := false
+		fs.Visit(func(f *flag.Flag) {
+			if f.Name == "targets" {
+				isSet = true
+			}
+		})
+
+		if isSet {
+			cfg.TargetsFile = tf.Value.String()
+		}
+	}
+
+	if pf := fs.Lookup("prune-targets"); pf != nil {
+		isSet := false
+		fs.Visit(func(f *flag.Flag) {
+			if f.Name == "prune-targets" {
+				isSet = true
+			}
+		})
+
+		if isSet {
+			if val, err := strconv.ParseBool(pf.Value.String()); err == nil {
+				cfg.PruneTargets = val
+			}
+		}
+	}
+
+	if cf := fs.Lookup("check-once"); cf != nil {
+		if val, err := strconv.ParseBool(cf.Value.String()); err == nil {
+			cfg.CheckOnce = val
+		}
+	}
+
 	return cfg
 }
+
+// parseCORSOrigins splits a comma-separated origin list, dropping blanks and any
+// trailing slash, since browsers send the Origin header without one.
+func parseCORSOrigins(s string) []string {
+	var origins []string
+	for _, o := range strings.Split(s, ",") {
+		o = strings.TrimSuffix(strings.TrimSpace(o), "/")
+		if o != "" {
+			origins = append(origins, o)
+		}
+	}
+	return origins
+}
@@ -0,0 +1,225 @@
+package config
+
+import (
+	"encoding/json"
+	"fmt"
+	"os"
+	"path/filepath"
+	"strconv"
+	"strings"
+)
+
+// TargetSpec describes a target declared in the targets file.
+// Targets are matched against the database by Name.
+type TargetSpec struct {
+	Name              string  `json:"name"`
+	Address           string  `json:"address"`
+	ProbeType         string  `json:"probe_type"`
+	ProbeConfig       string  `json:"probe_config"`       // JSON, same as the API
+	Interval          float64 `json:"interval"`           // seconds, default 1
+	Timeout           float64 `json:"timeout"`            // seconds, default 5
+	RetentionPolicies string  `json:"retention_policies"` // JSON, default policies when empty
+	MaxConcurrent     int     `json:"max_concurrent"`     // default 5
+	AddressFamily     string  `json:"address_family"`     // auto (default), ipv4 or ipv6
+	SourceAddress     string  `json:"source_address"`     // local IP to probe from, server default when empty
+}
+
+type targetsFile struct {
+	Targets []TargetSpec `json:"targets"`
+}
+
+// LoadTargetsFile reads a targets file. Files ending in .json are parsed as JSON,
+// anything else as TOML with one [[targets]] table per target:
+//
+//	[[targets]]
+//	name = "Google DNS"
+//	address = "8.8.8.8"
+//	probe_type = "dns"
+//	probe_config = '{"query_name": "example.com"}'
+//	interval = 5
+//
+// Only the subset of TOML needed for this layout is supported: [[targets]]
+// headers, comments, and string, number and boolean values.
+func LoadTargetsFile(path string) ([]TargetSpec, error) {
+	data, err := os.ReadFile(path)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read targets file: %w", err)
+	}
+
+	var specs []TargetSpec
+	if strings.EqualFold(filepath.Ext(path), ".json") {
+		var f targetsFile
+		if err := json.Unmarshal(data, &f); err != nil {
+			return nil, fmt.Errorf("%s: %w", path, err)
+		}
+		specs = f.Targets
+	} else {
+		specs, err = parseTargetsTOML(string(data))
+		if err != nil {
+			return nil, fmt.Errorf("%s: %w", path, err)
+		}
+	}
+
+	if err := validateTargetSpecs(specs); err != nil {
+		return nil, fmt.Errorf("%s: %w", path, err)
+	}
+	return specs, nil
+}
+
+func validateTargetSpecs(specs []TargetSpec) error {
+	seen := make(map[string]bool, len(specs))
+	for i, t := range specs {
+		switch {
+		case t.Name == "":
+			return fmt.Errorf("target %d: name is required", i+1)
+		case seen[t.Name]:
+			return fmt.Errorf("target %q: duplicate name", t.Name)
+		case t.Address == "":
+			return fmt.Errorf("target %q: address is required", t.Name)
+		case t.ProbeType == "":
+			return fmt.Errorf("target %q: probe_type is required", t.Name)
+		case t.Interval < 0:
+			return fmt.Errorf("target %q: interval cannot be negative", t.Name)
+		case t.Timeout < 0:
+			return fmt.Errorf("target %q: timeout cannot be negative", t.Name)
+		case t.MaxConcurrent < 0:
+			return fmt.Errorf("target %q: max_concurrent cannot be negative", t.Name)
+		}
+		seen[t.Name] = true
+	}
+	return nil
+}
+
+func parseTargetsTOML(data string) ([]TargetSpec, error) {
+	var specs []TargetSpec
+	var current *TargetSpec
+
+	for i, line := range strings.Split(data, "\n") {
+		lineNo := i + 1
+		line = strings.TrimSpace(stripTOMLComment(line))
+		if line == "" {
+			continue
+		}
+
+		if line == "[[targets]]" {
+			specs = append(specs, TargetSpec{})
+			current = &specs[len(specs)-1]
+			continue
+		}
+		if strings.HasPrefix(line, "[") {
+			return nil, fmt.Errorf("line %d: unsupported table %s (only [[targets]] is allowed)", lineNo, line)
+		}
+
+		key, raw, ok := strings.Cut(line, "=")
+		if !ok {
+			return nil, fmt.Errorf("line %d: expected key = value", lineNo)
+		}
+		if current == nil {
+			return nil, fmt.Errorf("line %d: key outside of a [[targets]] table", lineNo)
+		}
+		key = strings.TrimSpace(key)
+		value, err := parseTOMLValue(strings.TrimSpace(raw))
+		if err != nil {
+			return nil, fmt.Errorf("line %d: %s: %w", lineNo, key, err)
+		}
+		if err := current.set(key, value); err != nil {
+			return nil, fmt.Errorf("line %d: %w", lineNo, err)
+		}
+	}
+	return specs, nil
+}
+
+// stripTOMLComment removes a trailing # comment that is not inside a string.
+func stripTOMLComment(line string) string {
+	var quote byte
+	for i := 0; i < len(line); i++ {
+		c := line[i]
+		switch {
+		case quote != 0:
+			if c == '\\' && quote == '"' {
+				i++
+			} else if c == quote {
+				quote = 0
+			}
+		case c == '"' || c == '\'':
+			quote = c
+		case c == '#':
+			return line[:i]
+		}
+	}
+	return line
+}
+
+// parseTOMLValue parses a basic or literal string, a number or a boolean.
+func parseTOMLValue(raw string) (any, error) {
+	switch {
+	case raw == "":
+		return nil, fmt.Errorf("missing value")
+	case strings.HasPrefix(raw, `"`):
+		if len(raw) < 2 || !strings.HasSuffix(raw, `"`) {
+			return nil, fmt.Errorf("unterminated string")
+		}
+		return strconv.Unquote(raw)
+	case strings.HasPrefix(raw, "'"):
+		if len(raw) < 2 || !strings.HasSuffix(raw, "'") {
+			return nil, fmt.Errorf("unterminated string")
+		}
+		return raw[1 : len(raw)-1], nil
+	case raw == "true" || raw == "false":
+		return raw == "true", nil
+	}
+	f, err := strconv.ParseFloat(strings.ReplaceAll(raw, "_", ""), 64)
+	if err != nil {
+		return nil, fmt.Errorf("invalid value %s", raw)
+	}
+	return f, nil
+}
+
+func (t *TargetSpec) set(key string, value any) error {
+	str := func() (string, error) {
+		s, ok := value.(string)
+		if !ok {
+			return "", fmt.Errorf("%s must be a string", key)
+		}
+		return s, nil
+	}
+	num := func() (float64, error) {
+		f, ok := value.(float64)
+		if !ok {
+			return 0, fmt.Errorf("%s must be a number", key)
+		}
+		return f, nil
+	}
+
+	var err error
+	switch key {
+	case "name":
+		t.Name, err = str()
+	case "address":
+		t.Address, err = str()
+	case "probe_type":
+		t.ProbeType, err = str()
+	case "probe_config":
+		t.ProbeConfig, err = str()
+	case "retention_policies":
+		t.RetentionPolicies, err = str()
+	case "address_family":
+		t.AddressFamily, err = str()
+	case "source_address":
+		t.SourceAddress, err = str()
+	case "interval":
+		t.Interval, err = num()
+	case "timeout":
+		t.Timeout, err = num()
+	case "max_concurrent":
+		var f float64
+		f, err = num()
+		if err == nil && f != float64(int(f)) {
+			err = fmt.Errorf("max_concurrent must be an integer")
+		}
+		t.MaxConcurrent = int(f)
+	default:
+		err = fmt.Errorf("unknown key %q", key)
+	}
+	return err
+}
@@ -0,0 +1,107 @@
+package config
+
+import (
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+)
+
+func writeTargetsFile(t *testing.T, name, content string) string {
+	t.Helper()
+	path := filepath.Join(t.TempDir(), name)
+	if err := os.WriteFile(path, []byte(content), 0o644); err != nil {
+		t.Fatalf("Failed to write targets file: %v", err)
+	}
+	return path
+}
+
+func TestLoadTargetsFile_TOML(t *testing.T) {
+	path := writeTargetsFile(t, "targets.toml", `
+# Production checks
+[[targets]]
+name = "Google DNS"   # resolver
+address = "8.8.8.8"
+probe_type = "dns"
+probe_config = '{"query_name": "example.com", "query_type": "AAAA"}'
+interval = 5
+timeout = 2.5
+
+[[targets]]
+name = "API #1"
+address = "https://api.example.com/health"
+probe_type = "http"
+max_concurrent = 10
+retention_policies = '[{"window": 0, "retention": 86400}]'
+`)
+
+	specs, err := LoadTargetsFile(path)
+	if err != nil {
+		t.Fatalf("LoadTargetsFile failed: %v", err)
+	}
+	if len(specs) != 2 {
+		t.Fatalf("Expected 2 targets, got %d", len(specs))
+	}
+
+	dns := specs[0]
+	if dns.Name != "Google DNS" || dns.Address != "8.8.8.8" || dns.ProbeType != "dns" {
+		t.Errorf("Unexpected first target: %+v", dns)
+	}
+	if dns.ProbeConfig != `{"query_name": "example.com", "query_type": "AAAA"}` {
+		t.Errorf("Unexpected probe config: %q", dns.ProbeConfig)
+	}
+	if dns.Interval != 5 || dns.Timeout != 2.5 {
+		t.Errorf("Expected interval 5 and timeout 2.5, got %v and %v", dns.Interval, dns.Timeout)
+	}
+
+	api := specs[1]
+	if api.Name != "API #1" {
+		t.Errorf("Expected # inside a string to be kept, got name %q", api.Name)
+	}
+	if api.MaxConcurrent != 10 || api.RetentionPolicies != `[{"window": 0, "retention": 86400}]` {
+		t.Errorf("Unexpected second target: %+v", api)
+	}
+}
+
+func TestLoadTargetsFile_JSON(t *testing.T) {
+	path := writeTargetsFile(t, "targets.json", `{"targets": [
+		{"name": "Edge", "address": "edge.example.com:443", "probe_type": "tls", "interval": 30}
+	]}`)
+
+	specs, err := LoadTargetsFile(path)
+	if err != nil {
+		t.Fatalf("LoadTargetsFile failed: %v", err)
+	}
+	if len(specs) != 1 || specs[0].Name != "Edge" || specs[0].Interval != 30 {
+		t.Errorf("Unexpected targets: %+v", specs)
+	}
+}
+
+func TestLoadTargetsFile_Errors(t *testing.T) {
+	tests := []struct {
+		name    string
+		content string
+		wantErr string
+	}{
+		{"Unknown Key", "[[targets]]\nname = \"a\"\nadress = \"x\"\n", `unknown key "adress"`},
+		{"Key Outside Table", "name = \"a\"\n", "outside of a [[targets]] table"},
+		{"Wrong Type", "[[targets]]\nname = \"a\"\ninterval = \"5\"\n", "interval must be a number"},
+		{"Unterminated String", "[[targets]]\nname = \"a\n", "unterminated string"},
+		{"Unsupported Table", "[server]\n", "unsupported table"},
+		{"Missing Address", "[[targets]]\nname = \"a\"\nprobe_type = \"ping\"\n", "address is required"},
+		{"Duplicate Name", "[[targets]]\nname = \"a\"\naddress = \"x\"\nprobe_type = \"ping\"\n[[targets]]\nname = \"a\"\naddress = \"y\"\nprobe_type = \"ping\"\n", "duplicate name"},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			path := writeTargetsFile(t, "targets.toml", tt.content)
+			_, err := LoadTargetsFile(path)
+			if err == nil {
+				t.Fatal("Expected an error")
+			}
+			if !strings.Contains(err.Error(), tt.wantErr) {
+				t.Errorf("Expected error containing %q, got %v", tt.wantErr, err)
+			}
+		})
+	}
+}
@@ -0,0 +1 @@
+ALTER TABLE targets ADD COLUMN cert_expires_at DATETIME;
@@ -0,0 +1 @@
+ALTER TABLE raw_results DROP COLUMN loss;
@@ -0,0 +1 @@
+ALTER TABLE raw_results ADD COLUMN loss REAL;
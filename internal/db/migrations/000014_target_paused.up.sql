@@ -0,0 +1 @@
+ALTER TABLE targets ADD COLUMN paused BOOLEAN NOT NULL DEFAULT 0;
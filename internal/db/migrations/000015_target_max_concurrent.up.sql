@@ -0,0 +1 @@
+ALTER TABLE targets ADD COLUMN max_concurrent INTEGER NOT NULL DEFAULT 5;
@@ -0,0 +1,2 @@
+ALTER TABLE aggregated_results DROP COLUMN max_ns;
+ALTER TABLE aggregated_results DROP COLUMN min_ns;
@@ -0,0 +1,2 @@
+ALTER TABLE aggregated_results ADD COLUMN min_ns REAL;
+ALTER TABLE aggregated_results ADD COLUMN max_ns REAL;
@@ -0,0 +1 @@
+ALTER TABLE aggregated_results DROP COLUMN error_count;
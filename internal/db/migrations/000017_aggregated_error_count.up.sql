@@ -0,0 +1 @@
+ALTER TABLE aggregated_results ADD COLUMN error_count INTEGER NOT NULL DEFAULT 0;
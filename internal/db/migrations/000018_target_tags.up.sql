@@ -0,0 +1 @@
+ALTER TABLE targets ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';
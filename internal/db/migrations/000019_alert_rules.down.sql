@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_alert_rules_target;
+DROP TABLE IF EXISTS alert_rules;
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS alert_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    target_id INTEGER NOT NULL,
+    metric TEXT NOT NULL,
+    comparison TEXT NOT NULL,
+    threshold REAL NOT NULL,
+    window_seconds INTEGER NOT NULL,
+    webhook_url TEXT NOT NULL,
+    firing INTEGER NOT NULL DEFAULT 0,
+    last_window DATETIME,
+    FOREIGN KEY(target_id) REFERENCES targets(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_rules_target ON alert_rules(target_id);
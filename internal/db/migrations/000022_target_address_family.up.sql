@@ -0,0 +1 @@
+ALTER TABLE targets ADD COLUMN address_family TEXT NOT NULL DEFAULT 'auto';
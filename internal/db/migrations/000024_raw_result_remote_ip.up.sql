@@ -0,0 +1 @@
+ALTER TABLE raw_results ADD COLUMN remote_ip TEXT;
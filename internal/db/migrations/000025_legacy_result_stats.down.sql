@@ -0,0 +1,5 @@
+ALTER TABLE results DROP COLUMN p99_ns;
+ALTER TABLE results DROP COLUMN p50_ns;
+ALTER TABLE results DROP COLUMN max_ns;
+ALTER TABLE results DROP COLUMN avg_ns;
+ALTER TABLE results DROP COLUMN min_ns;
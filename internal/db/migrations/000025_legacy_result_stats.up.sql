@@ -0,0 +1,5 @@
+ALTER TABLE results ADD COLUMN min_ns REAL;
+ALTER TABLE results ADD COLUMN avg_ns REAL;
+ALTER TABLE results ADD COLUMN max_ns REAL;
+ALTER TABLE results ADD COLUMN p50_ns REAL;
+ALTER TABLE results ADD COLUMN p99_ns REAL;
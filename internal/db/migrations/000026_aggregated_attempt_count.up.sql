@@ -0,0 +1 @@
+ALTER TABLE aggregated_results ADD COLUMN attempt_count INTEGER;
@@ -0,0 +1 @@
+ALTER TABLE targets ADD COLUMN source_address TEXT NOT NULL DEFAULT '';
@@ -0,0 +1 @@
+ALTER TABLE raw_results ADD COLUMN status_code INTEGER;
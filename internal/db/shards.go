@@ -0,0 +1,300 @@
+package db
+
+import (
+	"database/sql"
+	"errors"
+	"fmt"
+	"os"
+	"path/filepath"
+	"sort"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+)
+
+// maxShardConns bounds the connection pool of each shard. A shard only serves one
+// target, so it needs far fewer connections than the main database.
+const maxShardConns = 2
+
+// shardConnMaxIdle is how long a shard connection may stay idle before it is
+// closed, so the open files don't grow with the number of targets.
+const shardConnMaxIdle = time.Minute
+
+// shardSet holds the per-target databases of sharded storage. Each shard has the
+// full schema, but only its results tables are used: targets, dashboards and alert
+// rules stay in the main database. Shards are opened on first use and kept, but
+// their connections close after shardConnMaxIdle without use.
+type shardSet struct {
+	dir   string
+	mu    sync.Mutex
+	conns map[int64]*sql.DB
+}
+
+// NewSharded opens the main database at path like New, and keeps each target's
+// raw, aggregated and legacy results in a database of its own in dir, which is
+// created if missing. Results already in the main database are not moved.
+func NewSharded(path, dir string) (*DB, error) {
+	if err := os.MkdirAll(dir, 0o755); err != nil {
+		return nil, fmt.Errorf("failed to create shard directory: %w", err)
+	}
+	d, err := New(path)
+	if err != nil {
+		return nil, err
+	}
+	d.shards = &shardSet{dir: dir, conns: make(map[int64]*sql.DB)}
+	return d, nil
+}
+
+// resultsDB returns the database holding a target's results: its shard when
+// storage is sharded, otherwise the main database.
+func (d *DB) resultsDB(targetID int64) (*sql.DB, error) {
+	if d.shards == nil {
+		return d.DB, nil
+	}
+	return d.shards.get(targetID)
+}
+
+// forEachResultDB calls fn with every database that holds results, for queries
+// that span targets: the main database first, with targetID 0, then each existing
+// shard by target ID. The main database keeps any results written before storage
+// was sharded. It stops at the first error from fn.
+func (d *DB) forEachResultDB(fn func(targetID int64, conn *sql.DB) error) error {
+	if err := fn(0, d.DB); err != nil {
+		return err
+	}
+	if d.shards == nil {
+		return nil
+	}
+	return d.shards.each(fn)
+}
+
+// ShardWriteError is returned by AddRawResults with sharded storage when the
+// shards of some targets failed to commit. The results of every other target in
+// the batch were written, so only those of TargetIDs need to be retried.
+type ShardWriteError struct {
+	TargetIDs []int64
+	Err       error
+}
+
+func (e *ShardWriteError) Error() string {
+	return fmt.Sprintf("failed to write results of targets %v: %v", e.TargetIDs, e.Err)
+}
+
+func (e *ShardWriteError) Unwrap() error {
+	return e.Err
+}
+
+// Close closes the shards, then the main database.
+func (d *DB) Close() error {
+	var errs []error
+	if d.shards != nil {
+		errs = append(errs, d.shards.close())
+	}
+	errs = append(errs, d.DB.Close())
+	return errors.Join(errs...)
+}
+
+func (s *shardSet) path(targetID int64) string {
+	return filepath.Join(s.dir, "target-"+strconv.FormatInt(targetID, 10)+".db")
+}
+
+// get returns the shard of a target, creating its file when it doesn't exist yet.
+func (s *shardSet) get(targetID int64) (*sql.DB, error) {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	if conn, ok := s.conns[targetID]; ok {
+		return conn, nil
+	}
+	conn, err := openShard(s.path(targetID))
+	if err != nil {
+		return nil, fmt.Errorf("failed to open shard for target %d: %w", targetID, err)
+	}
+	s.conns[targetID] = conn
+	return conn, nil
+}
+
+// openShard opens a shard database and applies the migrations to it. Foreign keys
+// are off, as the targets its results refer to are in the main database.
+func openShard(path string) (*sql.DB, error) {
+	conn, err := sql.Open("sqlite3", sqliteDSN(path+"?_foreign_keys=off"))
+	if err != nil {
+		return nil, err
+	}
+	conn.SetMaxOpenConns(maxShardConns)
+	conn.SetMaxIdleConns(maxShardConns)
+	conn.SetConnMaxIdleTime(shardConnMaxIdle)
+	if err := conn.Ping(); err != nil {
+		conn.Close()
+		return nil, err
+	}
+	if err := (&DB{DB: conn}).init(); err != nil {
+		conn.Close()
+		return nil, err
+	}
+	return conn, nil
+}
+
+// ids returns the target IDs that have a shard file, in ascending order.
+func (s *shardSet) ids() ([]int64, error) {
+	entries, err := os.ReadDir(s.dir)
+	if err != nil {
+		return nil, err
+	}
+	var ids []int64
+	for _, e := range entries {
+		name, ok := strings.CutPrefix(e.Name(), "target-")
+		if !ok || e.IsDir() {
+			continue
+		}
+		name, ok = strings.CutSuffix(name, ".db")
+		if !ok {
+			continue
+		}
+		if id, err := strconv.ParseInt(name, 10, 64); err == nil {
+			ids = append(ids, id)
+		}
+	}
+	sort.Slice(ids, func(i, j int) bool { return ids[i] < ids[j] })
+	return ids, nil
+}
+
+// each calls fn with every existing shard. Targets that never stored a result have
+// none. Shards that aren't open already are opened just for fn and closed again, so
+// a sweep over every target doesn't leave them all open.
+func (s *shardSet) each(fn func(targetID int64, conn *sql.DB) error) error {
+	ids, err := s.ids()
+	if err != nil {
+		return err
+	}
+	for _, id := range ids {
+		if err := s.with(id, fn); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+func (s *shardSet) with(targetID int64, fn func(targetID int64, conn *sql.DB) error) error {
+	s.mu.Lock()
+	conn, ok := s.conns[targetID]
+	s.mu.Unlock()
+	if ok {
+		return fn(targetID, conn)
+	}
+	conn, err := openShard(s.path(targetID))
+	if err != nil {
+		return fmt.Errorf("failed to open shard for target %d: %w", targetID, err)
+	}
+	defer conn.Close()
+	return fn(targetID, conn)
+}
+
+// drop closes a target's shard and deletes its files. A target without a shard
+// is not an error.
+func (s *shardSet) drop(targetID int64) error {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	if conn, ok := s.conns[targetID]; ok {
+		conn.Close()
+		delete(s.conns, targetID)
+	}
+	path := s.path(targetID)
+	for _, p := range []string{path, path + "-wal", path + "-shm"} {
+		if err := os.Remove(p); err != nil && !errors.Is(err, os.ErrNotExist) {
+			return fmt.Errorf("failed to delete shard for target %d: %w", targetID, err)
+		}
+	}
+	return nil
+}
+
+// dropOrphanedShards deletes the shards of targets that no longer exist, which
+// DeleteTarget leaves behind when it fails to remove the files, and adds their
+// rows to report.
+func (d *DB) dropOrphanedShards(report *OrphanedDataCleanupReport) error {
+	// List the shards first: a target created after this has its row before its shard.
+	ids, err := d.shards.ids()
+	if err != nil {
+		return err
+	}
+	targets, err := d.GetTargets()
+	if err != nil {
+		return err
+	}
+	exists := make(map[int64]bool, len(targets))
+	for _, t := range targets {
+		exists[t.ID] = true
+	}
+
+	for _, id := range ids {
+		if exists[id] {
+			continue
+		}
+		conn, err := d.shards.get(id)
+		if err != nil {
+			return err
+		}
+		var raw int64
+		if err := conn.QueryRow(`SELECT COUNT(*) FROM raw_results`).Scan(&raw); err != nil {
+			return err
+		}
+		if raw > 0 {
+			report.RawData = append(report.RawData, OrphanedRawDataGroup{TargetID: id, Count: raw})
+			report.RawRowsFound += raw
+			report.RawRowsDeleted += raw
+		}
+		groups, err := orphanedShardAggregates(conn, id)
+		if err != nil {
+			return err
+		}
+		for _, g := range groups {
+			report.AggregatedData = append(report.AggregatedData, g)
+			report.AggregatedRowsFound += g.Count
+			report.AggregatedRowsDeleted += g.Count
+		}
+		if err := d.shards.drop(id); err != nil {
+			return err
+		}
+	}
+
+	sort.SliceStable(report.RawData, func(i, j int) bool {
+		return report.RawData[i].TargetID < report.RawData[j].TargetID
+	})
+	sort.SliceStable(report.AggregatedData, func(i, j int) bool {
+		if report.AggregatedData[i].TargetID == report.AggregatedData[j].TargetID {
+			return report.AggregatedData[i].WindowSeconds < report.AggregatedData[j].WindowSeconds
+		}
+		return report.AggregatedData[i].TargetID < report.AggregatedData[j].TargetID
+	})
+	return nil
+}
+
+func orphanedShardAggregates(conn *sql.DB, targetID int64) ([]OrphanedAggregatedDataGroup, error) {
+	rows, err := conn.Query(`SELECT window_seconds, COUNT(*), COALESCE(SUM(LENGTH(tdigest_data)), 0)
+		FROM aggregated_results GROUP BY window_seconds ORDER BY window_seconds`)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	var groups []OrphanedAggregatedDataGroup
+	for rows.Next() {
+		g := OrphanedAggregatedDataGroup{TargetID: targetID}
+		if err := rows.Scan(&g.WindowSeconds, &g.Count, &g.TotalBytes); err != nil {
+			return nil, err
+		}
+		groups = append(groups, g)
+	}
+	return groups, rows.Err()
+}
+
+func (s *shardSet) close() error {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	var errs []error
+	for id, conn := range s.conns {
+		errs = append(errs, conn.Close())
+		delete(s.conns, id)
+	}
+	return errors.Join(errs...)
+}
@@ -0,0 +1,172 @@
+package db
+
+import (
+	"context"
+	"os"
+	"path/filepath"
+	"testing"
+	"time"
+)
+
+func newShardedTestDB(t *testing.T) *DB {
+	t.Helper()
+	dir := t.TempDir()
+	d, err := NewSharded(filepath.Join(dir, "main.db"), filepath.Join(dir, "shards"))
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	t.Cleanup(func() { d.Close() })
+	return d
+}
+
+func TestShardedStorage_RoutesResultsByTarget(t *testing.T) {
+	d := newShardedTestDB(t)
+
+	firstID, _ := d.AddTarget(&Target{Name: "first", Address: "first", ProbeType: "http"})
+	secondID, _ := d.AddTarget(&Target{Name: "second", Address: "second", ProbeType: "http"})
+	now := time.Now().UTC().Truncate(time.Second)
+
+	if err := d.AddRawResults([]RawResult{
+		{Time: now, TargetID: firstID, Latency: 10},
+		{Time: now, TargetID: secondID, Latency: 20},
+		{Time: now.Add(time.Second), TargetID: secondID, Latency: 30},
+	}); err != nil {
+		t.Fatalf("AddRawResults failed: %v", err)
+	}
+	if err := d.AddAggregatedResults([]*AggregatedResult{
+		{Time: now, TargetID: firstID, WindowSeconds: 60, TDigestData: []byte{1}},
+		{Time: now, TargetID: secondID, WindowSeconds: 60, TDigestData: []byte{1, 2}},
+	}); err != nil {
+		t.Fatalf("AddAggregatedResults failed: %v", err)
+	}
+
+	for _, id := range []int64{firstID, secondID} {
+		if _, err := os.Stat(d.shards.path(id)); err != nil {
+			t.Errorf("Expected a shard for target %d: %v", id, err)
+		}
+	}
+	var mainRows int
+	if err := d.QueryRow(`SELECT COUNT(*) FROM raw_results`).Scan(&mainRows); err != nil {
+		t.Fatalf("Count raw results failed: %v", err)
+	}
+	if mainRows != 0 {
+		t.Errorf("Expected no raw results in the main database, got %d", mainRows)
+	}
+
+	raws, err := d.GetRawResults(secondID, now.Add(-time.Hour), now.Add(time.Hour), 0)
+	if err != nil {
+		t.Fatalf("GetRawResults failed: %v", err)
+	}
+	if len(raws) != 2 {
+		t.Errorf("Expected 2 raw results for the second target, got %d", len(raws))
+	}
+	aggs, err := d.GetAggregatedResults(firstID, 60, now.Add(-time.Hour), now.Add(time.Hour))
+	if err != nil {
+		t.Fatalf("GetAggregatedResults failed: %v", err)
+	}
+	if len(aggs) != 1 || aggs[0].TargetID != firstID {
+		t.Errorf("Expected the first target's aggregated result, got %+v", aggs)
+	}
+
+	stats, err := d.GetRawStats()
+	if err != nil {
+		t.Fatalf("GetRawStats failed: %v", err)
+	}
+	if stats.Count != 3 {
+		t.Errorf("Expected raw stats to count 3 rows across shards, got %d", stats.Count)
+	}
+	tdigests, err := d.GetTDigestStats()
+	if err != nil {
+		t.Fatalf("GetTDigestStats failed: %v", err)
+	}
+	if len(tdigests) != 2 || tdigests[0].TargetName != "second" || tdigests[1].TargetName != "first" {
+		t.Errorf("Expected stats of both targets, largest first, got %+v", tdigests)
+	}
+
+	report, err := d.IntegrityCheck(context.Background())
+	if err != nil {
+		t.Fatalf("IntegrityCheck failed: %v", err)
+	}
+	if !report.OK {
+		t.Errorf("Expected integrity check to pass, got %v", report.Problems)
+	}
+
+	if err := d.DeleteTarget(firstID); err != nil {
+		t.Fatalf("DeleteTarget failed: %v", err)
+	}
+	if _, err := os.Stat(d.shards.path(firstID)); !os.IsNotExist(err) {
+		t.Errorf("Expected the deleted target's shard to be removed, got %v", err)
+	}
+	ids, err := d.shards.ids()
+	if err != nil {
+		t.Fatalf("Listing shards failed: %v", err)
+	}
+	if len(ids) != 1 || ids[0] != secondID {
+		t.Errorf("Expected only the second target's shard left, got %v", ids)
+	}
+}
+
+func TestShardedStorage_DeleteOrphanedDataDropsShards(t *testing.T) {
+	d := newShardedTestDB(t)
+
+	targetID, _ := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "http"})
+	now := time.Now().UTC().Truncate(time.Second)
+	if err := d.AddRawResults([]RawResult{
+		{Time: now, TargetID: targetID, Latency: 10},
+		{Time: now, TargetID: 999, Latency: 20},
+		{Time: now.Add(time.Second), TargetID: 999, Latency: 30},
+	}); err != nil {
+		t.Fatalf("AddRawResults failed: %v", err)
+	}
+	if err := d.AddAggregatedResult(&AggregatedResult{Time: now, TargetID: 999, WindowSeconds: 60, TDigestData: []byte{1, 2, 3}}); err != nil {
+		t.Fatalf("AddAggregatedResult failed: %v", err)
+	}
+
+	report, err := d.DeleteOrphanedData()
+	if err != nil {
+		t.Fatalf("DeleteOrphanedData failed: %v", err)
+	}
+	if report.RawRowsDeleted != 2 || report.AggregatedRowsDeleted != 1 {
+		t.Errorf("Expected 2 raw and 1 aggregated orphaned rows deleted, got %d and %d", report.RawRowsDeleted, report.AggregatedRowsDeleted)
+	}
+	if len(report.AggregatedData) != 1 || report.AggregatedData[0].TotalBytes != 3 {
+		t.Errorf("Unexpected aggregated orphan report: %+v", report.AggregatedData)
+	}
+	if _, err := os.Stat(d.shards.path(999)); !os.IsNotExist(err) {
+		t.Errorf("Expected the orphaned shard to be removed, got %v", err)
+	}
+	raws, _ := d.GetRawResults(targetID, now.Add(-time.Hour), now.Add(time.Hour), 0)
+	if len(raws) != 1 {
+		t.Errorf("Expected the existing target's raw result to be kept, got %d", len(raws))
+	}
+}
+
+func TestShardedStorage_SweepClosesShardsItOpened(t *testing.T) {
+	dir := t.TempDir()
+	path, shardDir := filepath.Join(dir, "main.db"), filepath.Join(dir, "shards")
+	d, err := NewSharded(path, shardDir)
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	targetID, _ := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "http"})
+	if err := d.AddRawResults([]RawResult{{Time: time.Now().UTC(), TargetID: targetID, Latency: 10}}); err != nil {
+		t.Fatalf("AddRawResults failed: %v", err)
+	}
+	d.Close()
+
+	reopened, err := NewSharded(path, shardDir)
+	if err != nil {
+		t.Fatalf("Failed to reopen db: %v", err)
+	}
+	defer reopened.Close()
+	stats, err := reopened.GetRawStats()
+	if err != nil {
+		t.Fatalf("GetRawStats failed: %v", err)
+	}
+	if stats.Count != 1 {
+		t.Errorf("Expected the shard's raw result to be counted, got %d", stats.Count)
+	}
+	if n := len(reopened.shards.conns); n != 0 {
+		t.Errorf("Expected the sweep to close the shards it opened, %d still open", n)
+	}
+}
@@ -7,6 +7,7 @@ import (
 	"encoding/base64"
 	"encoding/json"
 	"fmt"
+	"path/filepath"
 	"sort"
 	"strings"
 	"time"
@@ -23,15 +24,25 @@ import (
 //go:embed migrations/*.sql
 var fs embed.FS
 
+// Store is what the scheduler and web server need from the database. *DB, backed
+// by SQLite, is the only implementation.
 type Store interface {
 	AddTarget(t *Target) (int64, error)
+	AddTargets(targets []*Target) error
 	UpdateTarget(t *Target) error
 	GetTargets() ([]Target, error)
+	QueryTargets(q TargetQuery) ([]Target, int, error)
 	GetTarget(id int64) (*Target, error)
+	GetTags() ([]string, error)
 	DeleteTarget(id int64) error
+	UpdateTargetCertExpiry(id int64, expiry time.Time) error
+	SetTargetPaused(id int64, paused bool) error
+	SetTagPaused(tag string, paused bool) (int64, error)
+	ClearTargetData(id int64) (*ClearedTargetData, error)
 	AddResult(r *Result) error
 	GetResults(targetID int64, limit int) ([]Result, error)
 	GetResultsByTime(targetID int64, start, end time.Time) ([]Result, error)
+	DeleteResultsBefore(targetID int64, cutoff time.Time) error
 	Close() error
 
 	// New methods
@@ -39,21 +50,30 @@ type Store interface {
 	AddAggregatedResult(r *AggregatedResult) error
 	AddAggregatedResults(results []*AggregatedResult) error
 	GetLastRollupTime(targetID int64, windowSeconds int) (time.Time, error)
+	GetFirstRollupTime(targetID int64, windowSeconds int) (time.Time, error)
 	GetRawResults(targetID int64, start, end time.Time, limit int) ([]RawResult, error)
+	GetRawResultsDownsampled(targetID int64, start, end time.Time, maxPoints int) ([]RawResult, error)
+	ForEachRawResult(targetID int64, start, end time.Time, fn func(RawResult) error) error
 	GetAggregatedResults(targetID int64, windowSeconds int, start, end time.Time) ([]AggregatedResult, error)
 	DeleteRawResultsBefore(targetID int64, cutoff time.Time) error
 	DeleteAggregatedResultsBefore(targetID int64, windowSeconds int, cutoff time.Time) error
 	DeleteAggregatedResultsByWindow(targetID int64, windowSeconds int) error
 	GetEarliestRawResultTime(targetID int64) (time.Time, error)
+	GetLatestRawResult(targetID int64) (*RawResult, error)
+	GetLatestAggregatedResult(targetID int64, windowSeconds int) (*AggregatedResult, error)
+	GetTimeoutTotal(targetID int64, windowSeconds int) (int64, error)
 
 	// Status Page Stats
 	GetDBSizeBytes() (int64, error)
 	GetPageCount() (int64, error)
 	GetPageSize() (int64, error)
 	GetFreelistCount() (int64, error)
+	Ping() error
 	GetTDigestStats() ([]TDigestStat, error)
 	GetRawStats() (*RawStats, error)
 	DeleteOrphanedData() (*OrphanedDataCleanupReport, error)
+	Vacuum() (*VacuumReport, error)
+	IntegrityCheck(ctx context.Context) (*IntegrityReport, error)
 
 	// Dashboard methods
 	AddDashboard(d *Dashboard) (int64, error)
@@ -68,6 +88,13 @@ type Store interface {
 	SetGraphTargets(graphID int64, targetIDs []int64) error
 	GetDashboardBySlug(slug string) (*Dashboard, error)
 	RegenerateDashboardSlug(id int64) (string, error)
+
+	// Alert rule methods
+	AddAlertRule(r *AlertRule) (int64, error)
+	GetAlertRules() ([]AlertRule, error)
+	GetAlertRulesForTarget(targetID int64) ([]AlertRule, error)
+	DeleteAlertRule(id, targetID int64) error
+	SetAlertRuleState(id int64, firing bool, lastWindow time.Time) error
 }
 
 type TDigestStat struct {
@@ -79,6 +106,10 @@ type TDigestStat struct {
 	Count               int64
 	AvgBytes            float64
 	EstimatedTotalBytes int64
+
+	// AvgCentroids is the mean number of centroids in the newest
+	// tdigestCentroidSample digests, which is what their size mostly depends on.
+	AvgCentroids float64
 }
 
 type RawStats struct {
@@ -111,37 +142,101 @@ type OrphanedDataCleanupReport struct {
 	StopReason            string
 }
 
+// ClearedTargetData counts the rows ClearTargetData deleted from each results table.
+type ClearedTargetData struct {
+	RawResults        int64 `json:"raw_results"`
+	AggregatedResults int64 `json:"aggregated_results"`
+	Results           int64 `json:"results"`
+}
+
+// IntegrityReport is the outcome of IntegrityCheck.
+type IntegrityReport struct {
+	OK bool `json:"ok"`
+	// Problems lists what SQLite found wrong, at most 100 entries. Empty when OK.
+	Problems []string `json:"problems"`
+}
+
+type VacuumReport struct {
+	SizeBefore     int64
+	SizeAfter      int64
+	ReclaimedBytes int64
+	FreelistPages  int64
+	Skipped        bool
+	Note           string
+}
+
 type DB struct {
 	*sql.DB
+	// shards is set by NewSharded, and nil when every result is in the main database.
+	shards *shardSet
 }
 
+// maxOpenConns bounds the connection pool for file-backed databases. With WAL
+// enabled, readers on these connections run concurrently with the single writer.
+const maxOpenConns = 8
+
 func New(path string) (*DB, error) {
 	db, err := sql.Open("sqlite3", sqliteDSN(path))
 	if err != nil {
 		return nil, err
 	}
+	if !isMemoryDSN(path) {
+		db.SetMaxOpenConns(maxOpenConns)
+		db.SetMaxIdleConns(maxOpenConns)
+	}
 	if err := db.Ping(); err != nil {
 		return nil, err
 	}
 
-	s := &DB{db}
+	s := &DB{DB: db}
 	if err := s.init(); err != nil {
+		db.Close()
 		return nil, err
 	}
 	return s, nil
 }
 
+// sqliteDSN adds the connection parameters every pooled connection needs,
+// unless the caller already set them.
 func sqliteDSN(path string) string {
-	if strings.Contains(path, "_foreign_keys=") || strings.Contains(path, "_fk=") {
+	var params []string
+	if !strings.Contains(path, "_foreign_keys=") && !strings.Contains(path, "_fk=") {
+		params = append(params, "_foreign_keys=on")
+	}
+	// WAL lets the status page and API read while the batch writer commits.
+	// In-memory databases don't support it.
+	if !isMemoryDSN(path) && !strings.Contains(path, "_journal_mode=") && !strings.Contains(path, "_journal=") {
+		params = append(params, "_journal_mode=WAL")
+		// NORMAL is durable across application crashes in WAL mode and avoids an fsync per commit
+		if !strings.Contains(path, "_synchronous=") && !strings.Contains(path, "_sync=") {
+			params = append(params, "_synchronous=NORMAL")
+		}
+	}
+	// Wait for locks held by other writers (rollups, batch writer, another process)
+	// instead of failing with "database is locked".
+	if !strings.Contains(path, "_busy_timeout=") && !strings.Contains(path, "_timeout=") {
+		params = append(params, "_busy_timeout=5000")
+	}
+	if len(params) == 0 {
 		return path
 	}
+
 	separator := "?"
 	if strings.Contains(path, "?") {
 		separator = "&"
 	}
-	return path + separator + "_foreign_keys=on"
+	return path + separator + strings.Join(params, "&")
+}
+
+func isMemoryDSN(path string) bool {
+	return strings.Contains(path, ":memory:") || strings.Contains(path, "mode=memory")
 }
 
+// ErrMigration is wrapped by errors from applying the embedded schema migrations.
+var ErrMigration = errors.New("database migration failed")
+
+// init applies any embedded migrations newer than the version recorded in the
+// schema_migrations table. Already-applied migrations are skipped.
 func (d *DB) init() error {
 	driver, err := sqlite3.WithInstance(d.DB, &sqlite3.Config{})
 	if err != nil {
@@ -158,8 +253,18 @@ func (d *DB) init() error {
 		return fmt.Errorf("failed to create migrate instance: %w", err)
 	}
 
+	// A dirty version means a migration failed part-way through. Running Up again
+	// would skip it, so refuse to start until the schema has been repaired.
+	version, dirty, err := m.Version()
+	if err != nil && !errors.Is(err, migrate.ErrNilVersion) {
+		return fmt.Errorf("%w: failed to read schema version: %v", ErrMigration, err)
+	}
+	if dirty {
+		return fmt.Errorf("%w: schema version %d is dirty; a previous migration did not complete and the database must be repaired", ErrMigration, version)
+	}
+
 	if err := m.Up(); err != nil && !errors.Is(err, migrate.ErrNoChange) {
-		return fmt.Errorf("failed to run migrations: %w", err)
+		return fmt.Errorf("%w: %v", ErrMigration, err)
 	}
 
 	return nil
@@ -174,19 +279,69 @@ type Target struct {
 	ProbeInterval     float64
 	Timeout           float64
 	RetentionPolicies string // JSON
+	MaxConcurrent     int    // Max in-flight probes, default 5
+	// CertExpiresAt is the leaf certificate expiry last observed by a "tls" probe.
+	// It is maintained by the scheduler and ignored by AddTarget/UpdateTarget.
+	CertExpiresAt *time.Time
+	// Paused targets keep their config and history but are not probed.
+	// It is changed with SetTargetPaused and ignored by AddTarget/UpdateTarget.
+	Paused bool
+	// Tags group targets on the dashboard. Stored as a JSON array.
+	Tags []string
+	// AddressFamily is "auto", "ipv4" or "ipv6". Defaults to "auto".
+	AddressFamily string
+	// SourceAddress is the local IP address to probe from. Empty uses the server's
+	// default, see scheduler.Scheduler.SourceAddress.
+	SourceAddress string
+}
+
+// TargetQuery filters and pages QueryTargets. Zero values mean no filter or limit.
+type TargetQuery struct {
+	Limit        int
+	Offset       int
+	NameContains string
+	ProbeType    string
+	Tag          string
 }
 
+// Result is a row of the legacy results table, which older dashboards query. It is
+// only written when legacy results are enabled on the rollup manager.
 type Result struct {
 	Time         time.Time
 	TargetID     int64
 	TimeoutCount int64
 	TDigestData  []byte
+	// Latency statistics of the window's successful probes in nanoseconds. They are
+	// nil when the window has none, or for rows written before they were stored.
+	MinNS *float64
+	AvgNS *float64
+	MaxNS *float64
+	P50NS *float64
+	P99NS *float64
 }
 
+// Latency values recorded in place of a measurement when a probe produced none.
+const (
+	// LatencyTimeout marks a probe that timed out.
+	LatencyTimeout = -1.0
+	// LatencyError marks a probe that failed outright, e.g. connection refused or a DNS error.
+	LatencyError = -2.0
+)
+
 type RawResult struct {
 	Time     time.Time
 	TargetID int64
-	Latency  float64
+	Latency  float64 // Nanoseconds, or LatencyTimeout / LatencyError
+	// Loss is the fraction of echo requests lost, for multi-echo "ping" probes.
+	Loss float64
+	// TTL is the IP TTL of the echo reply, for "ping" probes. Zero when unknown.
+	TTL int
+	// RemoteIP is the address the probe reached, so a change of backend behind
+	// round-robin DNS or anycast shows up. Empty when unknown.
+	RemoteIP string
+	// StatusCode is the HTTP status of the response, for "http" probes. Zero when
+	// unknown.
+	StatusCode int
 }
 
 type AggregatedResult struct {
@@ -195,6 +350,33 @@ type AggregatedResult struct {
 	WindowSeconds int
 	TDigestData   []byte
 	TimeoutCount  int64
+	ErrorCount    int64 // Probes that failed with an error other than a timeout
+	// MinNS and MaxNS are the exact extremes of the successful probes in the window.
+	// They are nil when the window has no successful probes, or for rows written
+	// before they were tracked.
+	MinNS *float64
+	MaxNS *float64
+	// JitterNS is the mean absolute difference between consecutive successful probes.
+	// It is nil when the window has fewer than two, or for rows written before it was tracked.
+	JitterNS *float64
+	// AttemptCount is the number of probes sent in the window, whatever their outcome,
+	// so a window where every probe failed can be told apart from one with no probes.
+	// It is nil for rows written before it was tracked; see Attempts.
+	AttemptCount *int64
+}
+
+// Attempts returns AttemptCount, or for older rows without it, the sum of the
+// successes in the TDigest and the failures. That undercounts only when the
+// TDigest can't be read.
+func (r *AggregatedResult) Attempts() int64 {
+	if r.AttemptCount != nil {
+		return *r.AttemptCount
+	}
+	n := r.TimeoutCount + r.ErrorCount
+	if td, err := DeserializeTDigest(r.TDigestData); err == nil {
+		n += int64(td.Count())
+	}
+	return n
 }
 
 type Dashboard struct {
@@ -204,6 +386,22 @@ type Dashboard struct {
 	PublicSlug string
 }
 
+// AlertRule compares a metric of a target's newest aggregated window against a
+// threshold and posts to WebhookURL when the result changes.
+type AlertRule struct {
+	ID            int64
+	TargetID      int64
+	Metric        string // p50, p90, p95, p99, p999 or max (milliseconds), timeout_percent or error_percent
+	Comparison    string // ">" or "<"
+	Threshold     float64
+	WindowSeconds int // Rollup window to evaluate; must be one of the target's retention windows
+	WebhookURL    string
+	Channel       string // Message format for WebhookURL: "webhook" (JSON) or "slack"
+	// Firing and LastWindow are maintained by the alert manager and ignored by AddAlertRule.
+	Firing     bool
+	LastWindow *time.Time // Start of the newest window evaluated
+}
+
 type DashboardGraph struct {
 	ID          int64
 	DashboardID int64
@@ -213,42 +411,126 @@ type DashboardGraph struct {
 	TargetNames map[int64]string // Populated on read, implementation detail for API
 }
 
-func (d *DB) AddTarget(t *Target) (int64, error) {
+const insertTargetSQL = `INSERT INTO targets (name, address, probe_type, probe_config, probe_interval, timeout, retention_policies, max_concurrent, tags, address_family, source_address) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`
+
+// insertTargetArgs fills in t's defaults and returns the arguments for insertTargetSQL.
+func insertTargetArgs(t *Target) []any {
+	if t.AddressFamily == "" {
+		t.AddressFamily = "auto"
+	}
 	if t.ProbeInterval <= 0 {
 		t.ProbeInterval = 1.0
 	}
 	if t.Timeout <= 0 {
 		t.Timeout = 5.0
 	}
-	res, err := d.Exec(`INSERT INTO targets (name, address, probe_type, probe_config, probe_interval, timeout, retention_policies) VALUES (?, ?, ?, ?, ?, ?, ?)`,
-		t.Name, t.Address, t.ProbeType, t.ProbeConfig, t.ProbeInterval, t.Timeout, t.RetentionPolicies)
+	if t.MaxConcurrent <= 0 {
+		t.MaxConcurrent = 5
+	}
+	return []any{t.Name, t.Address, t.ProbeType, t.ProbeConfig, t.ProbeInterval, t.Timeout, t.RetentionPolicies, t.MaxConcurrent, encodeTags(t.Tags), t.AddressFamily, t.SourceAddress}
+}
+
+func (d *DB) AddTarget(t *Target) (int64, error) {
+	res, err := d.Exec(insertTargetSQL, insertTargetArgs(t)...)
 	if err != nil {
 		return 0, err
 	}
 	return res.LastInsertId()
 }
 
+// AddTargets adds targets in a single transaction, so either all of them are added
+// or none are. Each target's ID is set once the transaction has committed.
+func (d *DB) AddTargets(targets []*Target) error {
+	if len(targets) == 0 {
+		return nil
+	}
+	tx, err := d.Begin()
+	if err != nil {
+		return err
+	}
+
+	stmt, err := tx.Prepare(insertTargetSQL)
+	if err != nil {
+		tx.Rollback()
+		return err
+	}
+	defer stmt.Close()
+
+	ids := make([]int64, len(targets))
+	for i, t := range targets {
+		res, err := stmt.Exec(insertTargetArgs(t)...)
+		if err == nil {
+			ids[i], err = res.LastInsertId()
+		}
+		if err != nil {
+			tx.Rollback()
+			return err
+		}
+	}
+	if err := tx.Commit(); err != nil {
+		return err
+	}
+	for i, t := range targets {
+		t.ID = ids[i]
+	}
+	return nil
+}
+
 func (d *DB) UpdateTarget(t *Target) error {
+	if t.AddressFamily == "" {
+		t.AddressFamily = "auto"
+	}
 	if t.ProbeInterval <= 0 {
 		t.ProbeInterval = 1.0
 	}
 	if t.Timeout <= 0 {
 		t.Timeout = 5.0
 	}
-	_, err := d.Exec(`UPDATE targets SET name=?, address=?, probe_type=?, probe_interval=?, timeout=?, retention_policies=? WHERE id=?`,
-		t.Name, t.Address, t.ProbeType, t.ProbeInterval, t.Timeout, t.RetentionPolicies, t.ID)
+	if t.MaxConcurrent <= 0 {
+		t.MaxConcurrent = 5
+	}
+	_, err := d.Exec(`UPDATE targets SET name=?, address=?, probe_type=?, probe_config=?, probe_interval=?, timeout=?, retention_policies=?, max_concurrent=?, tags=?, address_family=?, source_address=? WHERE id=?`,
+		t.Name, t.Address, t.ProbeType, t.ProbeConfig, t.ProbeInterval, t.Timeout, t.RetentionPolicies, t.MaxConcurrent, encodeTags(t.Tags), t.AddressFamily, t.SourceAddress, t.ID)
 	return err
 }
 
 func (d *DB) AddResult(r *Result) error {
-	_, err := d.Exec(`INSERT INTO results (time, target_id, timeout_count, tdigest_data) 
-		VALUES (?, ?, ?, ?)`,
-		r.Time, r.TargetID, r.TimeoutCount, r.TDigestData)
+	conn, err := d.resultsDB(r.TargetID)
+	if err != nil {
+		return err
+	}
+	_, err = conn.Exec(`INSERT INTO results (time, target_id, timeout_count, tdigest_data, min_ns, avg_ns, max_ns, p50_ns, p99_ns) 
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)`,
+		r.Time, r.TargetID, r.TimeoutCount, r.TDigestData, r.MinNS, r.AvgNS, r.MaxNS, r.P50NS, r.P99NS)
 	return err
 }
 
+// targetColumns is the column list read by scanTarget.
+const targetColumns = `id, name, address, probe_type, probe_config, probe_interval, timeout, COALESCE(retention_policies, '[]'), cert_expires_at, paused, max_concurrent, tags, address_family, source_address`
+
+type rowScanner interface {
+	Scan(dest ...any) error
+}
+
+func scanTarget(row rowScanner) (Target, error) {
+	var t Target
+	var certExpiresAt sql.NullTime
+	var tags string
+	if err := row.Scan(&t.ID, &t.Name, &t.Address, &t.ProbeType, &t.ProbeConfig, &t.ProbeInterval, &t.Timeout, &t.RetentionPolicies, &certExpiresAt, &t.Paused, &t.MaxConcurrent, &tags, &t.AddressFamily, &t.SourceAddress); err != nil {
+		return Target{}, err
+	}
+	if err := json.Unmarshal([]byte(tags), &t.Tags); err != nil {
+		return Target{}, fmt.Errorf("invalid tags for target %d: %w", t.ID, err)
+	}
+	if certExpiresAt.Valid {
+		expiry := certExpiresAt.Time
+		t.CertExpiresAt = &expiry
+	}
+	return t, nil
+}
+
 func (d *DB) GetTargets() ([]Target, error) {
-	rows, err := d.Query(`SELECT id, name, address, probe_type, probe_config, probe_interval, timeout, COALESCE(retention_policies, '[]') FROM targets`)
+	rows, err := d.Query(`SELECT ` + targetColumns + ` FROM targets`)
 	if err != nil {
 		return nil, err
 	}
@@ -256,8 +538,8 @@ func (d *DB) GetTargets() ([]Target, error) {
 
 	var targets []Target
 	for rows.Next() {
-		var t Target
-		if err := rows.Scan(&t.ID, &t.Name, &t.Address, &t.ProbeType, &t.ProbeConfig, &t.ProbeInterval, &t.Timeout, &t.RetentionPolicies); err != nil {
+		t, err := scanTarget(rows)
+		if err != nil {
 			return nil, err
 		}
 		targets = append(targets, t)
@@ -265,19 +547,130 @@ func (d *DB) GetTargets() ([]Target, error) {
 	return targets, nil
 }
 
+// QueryTargets returns one page of the targets matching q, ordered by ID, and the
+// total number of matches.
+func (d *DB) QueryTargets(q TargetQuery) ([]Target, int, error) {
+	var where []string
+	var args []any
+	if q.NameContains != "" {
+		where = append(where, `name LIKE ? ESCAPE '\'`)
+		args = append(args, "%"+likeEscaper.Replace(q.NameContains)+"%")
+	}
+	if q.ProbeType != "" {
+		where = append(where, `probe_type = ?`)
+		args = append(args, q.ProbeType)
+	}
+	if q.Tag != "" {
+		where = append(where, `EXISTS (SELECT 1 FROM json_each(targets.tags) WHERE value = ?)`)
+		args = append(args, q.Tag)
+	}
+	filter := ""
+	if len(where) > 0 {
+		filter = ` WHERE ` + strings.Join(where, " AND ")
+	}
+
+	var total int
+	if err := d.QueryRow(`SELECT COUNT(*) FROM targets`+filter, args...).Scan(&total); err != nil {
+		return nil, 0, err
+	}
+
+	query := `SELECT ` + targetColumns + ` FROM targets` + filter + ` ORDER BY id`
+	if q.Limit > 0 || q.Offset > 0 {
+		limit := q.Limit
+		if limit <= 0 {
+			limit = -1 // SQLite needs a LIMIT for OFFSET
+		}
+		query += ` LIMIT ? OFFSET ?`
+		args = append(args, limit, q.Offset)
+	}
+	rows, err := d.Query(query, args...)
+	if err != nil {
+		return nil, 0, err
+	}
+	defer rows.Close()
+
+	var targets []Target
+	for rows.Next() {
+		t, err := scanTarget(rows)
+		if err != nil {
+			return nil, 0, err
+		}
+		targets = append(targets, t)
+	}
+	return targets, total, rows.Err()
+}
+
+// likeEscaper escapes the LIKE wildcards in a user-supplied substring.
+var likeEscaper = strings.NewReplacer(`\`, `\\`, `%`, `\%`, `_`, `\_`)
+
+// GetTags returns the distinct tags across all targets, sorted.
+func (d *DB) GetTags() ([]string, error) {
+	rows, err := d.Query(`SELECT DISTINCT json_each.value FROM targets, json_each(targets.tags) ORDER BY 1`)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	tags := []string{}
+	for rows.Next() {
+		var tag string
+		if err := rows.Scan(&tag); err != nil {
+			return nil, err
+		}
+		tags = append(tags, tag)
+	}
+	return tags, rows.Err()
+}
+
+func encodeTags(tags []string) string {
+	if len(tags) == 0 {
+		return "[]"
+	}
+	data, _ := json.Marshal(tags)
+	return string(data)
+}
+
 func (d *DB) GetTarget(id int64) (*Target, error) {
-	var t Target
-	err := d.QueryRow(`SELECT id, name, address, probe_type, probe_config, probe_interval, timeout, COALESCE(retention_policies, '[]') FROM targets WHERE id = ?`, id).Scan(
-		&t.ID, &t.Name, &t.Address, &t.ProbeType, &t.ProbeConfig, &t.ProbeInterval, &t.Timeout, &t.RetentionPolicies,
-	)
+	t, err := scanTarget(d.QueryRow(`SELECT `+targetColumns+` FROM targets WHERE id = ?`, id))
 	if err != nil {
 		return nil, err
 	}
 	return &t, nil
 }
 
+func (d *DB) UpdateTargetCertExpiry(id int64, expiry time.Time) error {
+	_, err := d.Exec(`UPDATE targets SET cert_expires_at = ? WHERE id = ?`, expiry.UTC(), id)
+	return err
+}
+
+func (d *DB) SetTargetPaused(id int64, paused bool) error {
+	res, err := d.Exec(`UPDATE targets SET paused = ? WHERE id = ?`, paused, id)
+	if err != nil {
+		return err
+	}
+	if n, err := res.RowsAffected(); err == nil && n == 0 {
+		return sql.ErrNoRows
+	}
+	return nil
+}
+
+// SetTagPaused sets the paused flag of every target with the tag and returns how
+// many targets changed. Targets already in that state are not counted.
+func (d *DB) SetTagPaused(tag string, paused bool) (int64, error) {
+	res, err := d.Exec(`UPDATE targets SET paused = ?
+		WHERE paused != ? AND EXISTS (SELECT 1 FROM json_each(targets.tags) WHERE value = ?)`, paused, paused, tag)
+	if err != nil {
+		return 0, err
+	}
+	return res.RowsAffected()
+}
+
 func (d *DB) GetResults(targetID int64, limit int) ([]Result, error) {
-	rows, err := d.Query(`SELECT time, target_id, timeout_count, tdigest_data 
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return nil, err
+	}
+	rows, err := conn.Query(`SELECT time, target_id, timeout_count, tdigest_data, min_ns, avg_ns, max_ns, p50_ns, p99_ns 
 		FROM results WHERE target_id = ? ORDER BY time DESC LIMIT ?`, targetID, limit)
 	if err != nil {
 		return nil, err
@@ -287,7 +680,7 @@ func (d *DB) GetResults(targetID int64, limit int) ([]Result, error) {
 	var results []Result
 	for rows.Next() {
 		var r Result
-		if err := rows.Scan(&r.Time, &r.TargetID, &r.TimeoutCount, &r.TDigestData); err != nil {
+		if err := rows.Scan(&r.Time, &r.TargetID, &r.TimeoutCount, &r.TDigestData, &r.MinNS, &r.AvgNS, &r.MaxNS, &r.P50NS, &r.P99NS); err != nil {
 			return nil, err
 		}
 		results = append(results, r)
@@ -296,7 +689,11 @@ func (d *DB) GetResults(targetID int64, limit int) ([]Result, error) {
 }
 
 func (d *DB) GetResultsByTime(targetID int64, start, end time.Time) ([]Result, error) {
-	rows, err := d.Query(`SELECT time, target_id, timeout_count, tdigest_data 
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return nil, err
+	}
+	rows, err := conn.Query(`SELECT time, target_id, timeout_count, tdigest_data, min_ns, avg_ns, max_ns, p50_ns, p99_ns 
 		FROM results WHERE target_id = ? AND time >= ? AND time <= ? ORDER BY time ASC`, targetID, start, end)
 	if err != nil {
 		return nil, err
@@ -306,7 +703,7 @@ func (d *DB) GetResultsByTime(targetID int64, start, end time.Time) ([]Result, e
 	var results []Result
 	for rows.Next() {
 		var r Result
-		if err := rows.Scan(&r.Time, &r.TargetID, &r.TimeoutCount, &r.TDigestData); err != nil {
+		if err := rows.Scan(&r.Time, &r.TargetID, &r.TimeoutCount, &r.TDigestData, &r.MinNS, &r.AvgNS, &r.MaxNS, &r.P50NS, &r.P99NS); err != nil {
 			return nil, err
 		}
 		results = append(results, r)
@@ -325,6 +722,7 @@ func (d *DB) DeleteTarget(id int64) error {
 		`DELETE FROM raw_results WHERE target_id = ?`,
 		`DELETE FROM aggregated_results WHERE target_id = ?`,
 		`DELETE FROM dashboard_graph_targets WHERE target_id = ?`,
+		`DELETE FROM alert_rules WHERE target_id = ?`,
 		`DELETE FROM targets WHERE id = ?`,
 	} {
 		if _, err := tx.Exec(query, id); err != nil {
@@ -333,20 +731,96 @@ func (d *DB) DeleteTarget(id int64) error {
 		}
 	}
 
-	return tx.Commit()
+	if err := tx.Commit(); err != nil {
+		return err
+	}
+	if d.shards != nil {
+		return d.shards.drop(id)
+	}
+	return nil
 }
 
+// ClearTargetData deletes a target's raw, aggregated and legacy results in one
+// transaction. The target itself, its alert rules and dashboard references are kept.
+func (d *DB) ClearTargetData(id int64) (*ClearedTargetData, error) {
+	conn, err := d.resultsDB(id)
+	if err != nil {
+		return nil, err
+	}
+	tx, err := conn.Begin()
+	if err != nil {
+		return nil, err
+	}
+
+	cleared := &ClearedTargetData{}
+	for _, step := range []struct {
+		query string
+		count *int64
+	}{
+		{`DELETE FROM raw_results WHERE target_id = ?`, &cleared.RawResults},
+		{`DELETE FROM aggregated_results WHERE target_id = ?`, &cleared.AggregatedResults},
+		{`DELETE FROM results WHERE target_id = ?`, &cleared.Results},
+	} {
+		res, err := tx.Exec(step.query, id)
+		if err != nil {
+			tx.Rollback()
+			return nil, err
+		}
+		if *step.count, err = res.RowsAffected(); err != nil {
+			tx.Rollback()
+			return nil, err
+		}
+	}
+
+	if err := tx.Commit(); err != nil {
+		return nil, err
+	}
+	return cleared, nil
+}
+
+// AddRawResults writes results in one transaction. With sharded storage each
+// target's shard commits on its own, and a *ShardWriteError names the targets whose
+// results weren't written.
 func (d *DB) AddRawResults(results []RawResult) error {
 	if len(results) == 0 {
 		return nil
 	}
-	tx, err := d.Begin()
+	if d.shards == nil {
+		return addRawResults(d.DB, results)
+	}
+	// Each shard commits on its own, so write every target and report the ones
+	// that failed rather than stopping at the first
+	byTarget := make(map[int64][]RawResult)
+	for _, r := range results {
+		byTarget[r.TargetID] = append(byTarget[r.TargetID], r)
+	}
+	var failed []int64
+	var errs []error
+	for targetID, rs := range byTarget {
+		conn, err := d.shards.get(targetID)
+		if err == nil {
+			err = addRawResults(conn, rs)
+		}
+		if err != nil {
+			failed = append(failed, targetID)
+			errs = append(errs, err)
+		}
+	}
+	if len(failed) > 0 {
+		sort.Slice(failed, func(i, j int) bool { return failed[i] < failed[j] })
+		return &ShardWriteError{TargetIDs: failed, Err: errors.Join(errs...)}
+	}
+	return nil
+}
+
+func addRawResults(conn *sql.DB, results []RawResult) error {
+	tx, err := conn.Begin()
 	if err != nil {
 		return err
 	}
 
 	// Prepare statement for bulk insert
-	stmt, err := tx.Prepare(`INSERT INTO raw_results (time, target_id, latency) VALUES (?, ?, ?)`)
+	stmt, err := tx.Prepare(`INSERT INTO raw_results (time, target_id, latency, loss, ttl, remote_ip, status_code) VALUES (?, ?, ?, ?, ?, ?, ?)`)
 	if err != nil {
 		tx.Rollback()
 		return err
@@ -354,7 +828,17 @@ func (d *DB) AddRawResults(results []RawResult) error {
 	defer stmt.Close()
 
 	for _, r := range results {
-		_, err = stmt.Exec(r.Time, r.TargetID, r.Latency)
+		var ttl, remoteIP, statusCode any
+		if r.TTL > 0 {
+			ttl = r.TTL
+		}
+		if r.RemoteIP != "" {
+			remoteIP = r.RemoteIP
+		}
+		if r.StatusCode > 0 {
+			statusCode = r.StatusCode
+		}
+		_, err = stmt.Exec(r.Time, r.TargetID, r.Latency, r.Loss, ttl, remoteIP, statusCode)
 		if err != nil {
 			tx.Rollback()
 			return err
@@ -364,12 +848,21 @@ func (d *DB) AddRawResults(results []RawResult) error {
 }
 
 func (d *DB) AddAggregatedResult(r *AggregatedResult) error {
-	_, err := d.Exec(`INSERT INTO aggregated_results (time, target_id, window_seconds, tdigest_data, timeout_count) 
-		VALUES (?, ?, ?, ?, ?)
+	conn, err := d.resultsDB(r.TargetID)
+	if err != nil {
+		return err
+	}
+	_, err = conn.Exec(`INSERT INTO aggregated_results (time, target_id, window_seconds, tdigest_data, timeout_count, error_count, min_ns, max_ns, jitter_ns, attempt_count) 
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 		ON CONFLICT(time, target_id, window_seconds) DO UPDATE SET
 		tdigest_data=excluded.tdigest_data,
-		timeout_count=excluded.timeout_count`,
-		r.Time, r.TargetID, r.WindowSeconds, r.TDigestData, r.TimeoutCount)
+		timeout_count=excluded.timeout_count,
+		error_count=excluded.error_count,
+		min_ns=excluded.min_ns,
+		max_ns=excluded.max_ns,
+		jitter_ns=excluded.jitter_ns,
+		attempt_count=excluded.attempt_count`,
+		r.Time, r.TargetID, r.WindowSeconds, r.TDigestData, r.TimeoutCount, r.ErrorCount, r.MinNS, r.MaxNS, r.JitterNS, r.AttemptCount)
 	return err
 }
 
@@ -377,16 +870,41 @@ func (d *DB) AddAggregatedResults(results []*AggregatedResult) error {
 	if len(results) == 0 {
 		return nil
 	}
-	tx, err := d.Begin()
+	if d.shards == nil {
+		return addAggregatedResults(d.DB, results)
+	}
+	byTarget := make(map[int64][]*AggregatedResult)
+	for _, r := range results {
+		byTarget[r.TargetID] = append(byTarget[r.TargetID], r)
+	}
+	for targetID, rs := range byTarget {
+		conn, err := d.shards.get(targetID)
+		if err != nil {
+			return err
+		}
+		if err := addAggregatedResults(conn, rs); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+func addAggregatedResults(conn *sql.DB, results []*AggregatedResult) error {
+	tx, err := conn.Begin()
 	if err != nil {
 		return err
 	}
 
-	stmt, err := tx.Prepare(`INSERT INTO aggregated_results (time, target_id, window_seconds, tdigest_data, timeout_count) 
-		VALUES (?, ?, ?, ?, ?)
+	stmt, err := tx.Prepare(`INSERT INTO aggregated_results (time, target_id, window_seconds, tdigest_data, timeout_count, error_count, min_ns, max_ns, jitter_ns, attempt_count) 
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 		ON CONFLICT(time, target_id, window_seconds) DO UPDATE SET
 		tdigest_data=excluded.tdigest_data,
-		timeout_count=excluded.timeout_count`)
+		timeout_count=excluded.timeout_count,
+		error_count=excluded.error_count,
+		min_ns=excluded.min_ns,
+		max_ns=excluded.max_ns,
+		jitter_ns=excluded.jitter_ns,
+		attempt_count=excluded.attempt_count`)
 	if err != nil {
 		tx.Rollback()
 		return err
@@ -394,7 +912,7 @@ func (d *DB) AddAggregatedResults(results []*AggregatedResult) error {
 	defer stmt.Close()
 
 	for _, r := range results {
-		_, err = stmt.Exec(r.Time, r.TargetID, r.WindowSeconds, r.TDigestData, r.TimeoutCount)
+		_, err = stmt.Exec(r.Time, r.TargetID, r.WindowSeconds, r.TDigestData, r.TimeoutCount, r.ErrorCount, r.MinNS, r.MaxNS, r.JitterNS, r.AttemptCount)
 		if err != nil {
 			tx.Rollback()
 			return err
@@ -404,8 +922,30 @@ func (d *DB) AddAggregatedResults(results []*AggregatedResult) error {
 }
 
 func (d *DB) GetLastRollupTime(targetID int64, windowSeconds int) (time.Time, error) {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return time.Time{}, err
+	}
 	var ns sql.NullString
-	err := d.QueryRow(`SELECT MAX(time) FROM aggregated_results WHERE target_id = ? AND window_seconds = ?`, targetID, windowSeconds).Scan(&ns)
+	err = conn.QueryRow(`SELECT MAX(time) FROM aggregated_results WHERE target_id = ? AND window_seconds = ?`, targetID, windowSeconds).Scan(&ns)
+	if err != nil {
+		return time.Time{}, err
+	}
+	if ns.Valid {
+		return parseDBTime(ns.String)
+	}
+	return time.Time{}, nil
+}
+
+// GetFirstRollupTime returns the start of the oldest stored window of the given size,
+// or the zero time if there is none.
+func (d *DB) GetFirstRollupTime(targetID int64, windowSeconds int) (time.Time, error) {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return time.Time{}, err
+	}
+	var ns sql.NullString
+	err = conn.QueryRow(`SELECT MIN(time) FROM aggregated_results WHERE target_id = ? AND window_seconds = ?`, targetID, windowSeconds).Scan(&ns)
 	if err != nil {
 		return time.Time{}, err
 	}
@@ -416,18 +956,22 @@ func (d *DB) GetLastRollupTime(targetID int64, windowSeconds int) (time.Time, er
 }
 
 func (d *DB) GetRawResults(targetID int64, start, end time.Time, limit int) ([]RawResult, error) {
-	query := `SELECT time, target_id, latency FROM raw_results
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return nil, err
+	}
+	query := `SELECT time, target_id, latency, COALESCE(loss, 0), COALESCE(ttl, 0), COALESCE(remote_ip, ''), COALESCE(status_code, 0) FROM raw_results
 		WHERE target_id = ? AND time >= ? AND time < ? ORDER BY time ASC`
 	args := []any{targetID, start, end}
 	if limit > 0 {
-		query = `SELECT time, target_id, latency FROM (
-			SELECT time, target_id, latency FROM raw_results
+		query = `SELECT time, target_id, latency, loss, ttl, remote_ip, status_code FROM (
+			SELECT time, target_id, latency, COALESCE(loss, 0) AS loss, COALESCE(ttl, 0) AS ttl, COALESCE(remote_ip, '') AS remote_ip, COALESCE(status_code, 0) AS status_code FROM raw_results
 			WHERE target_id = ? AND time >= ? AND time < ? ORDER BY time DESC LIMIT ?
 		) ORDER BY time ASC`
 		args = append(args, limit)
 	}
 
-	rows, err := d.Query(query, args...)
+	rows, err := conn.Query(query, args...)
 	if err != nil {
 		return nil, err
 	}
@@ -435,7 +979,7 @@ func (d *DB) GetRawResults(targetID int64, start, end time.Time, limit int) ([]R
 	var res []RawResult
 	for rows.Next() {
 		var r RawResult
-		if err := rows.Scan(&r.Time, &r.TargetID, &r.Latency); err != nil {
+		if err := rows.Scan(&r.Time, &r.TargetID, &r.Latency, &r.Loss, &r.TTL, &r.RemoteIP, &r.StatusCode); err != nil {
 			return nil, err
 		}
 		res = append(res, r)
@@ -443,8 +987,74 @@ func (d *DB) GetRawResults(targetID int64, start, end time.Time, limit int) ([]R
 	return res, nil
 }
 
+// GetRawResultsDownsampled returns at most maxPoints raw results spread evenly over
+// [start, end), in time order. When the range holds more, every nth result is kept.
+func (d *DB) GetRawResultsDownsampled(targetID int64, start, end time.Time, maxPoints int) ([]RawResult, error) {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return nil, err
+	}
+	var count int
+	if err := conn.QueryRow(`SELECT COUNT(*) FROM raw_results WHERE target_id = ? AND time >= ? AND time < ?`,
+		targetID, start, end).Scan(&count); err != nil {
+		return nil, err
+	}
+	step := 1
+	if maxPoints > 0 && count > maxPoints {
+		step = (count + maxPoints - 1) / maxPoints
+	}
+
+	rows, err := conn.Query(`SELECT time, target_id, latency, loss, ttl, remote_ip, status_code FROM (
+			SELECT time, target_id, latency, COALESCE(loss, 0) AS loss, COALESCE(ttl, 0) AS ttl, COALESCE(remote_ip, '') AS remote_ip, COALESCE(status_code, 0) AS status_code,
+				ROW_NUMBER() OVER (ORDER BY time) - 1 AS rn
+			FROM raw_results WHERE target_id = ? AND time >= ? AND time < ?
+		) WHERE rn % ? = 0 ORDER BY time ASC`, targetID, start, end, step)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+	var res []RawResult
+	for rows.Next() {
+		var r RawResult
+		if err := rows.Scan(&r.Time, &r.TargetID, &r.Latency, &r.Loss, &r.TTL, &r.RemoteIP, &r.StatusCode); err != nil {
+			return nil, err
+		}
+		res = append(res, r)
+	}
+	return res, rows.Err()
+}
+
+// ForEachRawResult calls fn for each raw result in [start, end) in time order without
+// loading the whole range into memory. Iteration stops at the first error from fn.
+func (d *DB) ForEachRawResult(targetID int64, start, end time.Time, fn func(RawResult) error) error {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return err
+	}
+	rows, err := conn.Query(`SELECT time, target_id, latency, COALESCE(loss, 0), COALESCE(ttl, 0), COALESCE(remote_ip, ''), COALESCE(status_code, 0) FROM raw_results
+		WHERE target_id = ? AND time >= ? AND time < ? ORDER BY time ASC`, targetID, start, end)
+	if err != nil {
+		return err
+	}
+	defer rows.Close()
+	for rows.Next() {
+		var r RawResult
+		if err := rows.Scan(&r.Time, &r.TargetID, &r.Latency, &r.Loss, &r.TTL, &r.RemoteIP, &r.StatusCode); err != nil {
+			return err
+		}
+		if err := fn(r); err != nil {
+			return err
+		}
+	}
+	return rows.Err()
+}
+
 func (d *DB) GetAggregatedResults(targetID int64, windowSeconds int, start, end time.Time) ([]AggregatedResult, error) {
-	rows, err := d.Query(`SELECT time, target_id, window_seconds, tdigest_data, timeout_count 
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return nil, err
+	}
+	rows, err := conn.Query(`SELECT time, target_id, window_seconds, tdigest_data, timeout_count, error_count, min_ns, max_ns, jitter_ns, attempt_count 
 		FROM aggregated_results 
 		WHERE target_id = ? AND window_seconds = ? AND time >= ? AND time < ? ORDER BY time ASC`, targetID, windowSeconds, start, end)
 	if err != nil {
@@ -454,7 +1064,7 @@ func (d *DB) GetAggregatedResults(targetID int64, windowSeconds int, start, end
 	var res []AggregatedResult
 	for rows.Next() {
 		var r AggregatedResult
-		if err := rows.Scan(&r.Time, &r.TargetID, &r.WindowSeconds, &r.TDigestData, &r.TimeoutCount); err != nil {
+		if err := rows.Scan(&r.Time, &r.TargetID, &r.WindowSeconds, &r.TDigestData, &r.TimeoutCount, &r.ErrorCount, &r.MinNS, &r.MaxNS, &r.JitterNS, &r.AttemptCount); err != nil {
 			return nil, err
 		}
 		res = append(res, r)
@@ -463,23 +1073,49 @@ func (d *DB) GetAggregatedResults(targetID int64, windowSeconds int, start, end
 }
 
 func (d *DB) DeleteRawResultsBefore(targetID int64, cutoff time.Time) error {
-	_, err := d.Exec(`DELETE FROM raw_results WHERE target_id = ? AND time < ?`, targetID, cutoff)
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return err
+	}
+	_, err = conn.Exec(`DELETE FROM raw_results WHERE target_id = ? AND time < ?`, targetID, cutoff)
+	return err
+}
+
+// DeleteResultsBefore deletes a target's legacy results older than cutoff.
+func (d *DB) DeleteResultsBefore(targetID int64, cutoff time.Time) error {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return err
+	}
+	_, err = conn.Exec(`DELETE FROM results WHERE target_id = ? AND time < ?`, targetID, cutoff)
 	return err
 }
 
 func (d *DB) DeleteAggregatedResultsBefore(targetID int64, windowSeconds int, cutoff time.Time) error {
-	_, err := d.Exec(`DELETE FROM aggregated_results WHERE target_id = ? AND window_seconds = ? AND time < ?`, targetID, windowSeconds, cutoff)
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return err
+	}
+	_, err = conn.Exec(`DELETE FROM aggregated_results WHERE target_id = ? AND window_seconds = ? AND time < ?`, targetID, windowSeconds, cutoff)
 	return err
 }
 
 func (d *DB) DeleteAggregatedResultsByWindow(targetID int64, windowSeconds int) error {
-	_, err := d.Exec(`DELETE FROM aggregated_results WHERE target_id = ? AND window_seconds = ?`, targetID, windowSeconds)
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return err
+	}
+	_, err = conn.Exec(`DELETE FROM aggregated_results WHERE target_id = ? AND window_seconds = ?`, targetID, windowSeconds)
 	return err
 }
 
 func (d *DB) GetEarliestRawResultTime(targetID int64) (time.Time, error) {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return time.Time{}, err
+	}
 	var ns sql.NullString
-	err := d.QueryRow(`SELECT MIN(time) FROM raw_results WHERE target_id = ?`, targetID).Scan(&ns)
+	err = conn.QueryRow(`SELECT MIN(time) FROM raw_results WHERE target_id = ?`, targetID).Scan(&ns)
 	if err != nil {
 		return time.Time{}, err
 	}
@@ -489,6 +1125,58 @@ func (d *DB) GetEarliestRawResultTime(targetID int64) (time.Time, error) {
 	return time.Time{}, nil
 }
 
+// GetLatestRawResult returns the newest successful (non-timeout) raw result for a target,
+// or nil if there is none.
+func (d *DB) GetLatestRawResult(targetID int64) (*RawResult, error) {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return nil, err
+	}
+	var r RawResult
+	err = conn.QueryRow(`SELECT time, target_id, latency, COALESCE(loss, 0), COALESCE(ttl, 0), COALESCE(remote_ip, ''), COALESCE(status_code, 0) FROM raw_results
+		WHERE target_id = ? AND latency >= 0 ORDER BY time DESC LIMIT 1`, targetID).
+		Scan(&r.Time, &r.TargetID, &r.Latency, &r.Loss, &r.TTL, &r.RemoteIP, &r.StatusCode)
+	if errors.Is(err, sql.ErrNoRows) {
+		return nil, nil
+	}
+	if err != nil {
+		return nil, err
+	}
+	return &r, nil
+}
+
+// GetLatestAggregatedResult returns the newest aggregated result for a target and window,
+// or nil if there is none.
+func (d *DB) GetLatestAggregatedResult(targetID int64, windowSeconds int) (*AggregatedResult, error) {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return nil, err
+	}
+	var r AggregatedResult
+	err = conn.QueryRow(`SELECT time, target_id, window_seconds, tdigest_data, timeout_count, error_count, min_ns, max_ns, jitter_ns, attempt_count FROM aggregated_results
+		WHERE target_id = ? AND window_seconds = ? ORDER BY time DESC LIMIT 1`, targetID, windowSeconds).
+		Scan(&r.Time, &r.TargetID, &r.WindowSeconds, &r.TDigestData, &r.TimeoutCount, &r.ErrorCount, &r.MinNS, &r.MaxNS, &r.JitterNS, &r.AttemptCount)
+	if errors.Is(err, sql.ErrNoRows) {
+		return nil, nil
+	}
+	if err != nil {
+		return nil, err
+	}
+	return &r, nil
+}
+
+// GetTimeoutTotal sums the timeouts recorded in the retained aggregated results for a window.
+func (d *DB) GetTimeoutTotal(targetID int64, windowSeconds int) (int64, error) {
+	conn, err := d.resultsDB(targetID)
+	if err != nil {
+		return 0, err
+	}
+	var total int64
+	err = conn.QueryRow(`SELECT COALESCE(SUM(timeout_count), 0) FROM aggregated_results
+		WHERE target_id = ? AND window_seconds = ?`, targetID, windowSeconds).Scan(&total)
+	return total, err
+}
+
 func parseDBTime(s string) (time.Time, error) {
 	// Try standard formats
 	// SQLite driver usually uses RFC3339Nano or similar
@@ -506,47 +1194,110 @@ func parseDBTime(s string) (time.Time, error) {
 	return time.Time{}, fmt.Errorf("failed to parse DB time: %s", s)
 }
 
+// GetDBSizeBytes returns the size of the main database plus every shard.
 func (d *DB) GetDBSizeBytes() (int64, error) {
-	var pageCount, pageSize int64
-	if err := d.QueryRow("PRAGMA page_count").Scan(&pageCount); err != nil {
+	var total int64
+	err := d.forEachResultDB(func(_ int64, conn *sql.DB) error {
+		size, err := dbSizeBytes(conn)
+		total += size
+		return err
+	})
+	return total, err
+}
+
+func dbSizeBytes(conn *sql.DB) (int64, error) {
+	pageCount, err := pragmaInt(conn, "page_count")
+	if err != nil {
 		return 0, err
 	}
-	if err := d.QueryRow("PRAGMA page_size").Scan(&pageSize); err != nil {
+	pageSize, err := pragmaInt(conn, "page_size")
+	if err != nil {
 		return 0, err
 	}
 	return pageCount * pageSize, nil
 }
 
+// GetPageCount returns the page count of the main database plus every shard.
 func (d *DB) GetPageCount() (int64, error) {
-	var count int64
-	if err := d.QueryRow("PRAGMA page_count").Scan(&count); err != nil {
-		return 0, err
-	}
-	return count, nil
+	return d.sumPragma("page_count")
 }
 
+// GetPageSize returns the page size of the main database. Shards are created with
+// the same default.
 func (d *DB) GetPageSize() (int64, error) {
-	var size int64
-	if err := d.QueryRow("PRAGMA page_size").Scan(&size); err != nil {
-		return 0, err
-	}
-	return size, nil
+	return pragmaInt(d.DB, "page_size")
 }
 
+// Ping checks that the database answers a query. Unlike sql.DB's Ping it goes
+// through SQLite itself, so a locked or unreadable file is reported.
+func (d *DB) Ping() error {
+	var one int
+	return d.QueryRow("SELECT 1").Scan(&one)
+}
+
+// GetFreelistCount returns the free pages of the main database plus every shard.
 func (d *DB) GetFreelistCount() (int64, error) {
-	var count int64
-	if err := d.QueryRow("PRAGMA freelist_count").Scan(&count); err != nil {
+	return d.sumPragma("freelist_count")
+}
+
+func pragmaInt(conn *sql.DB, pragma string) (int64, error) {
+	var v int64
+	if err := conn.QueryRow("PRAGMA " + pragma).Scan(&v); err != nil {
 		return 0, err
 	}
-	return count, nil
+	return v, nil
+}
+
+// sumPragma adds up an integer pragma over the main database and every shard.
+func (d *DB) sumPragma(pragma string) (int64, error) {
+	var total int64
+	err := d.forEachResultDB(func(_ int64, conn *sql.DB) error {
+		v, err := pragmaInt(conn, pragma)
+		total += v
+		return err
+	})
+	return total, err
 }
 
+// GetTDigestStats returns the stored size of each target's aggregated windows,
+// largest first, across the main database and every shard. Stats of targets that no
+// longer exist are left out.
 func (d *DB) GetTDigestStats() ([]TDigestStat, error) {
+	targets, err := d.GetTargets()
+	if err != nil {
+		return nil, err
+	}
+	byID := make(map[int64]Target, len(targets))
+	for _, t := range targets {
+		byID[t.ID] = t
+	}
+
+	var stats []TDigestStat
+	err = d.forEachResultDB(func(_ int64, conn *sql.DB) error {
+		dbStats, err := tdigestStats(conn, byID)
+		if err != nil {
+			return err
+		}
+		for i := range dbStats {
+			if dbStats[i].AvgCentroids, err = avgCentroids(conn, dbStats[i].TargetID, dbStats[i].WindowSeconds); err != nil {
+				return err
+			}
+		}
+		stats = append(stats, dbStats...)
+		return nil
+	})
+	if err != nil {
+		return nil, err
+	}
+	sort.SliceStable(stats, func(i, j int) bool { return stats[i].TotalBytes > stats[j].TotalBytes })
+	return stats, nil
+}
+
+func tdigestStats(conn *sql.DB, targets map[int64]Target) ([]TDigestStat, error) {
 	// Query pre-computed stats from data_stats table
 	// stat_key format: 'agg:<target_id>:<window_seconds>'
 	// We need to extract target_id and window_seconds from the key
-	// Also join with targets to get retention policies
-	rows, err := d.Query(`
+	rows, err := conn.Query(`
 		WITH parsed AS (
 			SELECT 
 				stat_key,
@@ -566,16 +1317,12 @@ func (d *DB) GetTDigestStats() ([]TDigestStat, error) {
 			FROM parsed
 		)
 		SELECT 
-			t.name,
 			e.target_id,
 			e.window_seconds,
 			e.total_bytes,
-			e.row_count,
-			COALESCE(t.retention_policies, '[]')
+			e.row_count
 		FROM extracted e
-		JOIN targets t ON e.target_id = t.id
 		WHERE e.row_count > 0
-		ORDER BY e.total_bytes DESC
 	`)
 	if err != nil {
 		return nil, err
@@ -585,10 +1332,14 @@ func (d *DB) GetTDigestStats() ([]TDigestStat, error) {
 	var stats []TDigestStat
 	for rows.Next() {
 		var s TDigestStat
-		var retentionPoliciesJSON string
-		if err := rows.Scan(&s.TargetName, &s.TargetID, &s.WindowSeconds, &s.TotalBytes, &s.Count, &retentionPoliciesJSON); err != nil {
+		if err := rows.Scan(&s.TargetID, &s.WindowSeconds, &s.TotalBytes, &s.Count); err != nil {
 			return nil, err
 		}
+		t, ok := targets[s.TargetID]
+		if !ok {
+			continue
+		}
+		s.TargetName = t.Name
 		if s.Count > 0 {
 			s.AvgBytes = float64(s.TotalBytes) / float64(s.Count)
 		}
@@ -599,7 +1350,7 @@ func (d *DB) GetTDigestStats() ([]TDigestStat, error) {
 			Retention int `json:"retention"`
 		}
 		var policies []retentionPolicy
-		json.Unmarshal([]byte(retentionPoliciesJSON), &policies)
+		json.Unmarshal([]byte(t.RetentionPolicies), &policies)
 		for _, p := range policies {
 			if p.Window == s.WindowSeconds {
 				s.RetentionSeconds = int64(p.Retention)
@@ -616,28 +1367,206 @@ func (d *DB) GetTDigestStats() ([]TDigestStat, error) {
 
 		stats = append(stats, s)
 	}
-	return stats, nil
+	return stats, rows.Err()
+}
+
+// tdigestCentroidSample is how many of the newest digests of a target's window are
+// decoded to report TDigestStat.AvgCentroids, so the status page stays fast.
+const tdigestCentroidSample = 100
+
+// avgCentroids returns the mean centroid count of the newest digests of a target's
+// window. Digests that can't be decoded are skipped.
+func avgCentroids(conn *sql.DB, targetID int64, windowSeconds int) (float64, error) {
+	rows, err := conn.Query(`SELECT tdigest_data FROM aggregated_results WHERE target_id = ? AND window_seconds = ? ORDER BY time DESC LIMIT ?`,
+		targetID, windowSeconds, tdigestCentroidSample)
+	if err != nil {
+		return 0, err
+	}
+	defer rows.Close()
+
+	var digests, centroids int
+	for rows.Next() {
+		var data []byte
+		if err := rows.Scan(&data); err != nil {
+			return 0, err
+		}
+		td, err := DeserializeTDigest(data)
+		if err != nil {
+			continue
+		}
+		digests++
+		td.ForEachCentroid(func(float64, uint64) bool {
+			centroids++
+			return true
+		})
+	}
+	if err := rows.Err(); err != nil {
+		return 0, err
+	}
+	if digests == 0 {
+		return 0, nil
+	}
+	return float64(centroids) / float64(digests), nil
 }
 
 func (d *DB) GetRawStats() (*RawStats, error) {
-	// Query pre-computed stats from data_stats table
-	var count, totalBytes int64
-	err := d.QueryRow(`
-		SELECT COALESCE(row_count, 0), COALESCE(total_bytes, 0) 
-		FROM data_stats 
-		WHERE stat_key = 'raw_count'
-	`).Scan(&count, &totalBytes)
-	if err == sql.ErrNoRows {
-		// No stats yet (empty table)
-		return &RawStats{Count: 0, TotalBytes: 0}, nil
+	stats := &RawStats{}
+	err := d.forEachResultDB(func(_ int64, conn *sql.DB) error {
+		// Query pre-computed stats from data_stats table
+		var count, totalBytes int64
+		err := conn.QueryRow(`
+			SELECT COALESCE(row_count, 0), COALESCE(total_bytes, 0) 
+			FROM data_stats 
+			WHERE stat_key = 'raw_count'
+		`).Scan(&count, &totalBytes)
+		if err == sql.ErrNoRows {
+			// No stats yet (empty table)
+			return nil
+		}
+		if err != nil {
+			return err
+		}
+		stats.Count += count
+		stats.TotalBytes += totalBytes
+		return nil
+	})
+	if err != nil {
+		return nil, err
+	}
+	return stats, nil
+}
+
+// vacuumMinFreelistBytes is the free space below which Vacuum doesn't bother
+// rewriting the database.
+const vacuumMinFreelistBytes = 1 << 20
+
+// Vacuum rebuilds the database file to return free pages to the filesystem, then
+// truncates the WAL. It rewrites the whole file and blocks writers while it runs,
+// so it is skipped when there is little free space to reclaim. With sharded storage
+// each shard is vacuumed on its own, and the report adds them up.
+func (d *DB) Vacuum() (*VacuumReport, error) {
+	if d.shards == nil {
+		return vacuum(d.DB)
 	}
+
+	total := &VacuumReport{Skipped: true}
+	var files, vacuumed int
+	err := d.forEachResultDB(func(_ int64, conn *sql.DB) error {
+		report, err := vacuum(conn)
+		if err != nil {
+			return err
+		}
+		files++
+		total.SizeBefore += report.SizeBefore
+		total.SizeAfter += report.SizeAfter
+		total.ReclaimedBytes += report.ReclaimedBytes
+		total.FreelistPages += report.FreelistPages
+		if !report.Skipped {
+			total.Skipped = false
+			vacuumed++
+		}
+		return nil
+	})
 	if err != nil {
 		return nil, err
 	}
-	return &RawStats{
-		Count:      count,
-		TotalBytes: totalBytes,
-	}, nil
+	if vacuumed < files {
+		total.Note = fmt.Sprintf("Vacuumed %d of %d database files; the others had less than %d free bytes.", vacuumed, files, vacuumMinFreelistBytes)
+	}
+	return total, nil
+}
+
+func vacuum(conn *sql.DB) (*VacuumReport, error) {
+	pageSize, err := pragmaInt(conn, "page_size")
+	if err != nil {
+		return nil, err
+	}
+	freelist, err := pragmaInt(conn, "freelist_count")
+	if err != nil {
+		return nil, err
+	}
+	before, err := dbSizeBytes(conn)
+	if err != nil {
+		return nil, err
+	}
+
+	report := &VacuumReport{SizeBefore: before, SizeAfter: before, FreelistPages: freelist}
+	if freelist*pageSize < vacuumMinFreelistBytes {
+		report.Skipped = true
+		report.Note = fmt.Sprintf("Skipped: only %d free pages (%d bytes), below the %d byte threshold.", freelist, freelist*pageSize, vacuumMinFreelistBytes)
+		return report, nil
+	}
+
+	if _, err := conn.Exec(`VACUUM`); err != nil {
+		return nil, fmt.Errorf("vacuum failed: %w", err)
+	}
+	if _, err := conn.Exec(`PRAGMA wal_checkpoint(TRUNCATE)`); err != nil {
+		return nil, fmt.Errorf("wal checkpoint failed: %w", err)
+	}
+
+	after, err := dbSizeBytes(conn)
+	if err != nil {
+		return nil, err
+	}
+	report.SizeAfter = after
+	report.ReclaimedBytes = before - after
+	return report, nil
+}
+
+// maxIntegrityProblems is the most problems IntegrityCheck reports, which is also
+// what PRAGMA integrity_check stops at for a single database.
+const maxIntegrityProblems = 100
+
+// IntegrityCheck runs PRAGMA integrity_check, which reads every page of the
+// database, so it can take a while on a large file. It stops early when ctx is done.
+// With sharded storage every shard is checked too, and their problems are prefixed
+// with the shard's file name.
+func (d *DB) IntegrityCheck(ctx context.Context) (*IntegrityReport, error) {
+	report := &IntegrityReport{Problems: []string{}}
+	err := d.forEachResultDB(func(targetID int64, conn *sql.DB) error {
+		if len(report.Problems) >= maxIntegrityProblems {
+			return nil
+		}
+		problems, err := integrityProblems(ctx, conn)
+		if err != nil {
+			return err
+		}
+		for _, p := range problems {
+			if targetID != 0 {
+				p = filepath.Base(d.shards.path(targetID)) + ": " + p
+			}
+			report.Problems = append(report.Problems, p)
+		}
+		return nil
+	})
+	if err != nil {
+		return nil, err
+	}
+	if len(report.Problems) > maxIntegrityProblems {
+		report.Problems = report.Problems[:maxIntegrityProblems]
+	}
+	report.OK = len(report.Problems) == 0
+	return report, nil
+}
+
+func integrityProblems(ctx context.Context, conn *sql.DB) ([]string, error) {
+	rows, err := conn.QueryContext(ctx, `PRAGMA integrity_check`)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	var problems []string
+	for rows.Next() {
+		var line string
+		if err := rows.Scan(&line); err != nil {
+			return nil, err
+		}
+		if line != "ok" {
+			problems = append(problems, line)
+		}
+	}
+	return problems, rows.Err()
 }
 
 const orphanedDataCleanupBatchLimit = 100000
@@ -786,6 +1715,12 @@ func (d *DB) DeleteOrphanedData() (*OrphanedDataCleanupReport, error) {
 
 	report.MoreRemaining = len(rawRowIDs) == orphanedDataCleanupBatchLimit || len(aggregatedRowIDs) == orphanedDataCleanupBatchLimit
 
+	if d.shards != nil {
+		if err := d.dropOrphanedShards(report); err != nil {
+			return nil, err
+		}
+	}
+
 	return report, nil
 }
 
@@ -1068,3 +2003,66 @@ func (d *DB) RegenerateDashboardSlug(id int64) (string, error) {
 	}
 	return slug, nil
 }
+
+func (d *DB) AddAlertRule(r *AlertRule) (int64, error) {
+	res, err := d.Exec(`INSERT INTO alert_rules (target_id, metric, comparison, threshold, window_seconds, webhook_url, channel) VALUES (?, ?, ?, ?, ?, ?, ?)`,
+		r.TargetID, r.Metric, r.Comparison, r.Threshold, r.WindowSeconds, r.WebhookURL, r.Channel)
+	if err != nil {
+		return 0, err
+	}
+	return res.LastInsertId()
+}
+
+const alertRuleColumns = `id, target_id, metric, comparison, threshold, window_seconds, webhook_url, channel, firing, last_window`
+
+func (d *DB) GetAlertRules() ([]AlertRule, error) {
+	return d.queryAlertRules(`SELECT ` + alertRuleColumns + ` FROM alert_rules ORDER BY id`)
+}
+
+func (d *DB) GetAlertRulesForTarget(targetID int64) ([]AlertRule, error) {
+	return d.queryAlertRules(`SELECT `+alertRuleColumns+` FROM alert_rules WHERE target_id = ? ORDER BY id`, targetID)
+}
+
+func (d *DB) queryAlertRules(query string, args ...any) ([]AlertRule, error) {
+	rows, err := d.Query(query, args...)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	rules := []AlertRule{}
+	for rows.Next() {
+		var r AlertRule
+		var lastWindow sql.NullTime
+		if err := rows.Scan(&r.ID, &r.TargetID, &r.Metric, &r.Comparison, &r.Threshold, &r.WindowSeconds, &r.WebhookURL, &r.Channel, &r.Firing, &lastWindow); err != nil {
+			return nil, err
+		}
+		if lastWindow.Valid {
+			t := lastWindow.Time
+			r.LastWindow = &t
+		}
+		rules = append(rules, r)
+	}
+	return rules, rows.Err()
+}
+
+func (d *DB) DeleteAlertRule(id, targetID int64) error {
+	res, err := d.Exec(`DELETE FROM alert_rules WHERE id = ? AND target_id = ?`, id, targetID)
+	if err != nil {
+		return err
+	}
+	rows, err := res.RowsAffected()
+	if err != nil {
+		return err
+	}
+	if rows == 0 {
+		return sql.ErrNoRows
+	}
+	return nil
+}
+
+// SetAlertRuleState records the outcome of evaluating the window starting at lastWindow.
+func (d *DB) SetAlertRuleState(id int64, firing bool, lastWindow time.Time) error {
+	_, err := d.Exec(`UPDATE alert_rules SET firing = ?, last_window = ? WHERE id = ?`, firing, lastWindow, id)
+	return err
+}
@@ -3,6 +3,7 @@ package db
 import (
 	"database/sql"
 	"errors"
+	"path/filepath"
 	"testing"
 	"time"
 )
@@ -80,6 +81,343 @@ func TestGetLastRollupTime(t *testing.T) {
 	}
 }
 
+func TestUpdateTargetCertExpiry(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, _ := d.AddTarget(&Target{Name: "tls", Address: "example.com:443", ProbeType: "tls"})
+
+	target, err := d.GetTarget(id)
+	if err != nil {
+		t.Fatalf("GetTarget failed: %v", err)
+	}
+	if target.CertExpiresAt != nil {
+		t.Fatalf("Expected no certificate expiry for new target, got %v", target.CertExpiresAt)
+	}
+
+	expiry := time.Now().UTC().Add(30 * 24 * time.Hour).Truncate(time.Second)
+	if err := d.UpdateTargetCertExpiry(id, expiry); err != nil {
+		t.Fatalf("UpdateTargetCertExpiry failed: %v", err)
+	}
+
+	target, err = d.GetTarget(id)
+	if err != nil {
+		t.Fatalf("GetTarget failed: %v", err)
+	}
+	if target.CertExpiresAt == nil || !target.CertExpiresAt.Equal(expiry) {
+		t.Errorf("Expected certificate expiry %v, got %v", expiry, target.CertExpiresAt)
+	}
+}
+
+func TestRawResultLoss(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, _ := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "ping"})
+	now := time.Now().UTC().Truncate(time.Second)
+	if err := d.AddRawResults([]RawResult{
+		{Time: now.Add(-2 * time.Second), TargetID: id, Latency: 100},
+		{Time: now.Add(-1 * time.Second), TargetID: id, Latency: 200, Loss: 0.4},
+	}); err != nil {
+		t.Fatalf("AddRawResults failed: %v", err)
+	}
+
+	for _, limit := range []int{0, 10} {
+		got, err := d.GetRawResults(id, now.Add(-time.Minute), now, limit)
+		if err != nil {
+			t.Fatalf("GetRawResults(limit=%d) failed: %v", limit, err)
+		}
+		if len(got) != 2 {
+			t.Fatalf("GetRawResults(limit=%d): expected 2 results, got %d", limit, len(got))
+		}
+		if got[0].Loss != 0 || got[1].Loss != 0.4 {
+			t.Errorf("GetRawResults(limit=%d): expected loss [0 0.4], got [%v %v]", limit, got[0].Loss, got[1].Loss)
+		}
+	}
+}
+
+func TestRawResultTTL(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, _ := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "ping"})
+	now := time.Now().UTC().Truncate(time.Second)
+	if err := d.AddRawResults([]RawResult{
+		{Time: now.Add(-2 * time.Second), TargetID: id, Latency: 100},
+		{Time: now.Add(-1 * time.Second), TargetID: id, Latency: 200, TTL: 57},
+	}); err != nil {
+		t.Fatalf("AddRawResults failed: %v", err)
+	}
+
+	got, err := d.GetRawResults(id, now.Add(-time.Minute), now, 10)
+	if err != nil {
+		t.Fatalf("GetRawResults failed: %v", err)
+	}
+	if len(got) != 2 || got[0].TTL != 0 || got[1].TTL != 57 {
+		t.Errorf("Expected TTLs [0 57], got %+v", got)
+	}
+	latest, err := d.GetLatestRawResult(id)
+	if err != nil || latest == nil || latest.TTL != 57 {
+		t.Errorf("Expected latest result with TTL 57, got %+v (err=%v)", latest, err)
+	}
+}
+
+func TestRawResultRemoteIP(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, _ := d.AddTarget(&Target{Name: "test", Address: "test:443", ProbeType: "tcp"})
+	now := time.Now().UTC().Truncate(time.Second)
+	if err := d.AddRawResults([]RawResult{
+		{Time: now.Add(-3 * time.Second), TargetID: id, Latency: LatencyTimeout},
+		{Time: now.Add(-2 * time.Second), TargetID: id, Latency: 100, RemoteIP: "192.0.2.1"},
+		{Time: now.Add(-1 * time.Second), TargetID: id, Latency: 200, RemoteIP: "2001:db8::1"},
+	}); err != nil {
+		t.Fatalf("AddRawResults failed: %v", err)
+	}
+
+	got, err := d.GetRawResults(id, now.Add(-time.Minute), now, 0)
+	if err != nil {
+		t.Fatalf("GetRawResults failed: %v", err)
+	}
+	if len(got) != 3 || got[0].RemoteIP != "" || got[1].RemoteIP != "192.0.2.1" || got[2].RemoteIP != "2001:db8::1" {
+		t.Errorf("Expected remote IPs [\"\" 192.0.2.1 2001:db8::1], got %+v", got)
+	}
+}
+
+func TestRawResultStatusCode(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, _ := d.AddTarget(&Target{Name: "test", Address: "example.com", ProbeType: "http"})
+	now := time.Now().UTC().Truncate(time.Second)
+	if err := d.AddRawResults([]RawResult{
+		{Time: now.Add(-2 * time.Second), TargetID: id, Latency: LatencyError},
+		{Time: now.Add(-1 * time.Second), TargetID: id, Latency: 100, StatusCode: 204},
+	}); err != nil {
+		t.Fatalf("AddRawResults failed: %v", err)
+	}
+
+	got, err := d.GetRawResults(id, now.Add(-time.Minute), now, 10)
+	if err != nil {
+		t.Fatalf("GetRawResults failed: %v", err)
+	}
+	if len(got) != 2 || got[0].StatusCode != 0 || got[1].StatusCode != 204 {
+		t.Errorf("Expected status codes [0 204], got %+v", got)
+	}
+	if latest, err := d.GetLatestRawResult(id); err != nil || latest == nil || latest.StatusCode != 204 {
+		t.Errorf("Expected latest result with status code 204, got %+v (err=%v)", latest, err)
+	}
+}
+
+func TestLegacyResultStats(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, _ := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "ping"})
+	now := time.Now().UTC().Truncate(time.Second)
+	minNS, avgNS, maxNS := 1e6, 2e6, 5e6
+	if err := d.AddResult(&Result{Time: now.Add(-time.Minute), TargetID: id, TimeoutCount: 3}); err != nil {
+		t.Fatalf("AddResult failed: %v", err)
+	}
+	if err := d.AddResult(&Result{Time: now, TargetID: id, MinNS: &minNS, AvgNS: &avgNS, MaxNS: &maxNS}); err != nil {
+		t.Fatalf("AddResult failed: %v", err)
+	}
+
+	got, err := d.GetResultsByTime(id, now.Add(-time.Hour), now)
+	if err != nil || len(got) != 2 {
+		t.Fatalf("Expected 2 results, got %d (err=%v)", len(got), err)
+	}
+	if got[0].MinNS != nil || got[0].AvgNS != nil {
+		t.Errorf("Expected no latencies for a timeout-only row, got %+v", got[0])
+	}
+	if got[1].MinNS == nil || *got[1].MinNS != minNS || got[1].AvgNS == nil || *got[1].AvgNS != avgNS || got[1].MaxNS == nil || *got[1].MaxNS != maxNS || got[1].P99NS != nil {
+		t.Errorf("Expected min/avg/max %v/%v/%v without p99, got %+v", minNS, avgNS, maxNS, got[1])
+	}
+}
+
+func TestAggregatedResultMinMax(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, _ := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "ping"})
+	now := time.Now().UTC().Truncate(time.Minute)
+	minNS, maxNS := 1.5, 900.0
+	if err := d.AddAggregatedResults([]*AggregatedResult{
+		{Time: now.Add(-2 * time.Minute), TargetID: id, WindowSeconds: 60, TimeoutCount: 3},
+		{Time: now.Add(-1 * time.Minute), TargetID: id, WindowSeconds: 60, MinNS: &minNS, MaxNS: &maxNS},
+	}); err != nil {
+		t.Fatalf("AddAggregatedResults failed: %v", err)
+	}
+
+	got, err := d.GetAggregatedResults(id, 60, now.Add(-time.Hour), now)
+	if err != nil {
+		t.Fatalf("GetAggregatedResults failed: %v", err)
+	}
+	if len(got) != 2 {
+		t.Fatalf("expected 2 results, got %d", len(got))
+	}
+	if got[0].MinNS != nil || got[0].MaxNS != nil {
+		t.Errorf("expected nil min/max for a window without successes, got %v/%v", got[0].MinNS, got[0].MaxNS)
+	}
+	if got[1].MinNS == nil || *got[1].MinNS != minNS || got[1].MaxNS == nil || *got[1].MaxNS != maxNS {
+		t.Errorf("expected min/max %v/%v, got %v/%v", minNS, maxNS, got[1].MinNS, got[1].MaxNS)
+	}
+
+	latest, err := d.GetLatestAggregatedResult(id, 60)
+	if err != nil {
+		t.Fatalf("GetLatestAggregatedResult failed: %v", err)
+	}
+	if latest == nil || latest.MaxNS == nil || *latest.MaxNS != maxNS {
+		t.Errorf("GetLatestAggregatedResult: expected max %v, got %+v", maxNS, latest)
+	}
+}
+
+func TestIntegrityCheck(t *testing.T) {
+	d, err := New(filepath.Join(t.TempDir(), "integrity.db"))
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	report, err := d.IntegrityCheck(context.Background())
+	if err != nil {
+		t.Fatalf("IntegrityCheck failed: %v", err)
+	}
+	if !report.OK || len(report.Problems) != 0 {
+		t.Errorf("expected a fresh database to be intact, got %+v", report)
+	}
+
+	ctx, cancel := context.WithCancel(context.Background())
+	cancel()
+	if _, err := d.IntegrityCheck(ctx); err == nil {
+		t.Error("expected a cancelled check to fail")
+	}
+}
+
+func TestVacuum(t *testing.T) {
+	d, err := New(filepath.Join(t.TempDir(), "vacuum.db"))
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	report, err := d.Vacuum()
+	if err != nil {
+		t.Fatalf("Vacuum failed: %v", err)
+	}
+	if !report.Skipped || report.Note == "" {
+		t.Errorf("expected vacuum of a fresh database to be skipped with a note, got %+v", report)
+	}
+
+	// Fill a few MB of rollups and delete them again, leaving free pages behind
+	id, _ := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "ping"})
+	start := time.Now().UTC().Truncate(time.Minute).Add(-time.Hour)
+	blob := make([]byte, 8192)
+	var batch []*AggregatedResult
+	for i := 0; i < 400; i++ {
+		batch = append(batch, &AggregatedResult{Time: start.Add(time.Duration(i) * time.Second), TargetID: id, WindowSeconds: 1, TDigestData: blob})
+	}
+	if err := d.AddAggregatedResults(batch); err != nil {
+		t.Fatalf("AddAggregatedResults failed: %v", err)
+	}
+	if err := d.DeleteAggregatedResultsByWindow(id, 1); err != nil {
+		t.Fatalf("DeleteAggregatedResultsByWindow failed: %v", err)
+	}
+
+	report, err = d.Vacuum()
+	if err != nil {
+		t.Fatalf("Vacuum failed: %v", err)
+	}
+	if report.Skipped {
+		t.Fatalf("expected vacuum to run with %d free pages", report.FreelistPages)
+	}
+	if report.ReclaimedBytes < vacuumMinFreelistBytes || report.SizeBefore-report.SizeAfter != report.ReclaimedBytes {
+		t.Errorf("expected at least %d bytes reclaimed, got %+v", vacuumMinFreelistBytes, report)
+	}
+	if freelist, _ := d.GetFreelistCount(); freelist != 0 {
+		t.Errorf("expected empty freelist after vacuum, got %d pages", freelist)
+	}
+}
+
+func TestTargetMaxConcurrent(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, err := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "tcp"})
+	if err != nil {
+		t.Fatalf("AddTarget failed: %v", err)
+	}
+	got, err := d.GetTarget(id)
+	if err != nil {
+		t.Fatalf("GetTarget failed: %v", err)
+	}
+	if got.MaxConcurrent != 5 {
+		t.Errorf("Expected default max concurrent 5, got %d", got.MaxConcurrent)
+	}
+
+	got.MaxConcurrent = 20
+	if err := d.UpdateTarget(got); err != nil {
+		t.Fatalf("UpdateTarget failed: %v", err)
+	}
+	got, err = d.GetTarget(id)
+	if err != nil {
+		t.Fatalf("GetTarget failed: %v", err)
+	}
+	if got.MaxConcurrent != 20 {
+		t.Errorf("Expected max concurrent 20, got %d", got.MaxConcurrent)
+	}
+}
+
+func TestAddTargets(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	targets := []*Target{
+		{Name: "one", Address: "one.example.com", ProbeType: "ping"},
+		{Name: "two", Address: "two.example.com", ProbeType: "ping", MaxConcurrent: 2, SourceAddress: "192.0.2.10"},
+	}
+	if err := d.AddTargets(targets); err != nil {
+		t.Fatalf("AddTargets failed: %v", err)
+	}
+	for _, want := range targets {
+		got, err := d.GetTarget(want.ID)
+		if err != nil {
+			t.Fatalf("GetTarget(%d) failed: %v", want.ID, err)
+		}
+		if got.Name != want.Name || got.MaxConcurrent != want.MaxConcurrent || got.AddressFamily != "auto" || got.SourceAddress != want.SourceAddress {
+			t.Errorf("Expected %+v, got %+v", want, got)
+		}
+	}
+}
+
 func TestDataStatsTriggers_RawResults(t *testing.T) {
 	d, err := New(":memory:")
 	if err != nil {
@@ -260,6 +598,155 @@ func TestDataStatsTriggers_AggregatedResults(t *testing.T) {
 	}
 }
 
+func TestSqliteDSN(t *testing.T) {
+	tests := []struct {
+		path string
+		want string
+	}{
+		{":memory:", ":memory:?_foreign_keys=on&_busy_timeout=5000"},
+		{"file::memory:?cache=shared", "file::memory:?cache=shared&_foreign_keys=on&_busy_timeout=5000"},
+		{"vaportrail.db", "vaportrail.db?_foreign_keys=on&_journal_mode=WAL&_synchronous=NORMAL&_busy_timeout=5000"},
+		{"vaportrail.db?_fk=1&_journal=DELETE&_timeout=100", "vaportrail.db?_fk=1&_journal=DELETE&_timeout=100"},
+	}
+	for _, tt := range tests {
+		if got := sqliteDSN(tt.path); got != tt.want {
+			t.Errorf("sqliteDSN(%q) = %q, want %q", tt.path, got, tt.want)
+		}
+	}
+}
+
+func TestFileDBUsesWALAndConcurrentReads(t *testing.T) {
+	d, err := New(filepath.Join(t.TempDir(), "test.db"))
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	var mode string
+	if err := d.QueryRow(`PRAGMA journal_mode`).Scan(&mode); err != nil {
+		t.Fatalf("PRAGMA journal_mode failed: %v", err)
+	}
+	if mode != "wal" {
+		t.Fatalf("Expected journal_mode wal, got %q", mode)
+	}
+
+	id, err := d.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "http"})
+	if err != nil {
+		t.Fatalf("AddTarget failed: %v", err)
+	}
+
+	// Hold an open write transaction; reads on other pooled connections must not block.
+	tx, err := d.Begin()
+	if err != nil {
+		t.Fatalf("Begin failed: %v", err)
+	}
+	defer tx.Rollback()
+	now := time.Now().UTC()
+	if _, err := tx.Exec(`INSERT INTO raw_results (time, target_id, latency) VALUES (?, ?, ?)`, now, id, 1.0); err != nil {
+		t.Fatalf("Insert failed: %v", err)
+	}
+
+	done := make(chan error, 1)
+	go func() {
+		_, err := d.GetRawResults(id, now.Add(-time.Minute), now.Add(time.Minute), 0)
+		done <- err
+	}()
+	select {
+	case err := <-done:
+		if err != nil {
+			t.Fatalf("GetRawResults during write failed: %v", err)
+		}
+	case <-time.After(2 * time.Second):
+		t.Fatal("GetRawResults blocked behind an open write transaction")
+	}
+}
+
+func TestConcurrentWritersFromTwoStores(t *testing.T) {
+	path := filepath.Join(t.TempDir(), "test.db")
+	d1, err := New(path)
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d1.Close()
+	d2, err := New(path)
+	if err != nil {
+		t.Fatalf("Failed to open second db: %v", err)
+	}
+	defer d2.Close()
+
+	id, err := d1.AddTarget(&Target{Name: "test", Address: "test", ProbeType: "http"})
+	if err != nil {
+		t.Fatalf("AddTarget failed: %v", err)
+	}
+
+	const batches, batchSize = 20, 50
+	start := time.Now().UTC().Add(-time.Hour)
+	errCh := make(chan error, 2)
+	for w, store := range []*DB{d1, d2} {
+		go func(w int, store *DB) {
+			for b := 0; b < batches; b++ {
+				batch := make([]RawResult, batchSize)
+				for i := range batch {
+					offset := time.Duration((w*batches+b)*batchSize+i) * time.Millisecond
+					batch[i] = RawResult{Time: start.Add(offset), TargetID: id, Latency: 1}
+				}
+				if err := store.AddRawResults(batch); err != nil {
+					errCh <- err
+					return
+				}
+			}
+			errCh <- nil
+		}(w, store)
+	}
+	for i := 0; i < 2; i++ {
+		if err := <-errCh; err != nil {
+			t.Fatalf("Concurrent AddRawResults failed: %v", err)
+		}
+	}
+
+	results, err := d1.GetRawResults(id, start, start.Add(time.Hour), 0)
+	if err != nil {
+		t.Fatalf("GetRawResults failed: %v", err)
+	}
+	if want := 2 * batches * batchSize; len(results) != want {
+		t.Errorf("Expected %d raw results, got %d", want, len(results))
+	}
+}
+
+func TestMigrationsTracked(t *testing.T) {
+	path := filepath.Join(t.TempDir(), "test.db")
+	d, err := New(path)
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+
+	var version int
+	var dirty bool
+	if err := d.QueryRow(`SELECT version, dirty FROM schema_migrations`).Scan(&version, &dirty); err != nil {
+		t.Fatalf("Failed to read schema_migrations: %v", err)
+	}
+	if version == 0 || dirty {
+		t.Fatalf("Expected a clean, non-zero schema version, got %d (dirty=%v)", version, dirty)
+	}
+	d.Close()
+
+	// Reopening an up-to-date database applies nothing and succeeds
+	d, err = New(path)
+	if err != nil {
+		t.Fatalf("Failed to reopen db: %v", err)
+	}
+
+	// Simulate a migration that failed part-way
+	if _, err := d.Exec(`UPDATE schema_migrations SET dirty = 1`); err != nil {
+		t.Fatalf("Failed to mark schema dirty: %v", err)
+	}
+	d.Close()
+
+	if _, err := New(path); !errors.Is(err, ErrMigration) {
+		t.Fatalf("Expected ErrMigration for dirty schema, got %v", err)
+	}
+}
+
 func TestForeignKeysEnabled(t *testing.T) {
 	d, err := New(":memory:")
 	if err != nil {
@@ -364,6 +851,44 @@ func TestDeleteTargetRemovesTimeSeriesAndGraphReferences(t *testing.T) {
 	}
 }
 
+func TestClearTargetData(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	targetID, _ := d.AddTarget(&Target{Name: "cleared", Address: "cleared", ProbeType: "http"})
+	otherID, _ := d.AddTarget(&Target{Name: "kept", Address: "kept", ProbeType: "http"})
+	now := time.Now().UTC().Truncate(time.Second)
+	for _, id := range []int64{targetID, otherID} {
+		d.AddResult(&Result{Time: now, TargetID: id, TDigestData: []byte{1}})
+		d.AddRawResults([]RawResult{{Time: now, TargetID: id, Latency: 1}, {Time: now.Add(time.Second), TargetID: id, Latency: 2}})
+		d.AddAggregatedResult(&AggregatedResult{Time: now, TargetID: id, WindowSeconds: 60, TDigestData: []byte{1}})
+	}
+
+	cleared, err := d.ClearTargetData(targetID)
+	if err != nil {
+		t.Fatalf("ClearTargetData failed: %v", err)
+	}
+	want := ClearedTargetData{RawResults: 2, AggregatedResults: 1, Results: 1}
+	if *cleared != want {
+		t.Errorf("Expected %+v, got %+v", want, *cleared)
+	}
+
+	if _, err := d.GetTarget(targetID); err != nil {
+		t.Errorf("Expected the target to be kept, got %v", err)
+	}
+	raws, _ := d.GetRawResults(targetID, now.Add(-time.Hour), now.Add(time.Hour), 0)
+	if len(raws) != 0 {
+		t.Errorf("Expected no raw results left, got %d", len(raws))
+	}
+	others, _ := d.GetRawResults(otherID, now.Add(-time.Hour), now.Add(time.Hour), 0)
+	if len(others) != 2 {
+		t.Errorf("Expected other target's raw results to be kept, got %d", len(others))
+	}
+}
+
 func TestDeleteOrphanedDataReportsAndDeletesOnlyOrphans(t *testing.T) {
 	d, err := New(":memory:")
 	if err != nil {
@@ -764,3 +1289,45 @@ func TestTDigestStats_EstimatedTotalBytes(t *testing.T) {
 		t.Errorf("Expected EstimatedTotalBytes %d, got %d", expectedEstimate3600, stat3600.EstimatedTotalBytes)
 	}
 }
+
+func TestTDigestStats_AvgCentroids(t *testing.T) {
+	d, err := New(":memory:")
+	if err != nil {
+		t.Fatalf("Failed to create db: %v", err)
+	}
+	defer d.Close()
+
+	id, err := d.AddTarget(&Target{Name: "Centroids", Address: "test", ProbeType: "http"})
+	if err != nil {
+		t.Fatalf("AddTarget failed: %v", err)
+	}
+
+	// One digest with 2 distinct values and one with 4
+	now := time.Now().UTC().Truncate(time.Minute)
+	for i, values := range [][]float64{{1, 2}, {1, 2, 3, 4}} {
+		td, _ := NewTDigest(DefaultTDigestCompression)
+		for _, v := range values {
+			td.Add(v)
+		}
+		data, _ := SerializeTDigest(td)
+		if err := d.AddAggregatedResult(&AggregatedResult{
+			Time:          now.Add(time.Duration(i) * time.Minute),
+			TargetID:      id,
+			WindowSeconds: 60,
+			TDigestData:   data,
+		}); err != nil {
+			t.Fatalf("AddAggregatedResult failed: %v", err)
+		}
+	}
+
+	tdStats, err := d.GetTDigestStats()
+	if err != nil {
+		t.Fatalf("GetTDigestStats failed: %v", err)
+	}
+	if len(tdStats) != 1 {
+		t.Fatalf("Expected 1 tdigest stat, got %d", len(tdStats))
+	}
+	if tdStats[0].AvgCentroids != 3 {
+		t.Errorf("Expected 3 centroids on average, got %v", tdStats[0].AvgCentroids)
+	}
+}
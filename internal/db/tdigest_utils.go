@@ -2,20 +2,72 @@ package db
 
 import (
 	"bytes"
+	"encoding/binary"
+	"math"
 
 	"github.com/caio/go-tdigest/v4"
 )
 
+// DefaultTDigestCompression is the compression used when a retention policy doesn't set one.
+const DefaultTDigestCompression = 100
+
+// NewTDigest returns an empty T-Digest with the given compression, or the default when zero.
+func NewTDigest(compression uint32) (*tdigest.TDigest, error) {
+	if compression == 0 {
+		compression = DefaultTDigestCompression
+	}
+	return tdigest.New(tdigest.Compression(compression))
+}
+
 // SerializeTDigest serializes the T-Digest to bytes for storage.
 func SerializeTDigest(td *tdigest.TDigest) ([]byte, error) {
 	return td.AsBytes()
 }
 
-// DeserializeTDigest deserializes bytes to a T-Digest.
+// DeserializeTDigest deserializes bytes to a T-Digest. The digest keeps the
+// compression it was serialized with, so further merges behave as they would
+// have on the original.
 func DeserializeTDigest(data []byte) (*tdigest.TDigest, error) {
 	// If empty data, return new empty digest
 	if len(data) == 0 {
-		return tdigest.New(tdigest.Compression(100))
+		return NewTDigest(DefaultTDigestCompression)
+	}
+	compression, ok := storedCompression(data)
+	if !ok {
+		compression = DefaultTDigestCompression
+	}
+	return tdigest.FromBytes(bytes.NewReader(data), tdigest.Compression(compression))
+}
+
+// storedCompression reads the compression from a serialized T-Digest. The
+// go-tdigest encoding starts with a 4-byte encoding marker followed by the
+// compression as a big-endian float64.
+func storedCompression(data []byte) (uint32, bool) {
+	if len(data) < 12 {
+		return 0, false
+	}
+	c := math.Float64frombits(binary.BigEndian.Uint64(data[4:12]))
+	if math.IsNaN(c) || c < 1 || c > math.MaxUint32 {
+		return 0, false
+	}
+	return uint32(c), true
+}
+
+// MergeTDigests combines digests into a new T-Digest with the given compression
+// (the default when zero). The centroids of every input are merged, so percentiles
+// of the result stay close to those of the original samples. Nil digests are skipped.
+func MergeTDigests(compression uint32, digests []*tdigest.TDigest) (*tdigest.TDigest, error) {
+	merged, err := NewTDigest(compression)
+	if err != nil {
+		return nil, err
+	}
+	for _, td := range digests {
+		if td == nil {
+			continue
+		}
+		if err := merged.Merge(td); err != nil {
+			return nil, err
+		}
 	}
-	return tdigest.FromBytes(bytes.NewReader(data))
+	return merged, nil
 }
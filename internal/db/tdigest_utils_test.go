@@ -0,0 +1,91 @@
+package db
+
+import (
+	"math"
+	"math/rand"
+	"sort"
+	"testing"
+
+	"github.com/caio/go-tdigest/v4"
+)
+
+func TestMergeTDigestsMatchesRawPercentiles(t *testing.T) {
+	rng := rand.New(rand.NewSource(1))
+
+	// 60 sub-windows of 100 samples each, from a long-tailed distribution
+	var raw []float64
+	var digests []*tdigest.TDigest
+	for w := 0; w < 60; w++ {
+		td, err := tdigest.New(tdigest.Compression(100))
+		if err != nil {
+			t.Fatalf("tdigest.New failed: %v", err)
+		}
+		for i := 0; i < 100; i++ {
+			v := 1e6 * math.Exp(rng.NormFloat64())
+			raw = append(raw, v)
+			td.Add(v)
+		}
+		// Round-trip through storage as the rollups do
+		data, err := SerializeTDigest(td)
+		if err != nil {
+			t.Fatalf("SerializeTDigest failed: %v", err)
+		}
+		stored, err := DeserializeTDigest(data)
+		if err != nil {
+			t.Fatalf("DeserializeTDigest failed: %v", err)
+		}
+		digests = append(digests, stored)
+	}
+	digests = append(digests, nil)
+
+	merged, err := MergeTDigests(DefaultTDigestCompression, digests)
+	if err != nil {
+		t.Fatalf("MergeTDigests failed: %v", err)
+	}
+	if merged.Count() != uint64(len(raw)) {
+		t.Fatalf("expected count %d, got %d", len(raw), merged.Count())
+	}
+
+	sort.Float64s(raw)
+	for _, q := range []float64{0.01, 0.25, 0.5, 0.75, 0.9, 0.99} {
+		// Compare ranks rather than values: the merged estimate must land
+		// within 1% of the true rank of the requested quantile
+		est := merged.Quantile(q)
+		rank := float64(sort.SearchFloat64s(raw, est)) / float64(len(raw))
+		if math.Abs(rank-q) > 0.01 {
+			t.Errorf("q=%v: merged estimate %v has rank %v in the raw samples", q, est, rank)
+		}
+	}
+}
+
+func TestDeserializeTDigestKeepsCompression(t *testing.T) {
+	for _, compression := range []uint32{50, DefaultTDigestCompression, 200} {
+		td, err := NewTDigest(compression)
+		if err != nil {
+			t.Fatalf("NewTDigest(%d) failed: %v", compression, err)
+		}
+		for i := 1; i <= 1000; i++ {
+			td.Add(float64(i))
+		}
+		data, err := SerializeTDigest(td)
+		if err != nil {
+			t.Fatalf("SerializeTDigest failed: %v", err)
+		}
+
+		got, ok := storedCompression(data)
+		if !ok || got != compression {
+			t.Errorf("storedCompression: expected %d, got %d (ok=%v)", compression, got, ok)
+		}
+
+		restored, err := DeserializeTDigest(data)
+		if err != nil {
+			t.Fatalf("DeserializeTDigest failed: %v", err)
+		}
+		if restored.Count() != td.Count() {
+			t.Errorf("compression %d: expected count %d, got %d", compression, td.Count(), restored.Count())
+		}
+		if math.Abs(restored.Quantile(0.5)-td.Quantile(0.5)) > 1 {
+			t.Errorf("compression %d: expected median %v, got %v", compression, td.Quantile(0.5), restored.Quantile(0.5))
+		}
+	}
+}
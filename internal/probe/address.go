@@ -0,0 +1,120 @@
+package probe
+
+import (
+	"errors"
+	"fmt"
+	"net"
+	"net/netip"
+	"net/url"
+	"strconv"
+	"strings"
+)
+
+// ErrInvalidAddress is returned when a target's address can't be used by its probe type.
+var ErrInvalidAddress = errors.New("invalid address")
+
+// ValidateAddress checks that address has the form probeType expects. It is purely
+// syntactic: nothing is resolved or dialed, so a well-formed host that doesn't exist
+// still passes.
+//
+//   - ping: a host
+//   - tcp, udp: host:port
+//   - tls, dns: a host, optionally with a port
+//   - http: a URL, or a host that is probed over http://
+func ValidateAddress(probeType, address string) error {
+	if address == "" {
+		return fmt.Errorf("%w: address is required", ErrInvalidAddress)
+	}
+	var err error
+	switch probeType {
+	case "ping":
+		err = validateHost(address)
+	case "tcp", "udp":
+		err = validateHostPort(address)
+	case "tls", "dns":
+		if _, _, splitErr := net.SplitHostPort(address); splitErr == nil {
+			err = validateHostPort(address)
+		} else {
+			err = validateHost(address)
+		}
+	case "http":
+		err = validateHTTPAddress(address)
+	default:
+		return nil // GetConfig reports unknown probe types
+	}
+	if err != nil {
+		return fmt.Errorf("%w %q for %s probe: %v", ErrInvalidAddress, address, probeType, err)
+	}
+	return nil
+}
+
+// validateHost accepts an IP address or a DNS hostname.
+func validateHost(host string) error {
+	if _, err := netip.ParseAddr(host); err == nil {
+		return nil
+	}
+	if strings.Contains(host, "://") {
+		return errors.New("expected a host, not a URL")
+	}
+	name := strings.TrimSuffix(host, ".")
+	if name == "" || len(name) > 253 {
+		return errors.New("expected an IP address or hostname")
+	}
+	for _, label := range strings.Split(name, ".") {
+		if !validHostLabel(label) {
+			return errors.New("expected an IP address or hostname")
+		}
+	}
+	return nil
+}
+
+// validHostLabel reports whether label is a valid hostname label. Underscores are
+// allowed, as they are common in internal names.
+func validHostLabel(label string) bool {
+	if label == "" || len(label) > 63 || label[0] == '-' || label[len(label)-1] == '-' {
+		return false
+	}
+	for _, r := range label {
+		switch {
+		case r >= 'a' && r <= 'z', r >= 'A' && r <= 'Z', r >= '0' && r <= '9', r == '-', r == '_':
+		default:
+			return false
+		}
+	}
+	return true
+}
+
+func validateHostPort(address string) error {
+	host, port, err := net.SplitHostPort(address)
+	if err != nil {
+		return errors.New("expected host:port")
+	}
+	if err := validateHost(host); err != nil {
+		return err
+	}
+	return validatePort(port)
+}
+
+func validatePort(port string) error {
+	if n, err := strconv.Atoi(port); err != nil || n < 1 || n > 65535 {
+		return fmt.Errorf("invalid port %q", port)
+	}
+	return nil
+}
+
+func validateHTTPAddress(address string) error {
+	u, err := url.Parse(httpURL(address))
+	if err != nil {
+		return errors.New("expected a URL")
+	}
+	if u.Scheme != "http" && u.Scheme != "https" {
+		return errors.New("scheme must be http or https")
+	}
+	if err := validateHost(u.Hostname()); err != nil {
+		return err
+	}
+	if port := u.Port(); port != "" {
+		return validatePort(port)
+	}
+	return nil
+}
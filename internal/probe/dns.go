@@ -0,0 +1,329 @@
+package probe
+
+import (
+	"context"
+	"encoding/binary"
+	"encoding/json"
+	"fmt"
+	"io"
+	"math/rand"
+	"net"
+	"strings"
+	"time"
+)
+
+// DNSOptions configures the "dns" probe. It is parsed from the target's probe_config.
+type DNSOptions struct {
+	// QueryName is the domain to resolve. Defaults to "example.com".
+	QueryName string `json:"query_name"`
+	// QueryType is the record type to ask for (A, AAAA, MX, TXT, ...). Defaults to "A".
+	QueryType string `json:"query_type"`
+	// ExpectAnswer fails the probe when the response has an empty answer section.
+	// Defaults to true.
+	ExpectAnswer *bool `json:"expect_answer"`
+	// ExpectedAnswer, when set, is the IP address the first A or AAAA record in the
+	// answer section must hold. Only valid for A and AAAA queries.
+	ExpectedAnswer string `json:"expected_answer"`
+
+	expectedIP net.IP
+}
+
+var dnsQueryTypes = map[string]uint16{
+	"A":     1,
+	"NS":    2,
+	"CNAME": 5,
+	"SOA":   6,
+	"PTR":   12,
+	"MX":    15,
+	"TXT":   16,
+	"AAAA":  28,
+	"SRV":   33,
+	"CAA":   257,
+}
+
+func parseDNSOptions(raw string) (DNSOptions, error) {
+	var opts DNSOptions
+	if strings.TrimSpace(raw) != "" {
+		if err := json.Unmarshal([]byte(raw), &opts); err != nil {
+			return DNSOptions{}, fmt.Errorf("%w: %v", ErrInvalidConfig, err)
+		}
+	}
+	opts = opts.withDefaults()
+	if _, ok := dnsQueryTypes[opts.QueryType]; !ok {
+		return DNSOptions{}, fmt.Errorf("%w: unknown DNS query type %q", ErrInvalidConfig, opts.QueryType)
+	}
+	if _, err := encodeDNSName(opts.QueryName); err != nil {
+		return DNSOptions{}, err
+	}
+	if opts.ExpectedAnswer != "" {
+		if opts.QueryType != "A" && opts.QueryType != "AAAA" {
+			return DNSOptions{}, fmt.Errorf("%w: expected_answer requires an A or AAAA query, got %s", ErrInvalidConfig, opts.QueryType)
+		}
+		opts.expectedIP = net.ParseIP(opts.ExpectedAnswer)
+		if opts.expectedIP == nil {
+			return DNSOptions{}, fmt.Errorf("%w: expected_answer %q is not an IP address", ErrInvalidConfig, opts.ExpectedAnswer)
+		}
+	}
+	return opts, nil
+}
+
+func (o DNSOptions) withDefaults() DNSOptions {
+	if o.QueryName == "" {
+		o.QueryName = "example.com"
+	}
+	if o.QueryType == "" {
+		o.QueryType = "A"
+	}
+	o.QueryType = strings.ToUpper(o.QueryType)
+	return o
+}
+
+func (o DNSOptions) expectAnswer() bool {
+	return o.ExpectAnswer == nil || *o.ExpectAnswer
+}
+
+// encodeDNSName converts a domain name into length-prefixed wire format labels.
+// example.com -> 7example3com0
+func encodeDNSName(name string) ([]byte, error) {
+	name = strings.TrimSuffix(name, ".")
+	if name == "" {
+		return []byte{0}, nil
+	}
+
+	var encoded []byte
+	for _, label := range strings.Split(name, ".") {
+		if len(label) == 0 || len(label) > 63 {
+			return nil, fmt.Errorf("%w: invalid DNS label %q in %q", ErrInvalidConfig, label, name)
+		}
+		encoded = append(encoded, byte(len(label)))
+		encoded = append(encoded, label...)
+	}
+	encoded = append(encoded, 0) // null terminator
+	if len(encoded) > 255 {
+		return nil, fmt.Errorf("%w: DNS name %q is too long", ErrInvalidConfig, name)
+	}
+	return encoded, nil
+}
+
+// buildDNSQuery builds a minimal single-question DNS query packet with recursion desired.
+func buildDNSQuery(txID uint16, name string, qtype uint16) ([]byte, error) {
+	// Header: 12 bytes
+	// Question: variable (domain name + type + class)
+
+	// Flags (2 bytes): standard query, recursion desired
+	// 0x0100 = recursion desired
+	flags := uint16(0x0100)
+
+	// Counts (each 2 bytes)
+	qdCount := uint16(1) // 1 question
+
+	header := make([]byte, 12)
+	header[0] = byte(txID >> 8)
+	header[1] = byte(txID)
+	header[2] = byte(flags >> 8)
+	header[3] = byte(flags)
+	header[4] = byte(qdCount >> 8)
+	header[5] = byte(qdCount)
+	// AN, NS and AR counts stay zero
+
+	question, err := encodeDNSName(name)
+	if err != nil {
+		return nil, err
+	}
+	// QTYPE, then QCLASS: IN = 1
+	question = append(question, byte(qtype>>8), byte(qtype), 0, 1)
+
+	return append(header, question...), nil
+}
+
+// dnsServerAddress returns address with the DNS port added when it has none. A bare
+// IPv6 address has no port, so it is bracketed rather than split at its last colon.
+func dnsServerAddress(address string) string {
+	if _, _, err := net.SplitHostPort(address); err == nil {
+		return address
+	}
+	return net.JoinHostPort(address, "53")
+}
+
+// runDNS queries the DNS server at address for the configured name and record type.
+// Every step, including the TCP fallback's dial, is bounded by ctx's deadline, so a
+// hung server is reported as a timeout rather than stalling the probe. Queries are
+// sent from the source IP address when set.
+func runDNS(ctx context.Context, address string, opts DNSOptions, source string) (Result, error) {
+	targetAddr := dnsServerAddress(address)
+
+	opts = opts.withDefaults()
+	qtype, ok := dnsQueryTypes[opts.QueryType]
+	if !ok {
+		return Result{}, fmt.Errorf("%w: unknown DNS query type %q", ErrInvalidConfig, opts.QueryType)
+	}
+
+	// Transaction ID (2 bytes) - random
+	txID := uint16(rand.Intn(65536))
+
+	packet, err := buildDNSQuery(txID, opts.QueryName, qtype)
+	if err != nil {
+		return Result{}, err
+	}
+
+	// 512 bytes is the standard max for UDP DNS
+	response, elapsed, remote, err := exchangeDatagram(ctx, "udp", targetAddr, source, packet, 512)
+	if err != nil {
+		return Result{}, fmt.Errorf("DNS query failed: %w", err)
+	}
+
+	// A truncated UDP answer is incomplete; retry the same query over TCP.
+	// Latency covers both attempts, as that is what a real client would see, and the
+	// remote IP is the TCP peer that gave the answer.
+	if dnsResponseTruncated(response) {
+		tcpStart := time.Now()
+		response, remote, err = queryDNSOverTCP(ctx, targetAddr, source, packet)
+		if err != nil {
+			return Result{}, fmt.Errorf("DNS TCP fallback after truncated response failed: %w", err)
+		}
+		elapsed += float64(time.Since(tcpStart).Nanoseconds())
+	}
+
+	if err := validateDNSResponse(response, txID, opts); err != nil {
+		return Result{}, err
+	}
+
+	return Result{Latency: elapsed, RemoteIP: remote}, nil
+}
+
+// dnsResponseTruncated reports whether the TC flag (bit 9 of the flags field) is set.
+func dnsResponseTruncated(response []byte) bool {
+	return len(response) >= 12 && response[2]&0x02 != 0
+}
+
+// queryDNSOverTCP sends a DNS query over TCP using the 2-byte length prefix framing
+// from RFC 1035 section 4.2.2 and returns the response message and the server's IP.
+func queryDNSOverTCP(ctx context.Context, address, source string, packet []byte) ([]byte, string, error) {
+	conn, err := dial(ctx, "tcp", address, source)
+	if err != nil {
+		return nil, "", fmt.Errorf("failed to dial DNS server over TCP: %w", err)
+	}
+	defer conn.Close()
+
+	if deadline, ok := ctx.Deadline(); ok {
+		conn.SetDeadline(deadline)
+	}
+
+	msg := make([]byte, 2+len(packet))
+	binary.BigEndian.PutUint16(msg, uint16(len(packet)))
+	copy(msg[2:], packet)
+	if _, err := conn.Write(msg); err != nil {
+		return nil, "", fmt.Errorf("failed to send DNS query over TCP: %w", err)
+	}
+
+	var lengthPrefix [2]byte
+	if _, err := io.ReadFull(conn, lengthPrefix[:]); err != nil {
+		return nil, "", fmt.Errorf("failed to read DNS TCP response length: %w", err)
+	}
+	response := make([]byte, binary.BigEndian.Uint16(lengthPrefix[:]))
+	if _, err := io.ReadFull(conn, response); err != nil {
+		return nil, "", fmt.Errorf("failed to read DNS TCP response: %w", err)
+	}
+	return response, remoteIP(conn), nil
+}
+
+// validateDNSResponse checks the header of a DNS response against the query that was sent.
+func validateDNSResponse(response []byte, txID uint16, opts DNSOptions) error {
+	// Basic validation: check we got at least a header and the transaction ID matches
+	if len(response) < 12 {
+		return fmt.Errorf("DNS response too short: %d bytes", len(response))
+	}
+	respTxID := uint16(response[0])<<8 | uint16(response[1])
+	if respTxID != txID {
+		return fmt.Errorf("DNS response transaction ID mismatch: got %d, expected %d", respTxID, txID)
+	}
+
+	// Check RCODE in flags (lower 4 bits of byte 3)
+	rcode := response[3] & 0x0F
+	if rcode != 0 {
+		return fmt.Errorf("DNS query failed with RCODE: %d", rcode)
+	}
+
+	anCount := uint16(response[6])<<8 | uint16(response[7])
+	if anCount == 0 && opts.expectAnswer() {
+		return fmt.Errorf("DNS response for %s %s has no answers", opts.QueryName, opts.QueryType)
+	}
+
+	if opts.expectedIP != nil {
+		ip, err := firstAddressAnswer(response)
+		if err != nil {
+			return err
+		}
+		if !ip.Equal(opts.expectedIP) {
+			return fmt.Errorf("DNS answer for %s %s is %s, expected %s", opts.QueryName, opts.QueryType, ip, opts.expectedIP)
+		}
+	}
+
+	return nil
+}
+
+// firstAddressAnswer returns the address in the first A or AAAA record of the answer
+// section. CNAMEs and other records before it are skipped.
+func firstAddressAnswer(response []byte) (net.IP, error) {
+	qdCount := int(binary.BigEndian.Uint16(response[4:6]))
+	anCount := int(binary.BigEndian.Uint16(response[6:8]))
+
+	offset := 12
+	for i := 0; i < qdCount; i++ {
+		var err error
+		if offset, err = skipDNSName(response, offset); err != nil {
+			return nil, err
+		}
+		offset += 4 // QTYPE, QCLASS
+	}
+
+	for i := 0; i < anCount; i++ {
+		var err error
+		if offset, err = skipDNSName(response, offset); err != nil {
+			return nil, err
+		}
+		// TYPE, CLASS, TTL and RDLENGTH
+		if offset+10 > len(response) {
+			return nil, fmt.Errorf("DNS answer record %d is truncated", i)
+		}
+		rrType := binary.BigEndian.Uint16(response[offset:])
+		rdLength := int(binary.BigEndian.Uint16(response[offset+8:]))
+		offset += 10
+		if offset+rdLength > len(response) {
+			return nil, fmt.Errorf("DNS answer record %d is truncated", i)
+		}
+		rdata := response[offset : offset+rdLength]
+		offset += rdLength
+
+		switch {
+		case rrType == dnsQueryTypes["A"] && rdLength == net.IPv4len:
+			return net.IP(rdata), nil
+		case rrType == dnsQueryTypes["AAAA"] && rdLength == net.IPv6len:
+			return net.IP(rdata), nil
+		}
+	}
+	return nil, fmt.Errorf("DNS response has no A or AAAA answer")
+}
+
+// skipDNSName returns the offset just past the name starting at offset. A compression
+// pointer (RFC 1035 section 4.1.4) ends the name, so its target is never followed.
+func skipDNSName(msg []byte, offset int) (int, error) {
+	for {
+		if offset >= len(msg) {
+			return 0, fmt.Errorf("DNS name at offset %d runs past the end of the message", offset)
+		}
+		length := int(msg[offset])
+		switch {
+		case length == 0:
+			return offset + 1, nil
+		case length&0xC0 == 0xC0:
+			if offset+2 > len(msg) {
+				return 0, fmt.Errorf("DNS compression pointer at offset %d is truncated", offset)
+			}
+			return offset + 2, nil
+		case length&0xC0 != 0:
+			return 0, fmt.Errorf("unsupported DNS label type 0x%x at offset %d", length&0xC0, offset)
+		}
+		offset += 1 + length
+	}
+}
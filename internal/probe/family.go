@@ -0,0 +1,204 @@
+package probe
+
+import (
+	"context"
+	"crypto/tls"
+	"errors"
+	"fmt"
+	"net"
+	"net/http"
+	"net/netip"
+	"strings"
+	"sync"
+	"syscall"
+	"time"
+)
+
+// Address families for Config.AddressFamily. With FamilyAuto the system resolver
+// and dialer pick the address, so a dual-stack host may be reached over either.
+const (
+	FamilyAuto = "auto"
+	FamilyIPv4 = "ipv4"
+	FamilyIPv6 = "ipv6"
+)
+
+// ValidAddressFamily reports whether f is a known address family. Empty means auto.
+func ValidAddressFamily(f string) bool {
+	switch f {
+	case "", FamilyAuto, FamilyIPv4, FamilyIPv6:
+		return true
+	}
+	return false
+}
+
+// familyNetwork restricts a "tcp" or "udp" dial network to an address family.
+func familyNetwork(network, family string) string {
+	switch family {
+	case FamilyIPv4:
+		return network + "4"
+	case FamilyIPv6:
+		return network + "6"
+	}
+	return network
+}
+
+// ValidSourceAddress reports whether s can be used as Config.SourceAddress: empty,
+// or an IP address without a port.
+func ValidSourceAddress(s string) bool {
+	if s == "" {
+		return true
+	}
+	_, err := netip.ParseAddr(s)
+	return err == nil
+}
+
+// newDialer returns a dialer for network that binds to source, an IP address, when
+// it is set. network is a "tcp" or "udp" network, optionally restricted to a family.
+func newDialer(network, source string) (*net.Dialer, error) {
+	dialer := &net.Dialer{}
+	if source == "" {
+		return dialer, nil
+	}
+	ip := net.ParseIP(source)
+	if ip == nil {
+		return nil, fmt.Errorf("%w: invalid source address %q", ErrInvalidConfig, source)
+	}
+	if strings.HasPrefix(network, "udp") {
+		dialer.LocalAddr = &net.UDPAddr{IP: ip}
+	} else {
+		dialer.LocalAddr = &net.TCPAddr{IP: ip}
+	}
+	return dialer, nil
+}
+
+// dialFrom dials address from the source IP address, or from whichever address the
+// system picks when source is empty. A source address this host doesn't have is
+// reported as ErrInvalidConfig, as retrying won't help.
+func dialFrom(ctx context.Context, dialer *net.Dialer, network, address, source string) (net.Conn, error) {
+	conn, err := dialer.DialContext(ctx, network, address)
+	if err != nil && source != "" && errors.Is(err, syscall.EADDRNOTAVAIL) {
+		return nil, fmt.Errorf("%w: source address %s is not available on this host: %v", ErrInvalidConfig, source, err)
+	}
+	return conn, err
+}
+
+// dial is dialFrom with a new dialer.
+func dial(ctx context.Context, network, address, source string) (net.Conn, error) {
+	dialer, err := newDialer(network, source)
+	if err != nil {
+		return nil, err
+	}
+	return dialFrom(ctx, dialer, network, address, source)
+}
+
+var (
+	httpClientIPv4 = newFamilyHTTPClient("tcp4", false, nil)
+	httpClientIPv6 = newFamilyHTTPClient("tcp6", false, nil)
+
+	// insecureHTTPClients skip certificate verification, for HTTPOptions.InsecureSkipVerify.
+	insecureHTTPClients = map[string]*http.Client{
+		FamilyAuto: newFamilyHTTPClient("tcp", true, nil),
+		FamilyIPv4: newFamilyHTTPClient("tcp4", true, nil),
+		FamilyIPv6: newFamilyHTTPClient("tcp6", true, nil),
+	}
+
+	// sourceHTTPClients holds the clients for source addresses, created on first use
+	// and keyed by sourceHTTPClientKey.
+	sourceHTTPClients sync.Map
+)
+
+type sourceHTTPClientKey struct {
+	family   string
+	insecure bool
+	source   string
+}
+
+// newFamilyHTTPClient returns a client like http.DefaultClient that only dials network.
+// When insecure is set it accepts any server certificate, and when source is set
+// connections are made from that address.
+func newFamilyHTTPClient(network string, insecure bool, source net.IP) *http.Client {
+	transport := http.DefaultTransport.(*http.Transport).Clone()
+	if insecure {
+		transport.TLSClientConfig = &tls.Config{InsecureSkipVerify: true}
+	}
+	dialer := &net.Dialer{Timeout: 30 * time.Second, KeepAlive: 30 * time.Second}
+	var local string
+	if source != nil {
+		dialer.LocalAddr = &net.TCPAddr{IP: source}
+		local = source.String()
+	}
+	transport.DialContext = func(ctx context.Context, _, addr string) (net.Conn, error) {
+		return dialFrom(ctx, dialer, network, addr, local)
+	}
+	return &http.Client{Transport: transport}
+}
+
+func httpClientFor(family string, insecure bool, source string) (*http.Client, error) {
+	if source != "" {
+		ip := net.ParseIP(source)
+		if ip == nil {
+			return nil, fmt.Errorf("%w: invalid source address %q", ErrInvalidConfig, source)
+		}
+		key := sourceHTTPClientKey{family: family, insecure: insecure, source: source}
+		if client, ok := sourceHTTPClients.Load(key); ok {
+			return client.(*http.Client), nil
+		}
+		client, _ := sourceHTTPClients.LoadOrStore(key, newFamilyHTTPClient(familyNetwork("tcp", family), insecure, ip))
+		return client.(*http.Client), nil
+	}
+	if insecure {
+		if client, ok := insecureHTTPClients[family]; ok {
+			return client, nil
+		}
+		return insecureHTTPClients[FamilyAuto], nil
+	}
+	switch family {
+	case FamilyIPv4:
+		return httpClientIPv4, nil
+	case FamilyIPv6:
+		return httpClientIPv6, nil
+	}
+	return http.DefaultClient, nil
+}
+
+// freshConnectionClient returns a copy of client that neither reuses pooled
+// connections nor keeps its own, so every request dials from scratch.
+func freshConnectionClient(client *http.Client) *http.Client {
+	base, ok := client.Transport.(*http.Transport)
+	if !ok {
+		base = http.DefaultTransport.(*http.Transport)
+	}
+	transport := base.Clone()
+	transport.DisableKeepAlives = true
+	fresh := *client
+	fresh.Transport = transport
+	return &fresh
+}
+
+// remoteIP returns the IP address of a connection's peer, or "" if it has none.
+func remoteIP(conn net.Conn) string {
+	host, _, err := net.SplitHostPort(conn.RemoteAddr().String())
+	if err != nil {
+		return ""
+	}
+	return host
+}
+
+// pingSourceArgs returns the ping arguments that send from source, if it is set.
+func pingSourceArgs(source string) []string {
+	if source == "" {
+		return nil
+	}
+	return []string{"-I", source}
+}
+
+// pingFamilyFlag returns the ping flag selecting an address family, if any.
+func pingFamilyFlag(family string) string {
+	switch family {
+	case FamilyIPv4:
+		return "-4"
+	case FamilyIPv6:
+		return "-6"
+	}
+	return ""
+}
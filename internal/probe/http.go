@@ -0,0 +1,244 @@
+package probe
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"fmt"
+	"io"
+	"net/http"
+	"net/http/httptrace"
+	"strings"
+	"time"
+)
+
+// HTTPOptions configures the "http" probe. It is parsed from the target's probe_config.
+type HTTPOptions struct {
+	// Method is the request method: GET, POST, PUT or HEAD. Defaults to GET.
+	Method string `json:"method"`
+	// Headers are added to the request.
+	Headers map[string]string `json:"headers"`
+	// Body is sent as the request body, if set.
+	Body string `json:"body"`
+	// ExpectedStatus is the exact status code the endpoint must return.
+	// When zero, any 2xx or 3xx status is accepted.
+	ExpectedStatus int `json:"expected_status"`
+	// ExpectedBodySubstring, when set, must appear in the first MiB of the response
+	// body.
+	ExpectedBodySubstring string `json:"expected_body_substring"`
+	// ExpectedContentType, when set, must begin the response's Content-Type header,
+	// ignoring case. "application/json" matches "application/json; charset=utf-8".
+	ExpectedContentType string `json:"expected_content_type"`
+	// FollowRedirects follows 3xx responses to their final target. When false, the
+	// redirect itself is the response that is timed and checked. Defaults to true.
+	FollowRedirects *bool `json:"follow_redirects"`
+	// Measure is "full" to time the request until the whole body has been read, or
+	// "ttfb" to stop at the response headers, leaving out the download. Defaults to full.
+	Measure string `json:"measure"`
+	// UserAgent replaces Go's default User-Agent header, including one set in Headers.
+	UserAgent string `json:"user_agent"`
+	// InsecureSkipVerify accepts any certificate from an https endpoint, such as a
+	// self-signed one. Defaults to false.
+	InsecureSkipVerify bool `json:"insecure_skip_verify"`
+	// FreshConnection opens a new connection for every request instead of reusing a
+	// pooled one, so each sample includes DNS, TCP and TLS setup. Defaults to false.
+	FreshConnection bool `json:"fresh_connection"`
+}
+
+// maxBodyCheckBytes is how much of a response body is kept to search for
+// HTTPOptions.ExpectedBodySubstring.
+const maxBodyCheckBytes = 1 << 20
+
+// Values for HTTPOptions.Measure.
+const (
+	HTTPMeasureFull = "full"
+	HTTPMeasureTTFB = "ttfb"
+)
+
+var httpMethods = map[string]bool{
+	http.MethodGet:  true,
+	http.MethodPost: true,
+	http.MethodPut:  true,
+	http.MethodHead: true,
+}
+
+func parseHTTPOptions(raw string) (HTTPOptions, error) {
+	var opts HTTPOptions
+	if strings.TrimSpace(raw) != "" {
+		if err := json.Unmarshal([]byte(raw), &opts); err != nil {
+			return HTTPOptions{}, fmt.Errorf("%w: %v", ErrInvalidConfig, err)
+		}
+	}
+	opts.Method = strings.ToUpper(opts.Method)
+	if opts.Method == "" {
+		opts.Method = http.MethodGet
+	}
+	if !httpMethods[opts.Method] {
+		return HTTPOptions{}, fmt.Errorf("%w: unsupported HTTP method %q", ErrInvalidConfig, opts.Method)
+	}
+	for name, value := range opts.Headers {
+		if !validHeaderName(name) {
+			return HTTPOptions{}, fmt.Errorf("%w: invalid HTTP header name %q", ErrInvalidConfig, name)
+		}
+		if strings.ContainsAny(value, "\r\n\x00") {
+			return HTTPOptions{}, fmt.Errorf("%w: invalid value for HTTP header %q", ErrInvalidConfig, name)
+		}
+	}
+	if strings.ContainsAny(opts.UserAgent, "\r\n\x00") {
+		return HTTPOptions{}, fmt.Errorf("%w: invalid user_agent", ErrInvalidConfig)
+	}
+	if opts.ExpectedStatus != 0 && (opts.ExpectedStatus < 100 || opts.ExpectedStatus > 599) {
+		return HTTPOptions{}, fmt.Errorf("%w: expected_status %d is not a valid HTTP status", ErrInvalidConfig, opts.ExpectedStatus)
+	}
+	opts.ExpectedContentType = strings.TrimSpace(opts.ExpectedContentType)
+	opts.Measure = strings.ToLower(opts.Measure)
+	if opts.Measure == "" {
+		opts.Measure = HTTPMeasureFull
+	}
+	if opts.Measure != HTTPMeasureFull && opts.Measure != HTTPMeasureTTFB {
+		return HTTPOptions{}, fmt.Errorf("%w: unknown measure %q (expected full or ttfb)", ErrInvalidConfig, opts.Measure)
+	}
+	return opts, nil
+}
+
+// validHeaderName reports whether name is a valid RFC 7230 token.
+func validHeaderName(name string) bool {
+	if name == "" {
+		return false
+	}
+	for _, r := range name {
+		switch {
+		case r >= 'a' && r <= 'z', r >= 'A' && r <= 'Z', r >= '0' && r <= '9':
+		case strings.ContainsRune("!#$%&'*+-.^_`|~", r):
+		default:
+			return false
+		}
+	}
+	return true
+}
+
+func (o HTTPOptions) followRedirects() bool {
+	return o.FollowRedirects == nil || *o.FollowRedirects
+}
+
+func (o HTTPOptions) statusOK(code int) bool {
+	if o.ExpectedStatus != 0 {
+		return code == o.ExpectedStatus
+	}
+	return code >= 200 && code <= 399
+}
+
+func (o HTTPOptions) contentTypeOK(contentType string) bool {
+	n := len(o.ExpectedContentType)
+	return len(contentType) >= n && strings.EqualFold(contentType[:n], o.ExpectedContentType)
+}
+
+// httpURL returns the URL an "http" target's address refers to. Addresses without a
+// scheme, such as "example.com/health", are probed over http://.
+func httpURL(address string) string {
+	if !strings.Contains(address, "://") {
+		return "http://" + address
+	}
+	return address
+}
+
+// runHTTP sends the configured request and times the response. It also reports the
+// final response's status code and the IP address of the server that sent it, even
+// when the response fails the configured checks.
+func runHTTP(ctx context.Context, address string, opts HTTPOptions, family, source string) (Result, error) {
+	address = httpURL(address)
+
+	method := opts.Method
+	if method == "" {
+		method = http.MethodGet
+	}
+	var reqBody io.Reader
+	if opts.Body != "" {
+		reqBody = strings.NewReader(opts.Body)
+	}
+
+	// The last connection used is the one that served the final response
+	var remote string
+	ctx = httptrace.WithClientTrace(ctx, &httptrace.ClientTrace{
+		GotConn: func(info httptrace.GotConnInfo) { remote = remoteIP(info.Conn) },
+	})
+	req, err := http.NewRequestWithContext(ctx, method, address, reqBody)
+	if err != nil {
+		return Result{}, err
+	}
+	for name, value := range opts.Headers {
+		// Host is not sent from req.Header, it has its own field
+		if strings.EqualFold(name, "Host") {
+			req.Host = value
+			continue
+		}
+		req.Header.Set(name, value)
+	}
+	if opts.UserAgent != "" {
+		req.Header.Set("User-Agent", opts.UserAgent)
+	}
+
+	client, err := httpClientFor(family, opts.InsecureSkipVerify, source)
+	if err != nil {
+		return Result{}, err
+	}
+	if opts.FreshConnection {
+		client = freshConnectionClient(client)
+	}
+	if !opts.followRedirects() {
+		noRedirects := *client
+		noRedirects.CheckRedirect = func(*http.Request, []*http.Request) error {
+			return http.ErrUseLastResponse
+		}
+		client = &noRedirects
+	}
+
+	start := time.Now()
+	resp, err := client.Do(req)
+	if err != nil {
+		return Result{}, err
+	}
+	defer resp.Body.Close()
+	ttfb := float64(time.Since(start).Nanoseconds())
+
+	// Read body to measure full transfer time. Only its start is kept in memory, and
+	// only when we need to search it.
+	var body bytes.Buffer
+	var readErr error
+	if opts.ExpectedBodySubstring != "" {
+		_, readErr = io.Copy(&body, io.LimitReader(resp.Body, maxBodyCheckBytes))
+	}
+	if opts.Measure == HTTPMeasureTTFB {
+		// The clock stopped at the headers. Without a body to search, reading its first
+		// byte is enough, and read errors don't fail the probe.
+		if opts.ExpectedBodySubstring == "" {
+			io.CopyN(io.Discard, resp.Body, 1)
+		}
+	} else if readErr == nil {
+		_, readErr = io.Copy(io.Discard, resp.Body)
+	}
+	elapsed := float64(time.Since(start).Nanoseconds())
+	if opts.Measure == HTTPMeasureTTFB {
+		elapsed = ttfb
+	}
+	// A response that fails its checks is still returned, so its status is recorded
+	res := Result{Latency: elapsed, RemoteIP: remote, StatusCode: resp.StatusCode}
+	if readErr != nil {
+		return res, readErr
+	}
+
+	if !opts.statusOK(resp.StatusCode) {
+		if opts.ExpectedStatus != 0 {
+			return res, fmt.Errorf("unexpected HTTP status %d (expected %d)", resp.StatusCode, opts.ExpectedStatus)
+		}
+		return res, fmt.Errorf("unexpected HTTP status %d (expected 200-399)", resp.StatusCode)
+	}
+	if contentType := resp.Header.Get("Content-Type"); !opts.contentTypeOK(contentType) {
+		return res, fmt.Errorf("unexpected Content-Type %q (expected %s)", contentType, opts.ExpectedContentType)
+	}
+	if opts.ExpectedBodySubstring != "" && !strings.Contains(body.String(), opts.ExpectedBodySubstring) {
+		return res, fmt.Errorf("HTTP response body does not contain %q", opts.ExpectedBodySubstring)
+	}
+
+	return res, nil
+}
@@ -0,0 +1,198 @@
+package probe
+
+import (
+	"encoding/json"
+	"errors"
+	"fmt"
+	"net"
+	"regexp"
+	"runtime"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// maxPingCount bounds the number of echo requests sent per probe.
+const maxPingCount = 100
+
+// pingInterval is the gap between echo requests of a multi-echo probe, passed to
+// ping -i. 200ms is the shortest interval allowed for unprivileged users.
+const pingInterval = 200 * time.Millisecond
+
+// minPingReplyWait is the least time a probe may leave for the last reply once all
+// of its echo requests are sent.
+const minPingReplyWait = time.Second
+
+// Bounds for PingOptions.PayloadSize. ping only reports a round-trip time when the
+// payload can hold its 16-byte timestamp, and 65507 bytes fill an IPv4 packet.
+const (
+	defaultPingPayloadSize = 56
+	minPingPayloadSize     = 16
+	maxPingPayloadSize     = 65507
+)
+
+// PingOptions configures the "ping" probe. It is parsed from the target's probe_config.
+type PingOptions struct {
+	// Count is the number of echo requests sent per probe. Defaults to 1.
+	Count int `json:"count"`
+	// PayloadSize is the number of data bytes in each echo request, as passed to
+	// ping -s. Defaults to 56, which makes a 64-byte ICMP packet.
+	PayloadSize int `json:"payload_size"`
+}
+
+func parsePingOptions(raw string) (PingOptions, error) {
+	var opts PingOptions
+	if strings.TrimSpace(raw) != "" {
+		if err := json.Unmarshal([]byte(raw), &opts); err != nil {
+			return PingOptions{}, fmt.Errorf("%w: %v", ErrInvalidConfig, err)
+		}
+	}
+	if opts.Count == 0 {
+		opts.Count = 1
+	}
+	if opts.Count < 1 || opts.Count > maxPingCount {
+		return PingOptions{}, fmt.Errorf("%w: ping count must be between 1 and %d, got %d", ErrInvalidConfig, maxPingCount, opts.Count)
+	}
+	if opts.PayloadSize == 0 {
+		opts.PayloadSize = defaultPingPayloadSize
+	}
+	if opts.PayloadSize < minPingPayloadSize || opts.PayloadSize > maxPingPayloadSize {
+		return PingOptions{}, fmt.Errorf("%w: ping payload_size must be between %d and %d, got %d", ErrInvalidConfig, minPingPayloadSize, maxPingPayloadSize, opts.PayloadSize)
+	}
+	return opts, nil
+}
+
+// pingReplyWait returns how long ping may wait for the last reply within timeout,
+// after spending (count-1) intervals sending its echo requests.
+func pingReplyWait(count int, timeout time.Duration) time.Duration {
+	if count <= 1 {
+		return timeout
+	}
+	return timeout - time.Duration(count-1)*pingInterval
+}
+
+// validatePingTimeout checks that a probe sending opts.Count echo requests fits in
+// timeout. Otherwise ping would be killed before it sent them all. A single echo
+// request is only bounded by the timeout itself.
+func validatePingTimeout(opts PingOptions, timeout time.Duration) error {
+	if opts.Count <= 1 {
+		return nil
+	}
+	if wait := pingReplyWait(opts.Count, timeout); wait < minPingReplyWait {
+		return fmt.Errorf("%w: %d pings %v apart need a timeout of at least %v, got %v", ErrInvalidConfig,
+			opts.Count, pingInterval, time.Duration(opts.Count-1)*pingInterval+minPingReplyWait, timeout)
+	}
+	return nil
+}
+
+// pingTimeoutArgs returns the ping arguments that bound the wait for a reply to
+// timeout, so ping reports the loss itself instead of being killed, without rounding
+// a sub-second timeout up. Linux's ping takes fractional seconds for -W; on other
+// systems -W is whole seconds or milliseconds, so only the probe's context applies.
+func pingTimeoutArgs(timeout time.Duration) []string {
+	if runtime.GOOS != "linux" || timeout <= 0 {
+		return nil
+	}
+	return []string{"-W", strconv.FormatFloat(timeout.Seconds(), 'f', -1, 64)}
+}
+
+// ErrUnreachable is wrapped by errors for echo requests answered with an ICMP
+// Destination Unreachable message, so a rejected host isn't mistaken for a slow one.
+var ErrUnreachable = errors.New("destination unreachable")
+
+// ErrTimeExceeded is wrapped by errors for echo requests that a router dropped with
+// an ICMP Time Exceeded message, which usually means a routing loop.
+var ErrTimeExceeded = errors.New("time to live exceeded")
+
+// pingICMPErrors maps the lines ping prints for ICMP errors to their sentinel, e.g.
+// "From 192.0.2.1 icmp_seq=1 Destination Host Unreachable" or, on Windows,
+// "Reply from 192.0.2.1: TTL expired in transit.".
+var pingICMPErrors = []struct {
+	pattern *regexp.Regexp
+	err     error
+}{
+	{regexp.MustCompile(`(?i)[^\n]*destination (host |net |network |port |protocol )?unreachable[^\n]*`), ErrUnreachable},
+	{regexp.MustCompile(`(?i)[^\n]*(time to live exceeded|ttl expired in transit)[^\n]*`), ErrTimeExceeded},
+}
+
+// pingICMPError returns an error describing the first ICMP error reported in ping
+// output, or nil if there is none.
+func pingICMPError(output string) error {
+	for _, e := range pingICMPErrors {
+		if line := e.pattern.FindString(output); line != "" {
+			return fmt.Errorf("%w: %s", e.err, strings.TrimSpace(line))
+		}
+	}
+	return nil
+}
+
+// pingReplyFromPattern matches the sender of a reply line, e.g. "64 bytes from
+// 192.0.2.1: icmp_seq=1", "64 bytes from host.example (2001:db8::1): icmp_seq=1" or,
+// on Windows, "Reply from 192.0.2.1: bytes=32". The address in parentheses is
+// captured separately, as the first is a name when ping resolves one.
+var pingReplyFromPattern = regexp.MustCompile(`(?i)\bfrom ([^\s(]+?)(?: \(([^\s)]+)\))?:? `)
+
+// parsePingAddress returns the IP address that sent the last reply in ping output,
+// or "" when there is none.
+func parsePingAddress(output string) string {
+	var ip string
+	for _, line := range strings.Split(output, "\n") {
+		// Only reply lines carry a round-trip time; error lines name a router
+		if !strings.Contains(line, "time=") && !strings.Contains(line, "time<") {
+			continue
+		}
+		m := pingReplyFromPattern.FindStringSubmatch(line)
+		if m == nil {
+			continue
+		}
+		addr := m[2]
+		if addr == "" {
+			addr = m[1]
+		}
+		if net.ParseIP(addr) != nil {
+			ip = addr
+		}
+	}
+	return ip
+}
+
+// pingSummaryPatterns match the statistics ping prints after its replies, capturing
+// the average round-trip time in ms: iputils' "rtt min/avg/max/mdev = 9.1/10.2/11.3/0.4 ms",
+// BusyBox's "round-trip min/avg/max = 9.1/10.2/11.3 ms", which has no deviation, and
+// Windows' "Minimum = 9ms, Maximum = 11ms, Average = 10ms".
+var pingSummaryPatterns = []*regexp.Regexp{
+	regexp.MustCompile(`(?:rtt|round-trip) min/avg/max(?:/(?:mdev|stddev))? = [0-9.]+/(?P<val>[0-9.]+)/`),
+	regexp.MustCompile(`Average = (?P<val>[0-9.]+) ?ms`),
+}
+
+// pingLossPattern matches the loss percentage of the statistics, e.g. "25% packet loss"
+// or, on Windows, "(25% loss)".
+var pingLossPattern = regexp.MustCompile(`([0-9.]+)% (?:packet )?loss`)
+
+// parsePingSummary returns the average round-trip time in ms and the loss fraction
+// from ping's statistics, for output whose reply lines couldn't be parsed. ok is false
+// when there are no statistics or no reply arrived.
+func parsePingSummary(output string) (avg, loss float64, ok bool) {
+	for _, re := range pingSummaryPatterns {
+		m := re.FindStringSubmatch(output)
+		if m == nil {
+			continue
+		}
+		v, err := strconv.ParseFloat(m[re.SubexpIndex("val")], 64)
+		if err != nil {
+			continue
+		}
+		if lm := pingLossPattern.FindStringSubmatch(output); lm != nil {
+			if pct, err := strconv.ParseFloat(lm[1], 64); err == nil {
+				loss = pct / 100
+			}
+		}
+		return v, loss, true
+	}
+	return 0, 0, false
+}
+
+// countPingDuplicates returns the number of duplicate replies in ping output.
+func countPingDuplicates(output string) int {
+	return strings.Count(output, "(DUP!)")
+}
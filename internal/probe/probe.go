@@ -1,15 +1,16 @@
 package probe
 
 import (
+	"bufio"
 	"context"
+	"encoding/json"
 	"errors"
 	"fmt"
 	"io"
-	"math/rand"
 	"net"
-	"net/http"
 	"os/exec"
 	"regexp"
+	"sort"
 	"strconv"
 	"strings"
 	"time"
@@ -17,19 +18,49 @@ import (
 
 // Runner defines the interface for running a probe.
 type Runner interface {
-	Run(cfg Config) (float64, error)
+	Run(cfg Config) (Result, error)
 }
 
 // RealRunner implements Runner using the actual system commands.
 type RealRunner struct{}
 
-func (r RealRunner) Run(cfg Config) (float64, error) {
+func (r RealRunner) Run(cfg Config) (Result, error) {
 	return Run(cfg)
 }
 
+// Result holds the measurements from a single successful probe. Each probe type
+// fills in the fields it can measure.
+type Result struct {
+	// Latency is the measured latency in nanoseconds. For multi-echo "ping"
+	// probes it is the median of the replies received.
+	Latency float64
+	// Loss is the fraction (0-1) of echo requests that got no reply, 1 when a
+	// ping times out without any. Only set by the "ping" probe; zero otherwise.
+	Loss float64
+	// CertExpiry is the NotAfter time of the peer's leaf certificate.
+	// Only set by the "tls" probe; zero otherwise.
+	CertExpiry time.Time
+	// TTL is the IP TTL of the last echo reply. Only set by the "ping" probe, and
+	// zero when ping doesn't report it.
+	TTL int
+	// Duplicates counts duplicate echo replies (marked DUP! by ping), which are left
+	// out of Latency and Loss. Only set by the "ping" probe.
+	Duplicates int
+	// RemoteIP is the IP address the probe reached, after name resolution. Set by
+	// every probe, and empty when unknown.
+	RemoteIP string
+	// StatusCode is the HTTP status of the final response. Only set by the "http"
+	// probe; zero otherwise.
+	StatusCode int
+	// SentAt is when the probe started, just before it was sent. Run also sets it
+	// when the probe fails, along with the Latency, Loss, RemoteIP and StatusCode
+	// the probe measured before failing.
+	SentAt time.Time
+}
+
 // Config defines how to run a probe.
 type Config struct {
-	Type    string `json:"type"`    // "ping", "http", "dns"
+	Type    string `json:"type"`    // "ping", "http", "dns", "tcp", "tls", "udp"
 	Address string `json:"address"` // Target address
 
 	// Deprecated fields, kept for "ping" command execution
@@ -39,10 +70,28 @@ type Config struct {
 	Multiplier float64        `json:"multiplier"`
 	Timeout    time.Duration  `json:"-"`
 	CompiledPattern *regexp.Regexp `json:"-"`
+
+	// AddressFamily restricts the ping, tcp, tls, http and udp probes to "ipv4" or
+	// "ipv6". Empty or "auto" uses whichever the system picks.
+	AddressFamily string `json:"address_family"`
+
+	// SourceAddress is the local IP address probes are sent from, for hosts with
+	// several interfaces. Empty lets the system pick.
+	SourceAddress string `json:"source_address"`
+
+	// Type-specific options parsed from the target's probe_config
+	DNS  DNSOptions  `json:"dns"`
+	HTTP HTTPOptions `json:"http"`
+	Ping PingOptions `json:"ping"`
+	UDP  UDPOptions  `json:"udp"`
 }
 
-// GetConfig returns the probe configuration for a given type and target address.
-func GetConfig(probeType, address string) (Config, error) {
+// ErrInvalidConfig is wrapped by errors caused by a malformed probe_config.
+var ErrInvalidConfig = errors.New("invalid probe config")
+
+// GetConfig returns the probe configuration for a given type, target address and
+// probe_config JSON. An empty probeConfig selects the defaults for the probe type.
+func GetConfig(probeType, address, probeConfig string) (Config, error) {
 	cfg := Config{
 		Type:    probeType,
 		Address: address,
@@ -50,17 +99,48 @@ func GetConfig(probeType, address string) (Config, error) {
 
 	switch probeType {
 	case "ping":
-		cfg.Command = "ping"
-		cfg.Args = []string{"-c", "1", address}
-		cfg.Pattern = "time=(?P<val>[0-9.]+) ms"
 		var err error
+		cfg.Ping, err = parsePingOptions(probeConfig)
+		if err != nil {
+			return Config{}, err
+		}
+		cfg.Command = "ping"
+		cfg.Args = []string{"-c", strconv.Itoa(cfg.Ping.Count)}
+		if cfg.Ping.Count > 1 {
+			cfg.Args = append(cfg.Args, "-i", strconv.FormatFloat(pingInterval.Seconds(), 'f', -1, 64))
+		}
+		if cfg.Ping.PayloadSize != defaultPingPayloadSize {
+			cfg.Args = append(cfg.Args, "-s", strconv.Itoa(cfg.Ping.PayloadSize))
+		}
+		cfg.Args = append(cfg.Args, address)
+		// Matches iputils and BusyBox "time=12.3 ms" as well as Windows "time=12ms"
+		// and "time<1ms"
+		cfg.Pattern = "time[=<](?P<val>[0-9.]+) ?ms"
 		cfg.CompiledPattern, err = regexp.Compile(cfg.Pattern)
 		if err != nil {
 			return Config{}, fmt.Errorf("failed to compile ping pattern: %w", err)
 		}
 		cfg.Multiplier = 1000000
 
-	case "http", "dns":
+	case "dns":
+		var err error
+		cfg.DNS, err = parseDNSOptions(probeConfig)
+		if err != nil {
+			return Config{}, err
+		}
+	case "http":
+		var err error
+		cfg.HTTP, err = parseHTTPOptions(probeConfig)
+		if err != nil {
+			return Config{}, err
+		}
+	case "udp":
+		var err error
+		cfg.UDP, err = parseUDPOptions(probeConfig)
+		if err != nil {
+			return Config{}, err
+		}
+	case "tcp", "tls":
 		// Native implementations don't need Command/Args/Pattern
 	default:
 		return Config{}, fmt.Errorf("unknown probe type: %s", probeType)
@@ -68,49 +148,113 @@ func GetConfig(probeType, address string) (Config, error) {
 	return cfg, nil
 }
 
-// Run executes the probe and returns the latency in nanoseconds.
-func Run(cfg Config) (float64, error) {
-	// Jitter: Sleep for a random duration between 0 and 100ms to avoid thundering herd on local resources
-	time.Sleep(time.Duration(rand.Intn(100)) * time.Millisecond)
+// ValidateProbeConfig checks a probe_config for probeType before it is saved. On top
+// of what GetConfig checks, keys the probe type doesn't know, usually typos, are
+// rejected, as are any keys for the "tcp" and "tls" probes, which take no options.
+// GetConfig itself ignores unknown keys, so configs saved before this check still run.
+func ValidateProbeConfig(probeType, probeConfig string) error {
+	if _, err := GetConfig(probeType, "", probeConfig); err != nil {
+		return err
+	}
+	if strings.TrimSpace(probeConfig) == "" {
+		return nil
+	}
+
+	var opts any
+	switch probeType {
+	case "ping":
+		opts = &PingOptions{}
+	case "dns":
+		opts = &DNSOptions{}
+	case "http":
+		opts = &HTTPOptions{}
+	case "udp":
+		opts = &UDPOptions{}
+	default:
+		opts = &struct{}{}
+	}
+	dec := json.NewDecoder(strings.NewReader(probeConfig))
+	dec.DisallowUnknownFields()
+	if err := dec.Decode(opts); err != nil {
+		return fmt.Errorf("%w: %v", ErrInvalidConfig, err)
+	}
+	return nil
+}
 
+// ValidateTimeout checks that a probe configured by probeConfig can finish within
+// timeout. Only multi-echo "ping" probes take a known minimum time; others always fit.
+func ValidateTimeout(probeType, probeConfig string, timeout time.Duration) error {
+	if probeType != "ping" {
+		return nil
+	}
+	opts, err := parsePingOptions(probeConfig)
+	if err != nil {
+		return err
+	}
+	return validatePingTimeout(opts, timeout)
+}
+
+// Run executes the probe and returns its measurements. Latency is in nanoseconds.
+func Run(cfg Config) (Result, error) {
 	ctx, cancel := context.WithTimeout(context.Background(), cfg.Timeout)
 	defer cancel()
 
-	var res float64
+	var res Result
 	var err error
 
+	sentAt := time.Now()
 	switch cfg.Type {
 	case "http":
-		res, err = runHTTP(ctx, cfg.Address)
+		res, err = runHTTP(ctx, cfg.Address, cfg.HTTP, cfg.AddressFamily, cfg.SourceAddress)
 	case "dns":
-		res, err = runDNS(ctx, cfg.Address)
+		res, err = runDNS(ctx, cfg.Address, cfg.DNS, cfg.SourceAddress)
+	case "tcp":
+		res, err = runTCP(ctx, cfg.Address, cfg.AddressFamily, cfg.SourceAddress)
+	case "tls":
+		res, err = runTLS(ctx, cfg.Address, cfg.AddressFamily, cfg.SourceAddress)
+	case "udp":
+		res, err = runUDP(ctx, cfg.Address, cfg.UDP, cfg.AddressFamily, cfg.SourceAddress)
 	case "ping":
 		res, err = runPing(ctx, cfg)
 	default:
-		return 0, fmt.Errorf("unknown probe type: %s", cfg.Type)
+		return Result{SentAt: sentAt}, fmt.Errorf("unknown probe type: %s", cfg.Type)
 	}
 
 	// If success, enforce timeout check. Sometimes net calls might return success slightly after timeout?
 	// Or maybe the precision of float64 ns vs duration?
 	// Let's be strict.
 	if err == nil {
-		if res >= float64(cfg.Timeout.Nanoseconds()) {
-			return 0, fmt.Errorf("probe timed out: duration %v exceeded limit %v", time.Duration(res), cfg.Timeout)
+		if res.Latency >= float64(cfg.Timeout.Nanoseconds()) {
+			return failedResult(res, sentAt), fmt.Errorf("probe timed out: duration %v exceeded limit %v", time.Duration(res.Latency), cfg.Timeout)
 		}
 	}
 
 	if err != nil {
 		if strings.Contains(err.Error(), "probe timed out") {
-			return 0, err
+			return failedResult(res, sentAt), err
 		}
 		if isTimeout(err) {
-			return 0, fmt.Errorf("probe timed out: %w", err)
+			return failedResult(res, sentAt), fmt.Errorf("probe timed out: %w", err)
 		}
-		return 0, err
+		return failedResult(res, sentAt), err
 	}
+	res.SentAt = sentAt
 	return res, nil
 }
 
+// failedResult keeps what a failed probe still measured, such as the status code
+// and remote address of an HTTP response that didn't pass its checks, or the loss
+// of a ping that got no reply.
+func failedResult(res Result, sentAt time.Time) Result {
+	return Result{
+		Latency:    res.Latency,
+		Loss:       res.Loss,
+		RemoteIP:   res.RemoteIP,
+		StatusCode: res.StatusCode,
+		SentAt:     sentAt,
+	}
+}
+
 func isTimeout(err error) bool {
 	if errors.Is(err, context.DeadlineExceeded) {
 		return true
@@ -122,179 +266,158 @@ func isTimeout(err error) bool {
 	return false
 }
 
-func runHTTP(ctx context.Context, address string) (float64, error) {
-	if !strings.HasPrefix(address, "http") {
-		address = "http://" + address
-	}
-
-	req, err := http.NewRequestWithContext(ctx, "GET", address, nil)
-	if err != nil {
-		return 0, err
+// runPing executes the ping command and parses the result: the latency, the
+// fraction of echo requests that were lost, the reply TTL, the replying address and
+// any duplicate replies.
+// An ICMP error for any echo request fails the probe as soon as ping reports it.
+func runPing(ctx context.Context, cfg Config) (Result, error) {
+	if flag := pingFamilyFlag(cfg.AddressFamily); flag != "" {
+		cfg.Args = append([]string{flag}, cfg.Args...)
 	}
-
-	start := time.Now()
-	resp, err := http.DefaultClient.Do(req)
-	if err != nil {
-		return 0, err
+	if args := pingSourceArgs(cfg.SourceAddress); args != nil {
+		cfg.Args = append(args, cfg.Args...)
 	}
-	defer resp.Body.Close()
-
-	// Read body to ensure we measure full transfer time
-	if _, err := io.Copy(io.Discard, resp.Body); err != nil {
-		return 0, err
+	if args := pingTimeoutArgs(pingReplyWait(cfg.Ping.Count, cfg.Timeout)); args != nil {
+		cfg.Args = append(args, cfg.Args...)
 	}
 
-	return float64(time.Since(start).Nanoseconds()), nil
-}
-
-func runDNS(ctx context.Context, address string) (float64, error) {
-	// Query the DNS server at `address` for "example.com" A record
-	// using raw DNS packet construction
-
-	targetAddr := address
-	if !strings.Contains(targetAddr, ":") {
-		targetAddr = targetAddr + ":53"
+	output, icmpErr, err := runPingCommand(ctx, cfg)
+	if icmpErr != nil {
+		return Result{}, icmpErr
 	}
-
-	// Build a minimal DNS query packet
-	// Header: 12 bytes
-	// Question: variable (domain name + type + class)
-
-	// Transaction ID (2 bytes) - random
-	txID := uint16(rand.Intn(65536))
-
-	// Flags (2 bytes): standard query, recursion desired
-	// 0x0100 = recursion desired
-	flags := uint16(0x0100)
-
-	// Counts (each 2 bytes)
-	qdCount := uint16(1) // 1 question
-	anCount := uint16(0)
-	nsCount := uint16(0)
-	arCount := uint16(0)
-
-	// Build header (12 bytes)
-	header := make([]byte, 12)
-	header[0] = byte(txID >> 8)
-	header[1] = byte(txID)
-	header[2] = byte(flags >> 8)
-	header[3] = byte(flags)
-	header[4] = byte(qdCount >> 8)
-	header[5] = byte(qdCount)
-	header[6] = byte(anCount >> 8)
-	header[7] = byte(anCount)
-	header[8] = byte(nsCount >> 8)
-	header[9] = byte(nsCount)
-	header[10] = byte(arCount >> 8)
-	header[11] = byte(arCount)
-
-	// Build question section for "example.com"
-	// Domain name encoding: length-prefixed labels, ending with 0
-	// example.com -> 7example3com0
-	domain := []byte{
-		7, 'e', 'x', 'a', 'm', 'p', 'l', 'e',
-		3, 'c', 'o', 'm',
-		0, // null terminator
+	// Some ping implementations exit non-zero on partial loss, so only
+	// treat the error as fatal when no replies could be parsed. Replies that
+	// arrived before ping was killed at the deadline count too; the echo
+	// requests still outstanding are counted as lost.
+	latency, loss, parseErr := parsePingReplies(output, cfg)
+	if parseErr != nil {
+		// No echo request got a reply, so all of them were lost
+		if err != nil && ctx.Err() == context.DeadlineExceeded {
+			return Result{Loss: 1}, fmt.Errorf("probe timed out after %v", cfg.Timeout)
+		}
+		if err != nil && cfg.SourceAddress != "" && strings.Contains(strings.ToLower(output), "cannot assign requested address") {
+			return Result{}, fmt.Errorf("%w: source address %s is not available on this host", ErrInvalidConfig, cfg.SourceAddress)
+		}
+		// ping exits with status 1 when no reply arrived within -W
+		var exitErr *exec.ExitError
+		if errors.As(err, &exitErr) && exitErr.ExitCode() == 1 {
+			return Result{Loss: 1}, fmt.Errorf("probe timed out after %v", cfg.Timeout)
+		}
+		if err != nil {
+			return Result{}, fmt.Errorf("command failed: %v, output: %s", err, output)
+		}
+		return Result{}, parseErr
 	}
+	return Result{
+		Latency:    latency,
+		Loss:       loss,
+		TTL:        parsePingTTL(output),
+		Duplicates: countPingDuplicates(output),
+		RemoteIP:   parsePingAddress(output),
+	}, nil
+}
 
-	// QTYPE: A record = 1
-	// QCLASS: IN = 1
-	question := append(domain, 0, 1, 0, 1) // type A (0x0001), class IN (0x0001)
-
-	// Complete packet
-	packet := append(header, question...)
+// runPingCommand runs ping and returns its combined output. Output is read line by
+// line so that ping can be stopped at the first ICMP error, which is returned as
+// icmpErr, rather than left waiting out the timeout. ping only prints errors that
+// quote one of its own echo requests, so errors for other processes are ignored.
+func runPingCommand(ctx context.Context, cfg Config) (output string, icmpErr error, err error) {
+	ctx, cancel := context.WithCancel(ctx)
+	defer cancel()
 
-	// Create UDP connection
-	dialer := net.Dialer{}
-	conn, err := dialer.DialContext(ctx, "udp", targetAddr)
+	cmd := exec.CommandContext(ctx, cfg.Command, cfg.Args...)
+	stdout, err := cmd.StdoutPipe()
 	if err != nil {
-		return 0, fmt.Errorf("failed to dial DNS server: %w", err)
+		return "", nil, err
 	}
-	defer conn.Close()
-
-	// Set deadline from context
-	if deadline, ok := ctx.Deadline(); ok {
-		conn.SetDeadline(deadline)
+	cmd.Stderr = cmd.Stdout
+	if err := cmd.Start(); err != nil {
+		return "", nil, fmt.Errorf("command failed: %v", err)
 	}
 
-	start := time.Now()
-
-	// Send query
-	_, err = conn.Write(packet)
-	if err != nil {
-		return 0, fmt.Errorf("failed to send DNS query: %w", err)
-	}
-
-	// Read response (512 bytes is standard max for UDP DNS)
-	response := make([]byte, 512)
-	n, err := conn.Read(response)
-	if err != nil {
-		return 0, fmt.Errorf("failed to read DNS response: %w", err)
+	var buf strings.Builder
+	scanner := bufio.NewScanner(stdout)
+	for scanner.Scan() {
+		line := scanner.Text()
+		buf.WriteString(line)
+		buf.WriteByte('\n')
+		if icmpErr = pingICMPError(line); icmpErr != nil {
+			cancel()
+			break
+		}
 	}
+	// Drain the rest so ping is never blocked writing to a full pipe
+	io.Copy(io.Discard, stdout)
+	err = cmd.Wait()
+	return buf.String(), icmpErr, err
+}
 
-	elapsed := float64(time.Since(start).Nanoseconds())
+// pingTTLPattern matches the TTL of a reply line. Windows prints it in upper case.
+var pingTTLPattern = regexp.MustCompile(`(?i)\bttl=(\d+)`)
 
-	// Basic validation: check we got at least a header and the transaction ID matches
-	if n < 12 {
-		return 0, fmt.Errorf("DNS response too short: %d bytes", n)
-	}
-	respTxID := uint16(response[0])<<8 | uint16(response[1])
-	if respTxID != txID {
-		return 0, fmt.Errorf("DNS response transaction ID mismatch: got %d, expected %d", respTxID, txID)
+// parsePingTTL returns the TTL of the last reply in ping output, or 0 when none
+// is reported.
+func parsePingTTL(output string) int {
+	matches := pingTTLPattern.FindAllStringSubmatch(output, -1)
+	if len(matches) == 0 {
+		return 0
 	}
-
-	// Check RCODE in flags (lower 4 bits of byte 3)
-	rcode := response[3] & 0x0F
-	if rcode != 0 {
-		return 0, fmt.Errorf("DNS query failed with RCODE: %d", rcode)
+	ttl, err := strconv.Atoi(matches[len(matches)-1][1])
+	if err != nil || ttl > 255 {
+		return 0
 	}
-
-	return elapsed, nil
-}
-
-// runPing executes the ping command and parses the result
-func runPing(ctx context.Context, cfg Config) (float64, error) {
-	return runCommand(ctx, cfg)
+	return ttl
 }
 
-func runCommand(ctx context.Context, cfg Config) (float64, error) {
-	cmd := exec.CommandContext(ctx, cfg.Command, cfg.Args...)
-	output, err := cmd.CombinedOutput()
-	if err != nil {
-		if ctx.Err() == context.DeadlineExceeded {
-			return 0, fmt.Errorf("probe timed out after %v", cfg.Timeout)
-		}
-		return 0, fmt.Errorf("command failed: %v, output: %s", err, string(output))
-	}
-
-	var re *regexp.Regexp
-	if cfg.CompiledPattern != nil {
-		re = cfg.CompiledPattern
-	} else {
+// parsePingReplies extracts every reply time from ping output and returns the
+// median latency in nanoseconds and the loss fraction.
+func parsePingReplies(output string, cfg Config) (float64, float64, error) {
+	re := cfg.CompiledPattern
+	if re == nil {
 		var err error
 		re, err = regexp.Compile(cfg.Pattern)
 		if err != nil {
-			return 0, fmt.Errorf("invalid regex pattern: %w", err)
+			return 0, 0, fmt.Errorf("invalid regex pattern: %w", err)
 		}
 	}
-
-	matches := re.FindStringSubmatch(string(output))
-	if matches == nil {
-		return 0, fmt.Errorf("pattern not found in output: %s", string(output))
+	valIdx := re.SubexpIndex("val")
+	if valIdx < 0 {
+		return 0, 0, fmt.Errorf("capture group 'val' not found")
 	}
 
-	valIdx := re.SubexpIndex("val")
-	if valIdx < 0 || valIdx >= len(matches) {
-		return 0, fmt.Errorf("capture group 'val' not found")
+	var samples []float64
+	for _, line := range strings.Split(output, "\n") {
+		// A duplicate is a second reply to a request that was already answered
+		if strings.Contains(line, "(DUP!)") {
+			continue
+		}
+		matches := re.FindStringSubmatch(line)
+		if matches == nil {
+			continue
+		}
+		val, err := strconv.ParseFloat(matches[valIdx], 64)
+		if err != nil {
+			return 0, 0, fmt.Errorf("failed to parse value '%s': %w", matches[valIdx], err)
+		}
+		samples = append(samples, val*cfg.Multiplier)
+	}
+	if len(samples) == 0 {
+		if avg, loss, ok := parsePingSummary(output); ok {
+			return avg * cfg.Multiplier, loss, nil
+		}
+		return 0, 0, fmt.Errorf("no ping replies in output: %s", output)
 	}
 
-	valStr := matches[valIdx]
-	val, err := strconv.ParseFloat(valStr, 64)
-	if err != nil {
-		return 0, fmt.Errorf("failed to parse value '%s': %w", valStr, err)
+	sort.Float64s(samples)
+	median := samples[len(samples)/2]
+	if len(samples)%2 == 0 {
+		median = (samples[len(samples)/2-1] + median) / 2
 	}
 
-	// Convert to nanoseconds
-	valNS := val * cfg.Multiplier
-	return valNS, nil
+	loss := 1 - float64(len(samples))/float64(cfg.Ping.Count)
+	if loss < 0 {
+		// More replies than requests, e.g. from a custom pattern
+		loss = 0
+	}
+	return median, loss, nil
 }
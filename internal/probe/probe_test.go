@@ -1,17 +1,30 @@
 package probe
 
 import (
+	"bytes"
+	"context"
+	"encoding/binary"
+	"errors"
+	"io"
+	"math"
+	"net"
+	"net/http"
+	"net/http/httptest"
+	"runtime"
+	"strings"
+	"sync"
 	"testing"
 	"time"
 )
 
 func TestGetConfig(t *testing.T) {
 	tests := []struct {
-		name      string
-		probeType string
-		address   string
-		wantErr   bool
-		check     func(*testing.T, Config)
+		name        string
+		probeType   string
+		address     string
+		probeConfig string
+		wantErr     bool
+		check       func(*testing.T, Config)
 	}{
 		{
 			name:      "Valid Ping",
@@ -31,6 +44,56 @@ func TestGetConfig(t *testing.T) {
 				}
 			},
 		},
+		{
+			name:        "Ping With Count",
+			probeType:   "ping",
+			address:     "1.1.1.1",
+			probeConfig: `{"count": 5}`,
+			wantErr:     false,
+			check: func(t *testing.T, c Config) {
+				if c.Ping.Count != 5 {
+					t.Errorf("expected count 5, got %d", c.Ping.Count)
+				}
+				want := []string{"-c", "5", "-i", "0.2", "1.1.1.1"}
+				if strings.Join(c.Args, " ") != strings.Join(want, " ") {
+					t.Errorf("expected args %v, got %v", want, c.Args)
+				}
+			},
+		},
+		{
+			name:        "Ping With Payload Size",
+			probeType:   "ping",
+			address:     "1.1.1.1",
+			probeConfig: `{"payload_size": 1472}`,
+			wantErr:     false,
+			check: func(t *testing.T, c Config) {
+				want := []string{"-c", "1", "-s", "1472", "1.1.1.1"}
+				if strings.Join(c.Args, " ") != strings.Join(want, " ") {
+					t.Errorf("expected args %v, got %v", want, c.Args)
+				}
+			},
+		},
+		{
+			name:        "Ping Payload Size Too Small",
+			probeType:   "ping",
+			address:     "1.1.1.1",
+			probeConfig: `{"payload_size": 8}`,
+			wantErr:     true,
+		},
+		{
+			name:        "Ping Payload Size Too Large",
+			probeType:   "ping",
+			address:     "1.1.1.1",
+			probeConfig: `{"payload_size": 70000}`,
+			wantErr:     true,
+		},
+		{
+			name:        "Ping Count Out Of Range",
+			probeType:   "ping",
+			address:     "1.1.1.1",
+			probeConfig: `{"count": 1000}`,
+			wantErr:     true,
+		},
 		{
 			name:      "Valid HTTP",
 			probeType: "http",
@@ -43,8 +106,61 @@ func TestGetConfig(t *testing.T) {
 				if c.Command != "" {
 					t.Errorf("expected empty command for http, got %s", c.Command)
 				}
+				if c.HTTP.Method != "GET" {
+					t.Errorf("expected default method GET, got %s", c.HTTP.Method)
+				}
+				if c.HTTP.Measure != HTTPMeasureFull {
+					t.Errorf("expected default measure full, got %s", c.HTTP.Measure)
+				}
 			},
 		},
+		{
+			name:        "HTTP With Expectations",
+			probeType:   "http",
+			address:     "http://google.com",
+			probeConfig: `{"expected_status": 204, "expected_body_substring": "ok"}`,
+			wantErr:     false,
+			check: func(t *testing.T, c Config) {
+				if c.HTTP.ExpectedStatus != 204 || c.HTTP.ExpectedBodySubstring != "ok" {
+					t.Errorf("expected status 204 and substring ok, got %+v", c.HTTP)
+				}
+			},
+		},
+		{
+			name:        "HTTP Invalid Expected Status",
+			probeType:   "http",
+			address:     "http://google.com",
+			probeConfig: `{"expected_status": 42}`,
+			wantErr:     true,
+		},
+		{
+			name:        "HTTP Unsupported Method",
+			probeType:   "http",
+			address:     "http://google.com",
+			probeConfig: `{"method": "DELETE"}`,
+			wantErr:     true,
+		},
+		{
+			name:        "HTTP Invalid Header Name",
+			probeType:   "http",
+			address:     "http://google.com",
+			probeConfig: `{"headers": {"Bad Header": "x"}}`,
+			wantErr:     true,
+		},
+		{
+			name:        "HTTP Invalid Header Value",
+			probeType:   "http",
+			address:     "http://google.com",
+			probeConfig: `{"headers": {"X-Token": "a\r\nInjected: 1"}}`,
+			wantErr:     true,
+		},
+		{
+			name:        "HTTP Unknown Measure",
+			probeType:   "http",
+			address:     "http://google.com",
+			probeConfig: `{"measure": "headers"}`,
+			wantErr:     true,
+		},
 		{
 			name:      "Valid DNS",
 			probeType: "dns",
@@ -60,8 +176,110 @@ func TestGetConfig(t *testing.T) {
 				if c.Address != "8.8.8.8" {
 					t.Errorf("expected address 8.8.8.8, got %s", c.Address)
 				}
+				if c.DNS.QueryName != "example.com" || c.DNS.QueryType != "A" {
+					t.Errorf("expected default query example.com A, got %s %s", c.DNS.QueryName, c.DNS.QueryType)
+				}
 			},
 		},
+		{
+			name:        "DNS With Query Config",
+			probeType:   "dns",
+			address:     "8.8.8.8",
+			probeConfig: `{"query_name": "example.org.", "query_type": "mx"}`,
+			wantErr:     false,
+			check: func(t *testing.T, c Config) {
+				if c.DNS.QueryName != "example.org." || c.DNS.QueryType != "MX" {
+					t.Errorf("expected query example.org. MX, got %s %s", c.DNS.QueryName, c.DNS.QueryType)
+				}
+			},
+		},
+		{
+			name:        "DNS Unknown Query Type",
+			probeType:   "dns",
+			address:     "8.8.8.8",
+			probeConfig: `{"query_type": "BOGUS"}`,
+			wantErr:     true,
+		},
+		{
+			name:        "DNS Malformed Config",
+			probeType:   "dns",
+			address:     "8.8.8.8",
+			probeConfig: `{not json`,
+			wantErr:     true,
+		},
+		{
+			name:      "Valid TCP",
+			probeType: "tcp",
+			address:   "example.com:443",
+			wantErr:   false,
+			check: func(t *testing.T, c Config) {
+				if c.Type != "tcp" {
+					t.Errorf("expected type tcp, got %s", c.Type)
+				}
+				if c.Command != "" {
+					t.Errorf("expected empty command for tcp, got %s", c.Command)
+				}
+			},
+		},
+		{
+			name:      "Valid TLS",
+			probeType: "tls",
+			address:   "example.com:443",
+			wantErr:   false,
+			check: func(t *testing.T, c Config) {
+				if c.Type != "tls" {
+					t.Errorf("expected type tls, got %s", c.Type)
+				}
+			},
+		},
+		{
+			name:        "Valid UDP",
+			probeType:   "udp",
+			address:     "10.0.0.1:9999",
+			probeConfig: `{"payload": "cafe01", "expect_prefix": "CAFE"}`,
+			wantErr:     false,
+			check: func(t *testing.T, c Config) {
+				if !bytes.Equal(c.UDP.payload, []byte{0xca, 0xfe, 0x01}) {
+					t.Errorf("expected payload cafe01, got %x", c.UDP.payload)
+				}
+				if !bytes.Equal(c.UDP.expectPrefix, []byte{0xca, 0xfe}) {
+					t.Errorf("expected prefix cafe, got %x", c.UDP.expectPrefix)
+				}
+			},
+		},
+		{
+			name:        "UDP Base64 Payload",
+			probeType:   "udp",
+			address:     "10.0.0.1:9999",
+			probeConfig: `{"payload": "aGVsbG8=", "encoding": "base64"}`,
+			wantErr:     false,
+			check: func(t *testing.T, c Config) {
+				if string(c.UDP.payload) != "hello" {
+					t.Errorf("expected payload hello, got %q", c.UDP.payload)
+				}
+			},
+		},
+		{
+			name:        "UDP Missing Payload",
+			probeType:   "udp",
+			address:     "10.0.0.1:9999",
+			probeConfig: `{"expect_prefix": "00"}`,
+			wantErr:     true,
+		},
+		{
+			name:        "UDP Invalid Hex",
+			probeType:   "udp",
+			address:     "10.0.0.1:9999",
+			probeConfig: `{"payload": "xyz"}`,
+			wantErr:     true,
+		},
+		{
+			name:        "UDP Unknown Encoding",
+			probeType:   "udp",
+			address:     "10.0.0.1:9999",
+			probeConfig: `{"payload": "00", "encoding": "rot13"}`,
+			wantErr:     true,
+		},
 		{
 			name:      "Invalid Type",
 			probeType: "rm -rf /",
@@ -73,7 +291,7 @@ func TestGetConfig(t *testing.T) {
 
 	for _, tt := range tests {
 		t.Run(tt.name, func(t *testing.T) {
-			got, err := GetConfig(tt.probeType, tt.address)
+			got, err := GetConfig(tt.probeType, tt.address, tt.probeConfig)
 			if (err != nil) != tt.wantErr {
 				t.Errorf("GetConfig() error = %v, wantErr %v", err, tt.wantErr)
 				return
@@ -85,6 +303,527 @@ func TestGetConfig(t *testing.T) {
 	}
 }
 
+func TestRunHTTP_Expectations(t *testing.T) {
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		switch r.URL.Path {
+		case "/error":
+			w.WriteHeader(http.StatusInternalServerError)
+		case "/created":
+			w.WriteHeader(http.StatusCreated)
+		case "/login":
+			http.Redirect(w, r, "/created", http.StatusFound)
+			return
+		case "/json":
+			w.Header().Set("Content-Type", "application/json; charset=utf-8")
+		case "/large":
+			w.Write(bytes.Repeat([]byte("x"), 2*maxBodyCheckBytes))
+		}
+		w.Write([]byte("status: healthy"))
+	}))
+	defer srv.Close()
+
+	noFollow := false
+	tests := []struct {
+		name    string
+		path    string
+		opts    HTTPOptions
+		wantErr bool
+	}{
+		{name: "Default OK", path: "/", wantErr: false},
+		{name: "Default Server Error", path: "/error", wantErr: true},
+		{name: "Exact Status Match", path: "/created", opts: HTTPOptions{ExpectedStatus: 201}, wantErr: false},
+		{name: "Exact Status Mismatch", path: "/", opts: HTTPOptions{ExpectedStatus: 201}, wantErr: true},
+		{name: "Expected 500", path: "/error", opts: HTTPOptions{ExpectedStatus: 500}, wantErr: false},
+		{name: "Body Substring Present", path: "/", opts: HTTPOptions{ExpectedBodySubstring: "healthy"}, wantErr: false},
+		{name: "Body Substring Missing", path: "/", opts: HTTPOptions{ExpectedBodySubstring: "degraded"}, wantErr: true},
+		{name: "Body Substring Past Search Limit", path: "/large", opts: HTTPOptions{ExpectedBodySubstring: "healthy"}, wantErr: true},
+		{name: "Body Substring Past Search Limit TTFB", path: "/large", opts: HTTPOptions{ExpectedBodySubstring: "healthy", Measure: HTTPMeasureTTFB}, wantErr: true},
+		{name: "Redirect Followed", path: "/login", opts: HTTPOptions{ExpectedStatus: 201}, wantErr: false},
+		{name: "Redirect Not Followed", path: "/login", opts: HTTPOptions{ExpectedStatus: 302, FollowRedirects: &noFollow}, wantErr: false},
+		{name: "Redirect Not Followed Mismatch", path: "/login", opts: HTTPOptions{ExpectedStatus: 201, FollowRedirects: &noFollow}, wantErr: true},
+		{name: "Content Type Match", path: "/json", opts: HTTPOptions{ExpectedContentType: "Application/JSON"}, wantErr: false},
+		{name: "Content Type Mismatch", path: "/", opts: HTTPOptions{ExpectedContentType: "application/json"}, wantErr: true},
+	}
+
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			cfg := Config{
+				Type:    "http",
+				Address: srv.URL + tt.path,
+				Timeout: 2 * time.Second,
+				HTTP:    tt.opts,
+			}
+			res, err := Run(cfg)
+			if (err != nil) != tt.wantErr {
+				t.Errorf("Run(http) error = %v, wantErr %v", err, tt.wantErr)
+			}
+			if err == nil && tt.opts.ExpectedStatus != 0 && res.StatusCode != tt.opts.ExpectedStatus {
+				t.Errorf("expected status code %d, got %d", tt.opts.ExpectedStatus, res.StatusCode)
+			}
+			// A response that fails its checks still reports where it came from
+			if err != nil && (res.StatusCode == 0 || res.RemoteIP != "127.0.0.1") {
+				t.Errorf("expected the failed response's status and remote IP, got %+v", res)
+			}
+		})
+	}
+}
+
+func TestRunHTTP_MethodHeadersBody(t *testing.T) {
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		body, _ := io.ReadAll(r.Body)
+		if r.Method != http.MethodPost || r.Header.Get("Authorization") != "Bearer secret" || string(body) != `{"ping":true}` {
+			w.WriteHeader(http.StatusBadRequest)
+			return
+		}
+		w.WriteHeader(http.StatusOK)
+	}))
+	defer srv.Close()
+
+	opts, err := parseHTTPOptions(`{"method": "post", "headers": {"Authorization": "Bearer secret"}, "body": "{\"ping\":true}"}`)
+	if err != nil {
+		t.Fatalf("parseHTTPOptions failed: %v", err)
+	}
+	cfg := Config{
+		Type:    "http",
+		Address: srv.URL,
+		Timeout: 2 * time.Second,
+		HTTP:    opts,
+	}
+	if _, err := Run(cfg); err != nil {
+		t.Errorf("Run(http) with method, headers and body failed: %v", err)
+	}
+}
+
+func TestRunHTTP_Measure(t *testing.T) {
+	// The headers arrive at once and the body 300ms later
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		w.WriteHeader(http.StatusOK)
+		w.(http.Flusher).Flush()
+		time.Sleep(300 * time.Millisecond)
+		w.Write([]byte("status: healthy"))
+	}))
+	defer srv.Close()
+
+	tests := []struct {
+		name     string
+		opts     HTTPOptions
+		wantSlow bool
+	}{
+		{"full", HTTPOptions{Measure: HTTPMeasureFull}, true},
+		{"ttfb", HTTPOptions{Measure: HTTPMeasureTTFB}, false},
+		{"ttfb with body check", HTTPOptions{Measure: HTTPMeasureTTFB, ExpectedBodySubstring: "healthy"}, false},
+	}
+	for _, tt := range tests {
+		res, err := Run(Config{Type: "http", Address: srv.URL, Timeout: 2 * time.Second, HTTP: tt.opts})
+		if err != nil {
+			t.Fatalf("%s: Run(http) failed: %v", tt.name, err)
+		}
+		if slow := res.Latency >= float64(300*time.Millisecond); slow != tt.wantSlow {
+			t.Errorf("%s: expected the body download timed = %v, got latency %v", tt.name, tt.wantSlow, time.Duration(res.Latency))
+		}
+	}
+}
+
+func TestRunHTTP_UserAgentAndInsecureSkipVerify(t *testing.T) {
+	// httptest's certificate is self-signed
+	srv := httptest.NewTLSServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.UserAgent() != "vaportrail-probe" {
+			w.WriteHeader(http.StatusForbidden)
+		}
+	}))
+	defer srv.Close()
+
+	if _, err := Run(Config{Type: "http", Address: srv.URL, Timeout: 2 * time.Second, HTTP: HTTPOptions{UserAgent: "vaportrail-probe"}}); err == nil {
+		t.Error("Expected an untrusted certificate error without insecure_skip_verify")
+	}
+
+	opts, err := parseHTTPOptions(`{"user_agent": "vaportrail-probe", "insecure_skip_verify": true}`)
+	if err != nil {
+		t.Fatalf("parseHTTPOptions failed: %v", err)
+	}
+	if _, err := Run(Config{Type: "http", Address: srv.URL, Timeout: 2 * time.Second, HTTP: opts}); err != nil {
+		t.Errorf("Run(http) with insecure_skip_verify and user_agent failed: %v", err)
+	}
+
+	if _, err := parseHTTPOptions(`{"user_agent": "probe\r\nX-Injected: 1"}`); !errors.Is(err, ErrInvalidConfig) {
+		t.Errorf("Expected ErrInvalidConfig for a user_agent with a line break, got %v", err)
+	}
+}
+
+func TestRunHTTP_FreshConnection(t *testing.T) {
+	var mu sync.Mutex
+	conns := make(map[string]bool)
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		mu.Lock()
+		conns[r.RemoteAddr] = true
+		mu.Unlock()
+		w.Write([]byte("ok"))
+	}))
+	defer srv.Close()
+
+	probe := func(opts HTTPOptions) int {
+		t.Helper()
+		mu.Lock()
+		clear(conns)
+		mu.Unlock()
+		for i := 0; i < 3; i++ {
+			if _, err := Run(Config{Type: "http", Address: srv.URL, Timeout: 2 * time.Second, HTTP: opts}); err != nil {
+				t.Fatalf("Run(http) failed: %v", err)
+			}
+		}
+		mu.Lock()
+		defer mu.Unlock()
+		return len(conns)
+	}
+
+	if n := probe(HTTPOptions{}); n != 1 {
+		t.Errorf("Expected pooled probes to share one connection, got %d", n)
+	}
+	// The body is left unread by the measurement, but must still be drained
+	if n := probe(HTTPOptions{Measure: HTTPMeasureTTFB}); n != 1 {
+		t.Errorf("Expected pooled ttfb probes to share one connection, got %d", n)
+	}
+	if n := probe(HTTPOptions{FreshConnection: true}); n != 3 {
+		t.Errorf("Expected a new connection per probe with fresh_connection, got %d", n)
+	}
+}
+
+func TestParsePingReplies(t *testing.T) {
+	cfg, err := GetConfig("ping", "192.0.2.1", `{"count": 4}`)
+	if err != nil {
+		t.Fatalf("GetConfig failed: %v", err)
+	}
+
+	output := `PING 192.0.2.1 (192.0.2.1) 56(84) bytes of data.
+64 bytes from 192.0.2.1: icmp_seq=1 ttl=57 time=10.0 ms
+64 bytes from 192.0.2.1: icmp_seq=2 ttl=57 time=30.0 ms
+64 bytes from 192.0.2.1: icmp_seq=4 ttl=57 time=20.0 ms
+
+--- 192.0.2.1 ping statistics ---
+4 packets transmitted, 3 received, 25% packet loss, time 603ms
+`
+	latency, loss, err := parsePingReplies(output, cfg)
+	if err != nil {
+		t.Fatalf("parsePingReplies failed: %v", err)
+	}
+	if latency != 20e6 {
+		t.Errorf("expected median latency 20ms, got %v", latency)
+	}
+	if loss != 0.25 {
+		t.Errorf("expected loss 0.25, got %v", loss)
+	}
+
+	if _, _, err := parsePingReplies("4 packets transmitted, 0 received, 100% packet loss", cfg); err == nil {
+		t.Error("expected error when no replies were received")
+	}
+
+	// Duplicate replies are counted but don't count as answers
+	dup := output + "64 bytes from 192.0.2.1: icmp_seq=2 ttl=57 time=90.0 ms (DUP!)\n"
+	latency, loss, err = parsePingReplies(dup, cfg)
+	if err != nil || latency != 20e6 || loss != 0.25 {
+		t.Errorf("expected duplicates to be ignored, got latency %v, loss %v, err %v", latency, loss, err)
+	}
+	if n := countPingDuplicates(dup); n != 1 {
+		t.Errorf("expected 1 duplicate, got %d", n)
+	}
+}
+
+func TestParsePingReplies_OtherFormats(t *testing.T) {
+	cfg, err := GetConfig("ping", "192.0.2.1", `{"count": 4}`)
+	if err != nil {
+		t.Fatalf("GetConfig failed: %v", err)
+	}
+
+	tests := []struct {
+		name        string
+		output      string
+		wantLatency float64
+		wantLoss    float64
+	}{
+		{
+			name: "busybox",
+			output: `PING 192.0.2.1 (192.0.2.1): 56 data bytes
+64 bytes from 192.0.2.1: seq=0 ttl=57 time=10.512 ms
+64 bytes from 192.0.2.1: seq=1 ttl=57 time=12.004 ms
+64 bytes from 192.0.2.1: seq=2 ttl=57 time=11.250 ms
+64 bytes from 192.0.2.1: seq=3 ttl=57 time=11.750 ms
+
+--- 192.0.2.1 ping statistics ---
+4 packets transmitted, 4 packets received, 0% packet loss
+round-trip min/avg/max = 10.512/11.379/12.004 ms
+`,
+			wantLatency: 11.5e6,
+		},
+		{
+			name: "windows",
+			output: `
+Pinging 192.0.2.1 with 32 bytes of data:
+Reply from 192.0.2.1: bytes=32 time=12ms TTL=57
+Reply from 192.0.2.1: bytes=32 time=14ms TTL=57
+Request timed out.
+Reply from 192.0.2.1: bytes=32 time=13ms TTL=57
+
+Ping statistics for 192.0.2.1:
+    Packets: Sent = 4, Received = 3, Lost = 1 (25% loss),
+Approximate round trip times in milli-seconds:
+    Minimum = 12ms, Maximum = 14ms, Average = 13ms
+`,
+			wantLatency: 13e6,
+			wantLoss:    0.25,
+		},
+		{
+			name: "windows sub-millisecond",
+			output: `Reply from 192.0.2.1: bytes=32 time<1ms TTL=128
+Reply from 192.0.2.1: bytes=32 time<1ms TTL=128
+Reply from 192.0.2.1: bytes=32 time<1ms TTL=128
+Reply from 192.0.2.1: bytes=32 time<1ms TTL=128
+`,
+			wantLatency: 1e6,
+		},
+		{
+			name: "busybox summary only",
+			output: `--- 192.0.2.1 ping statistics ---
+4 packets transmitted, 2 packets received, 50% packet loss
+round-trip min/avg/max = 9.000/10.000/11.000 ms
+`,
+			wantLatency: 10e6,
+			wantLoss:    0.5,
+		},
+		{
+			name: "iputils summary only",
+			output: `--- 192.0.2.1 ping statistics ---
+4 packets transmitted, 4 received, 0% packet loss, time 3004ms
+rtt min/avg/max/mdev = 9.100/10.200/11.300/0.400 ms
+`,
+			wantLatency: 10.2e6,
+		},
+		{
+			name: "windows summary only",
+			output: `Ping statistics for 192.0.2.1:
+    Packets: Sent = 4, Received = 4, Lost = 0 (0% loss),
+Approximate round trip times in milli-seconds:
+    Minimum = 1ms, Maximum = 3ms, Average = 2ms
+`,
+			wantLatency: 2e6,
+		},
+	}
+	for _, tt := range tests {
+		latency, loss, err := parsePingReplies(tt.output, cfg)
+		if err != nil {
+			t.Errorf("%s: parsePingReplies failed: %v", tt.name, err)
+			continue
+		}
+		if math.Abs(latency-tt.wantLatency) > 1 || loss != tt.wantLoss {
+			t.Errorf("%s: expected latency %v and loss %v, got %v and %v", tt.name, tt.wantLatency, tt.wantLoss, latency, loss)
+		}
+	}
+
+	// Windows reports no round trip times when every request was lost
+	lost := `Ping statistics for 192.0.2.1:
+    Packets: Sent = 4, Received = 0, Lost = 4 (100% loss),
+`
+	if _, _, err := parsePingReplies(lost, cfg); err == nil {
+		t.Error("expected error when no replies were received")
+	}
+}
+
+func TestPingICMPError(t *testing.T) {
+	unreachable := `PING 192.0.2.1 (192.0.2.1) 56(84) bytes of data.
+From 192.0.2.254 icmp_seq=1 Destination Host Unreachable
+
+--- 192.0.2.1 ping statistics ---
+1 packets transmitted, 0 received, +1 errors, 100% packet loss, time 0ms
+`
+	err := pingICMPError(unreachable)
+	if !errors.Is(err, ErrUnreachable) {
+		t.Fatalf("expected ErrUnreachable, got %v", err)
+	}
+	if !strings.Contains(err.Error(), "From 192.0.2.254") {
+		t.Errorf("expected the error to name the reporting router, got %q", err)
+	}
+	if isTimeout(err) {
+		t.Errorf("expected an unreachable host not to be reported as a timeout")
+	}
+
+	for _, line := range []string{
+		"From 192.0.2.254 icmp_seq=1 Time to live exceeded",
+		"Reply from 192.0.2.254: TTL expired in transit.",
+	} {
+		if err := pingICMPError(line); !errors.Is(err, ErrTimeExceeded) {
+			t.Errorf("%q: expected ErrTimeExceeded, got %v", line, err)
+		}
+	}
+
+	if err := pingICMPError("1 packets transmitted, 0 received, 100% packet loss"); err != nil {
+		t.Errorf("expected no ICMP error for plain loss, got %v", err)
+	}
+}
+
+func TestRunPing_StopsAtICMPError(t *testing.T) {
+	cfg, err := GetConfig("ping", "192.0.2.1", `{"count": 5}`)
+	if err != nil {
+		t.Fatalf("GetConfig failed: %v", err)
+	}
+	// Stand in for a ping that reports an error and would then keep waiting
+	cfg.Command = "sh"
+	cfg.Args = []string{"-c", "echo 'From 192.0.2.254 icmp_seq=1 Destination Host Unreachable'; exec sleep 10"}
+
+	ctx, cancel := context.WithTimeout(context.Background(), 5*time.Second)
+	defer cancel()
+	start := time.Now()
+	_, err = runPing(ctx, cfg)
+	if !errors.Is(err, ErrUnreachable) {
+		t.Fatalf("expected ErrUnreachable, got %v", err)
+	}
+	if elapsed := time.Since(start); elapsed > 2*time.Second {
+		t.Errorf("expected ping to be stopped at the error, took %v", elapsed)
+	}
+}
+
+func TestRunPing_NoReplyIsTimeout(t *testing.T) {
+	cfg, err := GetConfig("ping", "192.0.2.1", "")
+	if err != nil {
+		t.Fatalf("GetConfig failed: %v", err)
+	}
+	// Stand in for a ping that gave up waiting for a reply
+	cfg.Command = "sh"
+	cfg.Args = []string{"-c", "echo '1 packets transmitted, 0 received, 100% packet loss, time 0ms'; exit 1"}
+
+	_, err = runPing(context.Background(), cfg)
+	if err == nil || !strings.Contains(err.Error(), "probe timed out") {
+		t.Fatalf("expected a timeout, got %v", err)
+	}
+}
+
+func TestRunPing_DeadlineKeepsReplies(t *testing.T) {
+	cfg, err := GetConfig("ping", "192.0.2.1", `{"count": 4}`)
+	if err != nil {
+		t.Fatalf("GetConfig failed: %v", err)
+	}
+	// Stand in for a ping that is killed at the deadline after two replies
+	cfg.Command = "sh"
+	cfg.Args = []string{"-c", "echo '64 bytes from 192.0.2.1: icmp_seq=1 ttl=57 time=10.0 ms'; " +
+		"echo '64 bytes from 192.0.2.1: icmp_seq=2 ttl=57 time=20.0 ms'; exec sleep 10"}
+
+	ctx, cancel := context.WithTimeout(context.Background(), 500*time.Millisecond)
+	defer cancel()
+	res, err := runPing(ctx, cfg)
+	if err != nil {
+		t.Fatalf("expected the replies that arrived, got %v", err)
+	}
+	if res.Latency != 15e6 {
+		t.Errorf("expected a median of 15ms, got %v", time.Duration(res.Latency))
+	}
+	if res.Loss != 0.5 {
+		t.Errorf("expected the two outstanding requests as loss, got %v", res.Loss)
+	}
+}
+
+func TestRunPing_TimeoutWithoutRepliesIsTotalLoss(t *testing.T) {
+	cfg, err := GetConfig("ping", "192.0.2.1", `{"count": 4}`)
+	if err != nil {
+		t.Fatalf("GetConfig failed: %v", err)
+	}
+	// Stand in for a ping that is killed at the deadline before any reply
+	cfg.Command = "sh"
+	cfg.Args = []string{"-c", "exec sleep 10"}
+
+	ctx, cancel := context.WithTimeout(context.Background(), 200*time.Millisecond)
+	defer cancel()
+	res, err := runPing(ctx, cfg)
+	if err == nil || !strings.Contains(err.Error(), "probe timed out") {
+		t.Fatalf("expected a timeout, got %v", err)
+	}
+	if res.Loss != 1 {
+		t.Errorf("expected every echo request counted as lost, got %v", res.Loss)
+	}
+}
+
+func TestValidateTimeout(t *testing.T) {
+	tests := []struct {
+		probeType, config string
+		timeout           time.Duration
+		wantErr           bool
+	}{
+		{"ping", "", 200 * time.Millisecond, false},
+		{"ping", `{"count": 5}`, 5 * time.Second, false},
+		{"ping", `{"count": 21}`, 5 * time.Second, false},
+		{"ping", `{"count": 22}`, 5 * time.Second, true},
+		{"ping", `{"count": 100}`, 5 * time.Second, true},
+		{"ping", `{"count": 100}`, 21 * time.Second, false},
+		{"http", "", 100 * time.Millisecond, false},
+	}
+	for _, tt := range tests {
+		err := ValidateTimeout(tt.probeType, tt.config, tt.timeout)
+		if tt.wantErr && !errors.Is(err, ErrInvalidConfig) {
+			t.Errorf("%s %s with %v: expected ErrInvalidConfig, got %v", tt.probeType, tt.config, tt.timeout, err)
+		}
+		if !tt.wantErr && err != nil {
+			t.Errorf("%s %s with %v: expected valid, got %v", tt.probeType, tt.config, tt.timeout, err)
+		}
+	}
+}
+
+func TestPingTimeoutArgs(t *testing.T) {
+	if runtime.GOOS != "linux" {
+		t.Skip("-W only takes fractional seconds on Linux")
+	}
+	tests := []struct {
+		timeout time.Duration
+		want    []string
+	}{
+		{200 * time.Millisecond, []string{"-W", "0.2"}},
+		{1500 * time.Millisecond, []string{"-W", "1.5"}},
+		{5 * time.Second, []string{"-W", "5"}},
+		{0, nil},
+	}
+	for _, tt := range tests {
+		got := pingTimeoutArgs(tt.timeout)
+		if strings.Join(got, " ") != strings.Join(tt.want, " ") {
+			t.Errorf("pingTimeoutArgs(%v) = %v, want %v", tt.timeout, got, tt.want)
+		}
+	}
+}
+
+func TestParsePingTTL(t *testing.T) {
+	tests := []struct {
+		name   string
+		output string
+		want   int
+	}{
+		{"linux", "64 bytes from 192.0.2.1: icmp_seq=1 ttl=57 time=10.0 ms", 57},
+		{"last reply wins", "icmp_seq=1 ttl=57 time=10.0 ms\nicmp_seq=2 ttl=58 time=11.0 ms", 58},
+		{"windows", "Reply from 192.0.2.1: bytes=32 time=10ms TTL=117", 117},
+		{"not reported", "64 bytes from 192.0.2.1: icmp_seq=1 time=10.0 ms", 0},
+		{"out of range", "icmp_seq=1 ttl=300 time=10.0 ms", 0},
+	}
+	for _, tt := range tests {
+		if got := parsePingTTL(tt.output); got != tt.want {
+			t.Errorf("%s: expected TTL %d, got %d", tt.name, tt.want, got)
+		}
+	}
+}
+
+func TestParsePingAddress(t *testing.T) {
+	tests := []struct {
+		name   string
+		output string
+		want   string
+	}{
+		{"linux", "PING host.example (192.0.2.1) 56(84) bytes of data.\n64 bytes from 192.0.2.1: icmp_seq=1 ttl=57 time=10.0 ms", "192.0.2.1"},
+		{"resolved name", "64 bytes from host.example (2001:db8::1): icmp_seq=1 ttl=57 time=10.0 ms", "2001:db8::1"},
+		{"ipv6", "64 bytes from 2001:db8::1: icmp_seq=1 ttl=57 time=10.0 ms", "2001:db8::1"},
+		{"windows", "Reply from 192.0.2.1: bytes=32 time<1ms TTL=117", "192.0.2.1"},
+		{"last reply wins", "64 bytes from 192.0.2.1: icmp_seq=1 time=10.0 ms\n64 bytes from 192.0.2.2: icmp_seq=2 time=11.0 ms", "192.0.2.2"},
+		{"error line", "From 192.0.2.254 icmp_seq=1 Destination Host Unreachable", ""},
+	}
+	for _, tt := range tests {
+		if got := parsePingAddress(tt.output); got != tt.want {
+			t.Errorf("%s: expected %q, got %q", tt.name, tt.want, got)
+		}
+	}
+}
+
 func TestRunDNS(t *testing.T) {
 	// This test relies on external connectivity and a working DNS server at 8.8.8.8.
 	// In a purely hermetic environment, this should be mocked, but for now we test broadly.
@@ -103,19 +842,19 @@ func TestRunDNS(t *testing.T) {
 	// val, err := Run(cfg)
 
 	start := time.Now()
-	val, err := Run(cfg)
+	res, err := Run(cfg)
 	elapsed := time.Since(start)
 
 	if err != nil {
 		t.Fatalf("Run(dns) failed: %v", err)
 	}
 
-	if val <= 0 {
-		t.Errorf("expected positive latency, got %v", val)
+	if res.Latency <= 0 {
+		t.Errorf("expected positive latency, got %v", res.Latency)
 	}
 
 	// Verify we didn't just sleep and return 0
-	t.Logf("DNS Probe took %v ns (%.2f ms), total test time %v", val, val/1e6, elapsed)
+	t.Logf("DNS Probe took %v ns (%.2f ms), total test time %v", res.Latency, res.Latency/1e6, elapsed)
 }
 
 func TestRunDNS_LookupIP_Integration(t *testing.T) {
@@ -129,9 +868,588 @@ func TestRunDNS_LookupIP_Integration(t *testing.T) {
 		Timeout: 2 * time.Second,
 	}
 
-	val, err := Run(cfg)
+	res, err := Run(cfg)
 	if err != nil {
 		t.Fatalf("Run(dns) failed against 1.1.1.1: %v", err)
 	}
-	t.Logf("DNS Probe -> 1.1.1.1 took %.2f ms", val/1e6)
+	t.Logf("DNS Probe -> 1.1.1.1 took %.2f ms", res.Latency/1e6)
+}
+
+func TestRunTCP(t *testing.T) {
+	ln, err := net.Listen("tcp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	defer ln.Close()
+	go func() {
+		for {
+			conn, err := ln.Accept()
+			if err != nil {
+				return
+			}
+			conn.Close()
+		}
+	}()
+
+	cfg := Config{
+		Type:    "tcp",
+		Address: ln.Addr().String(),
+		Timeout: 2 * time.Second,
+	}
+	before := time.Now()
+	res, err := Run(cfg)
+	if err != nil {
+		t.Fatalf("Run(tcp) failed: %v", err)
+	}
+	if res.Latency <= 0 {
+		t.Errorf("expected positive latency, got %v", res.Latency)
+	}
+	if res.SentAt.Before(before) || res.SentAt.After(time.Now()) {
+		t.Errorf("expected SentAt within the Run call, got %v (started %v)", res.SentAt, before)
+	}
+	if res.RemoteIP != "127.0.0.1" {
+		t.Errorf("expected remote IP 127.0.0.1, got %q", res.RemoteIP)
+	}
+}
+
+func TestRunTCP_AddressFamily(t *testing.T) {
+	ln, err := net.Listen("tcp4", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	defer ln.Close()
+	go func() {
+		for {
+			conn, err := ln.Accept()
+			if err != nil {
+				return
+			}
+			conn.Close()
+		}
+	}()
+
+	cfg := Config{
+		Type:          "tcp",
+		Address:       ln.Addr().String(),
+		Timeout:       2 * time.Second,
+		AddressFamily: FamilyIPv4,
+	}
+	if _, err := Run(cfg); err != nil {
+		t.Fatalf("Run(tcp) over ipv4 failed: %v", err)
+	}
+
+	// An IPv4 literal has no IPv6 address, so the dial must fail rather than fall back
+	cfg.AddressFamily = FamilyIPv6
+	if _, err := Run(cfg); err == nil {
+		t.Fatal("expected error dialing an IPv4 address over ipv6, got nil")
+	}
+}
+
+func TestRunTCP_SourceAddress(t *testing.T) {
+	ln, err := net.Listen("tcp4", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	defer ln.Close()
+	peers := make(chan string, 1)
+	go func() {
+		conn, err := ln.Accept()
+		if err != nil {
+			return
+		}
+		peers <- remoteIP(conn)
+		conn.Close()
+	}()
+
+	cfg := Config{
+		Type:          "tcp",
+		Address:       ln.Addr().String(),
+		Timeout:       2 * time.Second,
+		SourceAddress: "127.0.0.1",
+	}
+	if _, err := Run(cfg); err != nil {
+		t.Fatalf("Run(tcp) from 127.0.0.1 failed: %v", err)
+	}
+	if peer := <-peers; peer != "127.0.0.1" {
+		t.Errorf("expected the connection to come from 127.0.0.1, got %q", peer)
+	}
+
+	// 192.0.2.0/24 is reserved for documentation, so no host has it
+	cfg.SourceAddress = "192.0.2.1"
+	if _, err := Run(cfg); !errors.Is(err, ErrInvalidConfig) {
+		t.Errorf("expected ErrInvalidConfig for an unavailable source address, got %v", err)
+	}
+	cfg.SourceAddress = "eth0"
+	if _, err := Run(cfg); !errors.Is(err, ErrInvalidConfig) {
+		t.Errorf("expected ErrInvalidConfig for a source address that isn't an IP, got %v", err)
+	}
+}
+
+func TestRunTCP_ConnectionRefused(t *testing.T) {
+	// Grab a free port and close the listener so nothing is accepting on it.
+	ln, err := net.Listen("tcp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	addr := ln.Addr().String()
+	ln.Close()
+
+	cfg := Config{
+		Type:    "tcp",
+		Address: addr,
+		Timeout: 2 * time.Second,
+	}
+	_, err = Run(cfg)
+	if err == nil {
+		t.Fatal("expected error for refused connection, got nil")
+	}
+	if strings.Contains(err.Error(), "probe timed out") {
+		t.Errorf("expected network error, got timeout: %v", err)
+	}
+}
+
+func TestRunTCP_MissingPort(t *testing.T) {
+	cfg := Config{
+		Type:    "tcp",
+		Address: "127.0.0.1",
+		Timeout: time.Second,
+	}
+	if _, err := Run(cfg); err == nil {
+		t.Fatal("expected error for address without port, got nil")
+	}
+}
+
+func TestRunTLS_UntrustedCertificate(t *testing.T) {
+	// httptest's certificate is self-signed, so verification must fail with a
+	// handshake error rather than being reported as a timeout.
+	srv := httptest.NewTLSServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {}))
+	defer srv.Close()
+
+	cfg := Config{
+		Type:    "tls",
+		Address: srv.Listener.Addr().String(),
+		Timeout: 2 * time.Second,
+	}
+	_, err := Run(cfg)
+	if err == nil {
+		t.Fatal("expected handshake error for untrusted certificate, got nil")
+	}
+	if strings.Contains(err.Error(), "probe timed out") {
+		t.Errorf("expected handshake failure, got timeout: %v", err)
+	}
+	if !strings.Contains(err.Error(), "tls handshake failed") {
+		t.Errorf("expected tls handshake error, got: %v", err)
+	}
+}
+
+func TestBuildDNSQuery(t *testing.T) {
+	packet, err := buildDNSQuery(0x1234, "www.example.com", dnsQueryTypes["AAAA"])
+	if err != nil {
+		t.Fatalf("buildDNSQuery failed: %v", err)
+	}
+
+	wantHeader := []byte{0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0}
+	if !bytes.Equal(packet[:12], wantHeader) {
+		t.Errorf("unexpected header: % x", packet[:12])
+	}
+
+	wantQuestion := []byte{3, 'w', 'w', 'w', 7, 'e', 'x', 'a', 'm', 'p', 'l', 'e', 3, 'c', 'o', 'm', 0, 0, 28, 0, 1}
+	if !bytes.Equal(packet[12:], wantQuestion) {
+		t.Errorf("unexpected question section: % x", packet[12:])
+	}
+
+	if _, err := buildDNSQuery(1, "bad..name", 1); !errors.Is(err, ErrInvalidConfig) {
+		t.Errorf("expected ErrInvalidConfig for empty label, got %v", err)
+	}
+}
+
+func TestValidateDNSResponse_AnswerCount(t *testing.T) {
+	// NOERROR response with an empty answer section
+	response := []byte{0xab, 0xcd, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0}
+
+	opts := DNSOptions{}.withDefaults()
+	if err := validateDNSResponse(response, 0xabcd, opts); err == nil {
+		t.Error("expected error for response without answers")
+	}
+
+	expectAnswer := false
+	opts.ExpectAnswer = &expectAnswer
+	if err := validateDNSResponse(response, 0xabcd, opts); err != nil {
+		t.Errorf("expected empty answer to pass when expect_answer is false, got %v", err)
+	}
+
+	response[7] = 1 // ANCOUNT = 1
+	if err := validateDNSResponse(response, 0xabcd, DNSOptions{}.withDefaults()); err != nil {
+		t.Errorf("expected response with an answer to pass, got %v", err)
+	}
+}
+
+func TestValidateDNSResponse_ExpectedAnswer(t *testing.T) {
+	opts, err := parseDNSOptions(`{"query_name": "www.example.com", "expected_answer": "192.0.2.7"}`)
+	if err != nil {
+		t.Fatalf("parseDNSOptions failed: %v", err)
+	}
+	query, err := buildDNSQuery(0xabcd, opts.QueryName, dnsQueryTypes["A"])
+	if err != nil {
+		t.Fatalf("buildDNSQuery failed: %v", err)
+	}
+
+	// A CNAME to cdn.example.com followed by its A record, both named with
+	// compression pointers back into the question
+	response := append([]byte{}, query...)
+	copy(response[2:], []byte{0x81, 0x80, 0, 1, 0, 2})
+	response = append(response,
+		0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 6, 3, 'c', 'd', 'n', 0xc0, 16,
+		0xc0, 45, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 7,
+	)
+	if err := validateDNSResponse(response, 0xabcd, opts); err != nil {
+		t.Errorf("expected matching answer to pass, got %v", err)
+	}
+
+	response[len(response)-1] = 8
+	if err := validateDNSResponse(response, 0xabcd, opts); err == nil || !strings.Contains(err.Error(), "192.0.2.8") {
+		t.Errorf("expected mismatch error naming 192.0.2.8, got %v", err)
+	}
+
+	if err := validateDNSResponse(response[:len(response)-2], 0xabcd, opts); err == nil {
+		t.Error("expected error for truncated answer record")
+	}
+
+	for _, raw := range []string{
+		`{"expected_answer": "not-an-ip"}`,
+		`{"query_type": "MX", "expected_answer": "192.0.2.7"}`,
+	} {
+		if _, err := parseDNSOptions(raw); !errors.Is(err, ErrInvalidConfig) {
+			t.Errorf("%s: expected ErrInvalidConfig, got %v", raw, err)
+		}
+	}
+}
+
+func TestDNSResponseTruncated(t *testing.T) {
+	truncated := []byte{0x12, 0x34, 0x83, 0x80, 0, 1, 0, 0, 0, 0, 0, 0} // QR, TC, RD, RA
+	if !dnsResponseTruncated(truncated) {
+		t.Error("expected TC flag to be detected")
+	}
+	complete := []byte{0x12, 0x34, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0} // QR, RD, RA
+	if dnsResponseTruncated(complete) {
+		t.Error("expected TC flag to be clear")
+	}
+	if dnsResponseTruncated(truncated[:4]) {
+		t.Error("expected short response not to be treated as truncated")
+	}
+}
+
+func TestDNSServerAddress(t *testing.T) {
+	tests := []struct{ address, want string }{
+		{"8.8.8.8", "8.8.8.8:53"},
+		{"8.8.8.8:5353", "8.8.8.8:5353"},
+		{"dns.example", "dns.example:53"},
+		{"2001:4860:4860::8888", "[2001:4860:4860::8888]:53"},
+		{"[2001:4860:4860::8888]:5353", "[2001:4860:4860::8888]:5353"},
+	}
+	for _, tt := range tests {
+		if got := dnsServerAddress(tt.address); got != tt.want {
+			t.Errorf("dnsServerAddress(%q) = %q, want %q", tt.address, got, tt.want)
+		}
+	}
+	if err := ValidateAddress("dns", "2001:4860:4860::8888"); err != nil {
+		t.Errorf("expected a bare IPv6 address to be valid for dns, got %v", err)
+	}
+}
+
+func TestRunDNS_RemoteIP(t *testing.T) {
+	udpConn, err := net.ListenPacket("udp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen on udp: %v", err)
+	}
+	defer udpConn.Close()
+
+	go func() {
+		buf := make([]byte, 512)
+		n, from, err := udpConn.ReadFrom(buf)
+		if err != nil || n < 12 {
+			return
+		}
+		resp := []byte{buf[0], buf[1], 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0}
+		udpConn.WriteTo(resp, from)
+	}()
+
+	ctx, cancel := context.WithTimeout(context.Background(), 2*time.Second)
+	defer cancel()
+	res, err := runDNS(ctx, udpConn.LocalAddr().String(), DNSOptions{}, "")
+	if err != nil {
+		t.Fatalf("runDNS failed: %v", err)
+	}
+	if res.RemoteIP != "127.0.0.1" {
+		t.Errorf("expected the server's IP as remote IP, got %q", res.RemoteIP)
+	}
+}
+
+func TestRunDNS_TCPFallbackOnTruncation(t *testing.T) {
+	udpConn, err := net.ListenPacket("udp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen on udp: %v", err)
+	}
+	defer udpConn.Close()
+	addr := udpConn.LocalAddr().String()
+
+	tcpLn, err := net.Listen("tcp", addr)
+	if err != nil {
+		t.Skipf("could not listen on tcp %s: %v", addr, err)
+	}
+	defer tcpLn.Close()
+
+	// UDP answers with the TC bit set and no answers.
+	go func() {
+		buf := make([]byte, 512)
+		n, from, err := udpConn.ReadFrom(buf)
+		if err != nil || n < 12 {
+			return
+		}
+		resp := []byte{buf[0], buf[1], 0x83, 0x80, 0, 1, 0, 0, 0, 0, 0, 0}
+		udpConn.WriteTo(resp, from)
+	}()
+
+	// TCP answers with a complete response carrying one answer.
+	tcpQueried := make(chan struct{})
+	go func() {
+		conn, err := tcpLn.Accept()
+		if err != nil {
+			return
+		}
+		defer conn.Close()
+		var lengthPrefix [2]byte
+		if _, err := io.ReadFull(conn, lengthPrefix[:]); err != nil {
+			return
+		}
+		query := make([]byte, binary.BigEndian.Uint16(lengthPrefix[:]))
+		if _, err := io.ReadFull(conn, query); err != nil {
+			return
+		}
+		close(tcpQueried)
+		resp := []byte{0, 12, query[0], query[1], 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0}
+		conn.Write(resp)
+	}()
+
+	ctx, cancel := context.WithTimeout(context.Background(), 2*time.Second)
+	defer cancel()
+	res, err := runDNS(ctx, addr, DNSOptions{}, "")
+	if err != nil {
+		t.Fatalf("runDNS with TCP fallback failed: %v", err)
+	}
+	select {
+	case <-tcpQueried:
+	default:
+		t.Fatal("expected truncated UDP response to trigger a TCP query")
+	}
+	if res.Latency <= 0 {
+		t.Errorf("expected positive latency, got %v", res.Latency)
+	}
+	if res.RemoteIP != "127.0.0.1" {
+		t.Errorf("expected the TCP peer as remote IP, got %q", res.RemoteIP)
+	}
+}
+
+func TestRunDNS_HungTCPFallbackTimesOut(t *testing.T) {
+	udpConn, err := net.ListenPacket("udp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen on udp: %v", err)
+	}
+	defer udpConn.Close()
+	addr := udpConn.LocalAddr().String()
+
+	tcpLn, err := net.Listen("tcp", addr)
+	if err != nil {
+		t.Skipf("could not listen on tcp %s: %v", addr, err)
+	}
+	defer tcpLn.Close()
+
+	go func() {
+		buf := make([]byte, 512)
+		n, from, err := udpConn.ReadFrom(buf)
+		if err != nil || n < 12 {
+			return
+		}
+		udpConn.WriteTo([]byte{buf[0], buf[1], 0x83, 0x80, 0, 1, 0, 0, 0, 0, 0, 0}, from)
+	}()
+	// Accept the fallback connection but never answer it
+	go func() {
+		conn, err := tcpLn.Accept()
+		if err != nil {
+			return
+		}
+		defer conn.Close()
+		io.Copy(io.Discard, conn)
+	}()
+
+	ctx, cancel := context.WithTimeout(context.Background(), 300*time.Millisecond)
+	defer cancel()
+	start := time.Now()
+	_, err = runDNS(ctx, addr, DNSOptions{}, "")
+	if err == nil {
+		t.Fatal("expected hung TCP fallback to fail")
+	}
+	if !isTimeout(err) {
+		t.Errorf("expected a timeout error, got %v", err)
+	}
+	if elapsed := time.Since(start); elapsed > time.Second {
+		t.Errorf("expected the probe to end at its deadline, took %v", elapsed)
+	}
+}
+
+// startUDPResponder replies to every datagram with reply(request) until the test ends.
+// A nil reply sends nothing.
+func startUDPResponder(t *testing.T, reply func([]byte) []byte) string {
+	t.Helper()
+	pc, err := net.ListenPacket("udp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("failed to listen: %v", err)
+	}
+	t.Cleanup(func() { pc.Close() })
+	go func() {
+		buf := make([]byte, 1500)
+		for {
+			n, addr, err := pc.ReadFrom(buf)
+			if err != nil {
+				return
+			}
+			if resp := reply(buf[:n]); resp != nil {
+				pc.WriteTo(resp, addr)
+			}
+		}
+	}()
+	return pc.LocalAddr().String()
+}
+
+func TestRunUDP(t *testing.T) {
+	addr := startUDPResponder(t, func(req []byte) []byte {
+		if string(req) == "PING" {
+			return []byte("PONG ok")
+		}
+		return []byte("ERR")
+	})
+
+	tests := []struct {
+		name        string
+		probeConfig string
+		wantErr     string
+	}{
+		{"Prefix Matches", `{"payload": "50494e47", "expect_prefix": "504f4e47"}`, ""},
+		{"No Prefix Check", `{"payload": "00"}`, ""},
+		{"Prefix Mismatch", `{"payload": "00", "expect_prefix": "504f4e47"}`, "expected prefix"},
+	}
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			cfg, err := GetConfig("udp", addr, tt.probeConfig)
+			if err != nil {
+				t.Fatalf("GetConfig failed: %v", err)
+			}
+			cfg.Timeout = 2 * time.Second
+			res, err := Run(cfg)
+			if tt.wantErr == "" {
+				if err != nil {
+					t.Fatalf("Run(udp) failed: %v", err)
+				}
+				if res.Latency <= 0 {
+					t.Errorf("expected positive latency, got %v", res.Latency)
+				}
+				return
+			}
+			if err == nil || !strings.Contains(err.Error(), tt.wantErr) {
+				t.Errorf("expected error containing %q, got %v", tt.wantErr, err)
+			}
+		})
+	}
+}
+
+func TestRunUDP_NoReplyTimesOut(t *testing.T) {
+	addr := startUDPResponder(t, func([]byte) []byte { return nil })
+
+	cfg, err := GetConfig("udp", addr, `{"payload": "00"}`)
+	if err != nil {
+		t.Fatalf("GetConfig failed: %v", err)
+	}
+	cfg.Timeout = 300 * time.Millisecond
+	_, err = Run(cfg)
+	if err == nil || !strings.Contains(err.Error(), "probe timed out") {
+		t.Errorf("expected probe timeout, got %v", err)
+	}
+}
+
+func TestValidateAddress(t *testing.T) {
+	valid := []struct{ probeType, address string }{
+		{"ping", "8.8.8.8"},
+		{"ping", "2001:db8::1"},
+		{"ping", "router-1.internal."},
+		{"tcp", "example.com:443"},
+		{"tcp", "[2001:db8::1]:22"},
+		{"udp", "10.0.0.1:161"},
+		{"tls", "example.com"},
+		{"tls", "example.com:8443"},
+		{"dns", "8.8.8.8"},
+		{"dns", "ns1.example.com:5353"},
+		{"http", "example.com"},
+		{"http", "httpbin.org/status/200"},
+		{"http", "https://example.com:8443/health?full=1"},
+	}
+	for _, tt := range valid {
+		if err := ValidateAddress(tt.probeType, tt.address); err != nil {
+			t.Errorf("%s %q: expected valid, got %v", tt.probeType, tt.address, err)
+		}
+	}
+
+	invalid := []struct{ probeType, address string }{
+		{"ping", ""},
+		{"ping", "-f"},
+		{"ping", "example .com"},
+		{"ping", "http://example.com"},
+		{"tcp", "example.com"},
+		{"tcp", "example.com:0"},
+		{"udp", "example.com:snmp"},
+		{"tls", "bad_host!:443"},
+		{"dns", "ns1..example.com"},
+		{"http", "htp://example.com"},
+		{"http", "ftp://example.com"},
+		{"http", "http://"},
+		{"http", "http://example.com:99999"},
+	}
+	for _, tt := range invalid {
+		if err := ValidateAddress(tt.probeType, tt.address); !errors.Is(err, ErrInvalidAddress) {
+			t.Errorf("%s %q: expected ErrInvalidAddress, got %v", tt.probeType, tt.address, err)
+		}
+	}
+}
+
+func TestValidateProbeConfig(t *testing.T) {
+	valid := []struct{ probeType, config string }{
+		{"ping", ""},
+		{"ping", `{"count": 3}`},
+		{"http", `{"method": "HEAD", "headers": {"Host": "example.com"}}`},
+		{"dns", `{"query_type": "MX"}`},
+		{"udp", `{"payload": "00"}`},
+		{"tcp", `{}`},
+	}
+	for _, tt := range valid {
+		if err := ValidateProbeConfig(tt.probeType, tt.config); err != nil {
+			t.Errorf("%s %s: expected valid, got %v", tt.probeType, tt.config, err)
+		}
+	}
+
+	invalid := []struct{ probeType, config string }{
+		{"http", `{"heders": {"Host": "example.com"}}`},
+		{"ping", `{"count": 3, "interval": 1}`},
+		{"tls", `{"server_name": "example.com"}`},
+		{"dns", `{"query_type": "BOGUS"}`},
+		{"http", `[1, 2]`},
+	}
+	for _, tt := range invalid {
+		if err := ValidateProbeConfig(tt.probeType, tt.config); !errors.Is(err, ErrInvalidConfig) {
+			t.Errorf("%s %s: expected ErrInvalidConfig, got %v", tt.probeType, tt.config, err)
+		}
+	}
+
+	// GetConfig stays lenient, so targets saved with unknown keys keep probing
+	if _, err := GetConfig("http", "example.com", `{"heders": {}}`); err != nil {
+		t.Errorf("Expected GetConfig to ignore unknown keys, got %v", err)
+	}
 }
@@ -0,0 +1,32 @@
+package probe
+
+import (
+	"context"
+	"fmt"
+	"net"
+	"time"
+)
+
+// runTCP measures the time taken to establish a TCP connection to a host:port address,
+// from the source IP address when set. It also reports the IP address that was
+// connected to.
+func runTCP(ctx context.Context, address, family, source string) (Result, error) {
+	if _, _, err := net.SplitHostPort(address); err != nil {
+		return Result{}, fmt.Errorf("invalid tcp address %q (expected host:port): %w", address, err)
+	}
+
+	network := familyNetwork("tcp", family)
+	dialer, err := newDialer(network, source)
+	if err != nil {
+		return Result{}, err
+	}
+	start := time.Now()
+	conn, err := dialFrom(ctx, dialer, network, address, source)
+	if err != nil {
+		return Result{}, fmt.Errorf("tcp connect failed: %w", err)
+	}
+	elapsed := float64(time.Since(start).Nanoseconds())
+	conn.Close()
+
+	return Result{Latency: elapsed, RemoteIP: remoteIP(conn)}, nil
+}
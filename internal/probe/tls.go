@@ -0,0 +1,48 @@
+package probe
+
+import (
+	"context"
+	"crypto/tls"
+	"errors"
+	"fmt"
+	"net"
+	"time"
+)
+
+// runTLS connects to address over TCP and measures the duration of the TLS handshake.
+// It also reports the NotAfter time of the peer's leaf certificate so callers can track expiry,
+// and the IP address that was connected to. The connection is made from the source IP
+// address when set.
+func runTLS(ctx context.Context, address, family, source string) (Result, error) {
+	host, _, err := net.SplitHostPort(address)
+	if err != nil {
+		// No port given, assume HTTPS
+		host = address
+		address = net.JoinHostPort(address, "443")
+	}
+
+	rawConn, err := dial(ctx, familyNetwork("tcp", family), address, source)
+	if err != nil {
+		return Result{}, fmt.Errorf("tcp connect failed: %w", err)
+	}
+	defer rawConn.Close()
+
+	conn := tls.Client(rawConn, &tls.Config{ServerName: host})
+
+	start := time.Now()
+	if err := conn.HandshakeContext(ctx); err != nil {
+		return Result{}, fmt.Errorf("tls handshake failed: %w", err)
+	}
+	elapsed := float64(time.Since(start).Nanoseconds())
+
+	state := conn.ConnectionState()
+	if len(state.PeerCertificates) == 0 {
+		return Result{}, errors.New("tls handshake completed without a peer certificate")
+	}
+
+	return Result{
+		Latency:    elapsed,
+		CertExpiry: state.PeerCertificates[0].NotAfter,
+		RemoteIP:   remoteIP(rawConn),
+	}, nil
+}
@@ -0,0 +1,116 @@
+package probe
+
+import (
+	"bytes"
+	"context"
+	"encoding/base64"
+	"encoding/hex"
+	"encoding/json"
+	"fmt"
+	"net"
+	"strings"
+	"time"
+)
+
+// maxUDPReply is the largest reply read by the "udp" probe. Longer datagrams are truncated.
+const maxUDPReply = 65535
+
+// UDPOptions configures the "udp" probe. It is parsed from the target's probe_config.
+type UDPOptions struct {
+	// Payload is the request datagram, encoded as Encoding. Required.
+	Payload string `json:"payload"`
+	// ExpectPrefix, when set, must match the start of the reply. Encoded as Encoding.
+	ExpectPrefix string `json:"expect_prefix"`
+	// Encoding is "hex" or "base64". Defaults to "hex".
+	Encoding string `json:"encoding"`
+
+	payload      []byte
+	expectPrefix []byte
+}
+
+func parseUDPOptions(raw string) (UDPOptions, error) {
+	var opts UDPOptions
+	if strings.TrimSpace(raw) != "" {
+		if err := json.Unmarshal([]byte(raw), &opts); err != nil {
+			return UDPOptions{}, fmt.Errorf("%w: %v", ErrInvalidConfig, err)
+		}
+	}
+	opts.Encoding = strings.ToLower(opts.Encoding)
+	if opts.Encoding == "" {
+		opts.Encoding = "hex"
+	}
+
+	var decode func(string) ([]byte, error)
+	switch opts.Encoding {
+	case "hex":
+		decode = hex.DecodeString
+	case "base64":
+		decode = base64.StdEncoding.DecodeString
+	default:
+		return UDPOptions{}, fmt.Errorf("%w: unknown encoding %q (expected hex or base64)", ErrInvalidConfig, opts.Encoding)
+	}
+
+	var err error
+	opts.payload, err = decode(opts.Payload)
+	if err != nil {
+		return UDPOptions{}, fmt.Errorf("%w: invalid payload: %v", ErrInvalidConfig, err)
+	}
+	if len(opts.payload) == 0 {
+		return UDPOptions{}, fmt.Errorf("%w: payload is required for udp probes", ErrInvalidConfig)
+	}
+	opts.expectPrefix, err = decode(opts.ExpectPrefix)
+	if err != nil {
+		return UDPOptions{}, fmt.Errorf("%w: invalid expect_prefix: %v", ErrInvalidConfig, err)
+	}
+	return opts, nil
+}
+
+// runUDP sends the configured payload to a host:port address and measures the time
+// until a reply arrives, which it reports with the IP address the payload was sent
+// to. No reply before the deadline is reported as a timeout. The payload is sent from
+// the source IP address when set.
+func runUDP(ctx context.Context, address string, opts UDPOptions, family, source string) (Result, error) {
+	if _, _, err := net.SplitHostPort(address); err != nil {
+		return Result{}, fmt.Errorf("invalid udp address %q (expected host:port): %w", address, err)
+	}
+
+	reply, elapsed, remote, err := exchangeDatagram(ctx, familyNetwork("udp", family), address, source, opts.payload, maxUDPReply)
+	if err != nil {
+		return Result{}, err
+	}
+	if !bytes.HasPrefix(reply, opts.expectPrefix) {
+		return Result{}, fmt.Errorf("udp reply does not start with expected prefix %x (got %x)", opts.expectPrefix, reply[:min(len(reply), len(opts.expectPrefix))])
+	}
+	return Result{Latency: elapsed, RemoteIP: remote}, nil
+}
+
+// exchangeDatagram sends request over a new connection and reads a single reply of at
+// most bufSize bytes. It returns the reply, the round-trip time in nanoseconds,
+// measured from just before the write, so dialing is not included, and the peer's IP.
+// The connection is made from the source IP address when set.
+func exchangeDatagram(ctx context.Context, network, address, source string, request []byte, bufSize int) ([]byte, float64, string, error) {
+	conn, err := dial(ctx, network, address, source)
+	if err != nil {
+		return nil, 0, "", fmt.Errorf("failed to dial %s: %w", address, err)
+	}
+	defer conn.Close()
+
+	// Set deadline from context
+	if deadline, ok := ctx.Deadline(); ok {
+		conn.SetDeadline(deadline)
+	}
+
+	start := time.Now()
+	if _, err := conn.Write(request); err != nil {
+		return nil, 0, "", fmt.Errorf("failed to send request: %w", err)
+	}
+
+	reply := make([]byte, bufSize)
+	n, err := conn.Read(reply)
+	if err != nil {
+		return nil, 0, "", fmt.Errorf("failed to read reply: %w", err)
+	}
+	elapsed := float64(time.Since(start).Nanoseconds())
+
+	return reply[:n], elapsed, remoteIP(conn), nil
+}
@@ -0,0 +1,225 @@
+package scheduler
+
+import (
+	"errors"
+	"fmt"
+	"log"
+	"net/http"
+	"sync"
+	"time"
+	"vaportrail/internal/db"
+
+	"github.com/jonboulle/clockwork"
+)
+
+// alertMetrics maps each supported metric to the quantile it reads from the
+// window's TDigest. The percent metrics have no quantile.
+var alertMetrics = map[string]float64{
+	"p50":             0.5,
+	"p90":             0.9,
+	"p95":             0.95,
+	"p99":             0.99,
+	"p999":            0.999,
+	"max":             1.0,
+	"timeout_percent": -1,
+	"error_percent":   -1,
+}
+
+// ValidateAlertRule checks a rule's metric, comparison, window, channel and webhook URL.
+// It does not check that the window is configured for the target.
+func ValidateAlertRule(r db.AlertRule) error {
+	if _, ok := alertMetrics[r.Metric]; !ok {
+		return fmt.Errorf("unknown metric %q (expected p50, p90, p95, p99, p999, max, timeout_percent or error_percent)", r.Metric)
+	}
+	if r.Comparison != ">" && r.Comparison != "<" {
+		return fmt.Errorf("unknown comparison %q (expected > or <)", r.Comparison)
+	}
+	if r.WindowSeconds <= 0 {
+		return fmt.Errorf("window must be a rollup window, got %d", r.WindowSeconds)
+	}
+	if _, err := newNotifier(r, nil); err != nil {
+		return err
+	}
+	req, err := http.NewRequest(http.MethodPost, r.WebhookURL, nil)
+	if err != nil || (req.URL.Scheme != "http" && req.URL.Scheme != "https") || req.URL.Host == "" {
+		return fmt.Errorf("invalid webhook URL %q", r.WebhookURL)
+	}
+	return nil
+}
+
+// AlertPayload describes a rule's state change. It is the JSON body posted by the
+// generic webhook channel.
+type AlertPayload struct {
+	State         string    `json:"state"` // "firing" or "resolved"
+	RuleID        int64     `json:"rule_id"`
+	TargetID      int64     `json:"target_id"`
+	TargetName    string    `json:"target_name"`
+	Metric        string    `json:"metric"`
+	Comparison    string    `json:"comparison"`
+	Threshold     float64   `json:"threshold"`
+	Value         float64   `json:"value"`
+	WindowSeconds int       `json:"window_seconds"`
+	WindowStart   time.Time `json:"window_start"`
+}
+
+// AlertManager evaluates alert rules against each new aggregated window. A webhook
+// is only sent when a rule starts breaching or recovers, never while its state holds.
+type AlertManager struct {
+	db       db.Store
+	clock    clockwork.Clock
+	client   *http.Client
+	stop     chan struct{}
+	stopOnce sync.Once
+	wg       sync.WaitGroup
+}
+
+func NewAlertManager(database db.Store) *AlertManager {
+	return &AlertManager{
+		db:     database,
+		clock:  clockwork.NewRealClock(),
+		client: &http.Client{Timeout: 10 * time.Second},
+		stop:   make(chan struct{}),
+	}
+}
+
+func (am *AlertManager) Start() {
+	am.wg.Add(1)
+	go am.run()
+}
+
+// Stop ends the evaluation loop and waits for a pass in progress to finish. It is
+// safe to call more than once, and before Start.
+func (am *AlertManager) Stop() {
+	am.stopOnce.Do(func() { close(am.stop) })
+	am.wg.Wait()
+}
+
+func (am *AlertManager) run() {
+	defer am.wg.Done()
+	ticker := am.clock.NewTicker(10 * time.Second)
+	defer ticker.Stop()
+
+	for {
+		select {
+		case <-am.stop:
+			return
+		case <-ticker.Chan():
+			am.evaluateAlerts()
+		}
+	}
+}
+
+func (am *AlertManager) evaluateAlerts() {
+	rules, err := am.db.GetAlertRules()
+	if err != nil {
+		log.Printf("AlertManager: Failed to get alert rules: %v", err)
+		return
+	}
+	for _, r := range rules {
+		if err := am.evaluateRule(r); err != nil {
+			log.Printf("AlertManager: Rule %d: %v", r.ID, err)
+		}
+	}
+}
+
+// evaluateRule checks the newest window of the rule's target once. When the webhook
+// can't be delivered the state is left unchanged, so the next window tries again.
+func (am *AlertManager) evaluateRule(r db.AlertRule) error {
+	latest, err := am.db.GetLatestAggregatedResult(r.TargetID, r.WindowSeconds)
+	if err != nil {
+		return fmt.Errorf("failed to get latest rollup: %w", err)
+	}
+	if latest == nil || (r.LastWindow != nil && !latest.Time.After(*r.LastWindow)) {
+		return nil
+	}
+
+	value, ok, err := alertMetricValue(r.Metric, latest)
+	if err != nil {
+		return err
+	}
+	// A window without samples neither breaches nor recovers
+	firing := r.Firing
+	if ok {
+		if r.Comparison == ">" {
+			firing = value > r.Threshold
+		} else {
+			firing = value < r.Threshold
+		}
+	}
+
+	if firing != r.Firing {
+		if err := am.notify(r, firing, value, latest.Time); err != nil {
+			if stateErr := am.db.SetAlertRuleState(r.ID, r.Firing, latest.Time); stateErr != nil {
+				return errors.Join(err, fmt.Errorf("failed to record alert state: %w", stateErr))
+			}
+			return err
+		}
+	}
+	return am.db.SetAlertRuleState(r.ID, firing, latest.Time)
+}
+
+func (am *AlertManager) notify(r db.AlertRule, firing bool, value float64, windowStart time.Time) error {
+	payload := AlertPayload{
+		State:         "resolved",
+		RuleID:        r.ID,
+		TargetID:      r.TargetID,
+		Metric:        r.Metric,
+		Comparison:    r.Comparison,
+		Threshold:     r.Threshold,
+		Value:         value,
+		WindowSeconds: r.WindowSeconds,
+		WindowStart:   windowStart,
+	}
+	if firing {
+		payload.State = "firing"
+	}
+	if t, err := am.db.GetTarget(r.TargetID); err == nil {
+		payload.TargetName = t.Name
+	}
+
+	notifier, err := newNotifier(r, am.client)
+	if err != nil {
+		return err
+	}
+	if err := notifier.Send(payload); err != nil {
+		return err
+	}
+	log.Printf("AlertManager: Rule %d for %s is %s (%s = %g)", r.ID, payload.TargetName, payload.State, r.Metric, value)
+	return nil
+}
+
+// alertMetricValue reads a metric from a window. Latencies are in milliseconds.
+// ok is false when the window has no samples for the metric.
+func alertMetricValue(metric string, agg *db.AggregatedResult) (value float64, ok bool, err error) {
+	td, err := db.DeserializeTDigest(agg.TDigestData)
+	if err != nil {
+		return 0, false, fmt.Errorf("failed to read TDigest: %w", err)
+	}
+	successes := int64(td.Count())
+
+	switch metric {
+	case "timeout_percent", "error_percent":
+		total := successes + agg.TimeoutCount + agg.ErrorCount
+		if total == 0 {
+			return 0, false, nil
+		}
+		failed := agg.TimeoutCount
+		if metric == "error_percent" {
+			failed = agg.ErrorCount
+		}
+		return float64(failed) * 100 / float64(total), true, nil
+	case "max":
+		if agg.MaxNS != nil {
+			return *agg.MaxNS / 1e6, true, nil
+		}
+	}
+
+	q, known := alertMetrics[metric]
+	if !known {
+		return 0, false, fmt.Errorf("unknown metric %q", metric)
+	}
+	if successes == 0 {
+		return 0, false, nil
+	}
+	return td.Quantile(q) / 1e6, true, nil
+}
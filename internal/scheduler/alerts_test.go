@@ -0,0 +1,197 @@
+package scheduler
+
+import (
+	"encoding/json"
+	"errors"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"testing"
+	"time"
+	"vaportrail/internal/db"
+)
+
+func TestAlertManager_FiresOnTransitions(t *testing.T) {
+	var payloads []AlertPayload
+	status := http.StatusOK
+	webhook := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		var p AlertPayload
+		if err := json.NewDecoder(r.Body).Decode(&p); err != nil {
+			t.Errorf("Failed to decode webhook payload: %v", err)
+		}
+		payloads = append(payloads, p)
+		w.WriteHeader(status)
+	}))
+	defer webhook.Close()
+
+	mockDB := NewMockStore()
+	am := NewAlertManager(mockDB)
+
+	target := db.Target{Name: "AlertTarget"}
+	id, _ := mockDB.AddTarget(&target)
+	ruleID, _ := mockDB.AddAlertRule(&db.AlertRule{
+		TargetID:      id,
+		Metric:        "p99",
+		Comparison:    ">",
+		Threshold:     50, // ms
+		WindowSeconds: 60,
+		WebhookURL:    webhook.URL,
+	})
+
+	start := time.Unix(0, 0).UTC()
+	addWindow := func(minute int, latencyMS float64) {
+		td, _ := db.NewTDigest(0)
+		for i := 0; i < 100; i++ {
+			td.Add(latencyMS * 1e6)
+		}
+		data, _ := db.SerializeTDigest(td)
+		mockDB.AddAggregatedResult(&db.AggregatedResult{
+			Time:          start.Add(time.Duration(minute) * time.Minute),
+			TargetID:      id,
+			WindowSeconds: 60,
+			TDigestData:   data,
+		})
+	}
+
+	// Each step optionally adds a window, evaluates, and lists every webhook sent so far
+	steps := []struct {
+		name       string
+		latencyMS  float64
+		newWindow  bool
+		wantStates []string
+	}{
+		{"healthy", 10, true, nil},
+		{"crosses threshold", 100, true, []string{"firing"}},
+		{"same window again", 100, false, []string{"firing"}},
+		{"still breaching", 200, true, []string{"firing"}},
+		{"recovers", 10, true, []string{"firing", "resolved"}},
+		{"stays healthy", 10, true, []string{"firing", "resolved"}},
+	}
+	minute := 0
+	for _, step := range steps {
+		if step.newWindow {
+			addWindow(minute, step.latencyMS)
+			minute++
+		}
+		am.evaluateAlerts()
+
+		var states []string
+		for _, p := range payloads {
+			states = append(states, p.State)
+		}
+		if len(states) != len(step.wantStates) {
+			t.Fatalf("%s: expected webhooks %v, got %v", step.name, step.wantStates, states)
+		}
+		for j := range states {
+			if states[j] != step.wantStates[j] {
+				t.Fatalf("%s: expected webhooks %v, got %v", step.name, step.wantStates, states)
+			}
+		}
+	}
+
+	first := payloads[0]
+	if first.RuleID != ruleID || first.TargetName != "AlertTarget" || first.Value < 99 || first.Value > 101 {
+		t.Errorf("Unexpected firing payload: %+v", first)
+	}
+	if !first.WindowStart.Equal(start.Add(time.Minute)) {
+		t.Errorf("Expected the breaching window to start at %v, got %v", start.Add(time.Minute), first.WindowStart)
+	}
+
+	// A failed delivery leaves the rule resolved, so the next breaching window retries
+	status = http.StatusInternalServerError
+	addWindow(minute, 100)
+	minute++
+	am.evaluateAlerts()
+	if mockDB.AlertRules[ruleID].Firing {
+		t.Errorf("Expected rule to stay resolved after a failed webhook")
+	}
+	status = http.StatusOK
+	addWindow(minute, 100)
+	am.evaluateAlerts()
+	if !mockDB.AlertRules[ruleID].Firing || len(payloads) != 4 {
+		t.Errorf("Expected the next window to fire again, got firing=%v after %d webhooks", mockDB.AlertRules[ruleID].Firing, len(payloads))
+	}
+}
+
+func TestAlertManager_ReturnsStateErrorAfterFailedWebhook(t *testing.T) {
+	webhook := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		w.WriteHeader(http.StatusInternalServerError)
+	}))
+	defer webhook.Close()
+
+	mockDB := NewMockStore()
+	am := NewAlertManager(mockDB)
+	target := db.Target{Name: "AlertTarget"}
+	id, _ := mockDB.AddTarget(&target)
+	ruleID, _ := mockDB.AddAlertRule(&db.AlertRule{TargetID: id, Metric: "p99", Comparison: ">", Threshold: 50, WindowSeconds: 60, WebhookURL: webhook.URL})
+
+	td, _ := db.NewTDigest(0)
+	td.Add(100 * 1e6)
+	data, _ := db.SerializeTDigest(td)
+	mockDB.AddAggregatedResult(&db.AggregatedResult{Time: time.Unix(0, 0).UTC(), TargetID: id, WindowSeconds: 60, TDigestData: data})
+	mockDB.SetAlertRuleStateFn = func(int64, bool, time.Time) error {
+		return errors.New("database is locked")
+	}
+
+	err := am.evaluateRule(mockDB.AlertRules[ruleID])
+	if err == nil || !strings.Contains(err.Error(), "database is locked") {
+		t.Errorf("Expected the failed state write to be returned with the webhook error, got %v", err)
+	}
+}
+
+func TestAlertMetricValue(t *testing.T) {
+	td, _ := db.NewTDigest(0)
+	for i := 0; i < 90; i++ {
+		td.Add(2e6)
+	}
+	data, _ := db.SerializeTDigest(td)
+	maxNS := 7e6
+	agg := &db.AggregatedResult{TDigestData: data, TimeoutCount: 6, ErrorCount: 4, MaxNS: &maxNS}
+
+	tests := []struct {
+		metric string
+		want   float64
+	}{
+		{"p50", 2},
+		{"max", 7},
+		{"timeout_percent", 6},
+		{"error_percent", 4},
+	}
+	for _, tt := range tests {
+		got, ok, err := alertMetricValue(tt.metric, agg)
+		if err != nil || !ok || got != tt.want {
+			t.Errorf("%s: expected %v, got %v (ok=%v, err=%v)", tt.metric, tt.want, got, ok, err)
+		}
+	}
+
+	// No successful probes: latency metrics have no value
+	empty := &db.AggregatedResult{TimeoutCount: 3}
+	if _, ok, _ := alertMetricValue("p99", empty); ok {
+		t.Errorf("Expected no p99 for a window without successes")
+	}
+	if v, ok, _ := alertMetricValue("timeout_percent", empty); !ok || v != 100 {
+		t.Errorf("Expected 100%% timeouts, got %v (ok=%v)", v, ok)
+	}
+}
+
+func TestValidateAlertRule(t *testing.T) {
+	valid := db.AlertRule{Metric: "p99", Comparison: ">", Threshold: 100, WindowSeconds: 60, WebhookURL: "https://hooks.example.com/alert"}
+	if err := ValidateAlertRule(valid); err != nil {
+		t.Errorf("Expected valid rule, got %v", err)
+	}
+
+	for name, mutate := range map[string]func(*db.AlertRule){
+		"metric":     func(r *db.AlertRule) { r.Metric = "p42" },
+		"comparison": func(r *db.AlertRule) { r.Comparison = ">=" },
+		"raw window": func(r *db.AlertRule) { r.WindowSeconds = 0 },
+		"url":        func(r *db.AlertRule) { r.WebhookURL = "hooks.example.com/alert" },
+		"scheme":     func(r *db.AlertRule) { r.WebhookURL = "ftp://hooks.example.com/alert" },
+		"channel":    func(r *db.AlertRule) { r.Channel = "pager" },
+	} {
+		r := valid
+		mutate(&r)
+		if err := ValidateAlertRule(r); err == nil {
+			t.Errorf("%s: expected an error", name)
+		}
+	}
+}
@@ -0,0 +1,72 @@
+package scheduler
+
+import (
+	"sync"
+	"vaportrail/internal/db"
+)
+
+// resultBroadcaster fans raw results out to live subscribers, such as the SSE stream.
+// Sends never block: a subscriber that falls behind misses results instead of
+// stalling the batch writer.
+type resultBroadcaster struct {
+	mu     sync.Mutex
+	nextID int
+	subs   map[int]chan db.RawResult
+	closed bool
+}
+
+func newResultBroadcaster() *resultBroadcaster {
+	return &resultBroadcaster{subs: make(map[int]chan db.RawResult)}
+}
+
+func (b *resultBroadcaster) subscribe(buffer int) (<-chan db.RawResult, func()) {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+
+	ch := make(chan db.RawResult, buffer)
+	if b.closed {
+		close(ch)
+		return ch, func() {}
+	}
+
+	id := b.nextID
+	b.nextID++
+	b.subs[id] = ch
+
+	var once sync.Once
+	return ch, func() {
+		once.Do(func() {
+			b.mu.Lock()
+			defer b.mu.Unlock()
+			if sub, ok := b.subs[id]; ok {
+				delete(b.subs, id)
+				close(sub)
+			}
+		})
+	}
+}
+
+func (b *resultBroadcaster) publish(results []db.RawResult) {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+	for _, ch := range b.subs {
+		for _, r := range results {
+			select {
+			case ch <- r:
+			default:
+				// Subscriber is lagging; drop rather than block.
+			}
+		}
+	}
+}
+
+// close closes every subscriber channel and rejects new subscriptions.
+func (b *resultBroadcaster) close() {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+	b.closed = true
+	for id, ch := range b.subs {
+		close(ch)
+		delete(b.subs, id)
+	}
+}
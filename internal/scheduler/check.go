@@ -0,0 +1,50 @@
+package scheduler
+
+import (
+	"sync"
+	"vaportrail/internal/db"
+	"vaportrail/internal/probe"
+)
+
+// checkConcurrency bounds the number of probes CheckOnce runs at the same time.
+const checkConcurrency = 16
+
+// CheckResult is the outcome of probing a target once with CheckOnce. Err is set
+// when the probe failed or the target's configuration is invalid.
+type CheckResult struct {
+	Target db.Target
+	Result probe.Result
+	Err    error
+}
+
+// CheckOnce probes each target once, without jitter, and returns the outcomes in the
+// order of targets. Paused targets are skipped. Nothing is written to the database,
+// and the scheduler doesn't need to be started.
+func (s *Scheduler) CheckOnce(targets []db.Target) []CheckResult {
+	var checked []CheckResult
+	for _, t := range targets {
+		if !t.Paused {
+			checked = append(checked, CheckResult{Target: t})
+		}
+	}
+
+	sem := make(chan struct{}, checkConcurrency)
+	var wg sync.WaitGroup
+	for i := range checked {
+		wg.Add(1)
+		sem <- struct{}{}
+		go func(c *CheckResult) {
+			defer wg.Done()
+			defer func() { <-sem }()
+
+			cfg, err := s.probeConfig(c.Target)
+			if err != nil {
+				c.Err = err
+				return
+			}
+			c.Result, c.Err = s.probeRunner.Run(cfg)
+		}(&checked[i])
+	}
+	wg.Wait()
+	return checked
+}
@@ -1,7 +1,9 @@
 package scheduler
 
 import (
+	"context"
 	"errors"
+	"slices"
 	"time"
 	"vaportrail/internal/db"
 	"vaportrail/internal/probe"
@@ -13,12 +15,16 @@ type MockStore struct {
 	Results           map[int64][]db.Result // Legacy
 	RawResults        map[int64][]db.RawResult
 	AggregatedResults map[int64][]db.AggregatedResult
+	AlertRules        map[int64]db.AlertRule
 
-	AddTargetFn    func(t *db.Target) (int64, error)
-	GetTargetsFn   func() ([]db.Target, error)
-	AddResultFn    func(r *db.Result) error
-	DeleteTargetFn func(id int64) error
-	CloseFn        func() error
+	AddTargetFn                       func(t *db.Target) (int64, error)
+	GetTargetsFn                      func() ([]db.Target, error)
+	AddResultFn                       func(r *db.Result) error
+	AddRawResultsFn                   func(results []db.RawResult) error
+	DeleteTargetFn                    func(id int64) error
+	DeleteAggregatedResultsByWindowFn func(targetID int64, windowSeconds int) error
+	SetAlertRuleStateFn               func(id int64, firing bool, lastWindow time.Time) error
+	CloseFn                           func() error
 }
 
 func NewMockStore() *MockStore {
@@ -27,6 +33,7 @@ func NewMockStore() *MockStore {
 		Results:           make(map[int64][]db.Result),
 		RawResults:        make(map[int64][]db.RawResult),
 		AggregatedResults: make(map[int64][]db.AggregatedResult),
+		AlertRules:        make(map[int64]db.AlertRule),
 	}
 }
 
@@ -40,6 +47,15 @@ func (m *MockStore) AddTarget(t *db.Target) (int64, error) {
 	return id, nil
 }
 
+func (m *MockStore) AddTargets(targets []*db.Target) error {
+	for _, t := range targets {
+		if _, err := m.AddTarget(t); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
 func (m *MockStore) UpdateTarget(t *db.Target) error {
 	if _, ok := m.Targets[t.ID]; !ok {
 		return errors.New("target not found")
@@ -59,6 +75,15 @@ func (m *MockStore) GetTargets() ([]db.Target, error) {
 	return targets, nil
 }
 
+func (m *MockStore) QueryTargets(q db.TargetQuery) ([]db.Target, int, error) {
+	targets, err := m.GetTargets()
+	return targets, len(targets), err
+}
+
+func (m *MockStore) GetTags() ([]string, error) {
+	return []string{}, nil
+}
+
 func (m *MockStore) DeleteTarget(id int64) error {
 	if m.DeleteTargetFn != nil {
 		return m.DeleteTargetFn(id)
@@ -74,6 +99,50 @@ func (m *MockStore) GetTarget(id int64) (*db.Target, error) {
 	return nil, errors.New("target not found")
 }
 
+func (m *MockStore) UpdateTargetCertExpiry(id int64, expiry time.Time) error {
+	t, ok := m.Targets[id]
+	if !ok {
+		return errors.New("target not found")
+	}
+	t.CertExpiresAt = &expiry
+	m.Targets[id] = t
+	return nil
+}
+
+func (m *MockStore) SetTargetPaused(id int64, paused bool) error {
+	t, ok := m.Targets[id]
+	if !ok {
+		return errors.New("target not found")
+	}
+	t.Paused = paused
+	m.Targets[id] = t
+	return nil
+}
+
+func (m *MockStore) SetTagPaused(tag string, paused bool) (int64, error) {
+	var n int64
+	for id, t := range m.Targets {
+		if slices.Contains(t.Tags, tag) && t.Paused != paused {
+			t.Paused = paused
+			m.Targets[id] = t
+			n++
+		}
+	}
+	return n, nil
+}
+
+func (m *MockStore) ClearTargetData(id int64) (*db.ClearedTargetData, error) {
+	cleared := &db.ClearedTargetData{
+		RawResults:        int64(len(m.RawResults[id])),
+		AggregatedResults: int64(len(m.AggregatedResults[id])),
+		Results:           int64(len(m.Results[id])),
+	}
+	delete(m.RawResults, id)
+	delete(m.AggregatedResults, id)
+	delete(m.Results, id)
+	return cleared, nil
+}
+
 func (m *MockStore) AddResult(r *db.Result) error {
 	if m.AddResultFn != nil {
 		return m.AddResultFn(r)
@@ -108,6 +177,9 @@ func (m *MockStore) Close() error {
 }
 
 func (m *MockStore) AddRawResults(results []db.RawResult) error {
+	if m.AddRawResultsFn != nil {
+		return m.AddRawResultsFn(results)
+	}
 	for _, r := range results {
 		m.RawResults[r.TargetID] = append(m.RawResults[r.TargetID], r)
 	}
@@ -138,6 +210,18 @@ func (m *MockStore) GetLastRollupTime(targetID int64, windowSeconds int) (time.T
 	return maxTime, nil
 }
 
+func (m *MockStore) GetFirstRollupTime(targetID int64, windowSeconds int) (time.Time, error) {
+	var minTime time.Time
+	for _, r := range m.AggregatedResults[targetID] {
+		if r.WindowSeconds == windowSeconds {
+			if minTime.IsZero() || r.Time.Before(minTime) {
+				minTime = r.Time
+			}
+		}
+	}
+	return minTime, nil
+}
+
 func (m *MockStore) GetRawResults(targetID int64, start, end time.Time, limit int) ([]db.RawResult, error) {
 	var res []db.RawResult
 	for _, r := range m.RawResults[targetID] {
@@ -153,6 +237,29 @@ func (m *MockStore) GetRawResults(targetID int64, start, end time.Time, limit in
 	return res, nil
 }
 
+func (m *MockStore) GetRawResultsDownsampled(targetID int64, start, end time.Time, maxPoints int) ([]db.RawResult, error) {
+	all, _ := m.GetRawResults(targetID, start, end, 0)
+	step := 1
+	if maxPoints > 0 && len(all) > maxPoints {
+		step = (len(all) + maxPoints - 1) / maxPoints
+	}
+	var res []db.RawResult
+	for i := 0; i < len(all); i += step {
+		res = append(res, all[i])
+	}
+	return res, nil
+}
+
+func (m *MockStore) ForEachRawResult(targetID int64, start, end time.Time, fn func(db.RawResult) error) error {
+	results, _ := m.GetRawResults(targetID, start, end, 0)
+	for _, r := range results {
+		if err := fn(r); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
 func (m *MockStore) GetAggregatedResults(targetID int64, windowSeconds int, start, end time.Time) ([]db.AggregatedResult, error) {
 	var res []db.AggregatedResult
 	for _, r := range m.AggregatedResults[targetID] {
@@ -175,6 +282,17 @@ func (m *MockStore) DeleteRawResultsBefore(targetID int64, cutoff time.Time) err
 	return nil
 }
 
+func (m *MockStore) DeleteResultsBefore(targetID int64, cutoff time.Time) error {
+	var keep []db.Result
+	for _, r := range m.Results[targetID] {
+		if !r.Time.Before(cutoff) {
+			keep = append(keep, r)
+		}
+	}
+	m.Results[targetID] = keep
+	return nil
+}
+
 func (m *MockStore) DeleteAggregatedResultsBefore(targetID int64, windowSeconds int, cutoff time.Time) error {
 	var keep []db.AggregatedResult
 	for _, r := range m.AggregatedResults[targetID] {
@@ -193,6 +311,9 @@ func (m *MockStore) DeleteAggregatedResultsBefore(targetID int64, windowSeconds
 }
 
 func (m *MockStore) DeleteAggregatedResultsByWindow(targetID int64, windowSeconds int) error {
+	if m.DeleteAggregatedResultsByWindowFn != nil {
+		return m.DeleteAggregatedResultsByWindowFn(targetID, windowSeconds)
+	}
 	var keep []db.AggregatedResult
 	for _, r := range m.AggregatedResults[targetID] {
 		if r.WindowSeconds != windowSeconds {
@@ -213,6 +334,42 @@ func (m *MockStore) GetEarliestRawResultTime(targetID int64) (time.Time, error)
 	return minTime, nil
 }
 
+func (m *MockStore) GetLatestRawResult(targetID int64) (*db.RawResult, error) {
+	var latest *db.RawResult
+	for i, r := range m.RawResults[targetID] {
+		if r.Latency < 0 {
+			continue
+		}
+		if latest == nil || r.Time.After(latest.Time) {
+			latest = &m.RawResults[targetID][i]
+		}
+	}
+	return latest, nil
+}
+
+func (m *MockStore) GetLatestAggregatedResult(targetID int64, windowSeconds int) (*db.AggregatedResult, error) {
+	var latest *db.AggregatedResult
+	for i, r := range m.AggregatedResults[targetID] {
+		if r.WindowSeconds != windowSeconds {
+			continue
+		}
+		if latest == nil || r.Time.After(latest.Time) {
+			latest = &m.AggregatedResults[targetID][i]
+		}
+	}
+	return latest, nil
+}
+
+func (m *MockStore) GetTimeoutTotal(targetID int64, windowSeconds int) (int64, error) {
+	var total int64
+	for _, r := range m.AggregatedResults[targetID] {
+		if r.WindowSeconds == windowSeconds {
+			total += r.TimeoutCount
+		}
+	}
+	return total, nil
+}
+
 // MockStore implements db.Store interface
 func (m *MockStore) GetDBSizeBytes() (int64, error) {
 	return 0, nil
@@ -226,6 +383,10 @@ func (m *MockStore) GetPageSize() (int64, error) {
 	return 0, nil
 }
 
+func (m *MockStore) Ping() error {
+	return nil
+}
+
 func (m *MockStore) GetFreelistCount() (int64, error) {
 	return 0, nil
 }
@@ -247,6 +408,14 @@ func (m *MockStore) DeleteOrphanedData() (*db.OrphanedDataCleanupReport, error)
 	return &db.OrphanedDataCleanupReport{}, nil
 }
 
+func (m *MockStore) Vacuum() (*db.VacuumReport, error) {
+	return &db.VacuumReport{Skipped: true}, nil
+}
+
+func (m *MockStore) IntegrityCheck(ctx context.Context) (*db.IntegrityReport, error) {
+	return &db.IntegrityReport{OK: true, Problems: []string{}}, nil
+}
+
 // Dashboard methods (stubs - not used in scheduler tests)
 func (m *MockStore) AddDashboard(d *db.Dashboard) (int64, error) {
 	return 0, nil
@@ -301,9 +470,54 @@ type MockRunner struct {
 	RunFn func(cfg probe.Config) (float64, error)
 }
 
-func (m *MockRunner) Run(cfg probe.Config) (float64, error) {
+func (m *MockRunner) Run(cfg probe.Config) (probe.Result, error) {
 	if m.RunFn != nil {
-		return m.RunFn(cfg)
+		latency, err := m.RunFn(cfg)
+		return probe.Result{Latency: latency}, err
+	}
+	return probe.Result{Latency: 100.0}, nil // Default 100ns latency
+}
+
+func (m *MockStore) AddAlertRule(r *db.AlertRule) (int64, error) {
+	id := int64(len(m.AlertRules) + 1)
+	r.ID = id
+	m.AlertRules[id] = *r
+	return id, nil
+}
+
+func (m *MockStore) GetAlertRules() ([]db.AlertRule, error) {
+	var rules []db.AlertRule
+	for _, r := range m.AlertRules {
+		rules = append(rules, r)
 	}
-	return 100.0, nil // Default 100ns latency
+	return rules, nil
+}
+
+func (m *MockStore) GetAlertRulesForTarget(targetID int64) ([]db.AlertRule, error) {
+	var rules []db.AlertRule
+	for _, r := range m.AlertRules {
+		if r.TargetID == targetID {
+			rules = append(rules, r)
+		}
+	}
+	return rules, nil
+}
+
+func (m *MockStore) DeleteAlertRule(id, targetID int64) error {
+	if r, ok := m.AlertRules[id]; !ok || r.TargetID != targetID {
+		return errors.New("not found")
+	}
+	delete(m.AlertRules, id)
+	return nil
+}
+
+func (m *MockStore) SetAlertRuleState(id int64, firing bool, lastWindow time.Time) error {
+	if m.SetAlertRuleStateFn != nil {
+		return m.SetAlertRuleStateFn(id, firing, lastWindow)
+	}
+	r := m.AlertRules[id]
+	r.Firing = firing
+	r.LastWindow = &lastWindow
+	m.AlertRules[id] = r
+	return nil
 }
@@ -0,0 +1,108 @@
+package scheduler
+
+import (
+	"bytes"
+	"encoding/json"
+	"fmt"
+	"net/http"
+	"time"
+	"vaportrail/internal/db"
+)
+
+// Notifier delivers an alert state change to an external service.
+type Notifier interface {
+	Send(alert AlertPayload) error
+}
+
+// newNotifier returns the notifier for a rule's channel. Rules without a channel
+// use the generic webhook.
+func newNotifier(r db.AlertRule, client *http.Client) (Notifier, error) {
+	switch r.Channel {
+	case "", "webhook":
+		return GenericWebhookNotifier{URL: r.WebhookURL, Client: client}, nil
+	case "slack":
+		return SlackNotifier{URL: r.WebhookURL, Client: client}, nil
+	default:
+		return nil, fmt.Errorf("unknown channel %q (expected webhook or slack)", r.Channel)
+	}
+}
+
+// GenericWebhookNotifier posts the AlertPayload as JSON.
+type GenericWebhookNotifier struct {
+	URL    string
+	Client *http.Client
+}
+
+func (n GenericWebhookNotifier) Send(alert AlertPayload) error {
+	return postJSON(n.Client, n.URL, alert)
+}
+
+// SlackNotifier posts a Block Kit message to a Slack incoming webhook.
+type SlackNotifier struct {
+	URL    string
+	Client *http.Client
+}
+
+type slackText struct {
+	Type string `json:"type"`
+	Text string `json:"text"`
+}
+
+type slackBlock struct {
+	Type   string      `json:"type"`
+	Text   *slackText  `json:"text,omitempty"`
+	Fields []slackText `json:"fields,omitempty"`
+}
+
+type slackMessage struct {
+	Text   string       `json:"text"` // Shown in notifications and clients without blocks
+	Blocks []slackBlock `json:"blocks"`
+}
+
+func (n SlackNotifier) Send(alert AlertPayload) error {
+	return postJSON(n.Client, n.URL, slackAlertMessage(alert))
+}
+
+func slackAlertMessage(alert AlertPayload) slackMessage {
+	name := alert.TargetName
+	if name == "" {
+		name = fmt.Sprintf("Target %d", alert.TargetID)
+	}
+	summary := fmt.Sprintf(":rotating_light: %s is breaching its %s alert", name, alert.Metric)
+	if alert.State == "resolved" {
+		summary = fmt.Sprintf(":white_check_mark: %s has recovered from its %s alert", name, alert.Metric)
+	}
+
+	unit := " ms"
+	if alert.Metric == "timeout_percent" || alert.Metric == "error_percent" {
+		unit = "%"
+	}
+	return slackMessage{
+		Text: summary,
+		Blocks: []slackBlock{
+			{Type: "section", Text: &slackText{Type: "mrkdwn", Text: "*" + summary + "*"}},
+			{Type: "section", Fields: []slackText{
+				{Type: "mrkdwn", Text: fmt.Sprintf("*Value*\n%.2f%s", alert.Value, unit)},
+				{Type: "mrkdwn", Text: fmt.Sprintf("*Rule*\n%s %s %g%s", alert.Metric, alert.Comparison, alert.Threshold, unit)},
+				{Type: "mrkdwn", Text: fmt.Sprintf("*Window*\n%ds from %s", alert.WindowSeconds, alert.WindowStart.UTC().Format(time.RFC3339))},
+				{Type: "mrkdwn", Text: fmt.Sprintf("*State*\n%s", alert.State)},
+			}},
+		},
+	}
+}
+
+func postJSON(client *http.Client, url string, v any) error {
+	body, err := json.Marshal(v)
+	if err != nil {
+		return err
+	}
+	resp, err := client.Post(url, "application/json", bytes.NewReader(body))
+	if err != nil {
+		return fmt.Errorf("webhook failed: %w", err)
+	}
+	resp.Body.Close()
+	if resp.StatusCode < 200 || resp.StatusCode > 299 {
+		return fmt.Errorf("webhook returned status %d", resp.StatusCode)
+	}
+	return nil
+}
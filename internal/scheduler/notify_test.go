@@ -0,0 +1,76 @@
+package scheduler
+
+import (
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"testing"
+	"time"
+	"vaportrail/internal/db"
+)
+
+func TestSlackNotifier(t *testing.T) {
+	var msg slackMessage
+	slack := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if ct := r.Header.Get("Content-Type"); ct != "application/json" {
+			t.Errorf("Expected JSON content type, got %q", ct)
+		}
+		if err := json.NewDecoder(r.Body).Decode(&msg); err != nil {
+			t.Errorf("Failed to decode Slack message: %v", err)
+		}
+	}))
+	defer slack.Close()
+
+	notifier, err := newNotifier(db.AlertRule{Channel: "slack", WebhookURL: slack.URL}, slack.Client())
+	if err != nil {
+		t.Fatalf("newNotifier failed: %v", err)
+	}
+	if _, ok := notifier.(SlackNotifier); !ok {
+		t.Fatalf("Expected a SlackNotifier, got %T", notifier)
+	}
+
+	err = notifier.Send(AlertPayload{
+		State:         "firing",
+		TargetName:    "voip-gw",
+		Metric:        "p99",
+		Comparison:    ">",
+		Threshold:     50,
+		Value:         123.456,
+		WindowSeconds: 60,
+		WindowStart:   time.Date(2024, 1, 2, 3, 4, 0, 0, time.UTC),
+	})
+	if err != nil {
+		t.Fatalf("Send failed: %v", err)
+	}
+
+	if !strings.Contains(msg.Text, "voip-gw is breaching its p99 alert") {
+		t.Errorf("Unexpected fallback text %q", msg.Text)
+	}
+	if len(msg.Blocks) != 2 || len(msg.Blocks[1].Fields) != 4 {
+		t.Fatalf("Expected a summary and a fields block, got %+v", msg.Blocks)
+	}
+	fields := msg.Blocks[1].Fields
+	for i, want := range []string{"123.46 ms", "p99 > 50 ms", "60s from 2024-01-02T03:04:00Z", "firing"} {
+		if !strings.Contains(fields[i].Text, want) {
+			t.Errorf("Field %d: expected %q in %q", i, want, fields[i].Text)
+		}
+	}
+
+	recovered := slackAlertMessage(AlertPayload{State: "resolved", TargetID: 7, Metric: "timeout_percent", Comparison: ">", Threshold: 5, Value: 1})
+	if !strings.Contains(recovered.Text, "Target 7 has recovered") || !strings.Contains(recovered.Blocks[1].Fields[0].Text, "1.00%") {
+		t.Errorf("Unexpected recovery message: %+v", recovered)
+	}
+}
+
+func TestNewNotifierDefaultsToWebhook(t *testing.T) {
+	for _, channel := range []string{"", "webhook"} {
+		notifier, err := newNotifier(db.AlertRule{Channel: channel, WebhookURL: "http://example.com"}, http.DefaultClient)
+		if err != nil {
+			t.Fatalf("%q: newNotifier failed: %v", channel, err)
+		}
+		if _, ok := notifier.(GenericWebhookNotifier); !ok {
+			t.Errorf("%q: expected a GenericWebhookNotifier, got %T", channel, notifier)
+		}
+	}
+}
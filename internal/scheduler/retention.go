@@ -9,18 +9,29 @@ import (
 	"github.com/jonboulle/clockwork"
 )
 
+// DefaultRetentionInterval is how often retention runs unless configured otherwise.
+const DefaultRetentionInterval = time.Hour
+
 type RetentionManager struct {
-	db    db.Store
-	clock clockwork.Clock
-	stop  chan struct{}
-	wg    sync.WaitGroup
+	db       db.Store
+	clock    clockwork.Clock
+	interval time.Duration
+	stop     chan struct{}
+	stopOnce sync.Once
+	wg       sync.WaitGroup
 }
 
-func NewRetentionManager(database db.Store) *RetentionManager {
+// NewRetentionManager returns a manager that enforces retention every interval, or
+// every DefaultRetentionInterval when interval is zero.
+func NewRetentionManager(database db.Store, interval time.Duration) *RetentionManager {
+	if interval <= 0 {
+		interval = DefaultRetentionInterval
+	}
 	return &RetentionManager{
-		db:    database,
-		clock: clockwork.NewRealClock(),
-		stop:  make(chan struct{}),
+		db:       database,
+		clock:    clockwork.NewRealClock(),
+		interval: interval,
+		stop:     make(chan struct{}),
 	}
 }
 
@@ -29,15 +40,16 @@ func (rm *RetentionManager) Start() {
 	go rm.run()
 }
 
+// Stop ends the retention loop and waits for a pass in progress to finish. It is
+// safe to call more than once, and before Start.
 func (rm *RetentionManager) Stop() {
-	close(rm.stop)
+	rm.stopOnce.Do(func() { close(rm.stop) })
 	rm.wg.Wait()
 }
 
 func (rm *RetentionManager) run() {
 	defer rm.wg.Done()
-	// Run retention checks every hour
-	ticker := rm.clock.NewTicker(1 * time.Hour)
+	ticker := rm.clock.NewTicker(rm.interval)
 	defer ticker.Stop()
 
 	// Initial run
@@ -67,13 +79,20 @@ func (rm *RetentionManager) enforceRetention() {
 			continue
 		}
 
+		sortPolicies(policies)
 		for _, p := range policies {
+			if p.Retention == RetainForever {
+				continue
+			}
 			cutoff := rm.clock.Now().Add(-time.Duration(p.Retention) * time.Second)
 
 			if p.Window == 0 {
-				// Raw data retention
-				// We need a DeleteRawResults method in DB
-				if err := rm.db.DeleteRawResultsBefore(t.ID, cutoff); err != nil {
+				// Raw data retention, held back until it has been rolled up
+				safe, ok := rm.rawCutoff(t, policies, cutoff)
+				if !ok {
+					continue
+				}
+				if err := rm.db.DeleteRawResultsBefore(t.ID, safe); err != nil {
 					log.Printf("RetentionManager: Failed to delete raw results for %s: %v", t.Name, err)
 				}
 			} else {
@@ -84,5 +103,59 @@ func (rm *RetentionManager) enforceRetention() {
 				}
 			}
 		}
+		rm.enforceLegacyRetention(t, policies)
+	}
+}
+
+// enforceLegacyRetention deletes legacy results that have outlived the rollups
+// they were copied from, which are those of the finest window. policies must be
+// sorted.
+func (rm *RetentionManager) enforceLegacyRetention(t db.Target, policies []RetentionPolicy) {
+	for _, p := range policies {
+		if p.Window == 0 {
+			continue
+		}
+		if p.Retention == RetainForever {
+			return
+		}
+		cutoff := rm.clock.Now().Add(-time.Duration(p.Retention) * time.Second)
+		if err := rm.db.DeleteResultsBefore(t.ID, cutoff); err != nil {
+			log.Printf("RetentionManager: Failed to delete legacy results for %s: %v", t.Name, err)
+		}
+		return
+	}
+}
+
+// rawCutoff limits the raw retention cutoff to the end of the last rollup of the
+// smallest window, which is built from raw results, so raw data is never deleted
+// before it has been rolled up when rollups lag behind. ok is false when nothing
+// can be deleted yet. policies must be sorted.
+func (rm *RetentionManager) rawCutoff(t db.Target, policies []RetentionPolicy, cutoff time.Time) (time.Time, bool) {
+	var window int
+	for _, p := range policies {
+		if p.Window > 0 {
+			window = p.Window
+			break
+		}
+	}
+	if window == 0 {
+		// Raw data is the only data kept
+		return cutoff, true
+	}
+
+	last, err := rm.db.GetLastRollupTime(t.ID, window)
+	if err != nil {
+		log.Printf("RetentionManager: Failed to get last rollup time (w=%d) for %s: %v", window, t.Name, err)
+		return time.Time{}, false
+	}
+	if last.IsZero() {
+		// Nothing was rolled up yet
+		return time.Time{}, false
+	}
+	rolledUp := last.Add(time.Duration(window) * time.Second)
+	if rolledUp.Before(cutoff) {
+		targetLogger("RetentionManager: ", t).Printf("Keeping raw results since %s until the %ds rollup catches up", rolledUp.Format(time.RFC3339), window)
+		return rolledUp, true
 	}
+	return cutoff, true
 }
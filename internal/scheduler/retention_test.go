@@ -10,7 +10,7 @@ import (
 
 func TestRetentionManager(t *testing.T) {
 	mockDB := NewMockStore()
-	rm := NewRetentionManager(mockDB)
+	rm := NewRetentionManager(mockDB, DefaultRetentionInterval)
 	fakeClock := clockwork.NewFakeClock()
 	rm.clock = fakeClock
 
@@ -73,3 +73,105 @@ func TestRetentionManager(t *testing.T) {
 		t.Errorf("Expected T-10s agg to be kept, got %v", aggs[0].Time)
 	}
 }
+
+func TestRetentionManager_RetainRawForever(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRetentionManager(mockDB, DefaultRetentionInterval)
+	fakeClock := clockwork.NewFakeClock()
+	rm.clock = fakeClock
+
+	target := db.Target{
+		Name:              "ForeverTarget",
+		ProbeType:         "http",
+		RetentionPolicies: `[{"window": 0, "retention": -1}, {"window": 60, "retention": 3600}]`,
+	}
+	id, _ := mockDB.AddTarget(&target)
+
+	now := fakeClock.Now().Truncate(time.Minute)
+	old := now.Add(-365 * 24 * time.Hour)
+	mockDB.AddRawResults([]db.RawResult{{Time: old, TargetID: id, Latency: 100}})
+	mockDB.AddAggregatedResult(&db.AggregatedResult{Time: old, TargetID: id, WindowSeconds: 60})
+	mockDB.AddAggregatedResult(&db.AggregatedResult{Time: now.Add(-time.Minute), TargetID: id, WindowSeconds: 60})
+
+	rm.enforceRetention()
+
+	if raws, _ := mockDB.GetRawResults(id, old, now, -1); len(raws) != 1 {
+		t.Errorf("Expected the year old raw result kept, got %d", len(raws))
+	}
+	// Rollups still expire
+	if aggs, _ := mockDB.GetAggregatedResults(id, 60, old, now); len(aggs) != 1 {
+		t.Errorf("Expected only the recent rollup kept, got %d", len(aggs))
+	}
+}
+
+func TestRetentionManager_LegacyResults(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRetentionManager(mockDB, DefaultRetentionInterval)
+	fakeClock := clockwork.NewFakeClock()
+	rm.clock = fakeClock
+
+	// Legacy results are copied from the 60s rollups, so they share their retention
+	target := db.Target{
+		Name:              "LegacyTarget",
+		ProbeType:         "http",
+		RetentionPolicies: `[{"window": 0, "retention": 10}, {"window": 60, "retention": 3600}, {"window": 300, "retention": 86400}]`,
+	}
+	id, _ := mockDB.AddTarget(&target)
+
+	now := fakeClock.Now()
+	mockDB.AddResult(&db.Result{Time: now.Add(-2 * time.Hour), TargetID: id})
+	mockDB.AddResult(&db.Result{Time: now.Add(-30 * time.Minute), TargetID: id})
+
+	rm.enforceRetention()
+
+	results, _ := mockDB.GetResultsByTime(id, now.Add(-24*time.Hour), now)
+	if len(results) != 1 {
+		t.Fatalf("Expected 1 legacy result kept, got %d", len(results))
+	}
+	if !results[0].Time.Equal(now.Add(-30 * time.Minute)) {
+		t.Errorf("Expected the T-30m legacy result kept, got %v", results[0].Time)
+	}
+}
+
+func TestRetentionManager_KeepsRawDataUntilRolledUp(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRetentionManager(mockDB, DefaultRetentionInterval)
+	fakeClock := clockwork.NewFakeClock()
+	rm.clock = fakeClock
+
+	target := db.Target{
+		Name:              "LaggingTarget",
+		ProbeType:         "http",
+		RetentionPolicies: `[{"window": 0, "retention": 600}, {"window": 60, "retention": 3600}]`,
+	}
+	id, _ := mockDB.AddTarget(&target)
+
+	now := fakeClock.Now().Truncate(time.Minute)
+	for _, ago := range []time.Duration{30 * time.Minute, 20 * time.Minute, 15 * time.Minute} {
+		mockDB.AddRawResults([]db.RawResult{{Time: now.Add(-ago), TargetID: id, Latency: 100}})
+	}
+	countRaw := func() int {
+		raws, _ := mockDB.GetRawResults(id, now.Add(-time.Hour), now, -1)
+		return len(raws)
+	}
+
+	// Nothing was rolled up yet, so nothing is deleted
+	rm.enforceRetention()
+	if n := countRaw(); n != 3 {
+		t.Fatalf("Expected all 3 raw results kept before any rollup, got %d", n)
+	}
+
+	// Rollups reached the window starting 21 minutes ago: only the oldest result is covered
+	mockDB.AddAggregatedResult(&db.AggregatedResult{Time: now.Add(-21 * time.Minute), TargetID: id, WindowSeconds: 60})
+	rm.enforceRetention()
+	if n := countRaw(); n != 2 {
+		t.Fatalf("Expected 2 raw results kept while rollups lag, got %d", n)
+	}
+
+	// Once rollups catch up, the retention cutoff applies
+	mockDB.AddAggregatedResult(&db.AggregatedResult{Time: now.Add(-time.Minute), TargetID: id, WindowSeconds: 60})
+	rm.enforceRetention()
+	if n := countRaw(); n != 0 {
+		t.Errorf("Expected raw results past retention deleted, got %d", n)
+	}
+}
@@ -5,6 +5,7 @@ import (
 	"errors"
 	"fmt"
 	"log"
+	"math"
 	"sort"
 	"sync"
 	"time"
@@ -14,11 +15,34 @@ import (
 	"github.com/jonboulle/clockwork"
 )
 
+// RetainForever is the retention that keeps raw results indefinitely. It is only
+// accepted for the raw (window 0) policy. A retention of 0 is not a sentinel: it is
+// rejected like any other retention that isn't positive.
+const RetainForever = -1
+
 type RetentionPolicy struct {
 	Window    int `json:"window"`
 	Retention int `json:"retention"`
+	// Compression is the TDigest compression for this window's rollups. Higher values
+	// give more accurate tails at the cost of space. Zero uses RollupManager.Compression.
+	Compression uint32 `json:"compression,omitempty"`
 }
 
+const (
+	minCompression = 10
+	maxCompression = 1000
+)
+
+// maxRetentionWindows bounds the number of retention policies a target can have.
+const maxRetentionWindows = 8
+
+// Limits on the number of aggregated rows a target's policies keep. Above the
+// warning the policies are accepted but logged, above the limit they are rejected.
+const (
+	rollupRowsWarning = 5_000_000
+	rollupRowsLimit   = 100_000_000
+)
+
 var defaultPolicies = []RetentionPolicy{
 	{Window: 0, Retention: 604800},         // Raw: 7 days
 	{Window: 60, Retention: 15768000},      // 1m: 6 months
@@ -27,7 +51,14 @@ var defaultPolicies = []RetentionPolicy{
 	{Window: 86400, Retention: 3153600000}, // 1d: ~100 years (User didn't specify retention for 1d, assuming long)
 }
 
+// ValidateRetentionPolicies checks a target's policies and sorts them by window. It
+// logs a warning, but doesn't fail, when the rollup windows would keep more than
+// rollupRowsWarning rows.
 func ValidateRetentionPolicies(policies []RetentionPolicy) error {
+	if len(policies) > maxRetentionWindows {
+		return fmt.Errorf("too many retention windows: %d (at most %d)", len(policies), maxRetentionWindows)
+	}
+
 	// Sort policies by window size
 	sortPolicies(policies)
 
@@ -35,6 +66,22 @@ func ValidateRetentionPolicies(policies []RetentionPolicy) error {
 		if p.Window < 0 {
 			return errors.New("retention window cannot be negative")
 		}
+		if p.Retention <= 0 && !(p.Window == 0 && p.Retention == RetainForever) {
+			if p.Window == 0 {
+				return fmt.Errorf("retention for window 0 must be positive, or %d to keep raw results forever", RetainForever)
+			}
+			return fmt.Errorf("retention for window %d must be positive", p.Window)
+		}
+		if i > 0 && p.Window == policies[i-1].Window {
+			return fmt.Errorf("window %d is configured more than once", p.Window)
+		}
+		// A window's data must outlive the next window, which is rolled up from it
+		if i+1 < len(policies) && p.Retention != RetainForever && p.Retention < policies[i+1].Window {
+			return fmt.Errorf("retention %d for window %d is shorter than the next window %d", p.Retention, p.Window, policies[i+1].Window)
+		}
+		if p.Compression != 0 && (p.Compression < minCompression || p.Compression > maxCompression) {
+			return fmt.Errorf("compression %d for window %d must be between %d and %d", p.Compression, p.Window, minCompression, maxCompression)
+		}
 		if i == 0 {
 			if p.Window == 0 {
 				continue // 0 (Raw) is valid base
@@ -52,9 +99,29 @@ func ValidateRetentionPolicies(policies []RetentionPolicy) error {
 			}
 		}
 	}
+
+	rows := rollupRows(policies)
+	if rows > rollupRowsLimit {
+		return fmt.Errorf("retention policies would keep about %d aggregated rows per target (at most %d)", rows, rollupRowsLimit)
+	}
+	if rows > rollupRowsWarning {
+		log.Printf("RollupManager: Warning: retention policies keep about %d aggregated rows per target", rows)
+	}
 	return nil
 }
 
+// rollupRows estimates the number of aggregated rows policies keep per target. Raw
+// results are left out, as their number depends on the probe interval.
+func rollupRows(policies []RetentionPolicy) int64 {
+	var rows int64
+	for _, p := range policies {
+		if p.Window > 0 {
+			rows += int64(p.Retention / p.Window)
+		}
+	}
+	return rows
+}
+
 func sortPolicies(policies []RetentionPolicy) {
 	sort.Slice(policies, func(i, j int) bool {
 		return policies[i].Window < policies[j].Window
@@ -94,18 +161,40 @@ func GetRetentionPolicies(t db.Target) ([]RetentionPolicy, error) {
 	return p, nil
 }
 
+// DefaultRollupInterval is how often rollups run unless configured otherwise.
+const DefaultRollupInterval = 10 * time.Second
+
 type RollupManager struct {
-	db    db.Store
-	clock clockwork.Clock
-	stop  chan struct{}
-	wg    sync.WaitGroup
+	db       db.Store
+	clock    clockwork.Clock
+	interval time.Duration
+	stop     chan struct{}
+	stopOnce sync.Once
+	wg       sync.WaitGroup
+	// mu serializes rollup passes, so a manual RollupTarget doesn't race the ticker
+	mu sync.Mutex
+
+	// LegacyResults also copies each window of a target's finest rollup, the one
+	// built from raw results, into the legacy results table with its min, avg, max,
+	// p50 and p99, for dashboards that still query it. The retention manager expires
+	// those rows with the finest rollup. It must be set before Start.
+	LegacyResults bool
+	// Compression is the TDigest compression for windows whose policy doesn't set
+	// one. Zero uses db.DefaultTDigestCompression. It must be set before Start.
+	Compression uint32
 }
 
-func NewRollupManager(database db.Store) *RollupManager {
+// NewRollupManager returns a manager that rolls up every interval, or every
+// DefaultRollupInterval when interval is zero.
+func NewRollupManager(database db.Store, interval time.Duration) *RollupManager {
+	if interval <= 0 {
+		interval = DefaultRollupInterval
+	}
 	return &RollupManager{
-		db:    database,
-		clock: clockwork.NewRealClock(),
-		stop:  make(chan struct{}),
+		db:       database,
+		clock:    clockwork.NewRealClock(),
+		interval: interval,
+		stop:     make(chan struct{}),
 	}
 }
 
@@ -114,14 +203,16 @@ func (rm *RollupManager) Start() {
 	go rm.run()
 }
 
+// Stop ends the rollup loop and waits for a pass in progress to finish. It is safe
+// to call more than once, and before Start.
 func (rm *RollupManager) Stop() {
-	close(rm.stop)
+	rm.stopOnce.Do(func() { close(rm.stop) })
 	rm.wg.Wait()
 }
 
 func (rm *RollupManager) run() {
 	defer rm.wg.Done()
-	ticker := rm.clock.NewTicker(10 * time.Second)
+	ticker := rm.clock.NewTicker(rm.interval)
 	defer ticker.Stop()
 
 	for {
@@ -135,6 +226,9 @@ func (rm *RollupManager) run() {
 }
 
 func (rm *RollupManager) processRollups() {
+	rm.mu.Lock()
+	defer rm.mu.Unlock()
+
 	targets, err := rm.db.GetTargets()
 	if err != nil {
 		log.Printf("RollupManager: Failed to get targets: %v", err)
@@ -142,37 +236,130 @@ func (rm *RollupManager) processRollups() {
 	}
 
 	for _, t := range targets {
-		policies, err := GetRetentionPolicies(t)
-		if err != nil {
-			// Skip targets with no policies configured
-			continue
+		if _, err := rm.rollupTarget(t); err != nil {
+			// Targets with no policies configured are skipped silently
+			if !errors.Is(err, ErrNoRetentionPolicies) {
+				targetLogger("RollupManager: ", t).Printf("%v", err)
+			}
 		}
-		// Ensure sorted
-		sortPolicies(policies)
+	}
+}
 
-		// Map windows to find source
-		// 0 -> Raw
-		lastWindow := 0
+// RollupTarget immediately runs a rollup pass for every configured window of t and
+// returns the number of aggregated windows written. Like the periodic pass, it only
+// writes windows that are past the cutoff, so it never stores an incomplete window.
+func (rm *RollupManager) RollupTarget(t db.Target) (int, error) {
+	rm.mu.Lock()
+	defer rm.mu.Unlock()
+	return rm.rollupTarget(t)
+}
 
-		for _, p := range policies {
-			if p.Window == 0 {
-				lastWindow = 0
+// DiffRetentionWindows lists the rollup windows that are in old but not in new, and
+// those in new but not in old. The raw window is left out.
+func DiffRetentionWindows(old, new []RetentionPolicy) (removed, added []int) {
+	oldWindows := make(map[int]bool, len(old))
+	for _, p := range old {
+		oldWindows[p.Window] = true
+	}
+	newWindows := make(map[int]bool, len(new))
+	for _, p := range new {
+		newWindows[p.Window] = true
+		if p.Window != 0 && !oldWindows[p.Window] {
+			added = append(added, p.Window)
+		}
+	}
+	for _, p := range old {
+		if p.Window != 0 && !newWindows[p.Window] {
+			removed = append(removed, p.Window)
+		}
+	}
+	return removed, added
+}
+
+// ApplyRetentionChange brings t's rollups in line with its updated retention policies
+// in the background: the aggregated results of removed windows are deleted, and added
+// windows are rolled up from the data already stored rather than on the next pass.
+// Stop waits for it to finish.
+func (rm *RollupManager) ApplyRetentionChange(t db.Target, removed, added []int) {
+	if len(removed) == 0 && len(added) == 0 {
+		return
+	}
+	rm.wg.Add(1)
+	go func() {
+		defer rm.wg.Done()
+		rm.mu.Lock()
+		defer rm.mu.Unlock()
+
+		logger := targetLogger("RollupManager: ", t)
+		for _, window := range removed {
+			if err := rm.db.DeleteAggregatedResultsByWindow(t.ID, window); err != nil {
+				logger.Printf("Failed to delete rollups of removed window %ds: %v", window, err)
 				continue
 			}
+			logger.Printf("Deleted rollups of removed window %ds", window)
+		}
+		if len(added) == 0 {
+			return
+		}
+		n, err := rm.rollupTarget(t)
+		if err != nil {
+			logger.Printf("Rollup for added windows %v failed after %d windows: %v", added, n, err)
+			return
+		}
+		logger.Printf("Rolled up %d windows for added windows %v", n, added)
+	}()
+}
 
-			// Process this window using lastWindow as source
-			rm.processTargetWindow(t, p.Window, lastWindow)
-			lastWindow = p.Window
+// rollupTarget processes each window of t in ascending order, so every window is
+// built from the freshly updated one below it. It keeps going after a failed window
+// and returns the first error.
+func (rm *RollupManager) rollupTarget(t db.Target) (int, error) {
+	policies, err := GetRetentionPolicies(t)
+	if err != nil {
+		return 0, err
+	}
+	// Ensure sorted
+	sortPolicies(policies)
+
+	// Map windows to find source
+	// 0 -> Raw
+	lastWindow := 0
+	created := 0
+	var firstErr error
+
+	for _, p := range policies {
+		if p.Window == 0 {
+			lastWindow = 0
+			continue
+		}
+
+		// Fill any gap before the first rollup, then continue forward from the last one
+		n, err := rm.backfillTargetWindow(t, p, lastWindow)
+		created += n
+		if err != nil && firstErr == nil {
+			firstErr = err
+		}
+
+		// Process this window using lastWindow as source
+		n, err = rm.processTargetWindow(t, p, lastWindow)
+		created += n
+		if err != nil && firstErr == nil {
+			firstErr = err
 		}
+		lastWindow = p.Window
 	}
+	return created, firstErr
 }
 
-func (rm *RollupManager) processTargetWindow(t db.Target, windowSeconds int, sourceWindow int) {
+// processTargetWindow writes every complete window of the given size since the last
+// rollup and returns how many were written.
+func (rm *RollupManager) processTargetWindow(t db.Target, policy RetentionPolicy, sourceWindow int) (int, error) {
+	windowSeconds := policy.Window
+
 	// 1. Get last rollup time
 	lastTime, err := rm.db.GetLastRollupTime(t.ID, windowSeconds)
 	if err != nil {
-		log.Printf("RollupManager: Failed to get last rollup time for %s (w=%d): %v", t.Name, windowSeconds, err)
-		return
+		return 0, fmt.Errorf("failed to get last rollup time for %s (w=%d): %w", t.Name, windowSeconds, err)
 	}
 
 	// 2. Determine start time. If never rolled up, start from... when?
@@ -185,15 +372,20 @@ func (rm *RollupManager) processTargetWindow(t db.Target, windowSeconds int, sou
 
 	start := lastTime
 	if start.IsZero() {
-		// Optimization: Find earliest raw data time.
-		earliest, err := rm.db.GetEarliestRawResultTime(t.ID)
+		// Start from the earliest source data, so a window added to a target that
+		// already has rollups is filled from them rather than only from raw data.
+		var earliest time.Time
+		if sourceWindow == 0 {
+			earliest, err = rm.db.GetEarliestRawResultTime(t.ID)
+		} else {
+			earliest, err = rm.db.GetFirstRollupTime(t.ID, sourceWindow)
+		}
 		if err != nil {
-			log.Printf("RollupManager: Error getting earliest raw time: %v", err)
-			return
+			return 0, fmt.Errorf("error getting earliest source time for %s (w=%d): %w", t.Name, windowSeconds, err)
 		}
 		if earliest.IsZero() {
-			// No raw data? Nothing to roll up.
-			return
+			// No source data? Nothing to roll up.
+			return 0, nil
 		}
 		// Truncate to window alignment
 		start = earliest.Truncate(time.Duration(windowSeconds) * time.Second)
@@ -224,7 +416,7 @@ func (rm *RollupManager) processTargetWindow(t db.Target, windowSeconds int, sou
 			break // Caught up
 		}
 
-		agg := rm.aggregateWindow(t, windowSeconds, sourceWindow, nextWindowStart, windowEnd)
+		agg := rm.aggregateWindow(t, policy, sourceWindow, nextWindowStart, windowEnd)
 		if agg != nil {
 			results = append(results, agg)
 		}
@@ -234,36 +426,111 @@ func (rm *RollupManager) processTargetWindow(t db.Target, windowSeconds int, sou
 	// Commit all results in a single transaction
 	if len(results) > 0 {
 		if err := rm.db.AddAggregatedResults(results); err != nil {
-			log.Printf("RollupManager: Failed to save batch AggResults for %s (w=%ds): %v", t.Name, windowSeconds, err)
+			return 0, fmt.Errorf("failed to save batch AggResults for %s (w=%ds): %w", t.Name, windowSeconds, err)
+		}
+		if rm.LegacyResults && sourceWindow == 0 {
+			rm.writeLegacyResults(t, results)
+		}
+	}
+	return len(results), nil
+}
+
+// backfillTargetWindow writes the windows between the earliest source data and the
+// first existing rollup. They are missing when older raw data is imported after
+// rollups already exist, since processTargetWindow only moves forward from the last
+// rollup. Windows that were already processed are never rewritten.
+func (rm *RollupManager) backfillTargetWindow(t db.Target, policy RetentionPolicy, sourceWindow int) (int, error) {
+	windowSeconds := policy.Window
+	window := time.Duration(windowSeconds) * time.Second
+
+	firstTime, err := rm.db.GetFirstRollupTime(t.ID, windowSeconds)
+	if err != nil {
+		return 0, fmt.Errorf("failed to get first rollup time for %s (w=%d): %w", t.Name, windowSeconds, err)
+	}
+	if firstTime.IsZero() {
+		// Never rolled up, processTargetWindow starts from the earliest data
+		return 0, nil
+	}
+
+	var earliest time.Time
+	if sourceWindow == 0 {
+		earliest, err = rm.db.GetEarliestRawResultTime(t.ID)
+	} else {
+		earliest, err = rm.db.GetFirstRollupTime(t.ID, sourceWindow)
+	}
+	if err != nil {
+		return 0, fmt.Errorf("error getting earliest source time for %s (w=%d): %w", t.Name, windowSeconds, err)
+	}
+	if earliest.IsZero() || !earliest.Before(firstTime) {
+		return 0, nil
+	}
+
+	var results []*db.AggregatedResult
+	for start := earliest.Truncate(window); start.Before(firstTime); start = start.Add(window) {
+		agg := rm.aggregateWindow(t, policy, sourceWindow, start, start.Add(window))
+		if agg != nil {
+			results = append(results, agg)
+		}
+	}
+
+	if len(results) > 0 {
+		targetLogger("RollupManager: ", t).Printf("Backfilling %d windows (w=%ds) before %s", len(results), windowSeconds, firstTime.Format(time.RFC3339))
+		if err := rm.db.AddAggregatedResults(results); err != nil {
+			return 0, fmt.Errorf("failed to save backfilled AggResults for %s (w=%ds): %w", t.Name, windowSeconds, err)
+		}
+		if rm.LegacyResults && sourceWindow == 0 {
+			rm.writeLegacyResults(t, results)
 		}
 	}
+	return len(results), nil
 }
 
-func (rm *RollupManager) aggregateWindow(t db.Target, windowSeconds int, sourceWindow int, start, end time.Time) *db.AggregatedResult {
+func (rm *RollupManager) aggregateWindow(t db.Target, policy RetentionPolicy, sourceWindow int, start, end time.Time) *db.AggregatedResult {
+	windowSeconds := policy.Window
+	logger := targetLogger("RollupManager: ", t)
+
 	// Source Data Fetching
 	var tDigest *tdigest.TDigest
-	var timeoutCount int64
+	var timeoutCount, errorCount, attempts int64
 	var rowsProcessed int
+	var extremes latencyRange
+	var jitter jitterMean
 	var err error
 
 	if sourceWindow == 0 {
 		// Aggregate from Raw
 		raws, err := rm.db.GetRawResults(t.ID, start, end, -1)
 		if err != nil {
-			log.Printf("RollupManager: Error fetching raw results: %v", err)
+			logger.Printf("Error fetching raw results: %v", err)
 			return nil
 		}
 		rowsProcessed = len(raws)
 		if len(raws) == 0 {
-			return rm.createEmptyRollup(t, windowSeconds, start)
+			return rm.createEmptyRollup(t, policy, start)
 		}
+		attempts = int64(len(raws))
 
-		tDigest, _ = tdigest.New(tdigest.Compression(100))
+		tDigest, err = db.NewTDigest(rm.compression(policy))
+		if err != nil {
+			logger.Printf("Failed to create TDigest (w=%ds): %v", windowSeconds, err)
+			return nil
+		}
+		// Jitter is measured between consecutive successes, skipping failed probes
+		var prev *float64
 		for _, r := range raws {
-			if r.Latency == -1 {
+			switch r.Latency {
+			case db.LatencyTimeout:
 				timeoutCount++
-			} else {
+			case db.LatencyError:
+				errorCount++
+			default:
 				tDigest.Add(r.Latency)
+				extremes.observe(r.Latency, r.Latency)
+				if prev != nil {
+					jitter.add(math.Abs(r.Latency-*prev), 1)
+				}
+				latency := r.Latency
+				prev = &latency
 			}
 		}
 
@@ -276,33 +543,51 @@ func (rm *RollupManager) aggregateWindow(t db.Target, windowSeconds int, sourceW
 
 		results, err := rm.db.GetAggregatedResults(t.ID, sourceWindow, start, end)
 		if err != nil {
-			log.Printf("RollupManager: Error fetching aggregated results (w=%d): %v", sourceWindow, err)
+			logger.Printf("Error fetching aggregated results (w=%d): %v", sourceWindow, err)
 			return nil
 		}
 		rowsProcessed = len(results)
 		if len(results) == 0 {
-			return rm.createEmptyRollup(t, windowSeconds, start)
+			return rm.createEmptyRollup(t, policy, start)
 		}
 
-		tDigest, _ = tdigest.New(tdigest.Compression(100))
+		// Merge the sub-window digests rather than resampling them, so coarse
+		// windows keep the shape of the original distribution
+		subTDs := make([]*tdigest.TDigest, 0, len(results))
 		for _, res := range results {
 			timeoutCount += res.TimeoutCount
+			errorCount += res.ErrorCount
+			attempts += res.Attempts()
+			if res.MinNS != nil && res.MaxNS != nil {
+				extremes.observe(*res.MinNS, *res.MaxNS)
+			}
 			if len(res.TDigestData) > 0 {
 				subTD, err := db.DeserializeTDigest(res.TDigestData)
-				if err == nil {
-					tDigest.Merge(subTD)
+				if err != nil {
+					logger.Printf("Skipping unreadable TDigest (w=%d, t=%s): %v", sourceWindow, res.Time.Format(time.RFC3339), err)
+					continue
+				}
+				subTDs = append(subTDs, subTD)
+				// Weight each sub-window's jitter by its number of successful probes
+				if res.JitterNS != nil {
+					jitter.add(*res.JitterNS, float64(subTD.Count()))
 				}
 			}
 		}
+		tDigest, err = db.MergeTDigests(rm.compression(policy), subTDs)
+		if err != nil {
+			logger.Printf("Failed to merge TDigests (w=%ds): %v", windowSeconds, err)
+			return nil
+		}
 	}
 
 	tdBytes, err := db.SerializeTDigest(tDigest)
 	if err != nil {
-		log.Printf("RollupManager: Serialization failed: %v", err)
+		logger.Printf("Serialization failed: %v", err)
 		return nil
 	}
 
-	log.Printf("RollupManager: Aggregated %s (w=%ds, start=%s): %d rows, %d timeouts", t.Name, windowSeconds, start.Format("15:04:05"), rowsProcessed, timeoutCount)
+	logger.Printf("Aggregated (w=%ds, start=%s): %d rows, %d timeouts, %d errors", windowSeconds, start.Format("15:04:05"), rowsProcessed, timeoutCount, errorCount)
 
 	return &db.AggregatedResult{
 		Time:          start,
@@ -310,17 +595,110 @@ func (rm *RollupManager) aggregateWindow(t db.Target, windowSeconds int, sourceW
 		WindowSeconds: windowSeconds,
 		TDigestData:   tdBytes,
 		TimeoutCount:  timeoutCount,
+		ErrorCount:    errorCount,
+		MinNS:         extremes.min,
+		MaxNS:         extremes.max,
+		JitterNS:      jitter.value(),
+		AttemptCount:  &attempts,
 	}
 }
 
-func (rm *RollupManager) createEmptyRollup(t db.Target, windowSeconds int, start time.Time) *db.AggregatedResult {
-	td, _ := tdigest.New(tdigest.Compression(100))
+// latencyRange tracks the exact minimum and maximum latency of a window,
+// which the TDigest can only approximate.
+type latencyRange struct {
+	min, max *float64
+}
+
+func (r *latencyRange) observe(lo, hi float64) {
+	if r.min == nil || lo < *r.min {
+		r.min = &lo
+	}
+	if r.max == nil || hi > *r.max {
+		r.max = &hi
+	}
+}
+
+// jitterMean is a weighted mean of jitter samples.
+type jitterMean struct {
+	sum, weight float64
+}
+
+func (j *jitterMean) add(v, weight float64) {
+	j.sum += v * weight
+	j.weight += weight
+}
+
+// value returns the mean, or nil if nothing was added.
+func (j *jitterMean) value() *float64 {
+	if j.weight == 0 {
+		return nil
+	}
+	mean := j.sum / j.weight
+	return &mean
+}
+
+// writeLegacyResults copies rolled up windows into the legacy results table. Errors
+// are only logged, so the legacy table can never hold up the rollups themselves.
+func (rm *RollupManager) writeLegacyResults(t db.Target, results []*db.AggregatedResult) {
+	logger := targetLogger("RollupManager: ", t)
+	for _, agg := range results {
+		r, err := legacyResult(agg)
+		if err == nil {
+			err = rm.db.AddResult(r)
+		}
+		if err != nil {
+			logger.Printf("Failed to write legacy result at %s: %v", agg.Time.Format(time.RFC3339), err)
+		}
+	}
+}
+
+// legacyResult computes a legacy results row from an aggregated window. The
+// latency statistics are left nil when the window has no successful probes.
+func legacyResult(agg *db.AggregatedResult) (*db.Result, error) {
+	r := &db.Result{
+		Time:         agg.Time,
+		TargetID:     agg.TargetID,
+		TimeoutCount: agg.TimeoutCount,
+		TDigestData:  agg.TDigestData,
+		MinNS:        agg.MinNS,
+		MaxNS:        agg.MaxNS,
+	}
+	td, err := db.DeserializeTDigest(agg.TDigestData)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read TDigest: %w", err)
+	}
+	var totalMass, weightedSum float64
+	td.ForEachCentroid(func(mean float64, count uint64) bool {
+		totalMass += float64(count)
+		weightedSum += mean * float64(count)
+		return true
+	})
+	if totalMass == 0 {
+		return r, nil
+	}
+	avg, p50, p99 := weightedSum/totalMass, td.Quantile(0.5), td.Quantile(0.99)
+	r.AvgNS, r.P50NS, r.P99NS = &avg, &p50, &p99
+	return r, nil
+}
+
+// compression returns the TDigest compression for a window's rollups.
+func (rm *RollupManager) compression(policy RetentionPolicy) uint32 {
+	if policy.Compression != 0 {
+		return policy.Compression
+	}
+	return rm.Compression
+}
+
+func (rm *RollupManager) createEmptyRollup(t db.Target, policy RetentionPolicy, start time.Time) *db.AggregatedResult {
+	td, _ := db.NewTDigest(rm.compression(policy))
 	tdBytes, _ := db.SerializeTDigest(td)
+	var attempts int64
 	return &db.AggregatedResult{
 		Time:          start,
 		TargetID:      t.ID,
-		WindowSeconds: windowSeconds,
+		WindowSeconds: policy.Window,
 		TDigestData:   tdBytes,
 		TimeoutCount:  0,
+		AttemptCount:  &attempts,
 	}
 }
@@ -1,6 +1,7 @@
 package scheduler
 
 import (
+	"strings"
 	"testing"
 	"time"
 	"vaportrail/internal/db"
@@ -10,7 +11,7 @@ import (
 
 func TestRollupManager(t *testing.T) {
 	mockDB := NewMockStore()
-	rm := NewRollupManager(mockDB)
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
 	fakeClock := clockwork.NewFakeClock()
 	rm.clock = fakeClock
 
@@ -82,7 +83,7 @@ func TestRollupManager(t *testing.T) {
 
 func TestRollupManager_CatchUp(t *testing.T) {
 	mockDB := NewMockStore()
-	rm := NewRollupManager(mockDB)
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
 	fakeClock := clockwork.NewFakeClock()
 	rm.clock = fakeClock
 
@@ -146,7 +147,7 @@ func TestRollupManager_CatchUp(t *testing.T) {
 
 func TestRollupManager_Cascading(t *testing.T) {
 	mockDB := NewMockStore()
-	rm := NewRollupManager(mockDB)
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
 	fakeClock := clockwork.NewFakeClock()
 	rm.clock = fakeClock
 
@@ -230,3 +231,380 @@ func TestRollupManager_Cascading(t *testing.T) {
 		t.Errorf("Expected Median 100.0, got %v", td.Quantile(0.5))
 	}
 }
+
+func TestRollupManager_TracksExactMinMax(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
+
+	target := db.Target{Name: "MinMaxTarget", Timeout: 1.0}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+
+	start := time.Unix(0, 0).UTC()
+	// Two 10s windows: the first holds 1..10 plus a timeout, the second 1000..1009
+	for i := 0; i < 10; i++ {
+		mockDB.AddRawResults([]db.RawResult{
+			{Time: start.Add(time.Duration(i) * time.Second), TargetID: id, Latency: float64(i + 1)},
+			{Time: start.Add(time.Duration(10+i) * time.Second), TargetID: id, Latency: float64(1000 + i)},
+		})
+	}
+	mockDB.AddRawResults([]db.RawResult{{Time: start.Add(5 * time.Second), TargetID: id, Latency: -1}})
+
+	first := rm.aggregateWindow(target, RetentionPolicy{Window: 10}, 0, start, start.Add(10*time.Second))
+	second := rm.aggregateWindow(target, RetentionPolicy{Window: 10}, 0, start.Add(10*time.Second), start.Add(20*time.Second))
+	for _, tc := range []struct {
+		agg      *db.AggregatedResult
+		min, max float64
+	}{
+		{first, 1, 10},
+		{second, 1000, 1009},
+	} {
+		if tc.agg.MinNS == nil || tc.agg.MaxNS == nil {
+			t.Fatalf("window %v: min/max not recorded", tc.agg.Time)
+		}
+		if *tc.agg.MinNS != tc.min || *tc.agg.MaxNS != tc.max {
+			t.Errorf("window %v: got min=%v max=%v, want %v/%v", tc.agg.Time, *tc.agg.MinNS, *tc.agg.MaxNS, tc.min, tc.max)
+		}
+	}
+
+	// Rolling the 10s windows up keeps the extremes of the whole minute
+	mockDB.AddAggregatedResults([]*db.AggregatedResult{first, second})
+	minute := rm.aggregateWindow(target, RetentionPolicy{Window: 60}, 10, start, start.Add(60*time.Second))
+	if minute.MinNS == nil || *minute.MinNS != 1 || minute.MaxNS == nil || *minute.MaxNS != 1009 {
+		t.Errorf("60s rollup: got min=%v max=%v, want 1/1009", minute.MinNS, minute.MaxNS)
+	}
+
+	// A window with only timeouts has no extremes
+	mockDB.AddRawResults([]db.RawResult{{Time: start.Add(25 * time.Second), TargetID: id, Latency: -1}})
+	timeouts := rm.aggregateWindow(target, RetentionPolicy{Window: 10}, 0, start.Add(20*time.Second), start.Add(30*time.Second))
+	if timeouts.MinNS != nil || timeouts.MaxNS != nil {
+		t.Errorf("timeout-only window: got min=%v max=%v, want nil", timeouts.MinNS, timeouts.MaxNS)
+	}
+}
+
+func TestValidateRetentionPoliciesCompression(t *testing.T) {
+	tests := []struct {
+		name     string
+		policies []RetentionPolicy
+		wantErr  bool
+	}{
+		{"default", []RetentionPolicy{{Window: 0, Retention: 60}, {Window: 60, Retention: 3600}}, false},
+		{"custom", []RetentionPolicy{{Window: 60, Retention: 3600, Compression: 50}, {Window: 3600, Retention: 86400, Compression: 200}}, false},
+		{"too small", []RetentionPolicy{{Window: 60, Retention: 3600, Compression: 5}}, true},
+		{"too large", []RetentionPolicy{{Window: 60, Retention: 3600, Compression: 5000}}, true},
+	}
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			err := ValidateRetentionPolicies(tt.policies)
+			if (err != nil) != tt.wantErr {
+				t.Errorf("ValidateRetentionPolicies() error = %v, wantErr %v", err, tt.wantErr)
+			}
+		})
+	}
+}
+
+func TestValidateRetentionPoliciesLimits(t *testing.T) {
+	if err := ValidateRetentionPolicies(DefaultPolicies()); err != nil {
+		t.Errorf("Expected the default policies to be valid, got %v", err)
+	}
+	if err := ValidateRetentionPolicies([]RetentionPolicy{{Window: 0, Retention: RetainForever}, {Window: 60, Retention: 3600}}); err != nil {
+		t.Errorf("Expected raw results kept forever to be valid, got %v", err)
+	}
+
+	var tooMany []RetentionPolicy
+	for i := 0; i <= maxRetentionWindows; i++ {
+		tooMany = append(tooMany, RetentionPolicy{Window: 60 << i, Retention: 86400 << i})
+	}
+
+	tests := []struct {
+		name     string
+		policies []RetentionPolicy
+		wantErr  string
+	}{
+		{"too many windows", tooMany, "too many retention windows"},
+		{"zero retention", []RetentionPolicy{{Window: 0, Retention: 0}, {Window: 60, Retention: 3600}}, "retention for window 0 must be positive"},
+		{"rollups kept forever", []RetentionPolicy{{Window: 0, Retention: RetainForever}, {Window: 60, Retention: RetainForever}}, "retention for window 60 must be positive"},
+		{"duplicate window", []RetentionPolicy{{Window: 60, Retention: 3600}, {Window: 60, Retention: 7200}}, "window 60 is configured more than once"},
+		{"expires before next window", []RetentionPolicy{{Window: 0, Retention: 30}, {Window: 60, Retention: 3600}}, "retention 30 for window 0 is shorter than the next window 60"},
+		{"100 years at 1s", []RetentionPolicy{{Window: 1, Retention: 3153600000}}, "aggregated rows per target"},
+	}
+	for _, tt := range tests {
+		err := ValidateRetentionPolicies(tt.policies)
+		if err == nil || !strings.Contains(err.Error(), tt.wantErr) {
+			t.Errorf("%s: expected error containing %q, got %v", tt.name, tt.wantErr, err)
+		}
+	}
+}
+
+func TestRollupManager_CountsErrorsSeparately(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
+
+	target := db.Target{Name: "ErrorTarget", Timeout: 1.0}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+
+	start := time.Unix(0, 0).UTC()
+	mockDB.AddRawResults([]db.RawResult{
+		{Time: start, TargetID: id, Latency: 100},
+		{Time: start.Add(1 * time.Second), TargetID: id, Latency: db.LatencyTimeout},
+		{Time: start.Add(2 * time.Second), TargetID: id, Latency: db.LatencyError},
+		{Time: start.Add(3 * time.Second), TargetID: id, Latency: db.LatencyError},
+		{Time: start.Add(10 * time.Second), TargetID: id, Latency: db.LatencyError},
+	})
+
+	first := rm.aggregateWindow(target, RetentionPolicy{Window: 10}, 0, start, start.Add(10*time.Second))
+	if first.TimeoutCount != 1 || first.ErrorCount != 2 {
+		t.Errorf("Expected 1 timeout and 2 errors, got %d and %d", first.TimeoutCount, first.ErrorCount)
+	}
+	td, _ := db.DeserializeTDigest(first.TDigestData)
+	if td.Count() != 1 {
+		t.Errorf("Expected only the successful probe in the TDigest, got count %d", td.Count())
+	}
+
+	second := rm.aggregateWindow(target, RetentionPolicy{Window: 10}, 0, start.Add(10*time.Second), start.Add(20*time.Second))
+	mockDB.AddAggregatedResults([]*db.AggregatedResult{first, second})
+	minute := rm.aggregateWindow(target, RetentionPolicy{Window: 60}, 10, start, start.Add(60*time.Second))
+	if minute.TimeoutCount != 1 || minute.ErrorCount != 3 {
+		t.Errorf("60s rollup: expected 1 timeout and 3 errors, got %d and %d", minute.TimeoutCount, minute.ErrorCount)
+	}
+
+	// Attempts count every probe, so the all-error window isn't mistaken for an empty one
+	empty := rm.aggregateWindow(target, RetentionPolicy{Window: 10}, 0, start.Add(20*time.Second), start.Add(30*time.Second))
+	for _, tc := range []struct {
+		name string
+		agg  *db.AggregatedResult
+		want int64
+	}{
+		{"first", first, 4},
+		{"all errors", second, 1},
+		{"no probes", empty, 0},
+		{"60s rollup", minute, 5},
+	} {
+		if tc.agg.AttemptCount == nil || *tc.agg.AttemptCount != tc.want {
+			t.Errorf("%s: expected %d attempts, got %v", tc.name, tc.want, tc.agg.AttemptCount)
+		}
+	}
+
+	// Rows written before attempts were tracked fall back to the outcome counts
+	first.AttemptCount = nil
+	if n := first.Attempts(); n != 4 {
+		t.Errorf("Expected 4 attempts derived from the outcome counts, got %d", n)
+	}
+}
+
+func TestRollupManager_BackfillsImportedHistory(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
+	fakeClock := clockwork.NewFakeClock()
+	rm.clock = fakeClock
+
+	target := db.Target{
+		Name:              "BackfillTarget",
+		Timeout:           1.0,
+		RetentionPolicies: `[{"window": 60, "retention": 3600}, {"window": 300, "retention": 3600}]`,
+	}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+
+	// Rollups already exist from startTime onwards
+	startTime := fakeClock.Now().Truncate(5 * time.Minute).Add(-10 * time.Minute)
+	mockDB.AddAggregatedResult(&db.AggregatedResult{Time: startTime, TargetID: id, WindowSeconds: 60, TimeoutCount: 7})
+	mockDB.AddAggregatedResult(&db.AggregatedResult{Time: startTime, TargetID: id, WindowSeconds: 300, TimeoutCount: 7})
+
+	// Then five minutes of older history is imported
+	for i := 0; i < 300; i++ {
+		mockDB.AddRawResults([]db.RawResult{{
+			Time:     startTime.Add(-5 * time.Minute).Add(time.Duration(i) * time.Second),
+			TargetID: id,
+			Latency:  100.0,
+		}})
+	}
+
+	if _, err := rm.RollupTarget(target); err != nil {
+		t.Fatalf("RollupTarget failed: %v", err)
+	}
+
+	minutes, _ := mockDB.GetAggregatedResults(id, 60, startTime.Add(-5*time.Minute), startTime)
+	if len(minutes) != 5 {
+		t.Fatalf("Expected 5 backfilled 60s rollups, got %d", len(minutes))
+	}
+	for _, r := range minutes {
+		td, _ := db.DeserializeTDigest(r.TDigestData)
+		if td.Count() != 60 {
+			t.Errorf("Expected 60 samples in window %v, got %d", r.Time, td.Count())
+		}
+	}
+
+	// The 300s window is built from the backfilled 60s windows
+	coarse, _ := mockDB.GetAggregatedResults(id, 300, startTime.Add(-5*time.Minute), startTime)
+	if len(coarse) != 1 {
+		t.Fatalf("Expected 1 backfilled 300s rollup, got %d", len(coarse))
+	}
+	td, _ := db.DeserializeTDigest(coarse[0].TDigestData)
+	if td.Count() != 300 {
+		t.Errorf("Expected 300 samples in the 300s rollup, got %d", td.Count())
+	}
+
+	// Existing windows are left alone
+	existing, _ := mockDB.GetAggregatedResults(id, 60, startTime, startTime.Add(time.Minute))
+	if len(existing) != 1 || existing[0].TimeoutCount != 7 {
+		t.Errorf("Expected the existing 60s rollup to be untouched, got %+v", existing)
+	}
+
+	// A second pass has nothing left to backfill
+	before := len(mockDB.AggregatedResults[id])
+	rm.RollupTarget(target)
+	if after := len(mockDB.AggregatedResults[id]); after != before {
+		t.Errorf("Expected no new rollups on a second pass, got %d more", after-before)
+	}
+}
+
+func TestRollupManager_ApplyRetentionChange(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
+	fakeClock := clockwork.NewFakeClock()
+	rm.clock = fakeClock
+
+	target := db.Target{
+		Name:              "RetentionChangeTarget",
+		Timeout:           1.0,
+		RetentionPolicies: `[{"window": 60, "retention": 3600}, {"window": 120, "retention": 3600}]`,
+	}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+
+	startTime := fakeClock.Now().Truncate(5 * time.Minute).Add(-10 * time.Minute)
+	for i := 0; i < 600; i++ {
+		mockDB.AddRawResults([]db.RawResult{{
+			Time:     startTime.Add(time.Duration(i) * time.Second),
+			TargetID: id,
+			Latency:  100.0,
+		}})
+	}
+	if _, err := rm.RollupTarget(target); err != nil {
+		t.Fatalf("RollupTarget failed: %v", err)
+	}
+
+	// Raw data has expired, so the added window can only be built from the 60s rollups
+	mockDB.RawResults[id] = nil
+	target.RetentionPolicies = `[{"window": 60, "retention": 3600}, {"window": 300, "retention": 3600}]`
+	mockDB.UpdateTarget(&target)
+
+	oldPolicies := []RetentionPolicy{{Window: 60}, {Window: 120}}
+	newPolicies, _ := GetRetentionPolicies(target)
+	removed, added := DiffRetentionWindows(oldPolicies, newPolicies)
+	if len(removed) != 1 || removed[0] != 120 || len(added) != 1 || added[0] != 300 {
+		t.Fatalf("Expected window 120 removed and 300 added, got %v and %v", removed, added)
+	}
+
+	rm.ApplyRetentionChange(target, removed, added)
+	rm.Stop() // waits for the background work
+
+	for _, r := range mockDB.AggregatedResults[id] {
+		if r.WindowSeconds == 120 {
+			t.Fatalf("Expected the 120s rollups to be deleted, found one at %v", r.Time)
+		}
+	}
+	coarse, _ := mockDB.GetAggregatedResults(id, 300, startTime, startTime.Add(10*time.Minute))
+	if len(coarse) != 2 {
+		t.Fatalf("Expected 2 300s rollups, got %d", len(coarse))
+	}
+	for _, r := range coarse {
+		td, _ := db.DeserializeTDigest(r.TDigestData)
+		if td.Count() != 300 {
+			t.Errorf("Expected 300 samples in window %v, got %d", r.Time, td.Count())
+		}
+	}
+}
+
+func TestRollupManager_Jitter(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
+
+	target := db.Target{Name: "JitterTarget", Timeout: 1.0}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+
+	start := time.Unix(0, 0).UTC()
+	at := func(s int) time.Time { return start.Add(time.Duration(s) * time.Second) }
+	mockDB.AddRawResults([]db.RawResult{
+		{Time: at(0), TargetID: id, Latency: 100},
+		{Time: at(1), TargetID: id, Latency: db.LatencyTimeout},
+		{Time: at(2), TargetID: id, Latency: 110},
+		{Time: at(3), TargetID: id, Latency: db.LatencyError},
+		{Time: at(4), TargetID: id, Latency: 90},
+		// Second window
+		{Time: at(10), TargetID: id, Latency: 50},
+		{Time: at(11), TargetID: id, Latency: 55},
+		// Third window has a single sample, so no jitter
+		{Time: at(20), TargetID: id, Latency: 70},
+	})
+
+	policy := RetentionPolicy{Window: 10}
+	first := rm.aggregateWindow(target, policy, 0, at(0), at(10))
+	second := rm.aggregateWindow(target, policy, 0, at(10), at(20))
+	third := rm.aggregateWindow(target, policy, 0, at(20), at(30))
+
+	// |110-100| and |90-110|; failed probes are skipped
+	if first.JitterNS == nil || *first.JitterNS != 15 {
+		t.Errorf("Expected jitter 15 for the first window, got %v", first.JitterNS)
+	}
+	if second.JitterNS == nil || *second.JitterNS != 5 {
+		t.Errorf("Expected jitter 5 for the second window, got %v", second.JitterNS)
+	}
+	if third.JitterNS != nil {
+		t.Errorf("Expected no jitter for a single sample, got %v", *third.JitterNS)
+	}
+
+	// The coarse window weights each sub-window by its successful probes: (15*3 + 5*2) / 5
+	mockDB.AddAggregatedResults([]*db.AggregatedResult{first, second, third})
+	minute := rm.aggregateWindow(target, RetentionPolicy{Window: 60}, 10, at(0), at(60))
+	if minute.JitterNS == nil || *minute.JitterNS != 11 {
+		t.Errorf("Expected weighted jitter 11, got %v", minute.JitterNS)
+	}
+}
+
+func TestRollupManager_LegacyResults(t *testing.T) {
+	mockDB := NewMockStore()
+	rm := NewRollupManager(mockDB, DefaultRollupInterval)
+	fakeClock := clockwork.NewFakeClockAt(time.Unix(600, 0).UTC())
+	rm.clock = fakeClock
+	rm.LegacyResults = true
+
+	target := db.Target{
+		Name:              "LegacyTarget",
+		Timeout:           1.0,
+		RetentionPolicies: `[{"window": 0, "retention": 3600}, {"window": 10, "retention": 3600}, {"window": 60, "retention": 3600}]`,
+	}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+
+	start := time.Unix(0, 0).UTC()
+	for i := 0; i < 10; i++ {
+		mockDB.AddRawResults([]db.RawResult{{Time: start.Add(time.Duration(i) * time.Second), TargetID: id, Latency: float64(i + 1)}})
+	}
+	mockDB.AddRawResults([]db.RawResult{{Time: start.Add(10 * time.Second), TargetID: id, Latency: db.LatencyTimeout}})
+
+	if _, err := rm.RollupTarget(target); err != nil {
+		t.Fatalf("RollupTarget failed: %v", err)
+	}
+
+	// Only the 10s windows, the finest rollup, are copied
+	legacy := mockDB.Results[id]
+	windows, _ := mockDB.GetAggregatedResults(id, 10, start, fakeClock.Now())
+	if len(legacy) == 0 || len(legacy) != len(windows) {
+		t.Fatalf("Expected one legacy row per 10s window (%d), got %d", len(windows), len(legacy))
+	}
+	first := legacy[0]
+	if first.MinNS == nil || *first.MinNS != 1 || first.MaxNS == nil || *first.MaxNS != 10 || first.AvgNS == nil || *first.AvgNS != 5.5 {
+		t.Errorf("Expected min 1, avg 5.5 and max 10, got %v, %v and %v", first.MinNS, first.AvgNS, first.MaxNS)
+	}
+	if first.P50NS == nil || first.P99NS == nil || *first.P99NS < *first.P50NS {
+		t.Errorf("Expected p50 <= p99, got %v and %v", first.P50NS, first.P99NS)
+	}
+	second := legacy[1]
+	if second.TimeoutCount != 1 || second.AvgNS != nil || second.P99NS != nil {
+		t.Errorf("Expected a timeout-only window without latencies, got %+v", second)
+	}
+}
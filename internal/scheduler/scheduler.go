@@ -1,9 +1,13 @@
 package scheduler
 
 import (
+	"errors"
+	"fmt"
 	"log"
+	"math/rand"
 	"strings"
 	"sync"
+	"sync/atomic"
 	"time"
 	"vaportrail/internal/db"
 	"vaportrail/internal/probe"
@@ -15,30 +19,96 @@ type Scheduler struct {
 	db          db.Store
 	probeRunner probe.Runner
 
+	// MaxJitter bounds the random delay before a target's first probe, so targets
+	// added together don't all probe on the same tick. Zero disables it.
+	MaxJitter time.Duration
+	// SpreadStart delays each target's first probe by a random part of its
+	// interval instead of MaxJitter, spreading a fleet of targets evenly.
+	SpreadStart bool
+	// LegacyResults makes rollups also fill the legacy results table. See
+	// RollupManager.LegacyResults.
+	LegacyResults bool
+	// TDigestCompression is the compression of rollups whose retention policy
+	// doesn't set one. See RollupManager.Compression.
+	TDigestCompression uint32
+	// SourceAddress is the local IP address probes are sent from when their target
+	// doesn't set one. Empty lets the system pick.
+	SourceAddress string
+	// WriteBufferSize is how many raw results the batch writer holds before
+	// WriteBufferPolicy applies. It must be set before Start.
+	WriteBufferSize int
+	// WriteBufferPolicy is WriteBufferFlush or WriteBufferDropOldest. It must be
+	// set before Start.
+	WriteBufferPolicy string
+
 	mu            sync.Mutex
 	stopChans     map[int64]chan struct{}
+	started       bool
 	stopped       bool
 	probeWG       sync.WaitGroup
 	Clock         clockwork.Clock
 	rawResultChan chan db.RawResult
 	batchStopChan chan struct{}
+	flushChan     chan chan flushResult
 	batchWG       sync.WaitGroup
 	stopOnce      sync.Once
+	broadcaster   *resultBroadcaster
+
+	// droppedResults counts raw results discarded by WriteBufferDropOldest.
+	droppedResults atomic.Int64
+
+	// timeBase and wallBase anchor sample times, see sampleTime. Samples are
+	// measured from timeBase on the monotonic clock and added to wallBase, which
+	// is timeBase's wall time unless a wall clock step has been detected.
+	timeMu   sync.Mutex
+	timeBase time.Time
+	wallBase time.Time
+
+	// loopDone holds a channel per target that its probe loop closes on exit, after
+	// its in-flight probes have queued their results.
+	loopDone map[int64]chan struct{}
 
 	rollupManager    *RollupManager
 	retentionManager *RetentionManager
+	alertManager     *AlertManager
 }
 
+// DefaultMaxJitter is the default Scheduler.MaxJitter.
+const DefaultMaxJitter = 100 * time.Millisecond
+
+// DefaultWriteBufferSize is the default Scheduler.WriteBufferSize.
+const DefaultWriteBufferSize = 500
+
+// Write buffer policies, see Scheduler.WriteBufferPolicy.
+const (
+	// WriteBufferFlush writes the buffer out as soon as it is full.
+	WriteBufferFlush = "flush"
+	// WriteBufferDropOldest only writes on the flush interval and discards the
+	// oldest results when the buffer is full, so a stalled write can't grow it.
+	WriteBufferDropOldest = "drop_oldest"
+)
+
 func New(database db.Store) *Scheduler {
+	clock := clockwork.NewRealClock()
+	now := clock.Now()
 	return &Scheduler{
-		db:               database,
-		probeRunner:      probe.RealRunner{},
-		stopChans:        make(map[int64]chan struct{}),
-		Clock:            clockwork.NewRealClock(),
-		rawResultChan:    make(chan db.RawResult, 1000), // Buffer size 1000
-		batchStopChan:    make(chan struct{}),
-		rollupManager:    NewRollupManager(database),
-		retentionManager: NewRetentionManager(database),
+		db:                database,
+		probeRunner:       probe.RealRunner{},
+		stopChans:         make(map[int64]chan struct{}),
+		loopDone:          make(map[int64]chan struct{}),
+		Clock:             clock,
+		timeBase:          now,
+		wallBase:          now.Round(0),
+		MaxJitter:         DefaultMaxJitter,
+		WriteBufferSize:   DefaultWriteBufferSize,
+		WriteBufferPolicy: WriteBufferFlush,
+		rawResultChan:     make(chan db.RawResult, 1000), // Buffer size 1000
+		batchStopChan:     make(chan struct{}),
+		flushChan:         make(chan chan flushResult),
+		broadcaster:       newResultBroadcaster(),
+		rollupManager:     NewRollupManager(database, DefaultRollupInterval),
+		retentionManager:  NewRetentionManager(database, DefaultRetentionInterval),
+		alertManager:      NewAlertManager(database),
 	}
 }
 
@@ -55,12 +125,35 @@ func (s *Scheduler) Start() error {
 
 	s.batchWG.Add(1)
 	go s.runBatchWriter()
+	s.rollupManager.LegacyResults = s.LegacyResults
+	s.rollupManager.Compression = s.TDigestCompression
 	s.rollupManager.Start()
 	s.retentionManager.Start()
+	s.alertManager.Start()
 
+	s.mu.Lock()
+	s.started = true
+	s.mu.Unlock()
 	return nil
 }
 
+// Running reports whether Start has completed and Stop has not been called.
+func (s *Scheduler) Running() bool {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	return s.started && !s.stopped
+}
+
+// SetMaintenanceIntervals sets how often rollups and retention run. Zero keeps the
+// default. It must be called before Start.
+func (s *Scheduler) SetMaintenanceIntervals(rollup, retention time.Duration) {
+	s.rollupManager = NewRollupManager(s.db, rollup)
+	s.retentionManager = NewRetentionManager(s.db, retention)
+}
+
+// Stop shuts the scheduler down: it stops every probe loop, waits for in-flight
+// probes, then flushes the batch writer's buffer to the database before returning.
+// It is safe to call more than once.
 func (s *Scheduler) Stop() {
 	s.stopOnce.Do(func() {
 		s.mu.Lock()
@@ -74,46 +167,173 @@ func (s *Scheduler) Stop() {
 		s.probeWG.Wait()
 		close(s.batchStopChan)
 		s.batchWG.Wait()
+		s.broadcaster.close()
 		s.rollupManager.Stop()
 		s.retentionManager.Stop()
+		s.alertManager.Stop()
 	})
 }
 
+// SubscribeResults returns a channel that receives every raw result once it has been
+// written to the database. The channel is closed when the returned cancel function
+// is called or the scheduler stops. Results are dropped if the channel's buffer is full.
+func (s *Scheduler) SubscribeResults(buffer int) (<-chan db.RawResult, func()) {
+	return s.broadcaster.subscribe(buffer)
+}
+
+// WriteStats describes the batch writer's backlog.
+type WriteStats struct {
+	// QueueDepth is how many results are waiting in the channel probes send to,
+	// out of QueueCapacity. A full queue makes probes wait.
+	QueueDepth    int
+	QueueCapacity int
+	// Dropped counts the results discarded since New because the write buffer was
+	// full, either under WriteBufferDropOldest or while writes were failing.
+	Dropped int64
+}
+
+// WriteStats reports the batch writer's current backlog.
+func (s *Scheduler) WriteStats() WriteStats {
+	return WriteStats{
+		QueueDepth:    len(s.rawResultChan),
+		QueueCapacity: cap(s.rawResultChan),
+		Dropped:       s.droppedResults.Load(),
+	}
+}
+
+// ErrNotRunning is returned by Flush when the scheduler hasn't started or has stopped.
+var ErrNotRunning = errors.New("scheduler is not running")
+
+// flushResult is the batch writer's reply to a Flush.
+type flushResult struct {
+	written int
+	err     error
+}
+
+// Flush makes the batch writer write its buffer now rather than on its next
+// interval, including results probes have queued but it hasn't picked up yet. It
+// returns how many raw results were written.
+func (s *Scheduler) Flush() (int, error) {
+	if !s.Running() {
+		return 0, ErrNotRunning
+	}
+	reply := make(chan flushResult, 1)
+	select {
+	case s.flushChan <- reply:
+	case <-s.batchStopChan:
+		return 0, ErrNotRunning
+	}
+	res := <-reply
+	return res.written, res.err
+}
+
+// flushErrorLogInterval throttles the batch writer's error log while writes keep
+// failing, e.g. because the disk is full.
+const flushErrorLogInterval = time.Minute
+
+// runBatchWriter writes raw results to the database in batches. When a write fails
+// the batch is kept and retried on the next flush interval, so a transient error
+// such as a full disk doesn't lose data. Meanwhile the buffer holds at most
+// WriteBufferSize results, dropping the oldest, whatever the policy.
 func (s *Scheduler) runBatchWriter() {
 	defer s.batchWG.Done()
 	ticker := s.Clock.NewTicker(2 * time.Second) // Flush every 2 seconds
 	defer ticker.Stop()
 
+	maxBuffer := s.WriteBufferSize
+	if maxBuffer <= 0 {
+		maxBuffer = DefaultWriteBufferSize
+	}
+	dropOldest := s.WriteBufferPolicy == WriteBufferDropOldest
+
 	var buffer []db.RawResult
+	var dropped int64 // since the last flush, logged once per flush
+
+	// failing is set while writes fail. failures counts the failed attempts that
+	// haven't been logged yet.
+	var failing bool
+	var failures int
+	var lastFailureLog time.Time
 
-	flush := func() {
+	// flushed counts the results written, so a Flush can report its share
+	var flushed int
+
+	flush := func() error {
+		if dropped > 0 {
+			log.Printf("Scheduler: Write buffer full (%d results), dropped %d oldest raw results", maxBuffer, dropped)
+			dropped = 0
+		}
 		if len(buffer) == 0 {
-			return
+			return nil
 		}
 		if err := s.db.AddRawResults(buffer); err != nil {
-			log.Printf("Failed to flush raw results: %v", err)
-		} else {
-			// log.Printf("Flushed %d raw results", len(buffer))
+			// With sharded storage the other targets' results were committed, so
+			// only keep the failed ones or a retry would write the rest twice
+			var written []db.RawResult
+			written, buffer = splitFailedWrite(buffer, err)
+			if len(written) > 0 {
+				s.broadcaster.publish(written)
+				flushed += len(written)
+			}
+			failing = true
+			failures++
+			if now := s.Clock.Now(); now.Sub(lastFailureLog) >= flushErrorLogInterval {
+				log.Printf("Scheduler: Failed to flush %d raw results (target_ids=%v), keeping them to retry (%d failed attempts): %v", len(buffer), batchTargetIDs(buffer), failures, err)
+				lastFailureLog = now
+				failures = 0
+			}
+			return err
+		}
+		if failing {
+			log.Printf("Scheduler: Flushed %d raw results after earlier failures", len(buffer))
+			failing = false
+			failures = 0
+			lastFailureLog = time.Time{}
 		}
+		// log.Printf("Flushed %d raw results", len(buffer))
+		s.broadcaster.publish(buffer)
+		flushed += len(buffer)
 		buffer = buffer[:0] // Reset buffer (reuse existing slice)
+		return nil
+	}
+
+	add := func(res db.RawResult) {
+		if (dropOldest || failing) && len(buffer) >= maxBuffer {
+			n := len(buffer) - maxBuffer + 1
+			buffer = append(buffer[:0], buffer[n:]...)
+			dropped += int64(n)
+			s.droppedResults.Add(int64(n))
+		}
+		buffer = append(buffer, res)
+		// While writes fail, only retry on the ticker rather than on every result
+		if !dropOldest && !failing && len(buffer) >= maxBuffer {
+			flush()
+		}
 	}
 
 	for {
 		select {
 		case res := <-s.rawResultChan:
-			buffer = append(buffer, res)
-			if len(buffer) >= 500 { // Max batch size
-				flush()
-			}
+			add(res)
 		case <-ticker.Chan():
 			flush()
+		case reply := <-s.flushChan:
+			before := flushed
+			for queued := len(s.rawResultChan); queued > 0; queued-- {
+				add(<-s.rawResultChan)
+			}
+			err := flush()
+			reply <- flushResult{written: flushed - before, err: err}
 		case <-s.batchStopChan:
 			for {
 				select {
 				case res := <-s.rawResultChan:
-					buffer = append(buffer, res)
+					add(res)
 				default:
 					flush()
+					if failing {
+						log.Printf("Scheduler: Discarding %d raw results that could not be written before shutdown", len(buffer))
+					}
 					return
 				}
 			}
@@ -121,7 +341,12 @@ func (s *Scheduler) runBatchWriter() {
 	}
 }
 
+// AddTarget starts probing t. Paused targets are ignored.
 func (s *Scheduler) AddTarget(t db.Target) {
+	if t.Paused {
+		log.Printf("Scheduler: Target %s is paused, not probing", t.Name)
+		return
+	}
 	s.mu.Lock()
 	if s.stopped {
 		s.mu.Unlock()
@@ -132,12 +357,17 @@ func (s *Scheduler) AddTarget(t db.Target) {
 		return // Already running
 	}
 	stopCh := make(chan struct{})
+	done := make(chan struct{})
 	s.stopChans[t.ID] = stopCh
+	s.loopDone[t.ID] = done
 	s.probeWG.Add(1)
 	s.mu.Unlock()
 
 	log.Printf("Scheduler: Adding new target %s", t.Name)
-	go s.runProbeLoop(t, stopCh)
+	go func() {
+		defer close(done)
+		s.runProbeLoop(t, stopCh)
+	}()
 }
 
 func (s *Scheduler) RemoveTarget(id int64) {
@@ -147,17 +377,209 @@ func (s *Scheduler) RemoveTarget(id int64) {
 		delete(s.stopChans, id)
 		log.Printf("Scheduler: Removed target %d", id)
 	}
+	delete(s.loopDone, id)
 	s.mu.Unlock()
 }
 
+// DrainTarget stops probing a target like RemoveTarget, then waits for its
+// in-flight probes and writes out their buffered results, so nothing is written
+// for the target afterwards. Call it before deleting the target's data.
+func (s *Scheduler) DrainTarget(id int64) error {
+	s.mu.Lock()
+	done := s.loopDone[id]
+	s.mu.Unlock()
+
+	s.RemoveTarget(id)
+	if done != nil {
+		<-done
+	}
+	if _, err := s.Flush(); err != nil && !errors.Is(err, ErrNotRunning) {
+		return err
+	}
+	return nil
+}
+
+// Reload re-reads the targets table and syncs the running probe loops with it:
+// new targets are started and deleted or paused ones are stopped. Loops for
+// targets that are still present keep running untouched. It is idempotent, so it
+// can be called after any bulk change to the table.
+func (s *Scheduler) Reload() error {
+	targets, err := s.db.GetTargets()
+	if err != nil {
+		return err
+	}
+
+	active := make(map[int64]bool, len(targets))
+	for _, t := range targets {
+		if !t.Paused {
+			active[t.ID] = true
+		}
+	}
+
+	s.mu.Lock()
+	var stale []int64
+	for id := range s.stopChans {
+		if !active[id] {
+			stale = append(stale, id)
+		}
+	}
+	s.mu.Unlock()
+
+	for _, id := range stale {
+		s.RemoveTarget(id)
+	}
+	// AddTarget ignores targets that are already running or paused
+	for _, t := range targets {
+		s.AddTarget(t)
+	}
+	log.Printf("Scheduler: Reloaded %d targets, stopped %d", len(active), len(stale))
+	return nil
+}
+
+// PauseTarget stops probing a target and marks it paused so it stays stopped across restarts.
+func (s *Scheduler) PauseTarget(id int64) error {
+	if err := s.db.SetTargetPaused(id, true); err != nil {
+		return err
+	}
+	s.RemoveTarget(id)
+	return nil
+}
+
+// ResumeTarget clears a target's paused flag and starts probing it again.
+func (s *Scheduler) ResumeTarget(id int64) error {
+	if err := s.db.SetTargetPaused(id, false); err != nil {
+		return err
+	}
+	t, err := s.db.GetTarget(id)
+	if err != nil {
+		return err
+	}
+	s.AddTarget(*t)
+	return nil
+}
+
+// RollupTarget runs the rollups for one target immediately, e.g. after a backfill of
+// raw data, and returns the number of aggregated windows written.
+func (s *Scheduler) RollupTarget(id int64) (int, error) {
+	t, err := s.db.GetTarget(id)
+	if err != nil {
+		return 0, err
+	}
+	return s.rollupManager.RollupTarget(*t)
+}
+
+// ApplyRetentionChange deletes the rollups of removed windows and fills added ones in
+// the background. See RollupManager.ApplyRetentionChange.
+func (s *Scheduler) ApplyRetentionChange(t db.Target, removed, added []int) {
+	s.rollupManager.ApplyRetentionChange(t, removed, added)
+}
+
+// sampleTime converts t to the UTC time a result is stored with. It is the time
+// elapsed since timeBase on the monotonic clock added to wallBase, so a small wall
+// clock correction can't make later samples go backwards. Once the wall clock is
+// more than maxDrift away from that, as after an NTP step, sample times are
+// re-anchored to it rather than staying offset until a restart. Times without a
+// monotonic reading, such as a fake clock's, are returned as is.
+func (s *Scheduler) sampleTime(t time.Time, maxDrift time.Duration) time.Time {
+	s.timeMu.Lock()
+	defer s.timeMu.Unlock()
+	st := s.wallBase.Add(t.Sub(s.timeBase))
+	if drift := t.Round(0).Sub(st); drift.Abs() > maxDrift {
+		log.Printf("Scheduler: Wall clock is %v away from sample times, re-anchoring them", drift)
+		s.timeBase, s.wallBase = t, t.Round(0)
+		st = s.wallBase
+	}
+	return st.UTC()
+}
+
+// targetLogger returns a logger that tags every line with the target's ID and name,
+// after the given component prefix, so lines from concurrent targets can be told apart.
+func targetLogger(component string, t db.Target) *log.Logger {
+	prefix := fmt.Sprintf("%s[target_id=%d target_name=%q] ", component, t.ID, t.Name)
+	return log.New(log.Writer(), prefix, log.Flags()|log.Lmsgprefix)
+}
+
+// splitFailedWrite splits a batch AddRawResults failed to write into the results
+// that were written anyway and those to retry. Only a *db.ShardWriteError leaves
+// results written; otherwise the whole batch is retried.
+func splitFailedWrite(batch []db.RawResult, err error) (written, retry []db.RawResult) {
+	var shardErr *db.ShardWriteError
+	if !errors.As(err, &shardErr) {
+		return nil, batch
+	}
+	failed := make(map[int64]bool, len(shardErr.TargetIDs))
+	for _, id := range shardErr.TargetIDs {
+		failed[id] = true
+	}
+	for _, r := range batch {
+		if failed[r.TargetID] {
+			retry = append(retry, r)
+		} else {
+			written = append(written, r)
+		}
+	}
+	return written, retry
+}
+
+// batchTargetIDs lists the distinct targets in a batch of raw results, in order of
+// first appearance.
+func batchTargetIDs(batch []db.RawResult) []int64 {
+	seen := make(map[int64]bool)
+	var ids []int64
+	for _, r := range batch {
+		if !seen[r.TargetID] {
+			seen[r.TargetID] = true
+			ids = append(ids, r.TargetID)
+		}
+	}
+	return ids
+}
+
+// probeConfig returns the configuration t is probed with. The timeout defaults to
+// 5s, and targets without a source address are probed from s.SourceAddress.
+func (s *Scheduler) probeConfig(t db.Target) (probe.Config, error) {
+	cfg, err := probe.GetConfig(t.ProbeType, t.Address, t.ProbeConfig)
+	if err != nil {
+		return probe.Config{}, err
+	}
+	timeout := t.Timeout
+	if timeout <= 0 {
+		timeout = 5.0
+	}
+	cfg.Timeout = time.Duration(timeout*1000) * time.Millisecond
+	cfg.AddressFamily = t.AddressFamily
+	cfg.SourceAddress = t.SourceAddress
+	if cfg.SourceAddress == "" {
+		cfg.SourceAddress = s.SourceAddress
+	}
+	return cfg, nil
+}
+
+// startOffset returns a random delay before the first probe of a target probed
+// every interval: up to the whole interval with SpreadStart, else up to MaxJitter.
+func (s *Scheduler) startOffset(interval time.Duration) time.Duration {
+	maxOffset := s.MaxJitter
+	if s.SpreadStart {
+		maxOffset = interval
+	}
+	if maxOffset <= 0 {
+		return 0
+	}
+	return time.Duration(rand.Int63n(int64(maxOffset)))
+}
+
 func (s *Scheduler) runProbeLoop(t db.Target, stopCh chan struct{}) {
 	defer s.probeWG.Done()
+	logger := targetLogger("Scheduler: ", t)
 
-	cfg, err := probe.GetConfig(t.ProbeType, t.Address)
+	cfg, err := s.probeConfig(t)
 	if err != nil {
-		log.Printf("Failed to get config for target %s: %v", t.Name, err)
+		logger.Printf("Failed to get config: %v", err)
 		return
 	}
+	if cfg.HTTP.InsecureSkipVerify {
+		logger.Printf("WARNING: insecure_skip_verify is set, TLS certificates will not be verified")
+	}
 
 	// Default interval 1s
 	if t.ProbeInterval <= 0 {
@@ -166,15 +588,50 @@ func (s *Scheduler) runProbeLoop(t db.Target, stopCh chan struct{}) {
 	if t.Timeout <= 0 {
 		t.Timeout = 5.0
 	}
-	cfg.Timeout = time.Duration(t.Timeout*1000) * time.Millisecond
 
-	probeTicker := s.Clock.NewTicker(time.Duration(t.ProbeInterval*1000) * time.Millisecond)
+	interval := time.Duration(t.ProbeInterval*1000) * time.Millisecond
+
+	// Offset the schedule rather than each probe, so the interval between probes
+	// and their recorded times stay exact
+	if offset := s.startOffset(interval); offset > 0 {
+		select {
+		case <-stopCh:
+			return
+		case <-s.Clock.After(offset):
+		}
+	}
+
+	probeTicker := s.Clock.NewTicker(interval)
 	// No aggregation loop here anymore.
 
-	// Concurrency limiter: ensure no more than 5 probes overlap for this target
-	sem := make(chan struct{}, 5)
+	if t.MaxConcurrent <= 0 {
+		t.MaxConcurrent = 5
+	}
+
+	// Concurrency limiter: ensure no more than MaxConcurrent probes overlap for this target
+	sem := make(chan struct{}, t.MaxConcurrent)
 	var wg sync.WaitGroup
 
+	// Last certificate expiry written for this target, so we only touch the
+	// targets table when a "tls" probe observes a different certificate.
+	var certMu sync.Mutex
+	var lastCertExpiry time.Time
+	if t.CertExpiresAt != nil {
+		lastCertExpiry = *t.CertExpiresAt
+	}
+	recordCertExpiry := func(expiry time.Time) {
+		certMu.Lock()
+		defer certMu.Unlock()
+		if expiry.Equal(lastCertExpiry) {
+			return
+		}
+		if err := s.db.UpdateTargetCertExpiry(t.ID, expiry); err != nil {
+			logger.Printf("Failed to record certificate expiry: %v", err)
+			return
+		}
+		lastCertExpiry = expiry
+	}
+
 	runProbe := func() {
 		select {
 		case sem <- struct{}{}:
@@ -184,28 +641,47 @@ func (s *Scheduler) runProbeLoop(t db.Target, stopCh chan struct{}) {
 				defer wg.Done()
 				defer func() { <-sem }() // Release
 
-				startTime := s.Clock.Now().UTC()
+				startTime := s.sampleTime(s.Clock.Now(), interval)
 				res, err := s.probeRunner.Run(cfg)
+				// Prefer the runner's own send time, which excludes any setup before the probe
+				if !res.SentAt.IsZero() {
+					startTime = s.sampleTime(res.SentAt, interval)
+				}
 
 				raw := db.RawResult{
-					Time:     startTime,
-					TargetID: t.ID,
-					Latency:  res,
+					Time:       startTime,
+					TargetID:   t.ID,
+					Latency:    res.Latency,
+					Loss:       res.Loss,
+					TTL:        res.TTL,
+					RemoteIP:   res.RemoteIP,
+					StatusCode: res.StatusCode,
 				}
 
 				if err != nil {
 					if strings.Contains(err.Error(), "probe timed out") {
-						raw.Latency = -1.0
-						s.rawResultChan <- raw
-						return
+						raw.Latency = db.LatencyTimeout
+					} else {
+						// Record the failure so it isn't mistaken for a gap in scheduling
+						logger.Printf("Probe failed: %v", err)
+						raw.Latency = db.LatencyError
 					}
-					log.Printf("Probe failed for %s: %v", t.Name, err)
+					// Keep the status and address of a response that failed its
+					// checks, e.g. a 503, and the loss of a ping without replies
+					raw.TTL = 0
+					s.rawResultChan <- raw
 					return
 				}
+				if res.Duplicates > 0 {
+					logger.Printf("Probe got %d duplicate replies", res.Duplicates)
+				}
+				if !res.CertExpiry.IsZero() {
+					recordCertExpiry(res.CertExpiry)
+				}
 				s.rawResultChan <- raw
 			}()
 		default:
-			log.Printf("Skipping probe for %s due to overlapping limit", t.Name)
+			logger.Printf("Skipping probe: %d probes already in flight (max_concurrent)", t.MaxConcurrent)
 		}
 	}
 
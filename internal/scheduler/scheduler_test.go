@@ -1,7 +1,13 @@
 package scheduler
 
 import (
+	"bytes"
+	"context"
+	"errors"
 	"fmt"
+	"log"
+	"os"
+	"strings"
 	"sync"
 	"testing"
 	"time"
@@ -123,6 +129,59 @@ func TestTargetRemovalRace_WithMocks(t *testing.T) {
 	}
 }
 
+func TestScheduler_DrainTargetWritesInFlightResults(t *testing.T) {
+	mockDB := NewMockStore()
+	fakeClock := clockwork.NewFakeClock()
+	s := New(mockDB)
+	s.Clock = fakeClock
+	s.MaxJitter = 0
+	s.Start()
+	defer s.Stop()
+
+	started := make(chan struct{}, 1)
+	release := make(chan struct{})
+	s.probeRunner = resultRunner(func(cfg probe.Config) (probe.Result, error) {
+		select {
+		case started <- struct{}{}:
+		default:
+		}
+		<-release
+		return probe.Result{Latency: 100}, nil
+	})
+
+	target := db.Target{Name: "Drained", Address: "example.com", ProbeType: "http", ProbeInterval: 0.1}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+	s.AddTarget(target)
+
+	for {
+		fakeClock.Advance(100 * time.Millisecond)
+		select {
+		case <-started:
+		case <-time.After(20 * time.Millisecond):
+			continue
+		}
+		break
+	}
+
+	drained := make(chan error, 1)
+	go func() { drained <- s.DrainTarget(id) }()
+	close(release)
+	if err := <-drained; err != nil {
+		t.Fatalf("DrainTarget failed: %v", err)
+	}
+
+	if len(mockDB.RawResults[id]) == 0 {
+		t.Fatal("Expected the in-flight probe's result to be written before DrainTarget returned")
+	}
+	s.mu.Lock()
+	_, running := s.stopChans[id]
+	s.mu.Unlock()
+	if running {
+		t.Error("Expected the target to stop being probed")
+	}
+}
+
 func TestScheduler_TimeoutLogic(t *testing.T) {
 	mockDB := NewMockStore()
 	fakeClock := clockwork.NewFakeClock()
@@ -185,6 +244,49 @@ func TestScheduler_TimeoutLogic(t *testing.T) {
 	s.RemoveTarget(id)
 }
 
+func TestScheduler_ErrorLogic(t *testing.T) {
+	mockDB := NewMockStore()
+	fakeClock := clockwork.NewFakeClock()
+	s := New(mockDB)
+	s.Clock = fakeClock
+	s.Start()
+
+	// Hard failures are recorded with their own marker instead of being dropped
+	s.probeRunner = &MockRunner{
+		RunFn: func(cfg probe.Config) (float64, error) {
+			return 0, fmt.Errorf("dial tcp %s: connection refused", cfg.Address)
+		},
+	}
+
+	target := db.Target{
+		Name:          "BrokenTarget",
+		Address:       "broken.example.com:80",
+		ProbeType:     "tcp",
+		ProbeInterval: 0.1,
+		Timeout:       0.05,
+	}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+	s.AddTarget(target)
+
+	for i := 0; i < 5; i++ {
+		fakeClock.Advance(100 * time.Millisecond)
+		time.Sleep(20 * time.Millisecond)
+	}
+	// Stop flushes the buffered results
+	s.Stop()
+
+	results, _ := mockDB.GetRawResults(id, time.Time{}, time.Now().Add(24*time.Hour), 1000)
+	if len(results) == 0 {
+		t.Fatal("Expected raw results for failed probes, got none")
+	}
+	for _, r := range results {
+		if r.Latency != db.LatencyError {
+			t.Errorf("Expected Latency %v (error), got %v", db.LatencyError, r.Latency)
+		}
+	}
+}
+
 func TestScheduler_StopFlushesBufferedRawResults(t *testing.T) {
 	mockDB := NewMockStore()
 	s := New(mockDB)
@@ -213,3 +315,599 @@ func TestScheduler_StopFlushesBufferedRawResults(t *testing.T) {
 		t.Fatalf("expected latency 123.4, got %v", results[0].Latency)
 	}
 }
+
+func TestScheduler_WriteBufferDropOldest(t *testing.T) {
+	mockDB := NewMockStore()
+	s := New(mockDB)
+	s.Clock = clockwork.NewFakeClock() // no flush ticks
+	s.WriteBufferSize = 2
+	s.WriteBufferPolicy = WriteBufferDropOldest
+
+	if err := s.Start(); err != nil {
+		t.Fatalf("Start failed: %v", err)
+	}
+
+	now := time.Now().UTC()
+	for i := 0; i < 5; i++ {
+		s.rawResultChan <- db.RawResult{Time: now.Add(time.Duration(i) * time.Millisecond), TargetID: 42, Latency: float64(i)}
+	}
+	s.Stop()
+
+	results, _ := mockDB.GetRawResults(42, now.Add(-time.Second), now.Add(time.Second), 10)
+	if len(results) != 2 || results[0].Latency != 3 || results[1].Latency != 4 {
+		t.Fatalf("Expected the 2 newest results to be kept, got %+v", results)
+	}
+	if stats := s.WriteStats(); stats.Dropped != 3 || stats.QueueCapacity != 1000 {
+		t.Errorf("Expected 3 dropped results, got %+v", stats)
+	}
+}
+
+func TestScheduler_FailedFlushKeepsResults(t *testing.T) {
+	mockDB := NewMockStore()
+	batches := make(chan []db.RawResult, 10)
+	calls := 0
+	mockDB.AddRawResultsFn = func(results []db.RawResult) error {
+		calls++
+		batches <- append([]db.RawResult(nil), results...)
+		if calls == 1 {
+			return fmt.Errorf("database or disk is full")
+		}
+		return nil
+	}
+
+	s := New(mockDB)
+	fakeClock := clockwork.NewFakeClock()
+	s.Clock = fakeClock
+	if err := s.Start(); err != nil {
+		t.Fatalf("Start failed: %v", err)
+	}
+	defer s.Stop()
+	if err := fakeClock.BlockUntilContext(context.Background(), 1); err != nil {
+		t.Fatalf("batch writer did not start: %v", err)
+	}
+
+	waitDrained := func() {
+		t.Helper()
+		deadline := time.Now().Add(time.Second)
+		for len(s.rawResultChan) > 0 {
+			if time.Now().After(deadline) {
+				t.Fatal("batch writer did not read its queue")
+			}
+			time.Sleep(time.Millisecond)
+		}
+	}
+
+	now := time.Now().UTC()
+	s.rawResultChan <- db.RawResult{Time: now, TargetID: 1, Latency: 1}
+	waitDrained()
+	fakeClock.Advance(2 * time.Second)
+	if first := <-batches; len(first) != 1 {
+		t.Fatalf("Expected the first flush to write 1 result, got %d", len(first))
+	}
+
+	// The failed batch is retried with the result that arrived since
+	s.rawResultChan <- db.RawResult{Time: now.Add(time.Second), TargetID: 1, Latency: 2}
+	waitDrained()
+	fakeClock.Advance(2 * time.Second)
+	if second := <-batches; len(second) != 2 || second[0].Latency != 1 || second[1].Latency != 2 {
+		t.Fatalf("Expected the retry to write both results, got %+v", second)
+	}
+}
+
+func TestScheduler_FailedShardWriteRetriesOnlyFailedTargets(t *testing.T) {
+	mockDB := NewMockStore()
+	var batches [][]db.RawResult
+	mockDB.AddRawResultsFn = func(results []db.RawResult) error {
+		batches = append(batches, append([]db.RawResult(nil), results...))
+		if len(batches) == 1 {
+			return &db.ShardWriteError{TargetIDs: []int64{2}, Err: fmt.Errorf("database or disk is full")}
+		}
+		return nil
+	}
+
+	s := New(mockDB)
+	s.Clock = clockwork.NewFakeClock()
+	if err := s.Start(); err != nil {
+		t.Fatalf("Start failed: %v", err)
+	}
+	defer s.Stop()
+
+	now := time.Now().UTC()
+	s.rawResultChan <- db.RawResult{Time: now, TargetID: 1, Latency: 1}
+	s.rawResultChan <- db.RawResult{Time: now, TargetID: 2, Latency: 2}
+	written, err := s.Flush()
+	if err == nil {
+		t.Fatal("Expected the first flush to fail")
+	}
+	if written != 1 {
+		t.Errorf("Expected the committed target's result to count as written, got %d", written)
+	}
+
+	if _, err := s.Flush(); err != nil {
+		t.Fatalf("Retry failed: %v", err)
+	}
+	if len(batches) != 2 || len(batches[1]) != 1 || batches[1][0].TargetID != 2 {
+		t.Fatalf("Expected the retry to write only the failed target's result, got %+v", batches)
+	}
+}
+
+func TestScheduler_SubscribeResultsReceivesFlushedResults(t *testing.T) {
+	mockDB := NewMockStore()
+	s := New(mockDB)
+
+	results, cancel := s.SubscribeResults(10)
+	defer cancel()
+
+	if err := s.Start(); err != nil {
+		t.Fatalf("Start failed: %v", err)
+	}
+
+	now := time.Now().UTC()
+	s.rawResultChan <- db.RawResult{
+		Time:     now,
+		TargetID: 7,
+		Latency:  42.0,
+	}
+
+	// Stop forces the final flush, which publishes to subscribers and then closes them.
+	s.Stop()
+
+	select {
+	case r, ok := <-results:
+		if !ok {
+			t.Fatal("expected a result before the subscription closed")
+		}
+		if r.TargetID != 7 || r.Latency != 42.0 {
+			t.Errorf("unexpected result %+v", r)
+		}
+	case <-time.After(time.Second):
+		t.Fatal("timed out waiting for published result")
+	}
+
+	if _, ok := <-results; ok {
+		t.Error("expected subscription to be closed after Stop")
+	}
+}
+
+func TestScheduler_PauseAndResumeTarget(t *testing.T) {
+	mockDB := NewMockStore()
+	s := New(mockDB)
+	s.Clock = clockwork.NewFakeClock()
+	defer s.Stop()
+
+	target := db.Target{Name: "PauseMe", Address: "example.com", ProbeType: "http"}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+
+	running := func() bool {
+		s.mu.Lock()
+		defer s.mu.Unlock()
+		_, ok := s.stopChans[id]
+		return ok
+	}
+
+	s.AddTarget(target)
+	if !running() {
+		t.Fatal("expected target to be running after AddTarget")
+	}
+
+	if err := s.PauseTarget(id); err != nil {
+		t.Fatalf("PauseTarget failed: %v", err)
+	}
+	if running() {
+		t.Error("expected target to stop after PauseTarget")
+	}
+	if !mockDB.Targets[id].Paused {
+		t.Error("expected target to be marked paused in the store")
+	}
+
+	// Paused targets are skipped, e.g. when the scheduler starts
+	s.AddTarget(mockDB.Targets[id])
+	if running() {
+		t.Error("expected AddTarget to skip a paused target")
+	}
+
+	if err := s.ResumeTarget(id); err != nil {
+		t.Fatalf("ResumeTarget failed: %v", err)
+	}
+	if !running() {
+		t.Error("expected target to run after ResumeTarget")
+	}
+	if mockDB.Targets[id].Paused {
+		t.Error("expected target to be unpaused in the store")
+	}
+}
+
+func TestScheduler_MaxConcurrentLimitsInFlightProbes(t *testing.T) {
+	mockDB := NewMockStore()
+	fakeClock := clockwork.NewFakeClock()
+	s := New(mockDB)
+	s.Clock = fakeClock
+
+	release := make(chan struct{})
+	var mu sync.Mutex
+	var inFlight, peak int
+	s.probeRunner = &MockRunner{
+		RunFn: func(cfg probe.Config) (float64, error) {
+			mu.Lock()
+			inFlight++
+			if inFlight > peak {
+				peak = inFlight
+			}
+			mu.Unlock()
+
+			<-release
+
+			mu.Lock()
+			inFlight--
+			mu.Unlock()
+			return 100.0, nil
+		},
+	}
+
+	target := db.Target{
+		Name:          "Busy",
+		Address:       "example.com",
+		ProbeType:     "http",
+		ProbeInterval: 0.1,
+		MaxConcurrent: 2,
+	}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+	s.AddTarget(target)
+
+	// Every probe blocks, so ticks beyond the limit must be skipped
+	for i := 0; i < 10; i++ {
+		fakeClock.Advance(100 * time.Millisecond)
+		time.Sleep(20 * time.Millisecond)
+	}
+
+	mu.Lock()
+	got := peak
+	mu.Unlock()
+	close(release)
+	s.Stop()
+
+	if got != 2 {
+		t.Errorf("expected at most 2 probes in flight, peak was %d", got)
+	}
+}
+
+func TestScheduler_ReloadSyncsTargets(t *testing.T) {
+	mockDB := NewMockStore()
+	s := New(mockDB)
+	s.Clock = clockwork.NewFakeClock()
+	defer s.Stop()
+
+	kept := db.Target{Name: "Kept", Address: "a.example.com", ProbeType: "http"}
+	keptID, _ := mockDB.AddTarget(&kept)
+	kept.ID = keptID
+	removed := db.Target{Name: "Removed", Address: "b.example.com", ProbeType: "http"}
+	removedID, _ := mockDB.AddTarget(&removed)
+	removed.ID = removedID
+
+	s.AddTarget(kept)
+	s.AddTarget(removed)
+
+	s.mu.Lock()
+	keptStopCh := s.stopChans[keptID]
+	s.mu.Unlock()
+
+	// Out-of-band changes: one target deleted, one added
+	mockDB.DeleteTarget(removedID)
+	added := db.Target{ID: 10, Name: "Added", Address: "c.example.com", ProbeType: "http"}
+	mockDB.Targets[added.ID] = added
+
+	if err := s.Reload(); err != nil {
+		t.Fatalf("Reload failed: %v", err)
+	}
+
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	if _, ok := s.stopChans[removedID]; ok {
+		t.Error("expected deleted target to be stopped")
+	}
+	if _, ok := s.stopChans[added.ID]; !ok {
+		t.Error("expected new target to be started")
+	}
+	if s.stopChans[keptID] != keptStopCh {
+		t.Error("expected existing target's probe loop to be left running")
+	}
+}
+
+func TestScheduler_StopDuringStartJitter(t *testing.T) {
+	mockDB := NewMockStore()
+	s := New(mockDB)
+	s.Clock = clockwork.NewFakeClock()
+	s.MaxJitter = time.Hour
+
+	var probes int
+	var mu sync.Mutex
+	s.probeRunner = &MockRunner{
+		RunFn: func(cfg probe.Config) (float64, error) {
+			mu.Lock()
+			probes++
+			mu.Unlock()
+			return 100.0, nil
+		},
+	}
+
+	target := db.Target{Name: "Jittered", Address: "example.com", ProbeType: "http", ProbeInterval: 0.1}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+	s.AddTarget(target)
+
+	// The clock never reaches the start offset, so no probe may run
+	time.Sleep(20 * time.Millisecond)
+
+	done := make(chan struct{})
+	go func() {
+		s.Stop()
+		close(done)
+	}()
+	select {
+	case <-done:
+	case <-time.After(2 * time.Second):
+		t.Fatal("Stop blocked on a probe loop waiting for its start offset")
+	}
+
+	mu.Lock()
+	defer mu.Unlock()
+	if probes != 0 {
+		t.Errorf("expected no probes before the start offset, got %d", probes)
+	}
+}
+
+func TestScheduler_SpreadStart(t *testing.T) {
+	s := New(NewMockStore())
+	s.MaxJitter = 100 * time.Millisecond
+
+	for i := 0; i < 100; i++ {
+		if offset := s.startOffset(10 * time.Second); offset >= s.MaxJitter {
+			t.Fatalf("Expected offsets below MaxJitter without SpreadStart, got %v", offset)
+		}
+	}
+
+	s.SpreadStart = true
+	var beyondJitter bool
+	for i := 0; i < 100; i++ {
+		offset := s.startOffset(10 * time.Second)
+		if offset < 0 || offset >= 10*time.Second {
+			t.Fatalf("Expected offsets within the interval, got %v", offset)
+		}
+		beyondJitter = beyondJitter || offset >= s.MaxJitter
+	}
+	if !beyondJitter {
+		t.Error("Expected SpreadStart offsets to spread past MaxJitter")
+	}
+}
+
+func TestScheduler_Flush(t *testing.T) {
+	mockDB := NewMockStore()
+	s := New(mockDB)
+	s.Clock = clockwork.NewFakeClock()
+
+	if _, err := s.Flush(); !errors.Is(err, ErrNotRunning) {
+		t.Errorf("Expected ErrNotRunning before Start, got %v", err)
+	}
+	if err := s.Start(); err != nil {
+		t.Fatalf("Start failed: %v", err)
+	}
+
+	// The fake clock never reaches the flush interval
+	now := time.Now()
+	s.rawResultChan <- db.RawResult{Time: now, TargetID: 1, Latency: 100}
+	s.rawResultChan <- db.RawResult{Time: now.Add(time.Second), TargetID: 1, Latency: 200}
+	written, err := s.Flush()
+	if err != nil {
+		t.Fatalf("Flush failed: %v", err)
+	}
+	if written != 2 {
+		t.Errorf("Expected 2 raw results written, got %d", written)
+	}
+	if results, _ := mockDB.GetRawResults(1, now.Add(-time.Minute), now.Add(time.Minute), 0); len(results) != 2 {
+		t.Errorf("Expected 2 stored raw results, got %d", len(results))
+	}
+	if written, err := s.Flush(); err != nil || written != 0 {
+		t.Errorf("Expected nothing left to flush, got %d %v", written, err)
+	}
+
+	s.Stop()
+	if _, err := s.Flush(); !errors.Is(err, ErrNotRunning) {
+		t.Errorf("Expected ErrNotRunning after Stop, got %v", err)
+	}
+}
+
+func TestScheduler_StopEndsMaintenanceLoops(t *testing.T) {
+	mockDB := NewMockStore()
+	fakeClock := clockwork.NewFakeClock()
+	s := New(mockDB)
+	s.rollupManager.clock = fakeClock
+	s.retentionManager.clock = fakeClock
+	s.alertManager.clock = fakeClock
+	if err := s.Start(); err != nil {
+		t.Fatalf("Start failed: %v", err)
+	}
+
+	// Each loop holds a ticker until it exits
+	if err := fakeClock.BlockUntilContext(context.Background(), 3); err != nil {
+		t.Fatalf("maintenance loops did not start: %v", err)
+	}
+	s.Stop()
+	s.Stop()
+	s.rollupManager.Stop()
+
+	ctx, cancel := context.WithTimeout(context.Background(), time.Second)
+	defer cancel()
+	if err := fakeClock.BlockUntilContext(ctx, 0); err != nil {
+		t.Errorf("expected maintenance loops to release their tickers after Stop: %v", err)
+	}
+}
+
+type resultRunner func(cfg probe.Config) (probe.Result, error)
+
+func (f resultRunner) Run(cfg probe.Config) (probe.Result, error) {
+	return f(cfg)
+}
+
+func TestScheduler_RecordsRunnerSendTime(t *testing.T) {
+	mockDB := NewMockStore()
+	fakeClock := clockwork.NewFakeClock()
+	s := New(mockDB)
+	s.Clock = fakeClock
+	s.MaxJitter = 0
+	s.Start()
+
+	sentAt := time.Date(2026, 3, 4, 5, 6, 7, 0, time.UTC)
+	s.probeRunner = resultRunner(func(cfg probe.Config) (probe.Result, error) {
+		return probe.Result{Latency: 100, SentAt: sentAt}, nil
+	})
+
+	target := db.Target{Name: "SendTime", Address: "example.com", ProbeType: "http", ProbeInterval: 0.1}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+	s.AddTarget(target)
+
+	for i := 0; i < 5; i++ {
+		fakeClock.Advance(100 * time.Millisecond)
+		time.Sleep(20 * time.Millisecond)
+	}
+	s.Stop()
+
+	results, _ := mockDB.GetRawResults(id, time.Time{}, time.Now().Add(24*time.Hour), 1000)
+	if len(results) == 0 {
+		t.Fatal("Expected raw results, got none")
+	}
+	for _, r := range results {
+		if !r.Time.Equal(sentAt) {
+			t.Errorf("Expected result time %v from the runner, got %v", sentAt, r.Time)
+		}
+	}
+}
+
+func TestScheduler_KeepsStatusOfFailedProbes(t *testing.T) {
+	mockDB := NewMockStore()
+	fakeClock := clockwork.NewFakeClock()
+	s := New(mockDB)
+	s.Clock = fakeClock
+	s.MaxJitter = 0
+	s.Start()
+
+	s.probeRunner = resultRunner(func(cfg probe.Config) (probe.Result, error) {
+		return probe.Result{Latency: 100, RemoteIP: "192.0.2.1", StatusCode: 503}, fmt.Errorf("unexpected HTTP status 503 (expected 200-399)")
+	})
+
+	target := db.Target{Name: "Unavailable", Address: "example.com", ProbeType: "http", ProbeInterval: 0.1}
+	id, _ := mockDB.AddTarget(&target)
+	target.ID = id
+	s.AddTarget(target)
+
+	for i := 0; i < 5; i++ {
+		fakeClock.Advance(100 * time.Millisecond)
+		time.Sleep(20 * time.Millisecond)
+	}
+	s.Stop()
+
+	results, _ := mockDB.GetRawResults(id, time.Time{}, time.Now().Add(24*time.Hour), 1000)
+	if len(results) == 0 {
+		t.Fatal("Expected raw results, got none")
+	}
+	for _, r := range results {
+		if r.Latency != db.LatencyError || r.StatusCode != 503 || r.RemoteIP != "192.0.2.1" {
+			t.Errorf("Expected an error row with status 503 from 192.0.2.1, got %+v", r)
+		}
+	}
+}
+
+func TestScheduler_CheckOnce(t *testing.T) {
+	s := New(NewMockStore())
+	s.SourceAddress = "192.0.2.10"
+	var mu sync.Mutex
+	var sources []string
+	s.probeRunner = resultRunner(func(cfg probe.Config) (probe.Result, error) {
+		mu.Lock()
+		sources = append(sources, cfg.SourceAddress)
+		mu.Unlock()
+		if cfg.Address == "down.example.com" {
+			return probe.Result{}, fmt.Errorf("probe timed out")
+		}
+		return probe.Result{Latency: 1e6}, nil
+	})
+
+	results := s.CheckOnce([]db.Target{
+		{Name: "Up", Address: "up.example.com", ProbeType: "http"},
+		{Name: "Down", Address: "down.example.com", ProbeType: "http"},
+		{Name: "Paused", Address: "paused.example.com", ProbeType: "http", Paused: true},
+		{Name: "Broken", Address: "broken.example.com", ProbeType: "carrier-pigeon"},
+	})
+
+	if len(results) != 3 {
+		t.Fatalf("Expected 3 results without the paused target, got %d", len(results))
+	}
+	for i, want := range []struct {
+		name   string
+		failed bool
+	}{{"Up", false}, {"Down", true}, {"Broken", true}} {
+		if results[i].Target.Name != want.name || (results[i].Err != nil) != want.failed {
+			t.Errorf("Result %d: expected %s failed=%v, got %s err=%v", i, want.name, want.failed, results[i].Target.Name, results[i].Err)
+		}
+	}
+	if results[0].Result.Latency != 1e6 {
+		t.Errorf("Expected latency 1ms, got %v", results[0].Result.Latency)
+	}
+	// The invalid target never reaches the runner
+	if len(sources) != 2 || sources[0] != "192.0.2.10" || sources[1] != "192.0.2.10" {
+		t.Errorf("Expected 2 probes from the default source address, got %v", sources)
+	}
+}
+
+func TestTargetLogger(t *testing.T) {
+	var buf bytes.Buffer
+	log.SetOutput(&buf)
+	defer log.SetOutput(os.Stderr)
+
+	logger := targetLogger("Scheduler: ", db.Target{ID: 7, Name: "edge router"})
+	logger.Printf("Probe failed: %v", "boom")
+
+	want := `Scheduler: [target_id=7 target_name="edge router"] Probe failed: boom`
+	if !strings.Contains(buf.String(), want) {
+		t.Errorf("Expected log line to contain %q, got %q", want, buf.String())
+	}
+}
+
+func TestSchedulerSampleTime(t *testing.T) {
+	s := New(NewMockStore())
+
+	// Without a monotonic reading the wall time is all there is
+	fake := time.Date(2024, 3, 1, 12, 0, 0, 123, time.UTC)
+	if got := s.sampleTime(fake, time.Second); !got.Equal(fake) || got.Location() != time.UTC {
+		t.Errorf("Expected %v, got %v", fake, got)
+	}
+
+	now := time.Now()
+	if got := s.sampleTime(now, time.Second); got.Sub(now.Round(0)).Abs() > time.Second || got.Location() != time.UTC {
+		t.Errorf("Expected a UTC time close to %v, got %v", now, got)
+	}
+}
+
+func TestSchedulerSampleTime_ReanchorsAfterClockStep(t *testing.T) {
+	s := New(NewMockStore())
+
+	// A correction below the threshold keeps the monotonic offset
+	s.wallBase = s.wallBase.Add(-100 * time.Millisecond)
+	now := time.Now()
+	if got := s.sampleTime(now, time.Second); now.Round(0).Sub(got) < 50*time.Millisecond {
+		t.Errorf("Expected a small wall clock correction to be ignored, got %v for %v", got, now)
+	}
+
+	// Simulate the wall clock stepping forward an hour since the scheduler started
+	s.wallBase = s.wallBase.Add(-time.Hour)
+	now = time.Now()
+	if got := s.sampleTime(now, time.Second); got.Sub(now.Round(0)).Abs() > time.Millisecond {
+		t.Errorf("Expected sample times to follow the stepped wall clock, got %v for %v", got, now)
+	}
+	later := time.Now()
+	if got := s.sampleTime(later, time.Second); got.Sub(later.Round(0)).Abs() > time.Millisecond {
+		t.Errorf("Expected later sample times to stay re-anchored, got %v for %v", got, later)
+	}
+}
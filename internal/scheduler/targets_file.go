@@ -0,0 +1,190 @@
+package scheduler
+
+import (
+	"encoding/json"
+	"errors"
+	"fmt"
+	"time"
+	"vaportrail/internal/config"
+	"vaportrail/internal/db"
+	"vaportrail/internal/probe"
+)
+
+// TargetSyncReport counts the changes made by SyncTargetSpecs.
+type TargetSyncReport struct {
+	Added   int
+	Updated int
+	Deleted int
+}
+
+// SyncTargetSpecs reconciles the targets table with the targets declared in a
+// targets file, matching them by name. Every spec is validated before anything
+// is written, so an invalid file leaves the database untouched. The writes are
+// not one transaction though: when one fails, the changes made before it are
+// kept and the error is returned, and syncing again finishes the job. With prune,
+// targets that are not in the file are deleted.
+func SyncTargetSpecs(store db.Store, specs []config.TargetSpec, prune bool) (TargetSyncReport, error) {
+	var report TargetSyncReport
+
+	desired := make([]db.Target, 0, len(specs))
+	for _, spec := range specs {
+		t, err := targetFromSpec(spec)
+		if err != nil {
+			return report, err
+		}
+		desired = append(desired, t)
+	}
+
+	existing, err := store.GetTargets()
+	if err != nil {
+		return report, err
+	}
+	byName := make(map[string]db.Target, len(existing))
+	for _, t := range existing {
+		if _, dup := byName[t.Name]; !dup {
+			byName[t.Name] = t
+		}
+	}
+
+	declared := make(map[string]bool, len(desired))
+	for _, t := range desired {
+		declared[t.Name] = true
+		current, ok := byName[t.Name]
+		if !ok {
+			if _, err := store.AddTarget(&t); err != nil {
+				return report, fmt.Errorf("target %q: %w", t.Name, err)
+			}
+			report.Added++
+			continue
+		}
+		if !targetChanged(current, t) {
+			continue
+		}
+
+		t.ID = current.ID
+		// Tags are managed through the API
+		t.Tags = current.Tags
+		// Drop rollups for windows that are no longer configured, as the API does.
+		// This comes first, so a sync that fails here sees the change again on retry.
+		if oldPolicies, err := GetRetentionPolicies(current); err == nil {
+			newPolicies, _ := GetRetentionPolicies(t)
+			removed, _ := DiffRetentionWindows(oldPolicies, newPolicies)
+			for _, window := range removed {
+				if err := store.DeleteAggregatedResultsByWindow(t.ID, window); err != nil {
+					return report, fmt.Errorf("target %q: %w", t.Name, err)
+				}
+			}
+		}
+		if err := store.UpdateTarget(&t); err != nil {
+			return report, fmt.Errorf("target %q: %w", t.Name, err)
+		}
+		report.Updated++
+	}
+
+	if prune {
+		for _, t := range existing {
+			if declared[t.Name] {
+				continue
+			}
+			if err := store.DeleteTarget(t.ID); err != nil {
+				return report, fmt.Errorf("target %q: %w", t.Name, err)
+			}
+			report.Deleted++
+		}
+	}
+	return report, nil
+}
+
+// PrepareTarget validates a target that is about to be added or updated and fills
+// in the defaults, both for the API and for targets files. Its errors are meant
+// for the client.
+func PrepareTarget(t *db.Target) error {
+	if t.RetentionPolicies != "" {
+		var policies []RetentionPolicy
+		// First unmarshal to check JSON validity
+		if err := json.Unmarshal([]byte(t.RetentionPolicies), &policies); err != nil {
+			return errors.New("Invalid retention policies JSON")
+		}
+		// Then validate policies logic (this also sorts them)
+		if err := ValidateRetentionPolicies(policies); err != nil {
+			return errors.New("Invalid retention policies: " + err.Error())
+		}
+		// Re-serialize sorted policies
+		sortedJSON, _ := json.Marshal(policies)
+		t.RetentionPolicies = string(sortedJSON)
+	}
+
+	if t.Name == "" || t.Address == "" || t.ProbeType == "" {
+		return errors.New("Missing required fields")
+	}
+	if err := probe.ValidateAddress(t.ProbeType, t.Address); err != nil {
+		return err
+	}
+	if !probe.ValidAddressFamily(t.AddressFamily) {
+		return errors.New("AddressFamily must be auto, ipv4 or ipv6")
+	}
+	if !probe.ValidSourceAddress(t.SourceAddress) {
+		return errors.New("SourceAddress must be an IP address")
+	}
+
+	if t.ProbeInterval <= 0 {
+		t.ProbeInterval = 1.0
+	}
+	if t.Timeout <= 0 {
+		t.Timeout = 5.0
+	}
+	if t.MaxConcurrent <= 0 {
+		t.MaxConcurrent = 5
+	}
+
+	// Check for valid probe type and config
+	if err := probe.ValidateProbeConfig(t.ProbeType, t.ProbeConfig); err != nil {
+		if errors.Is(err, probe.ErrInvalidConfig) {
+			return errors.New("Invalid probe config: " + err.Error())
+		}
+		return errors.New("Invalid probe type")
+	}
+	if err := probe.ValidateTimeout(t.ProbeType, t.ProbeConfig, time.Duration(t.Timeout*1000)*time.Millisecond); err != nil {
+		return errors.New("Invalid probe config: " + err.Error())
+	}
+
+	// Apply default retention policies if not provided
+	if t.RetentionPolicies == "" {
+		t.RetentionPolicies = DefaultPoliciesJSON()
+	}
+	return nil
+}
+
+func targetFromSpec(spec config.TargetSpec) (db.Target, error) {
+	t := db.Target{
+		Name:              spec.Name,
+		Address:           spec.Address,
+		ProbeType:         spec.ProbeType,
+		ProbeConfig:       spec.ProbeConfig,
+		ProbeInterval:     spec.Interval,
+		Timeout:           spec.Timeout,
+		MaxConcurrent:     spec.MaxConcurrent,
+		AddressFamily:     spec.AddressFamily,
+		SourceAddress:     spec.SourceAddress,
+		RetentionPolicies: spec.RetentionPolicies,
+	}
+	if t.AddressFamily == "" {
+		t.AddressFamily = probe.FamilyAuto
+	}
+	if err := PrepareTarget(&t); err != nil {
+		return db.Target{}, fmt.Errorf("target %q: %w", spec.Name, err)
+	}
+	return t, nil
+}
+
+func targetChanged(current, next db.Target) bool {
+	return current.Address != next.Address ||
+		current.ProbeType != next.ProbeType ||
+		current.ProbeConfig != next.ProbeConfig ||
+		current.ProbeInterval != next.ProbeInterval ||
+		current.Timeout != next.Timeout ||
+		current.MaxConcurrent != next.MaxConcurrent ||
+		current.AddressFamily != next.AddressFamily ||
+		current.SourceAddress != next.SourceAddress ||
+		current.RetentionPolicies != next.RetentionPolicies
+}
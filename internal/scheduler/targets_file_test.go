@@ -0,0 +1,92 @@
+package scheduler
+
+import (
+	"errors"
+	"testing"
+	"vaportrail/internal/config"
+	"vaportrail/internal/db"
+)
+
+func TestSyncTargetSpecs(t *testing.T) {
+	mockDB := NewMockStore()
+	unchanged := db.Target{Name: "Unchanged", Address: "a.example.com", ProbeType: "http", ProbeInterval: 1, Timeout: 5, MaxConcurrent: 5, RetentionPolicies: DefaultPoliciesJSON()}
+	mockDB.AddTarget(&unchanged)
+	changed := db.Target{Name: "Changed", Address: "old.example.com", ProbeType: "http", ProbeInterval: 1, Timeout: 5, MaxConcurrent: 5, RetentionPolicies: DefaultPoliciesJSON()}
+	mockDB.AddTarget(&changed)
+	extra := db.Target{Name: "Extra", Address: "c.example.com", ProbeType: "http"}
+	mockDB.AddTarget(&extra)
+
+	specs := []config.TargetSpec{
+		{Name: "Unchanged", Address: "a.example.com", ProbeType: "http"},
+		{Name: "Changed", Address: "new.example.com", ProbeType: "http"},
+		{Name: "New", Address: "8.8.8.8", ProbeType: "dns", ProbeConfig: `{"query_type": "MX"}`, Interval: 10},
+	}
+
+	report, err := SyncTargetSpecs(mockDB, specs, false)
+	if err != nil {
+		t.Fatalf("SyncTargetSpecs failed: %v", err)
+	}
+	if report.Added != 1 || report.Updated != 1 || report.Deleted != 0 {
+		t.Errorf("Expected 1 added, 1 updated, 0 deleted, got %+v", report)
+	}
+	if mockDB.Targets[changed.ID].Address != "new.example.com" {
+		t.Errorf("Expected changed target to be updated, got %q", mockDB.Targets[changed.ID].Address)
+	}
+	if _, ok := mockDB.Targets[extra.ID]; !ok {
+		t.Error("Expected undeclared target to be kept without prune")
+	}
+
+	report, err = SyncTargetSpecs(mockDB, specs, true)
+	if err != nil {
+		t.Fatalf("SyncTargetSpecs with prune failed: %v", err)
+	}
+	if report.Added != 0 || report.Updated != 0 || report.Deleted != 1 {
+		t.Errorf("Expected only 1 deleted on second sync, got %+v", report)
+	}
+	if _, ok := mockDB.Targets[extra.ID]; ok {
+		t.Error("Expected undeclared target to be pruned")
+	}
+}
+
+func TestSyncTargetSpecs_InvalidSpecWritesNothing(t *testing.T) {
+	mockDB := NewMockStore()
+
+	specs := []config.TargetSpec{
+		{Name: "Good", Address: "example.com", ProbeType: "http"},
+		{Name: "Bad", Address: "example.com", ProbeType: "carrier-pigeon"},
+	}
+	if _, err := SyncTargetSpecs(mockDB, specs, false); err == nil {
+		t.Fatal("Expected error for unknown probe type")
+	}
+	if len(mockDB.Targets) != 0 {
+		t.Errorf("Expected no targets to be written, got %d", len(mockDB.Targets))
+	}
+
+	specs = []config.TargetSpec{
+		{Name: "BadRetention", Address: "example.com", ProbeType: "http", RetentionPolicies: `[{"window": 60, "retention": 10}, {"window": 90, "retention": 10}]`},
+	}
+	if _, err := SyncTargetSpecs(mockDB, specs, false); err == nil {
+		t.Fatal("Expected error for invalid retention policies")
+	}
+}
+
+func TestSyncTargetSpecs_ReturnsRollupDeleteErrors(t *testing.T) {
+	mockDB := NewMockStore()
+	current := db.Target{Name: "Edge", Address: "example.com", ProbeType: "http", ProbeInterval: 1, Timeout: 5, MaxConcurrent: 5, RetentionPolicies: `[{"window":0,"retention":3600},{"window":60,"retention":86400}]`}
+	mockDB.AddTarget(&current)
+	mockDB.DeleteAggregatedResultsByWindowFn = func(int64, int) error {
+		return errors.New("disk full")
+	}
+
+	// Dropping the 60s window has to delete its rollups first
+	specs := []config.TargetSpec{
+		{Name: "Edge", Address: "example.com", ProbeType: "http", RetentionPolicies: `[{"window":0,"retention":3600}]`},
+	}
+	report, err := SyncTargetSpecs(mockDB, specs, false)
+	if err == nil {
+		t.Fatal("Expected the failed rollup delete to be returned")
+	}
+	if report.Updated != 0 || mockDB.Targets[current.ID].RetentionPolicies != current.RetentionPolicies {
+		t.Errorf("Expected the target to be left for the next sync, got %+v", mockDB.Targets[current.ID])
+	}
+}
@@ -0,0 +1,102 @@
+package web
+
+import (
+	"container/list"
+	"sync"
+	"time"
+)
+
+// Defaults for the cache of computed rollup results.
+const (
+	resultsCacheTTL  = 15 * time.Second
+	resultsCacheSize = 256
+)
+
+// resultsCacheKey identifies a target's rollups of one window over a range aligned
+// to that window, in Unix seconds.
+type resultsCacheKey struct {
+	targetID int64
+	window   int
+	start    int64
+	end      int64
+}
+
+type resultsCacheEntry struct {
+	key     resultsCacheKey
+	results []APIResult
+	// latest is the target's newest rollup of the window when results were computed
+	latest  time.Time
+	expires time.Time
+}
+
+// resultsCache keeps the most recently used rollup results, so dashboards that
+// refresh the same graph don't decode the same TDigests again. An entry is served
+// until its TTL runs out or the target gains a newer rollup of its window.
+type resultsCache struct {
+	mu      sync.Mutex
+	ttl     time.Duration
+	size    int
+	entries map[resultsCacheKey]*list.Element
+	lru     *list.List // front is the most recently used
+}
+
+func newResultsCache(ttl time.Duration, size int) *resultsCache {
+	return &resultsCache{
+		ttl:     ttl,
+		size:    size,
+		entries: make(map[resultsCacheKey]*list.Element),
+		lru:     list.New(),
+	}
+}
+
+// get returns the results cached for key, provided they are still fresh and were
+// computed when latest was the newest rollup. The slice must not be modified.
+func (c *resultsCache) get(key resultsCacheKey, latest, now time.Time) ([]APIResult, bool) {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+
+	el, ok := c.entries[key]
+	if !ok {
+		return nil, false
+	}
+	e := el.Value.(*resultsCacheEntry)
+	if now.After(e.expires) || !e.latest.Equal(latest) {
+		c.lru.Remove(el)
+		delete(c.entries, key)
+		return nil, false
+	}
+	c.lru.MoveToFront(el)
+	return e.results, true
+}
+
+// put caches results for key, evicting the least recently used entry when full.
+func (c *resultsCache) put(key resultsCacheKey, results []APIResult, latest, now time.Time) {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+
+	e := &resultsCacheEntry{key: key, results: results, latest: latest, expires: now.Add(c.ttl)}
+	if el, ok := c.entries[key]; ok {
+		el.Value = e
+		c.lru.MoveToFront(el)
+		return
+	}
+	c.entries[key] = c.lru.PushFront(e)
+	for c.lru.Len() > c.size {
+		oldest := c.lru.Back()
+		c.lru.Remove(oldest)
+		delete(c.entries, oldest.Value.(*resultsCacheEntry).key)
+	}
+}
+
+// invalidate drops every entry of a target, e.g. after its data was deleted.
+func (c *resultsCache) invalidate(targetID int64) {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+
+	for key, el := range c.entries {
+		if key.targetID == targetID {
+			c.lru.Remove(el)
+			delete(c.entries, key)
+		}
+	}
+}
@@ -1,19 +1,27 @@
 package web
 
 import (
+	"bytes"
 	"context"
+	"crypto/subtle"
 	"database/sql"
 	"embed"
+	"encoding/csv"
 	"encoding/json"
 	"errors"
 	"fmt"
 	"html/template"
 	"log"
 	"math"
+	"net"
 	"net/http"
+	"slices"
 	"strconv"
 	"strings"
+	"sync"
+	"sync/atomic"
 	"time"
+	"unicode"
 	"vaportrail/internal/config"
 	"vaportrail/internal/db"
 
@@ -21,6 +29,7 @@ import (
 	"vaportrail/internal/probe"
 	"vaportrail/internal/scheduler"
 
+	"github.com/caio/go-tdigest/v4"
 	"github.com/go-chi/chi/v5"
 	"github.com/go-chi/chi/v5/middleware"
 )
@@ -32,14 +41,26 @@ var templatesJS embed.FS
 var staticFS embed.FS
 
 type Server struct {
-	cfg       *config.ServerConfig
-	db        *db.DB
-	scheduler *scheduler.Scheduler
-	router    *chi.Mux
-	templates *template.Template
+	cfg        *config.ServerConfig
+	db         db.Store
+	scheduler  *scheduler.Scheduler
+	router     *chi.Mux
+	templates  *template.Template
+	httpServer *http.Server
+
+	// shutdownCh is closed by Shutdown so long-lived streams can end
+	shutdownCh   chan struct{}
+	shutdownOnce sync.Once
+
+	// ready is set by SetReady once startup has finished. Until then only the
+	// health checks and static files are served.
+	ready atomic.Bool
+
+	// results caches computed rollup results for /api/results
+	results *resultsCache
 }
 
-func New(cfg *config.ServerConfig, database *db.DB, sched *scheduler.Scheduler) *Server {
+func New(cfg *config.ServerConfig, database db.Store, sched *scheduler.Scheduler) *Server {
 	funcMap := template.FuncMap{
 		"byteSize": func(b int64) string {
 			const unit = 1024
@@ -108,28 +129,67 @@ func New(cfg *config.ServerConfig, database *db.DB, sched *scheduler.Scheduler)
 	}
 
 	s := &Server{
-		cfg:       cfg,
-		db:        database,
-		scheduler: sched,
-		router:    chi.NewRouter(),
-		templates: tmpl,
+		cfg:        cfg,
+		db:         database,
+		scheduler:  sched,
+		router:     chi.NewRouter(),
+		templates:  tmpl,
+		shutdownCh: make(chan struct{}),
+		results:    newResultsCache(resultsCacheTTL, resultsCacheSize),
 	}
 	s.routes()
+	s.httpServer = &http.Server{
+		Addr:    net.JoinHostPort(cfg.BindAddr, strconv.Itoa(cfg.HTTPPort)),
+		Handler: s.router,
+	}
 	return s
 }
 
 func (s *Server) routes() {
 	s.router.Use(middleware.Logger)
 	s.router.Use(middleware.Recoverer)
+	s.router.Use(s.allowCORSOrigins)
+	s.router.Use(s.requireAuthToken)
+	s.router.Use(s.requireReady)
+	s.router.Get("/healthz", s.handleHealthz)
+	s.router.Get("/readyz", s.handleReadyz)
 	s.router.Get("/", s.handleDashboard)
 	s.router.Get("/api/targets", s.handleGetTargets)
 	s.router.Post("/api/targets", s.handleCreateTarget)
+	s.router.Post("/api/targets/bulk", s.handleCreateTargets)
+	s.router.Post("/api/targets/pause", s.handlePauseTag)
+	s.router.Post("/api/targets/resume", s.handleResumeTag)
+	s.router.Get("/api/targets/{id}", s.handleGetTarget)
+	s.router.Get("/api/tags", s.handleGetTags)
 	s.router.Put("/api/targets/{id}", s.handleUpdateTarget)
 	s.router.Delete("/api/targets/{id}", s.handleDeleteTarget)
+	s.router.Post("/api/targets/{id}/pause", s.handlePauseTarget)
+	s.router.Post("/api/targets/{id}/resume", s.handleResumeTarget)
+	s.router.Post("/api/targets/{id}/rollup", s.handleRollupTarget)
+	s.router.Post("/api/targets/{id}/clear-data", s.handleClearTargetData)
+	s.router.Get("/api/targets/{id}/stats", s.handleGetTargetStats)
+	s.router.Get("/api/targets/{id}/slo", s.handleGetTargetSLO)
+	s.router.Get("/api/targets/{id}/sparkline", s.handleGetTargetSparkline)
+	s.router.Get("/api/targets/{id}/alerts", s.handleGetAlertRules)
+	s.router.Post("/api/targets/{id}/alerts", s.handleCreateAlertRule)
+	s.router.Delete("/api/targets/{id}/alerts/{ruleId}", s.handleDeleteAlertRule)
+	s.router.Get("/api/results", s.handleGetResults)
 	s.router.Get("/api/results/{id}", s.handleGetResults)
+	s.router.Get("/api/stream", s.handleStream)
+	s.router.Get("/ws", s.handleWebSocket)
+	s.router.Get("/api/export", s.handleExport)
+	s.router.Post("/api/import", s.handleImport)
+	s.router.Post("/api/probe/test", s.handleProbeTest)
+	s.router.Get("/api/histogram", s.handleHistogram)
 	s.router.Get("/graph/{id}", s.handleGraph)
 	s.router.Get("/status", s.handleStatus)
+	s.router.Get("/api/status", s.handleAPIStatus)
+	s.router.Get("/metrics", s.handleMetrics)
 	s.router.Post("/status/cleanup-orphaned-data", s.handleStatusCleanupOrphanedData)
+	s.router.Post("/api/maintenance/vacuum", s.handleVacuum)
+	s.router.Post("/api/maintenance/flush", s.handleFlush)
+	s.router.Get("/api/maintenance/integrity", s.handleIntegrityCheck)
+	s.router.Post("/api/scheduler/reload", s.handleReloadScheduler)
 	s.router.Get("/favicon.png", s.handleFavicon)
 	s.router.Get("/static/*", s.handleStatic)
 
@@ -154,60 +214,121 @@ func (s *Server) routes() {
 	s.router.Post("/api/dashboards/{id}/regenerate-slug", s.handleRegenerateDashboardSlug)
 }
 
-func (s *Server) Start() error {
-	return http.ListenAndServe(":"+strconv.Itoa(s.cfg.HTTPPort), s.router)
-}
+// corsMethods are the methods the API uses, offered to allowed cross-origin callers.
+const corsMethods = "GET, POST, PUT, DELETE"
 
-func (s *Server) handleCreateTarget(w http.ResponseWriter, r *http.Request) {
-	var t db.Target
-	if err := json.NewDecoder(r.Body).Decode(&t); err != nil {
-		http.Error(w, err.Error(), http.StatusBadRequest)
-		return
-	}
+// allowCORSOrigins adds CORS headers for requests from the configured origins and
+// answers their preflight requests. Nothing is added when no origins are configured.
+func (s *Server) allowCORSOrigins(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if len(s.cfg.CORSOrigins) == 0 {
+			next.ServeHTTP(w, r)
+			return
+		}
+		w.Header().Add("Vary", "Origin")
+		origin := r.Header.Get("Origin")
+		if origin == "" || !slices.Contains(s.cfg.CORSOrigins, origin) {
+			next.ServeHTTP(w, r)
+			return
+		}
+
+		w.Header().Set("Access-Control-Allow-Origin", origin)
+		if r.Method == http.MethodOptions && r.Header.Get("Access-Control-Request-Method") != "" {
+			w.Header().Set("Access-Control-Allow-Methods", corsMethods)
+			w.Header().Set("Access-Control-Allow-Headers", "Authorization, Content-Type")
+			w.Header().Set("Access-Control-Max-Age", "600")
+			w.WriteHeader(http.StatusNoContent)
+			return
+		}
+		next.ServeHTTP(w, r)
+	})
+}
 
-	// Basic validation
+// requireAuthToken rejects mutating requests that don't carry the configured bearer
+// token. Reads stay open, and nothing is checked when no token is configured.
+func (s *Server) requireAuthToken(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if s.cfg.AuthToken == "" {
+			next.ServeHTTP(w, r)
+			return
+		}
+		switch r.Method {
+		case http.MethodPost, http.MethodPut, http.MethodPatch, http.MethodDelete:
+		default:
+			next.ServeHTTP(w, r)
+			return
+		}
 
-	if t.RetentionPolicies != "" {
-		var policies []scheduler.RetentionPolicy
-		// First unmarshal to check JSON validity
-		if err := json.Unmarshal([]byte(t.RetentionPolicies), &policies); err != nil {
-			http.Error(w, "Invalid retention policies JSON", http.StatusBadRequest)
+		token, ok := strings.CutPrefix(r.Header.Get("Authorization"), "Bearer ")
+		if !ok || subtle.ConstantTimeCompare([]byte(token), []byte(s.cfg.AuthToken)) != 1 {
+			w.Header().Set("WWW-Authenticate", `Bearer realm="vaportrail"`)
+			http.Error(w, "Unauthorized", http.StatusUnauthorized)
 			return
 		}
-		// Then validate policies logic (this also sorts them)
-		if err := scheduler.ValidateRetentionPolicies(policies); err != nil {
-			http.Error(w, "Invalid retention policies: "+err.Error(), http.StatusBadRequest)
+		next.ServeHTTP(w, r)
+	})
+}
+
+// startupRetryAfter is the Retry-After, in seconds, sent while the server is starting.
+const startupRetryAfter = "5"
+
+// SetReady marks startup as finished, or not, for requireReady and /readyz.
+func (s *Server) SetReady(ready bool) {
+	s.ready.Store(ready)
+}
+
+// requireReady answers 503 with a Retry-After until SetReady is called, so requests
+// that arrive while the database or scheduler is starting are retried rather than
+// failing. The health checks and static files are always served.
+func (s *Server) requireReady(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		switch {
+		case s.ready.Load(),
+			r.URL.Path == "/healthz", r.URL.Path == "/readyz", r.URL.Path == "/favicon.png",
+			strings.HasPrefix(r.URL.Path, "/static/"):
+			next.ServeHTTP(w, r)
 			return
 		}
-		// Re-serialize sorted policies
-		sortedJSON, _ := json.Marshal(policies)
-		t.RetentionPolicies = string(sortedJSON)
+		w.Header().Set("Retry-After", startupRetryAfter)
+		http.Error(w, "Starting up", http.StatusServiceUnavailable)
+	})
+}
+
+// Start serves HTTP until Shutdown is called.
+func (s *Server) Start() error {
+	if err := s.httpServer.ListenAndServe(); err != nil && !errors.Is(err, http.ErrServerClosed) {
+		return err
 	}
+	return nil
+}
 
-	if t.Name == "" || t.Address == "" || t.ProbeType == "" {
-		http.Error(w, "Missing required fields", http.StatusBadRequest)
+// Shutdown stops accepting connections, ends any live result streams and waits
+// for in-flight requests to complete or ctx to expire.
+func (s *Server) Shutdown(ctx context.Context) error {
+	s.shutdownOnce.Do(func() { close(s.shutdownCh) })
+	return s.httpServer.Shutdown(ctx)
+}
+
+func (s *Server) handleCreateTarget(w http.ResponseWriter, r *http.Request) {
+	var t db.Target
+	if err := json.NewDecoder(r.Body).Decode(&t); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
 		return
 	}
 
-	if t.ProbeInterval <= 0 {
-		t.ProbeInterval = 1.0
-	}
-	if t.Timeout <= 0 {
-		t.Timeout = 5.0
+	if err := prepareTarget(&t); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
 	}
 
-	// Check for valid probe type
-	if _, err := probe.GetConfig(t.ProbeType, t.Address); err != nil {
-		http.Error(w, "Invalid probe type", http.StatusBadRequest)
+	targets, err := s.db.GetTargets()
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
 		return
 	}
-
-	// Drop any user provided config
-	t.ProbeConfig = ""
-
-	// Apply default retention policies if not provided
-	if t.RetentionPolicies == "" {
-		t.RetentionPolicies = scheduler.DefaultPoliciesJSON()
+	if err := s.checkTargetLimit(len(targets), 1); err != nil {
+		http.Error(w, err.Error(), http.StatusTooManyRequests)
+		return
 	}
 
 	id, err := s.db.AddTarget(&t)
@@ -226,6 +347,93 @@ func (s *Server) handleCreateTarget(w http.ResponseWriter, r *http.Request) {
 	json.NewEncoder(w).Encode(t)
 }
 
+// APIBulkTargetError is a target that failed validation in a bulk create. Index is
+// its position in the request.
+type APIBulkTargetError struct {
+	Index int    `json:"index"`
+	Name  string `json:"name"`
+	Error string `json:"error"`
+}
+
+// handleCreateTargets adds an array of targets at once. Every target is validated
+// first, and if any fails the errors are returned with a 400 and nothing is added.
+// The targets are then inserted in one transaction before the scheduler starts them.
+func (s *Server) handleCreateTargets(w http.ResponseWriter, r *http.Request) {
+	var targets []*db.Target
+	if err := json.NewDecoder(r.Body).Decode(&targets); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	if len(targets) == 0 {
+		http.Error(w, "No targets given", http.StatusBadRequest)
+		return
+	}
+
+	var invalid []APIBulkTargetError
+	for i, t := range targets {
+		if t == nil {
+			invalid = append(invalid, APIBulkTargetError{Index: i, Error: "Missing required fields"})
+			continue
+		}
+		if err := prepareTarget(t); err != nil {
+			invalid = append(invalid, APIBulkTargetError{Index: i, Name: t.Name, Error: err.Error()})
+		}
+	}
+	if len(invalid) > 0 {
+		w.Header().Set("Content-Type", "application/json")
+		w.WriteHeader(http.StatusBadRequest)
+		json.NewEncoder(w).Encode(map[string][]APIBulkTargetError{"errors": invalid})
+		return
+	}
+
+	existing, err := s.db.GetTargets()
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	if err := s.checkTargetLimit(len(existing), len(targets)); err != nil {
+		http.Error(w, err.Error(), http.StatusTooManyRequests)
+		return
+	}
+
+	if err := s.db.AddTargets(targets); err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	if s.scheduler != nil {
+		for _, t := range targets {
+			s.scheduler.AddTarget(*t)
+		}
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+	w.WriteHeader(http.StatusCreated)
+	json.NewEncoder(w).Encode(targets)
+}
+
+// checkTargetLimit returns an error for the client when adding more targets to the
+// current ones would go over the configured MaxTargets.
+func (s *Server) checkTargetLimit(current, adding int) error {
+	if s.cfg.MaxTargets > 0 && current+adding > s.cfg.MaxTargets {
+		return fmt.Errorf("Target limit of %d reached", s.cfg.MaxTargets)
+	}
+	return nil
+}
+
+// prepareTarget validates a target that is about to be added or updated and fills
+// in the defaults. Its errors are meant for the client.
+func prepareTarget(t *db.Target) error {
+	if err := scheduler.PrepareTarget(t); err != nil {
+		return err
+	}
+	tags, err := normalizeTags(t.Tags)
+	if err != nil {
+		return err
+	}
+	t.Tags = tags
+	return nil
+}
+
 func (s *Server) handleDeleteTarget(w http.ResponseWriter, r *http.Request) {
 	idStr := chi.URLParam(r, "id")
 	id, err := strconv.ParseInt(idStr, 10, 64)
@@ -234,18 +442,36 @@ func (s *Server) handleDeleteTarget(w http.ResponseWriter, r *http.Request) {
 		return
 	}
 
+	// Stop probing and write out the target's buffered results first, so none of
+	// them arrive after its data is deleted and recreate it
+	if s.scheduler != nil {
+		if err := s.scheduler.DrainTarget(id); err != nil {
+			s.restartTargets()
+			http.Error(w, err.Error(), http.StatusInternalServerError)
+			return
+		}
+	}
+
 	if err := s.db.DeleteTarget(id); err != nil {
+		s.restartTargets()
 		http.Error(w, err.Error(), http.StatusInternalServerError)
 		return
 	}
 
-	if s.scheduler != nil {
-		s.scheduler.RemoveTarget(id)
-	}
-
 	w.WriteHeader(http.StatusOK)
 }
 
+// restartTargets resumes probing the targets still in the database, after a
+// failed delete already stopped one.
+func (s *Server) restartTargets() {
+	if s.scheduler == nil {
+		return
+	}
+	if err := s.scheduler.Reload(); err != nil {
+		log.Printf("Failed to reload targets: %v", err)
+	}
+}
+
 func (s *Server) handleUpdateTarget(w http.ResponseWriter, r *http.Request) {
 	idStr := chi.URLParam(r, "id")
 	id, err := strconv.ParseInt(idStr, 10, 64)
@@ -267,137 +493,1464 @@ func (s *Server) handleUpdateTarget(w http.ResponseWriter, r *http.Request) {
 		return
 	}
 	t.ID = id
+	// Paused state is only changed through the pause/resume endpoints
+	t.Paused = existingTarget.Paused
+
+	if err := prepareTarget(&t); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+
+	// Rollups of removed windows are deleted and added windows are filled from the
+	// existing data. Raw data is kept when its policy is removed.
+	oldPolicies, _ := scheduler.GetRetentionPolicies(*existingTarget)
+	newPolicies, _ := scheduler.GetRetentionPolicies(t)
+	removed, added := scheduler.DiffRetentionWindows(oldPolicies, newPolicies)
+
+	if err := s.db.UpdateTarget(&t); err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+
+	// Update scheduler
+	if s.scheduler != nil {
+		s.scheduler.RemoveTarget(id)
+		s.scheduler.AddTarget(t)
+		if len(removed) > 0 || len(added) > 0 {
+			log.Printf("Target %d: retention windows changed (removed %v, added %v), updating rollups in the background", id, removed, added)
+			s.scheduler.ApplyRetentionChange(t, removed, added)
+		}
+	} else {
+		// Without a scheduler nothing rolls up, so only the deletion applies
+		for _, window := range removed {
+			s.db.DeleteAggregatedResultsByWindow(id, window)
+		}
+	}
+
+	w.WriteHeader(http.StatusOK)
+	json.NewEncoder(w).Encode(t)
+}
+
+func (s *Server) handlePauseTarget(w http.ResponseWriter, r *http.Request) {
+	s.setTargetPaused(w, r, true)
+}
+
+func (s *Server) handleResumeTarget(w http.ResponseWriter, r *http.Request) {
+	s.setTargetPaused(w, r, false)
+}
+
+func (s *Server) handlePauseTag(w http.ResponseWriter, r *http.Request) {
+	s.setTagPaused(w, r, true)
+}
+
+func (s *Server) handleResumeTag(w http.ResponseWriter, r *http.Request) {
+	s.setTagPaused(w, r, false)
+}
+
+// setTagPaused pauses or resumes every target with the ?tag= parameter, then syncs
+// the scheduler with the targets table. It reports how many targets changed, so
+// repeating the call is harmless.
+func (s *Server) setTagPaused(w http.ResponseWriter, r *http.Request, paused bool) {
+	tag := strings.TrimSpace(r.URL.Query().Get("tag"))
+	if tag == "" {
+		http.Error(w, "tag is required", http.StatusBadRequest)
+		return
+	}
 
-	var newPolicies []scheduler.RetentionPolicy
-	if t.RetentionPolicies != "" {
-		if err := json.Unmarshal([]byte(t.RetentionPolicies), &newPolicies); err != nil {
-			http.Error(w, "Invalid retention policies JSON", http.StatusBadRequest)
+	changed, err := s.db.SetTagPaused(tag, paused)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	if s.scheduler != nil {
+		if err := s.scheduler.Reload(); err != nil {
+			http.Error(w, "Failed to sync scheduler: "+err.Error(), http.StatusInternalServerError)
 			return
 		}
-		// Validate (this also sorts them)
-		if err := scheduler.ValidateRetentionPolicies(newPolicies); err != nil {
-			http.Error(w, "Invalid retention policies: "+err.Error(), http.StatusBadRequest)
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(map[string]any{"tag": tag, "paused": paused, "changed": changed})
+}
+
+func (s *Server) setTargetPaused(w http.ResponseWriter, r *http.Request, paused bool) {
+	idStr := chi.URLParam(r, "id")
+	id, err := strconv.ParseInt(idStr, 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+
+	switch {
+	case s.scheduler == nil:
+		err = s.db.SetTargetPaused(id, paused)
+	case paused:
+		err = s.scheduler.PauseTarget(id)
+	default:
+		err = s.scheduler.ResumeTarget(id)
+	}
+	if err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
 			return
 		}
-		// Re-serialize sorted policies
-		sortedJSON, _ := json.Marshal(newPolicies)
-		t.RetentionPolicies = string(sortedJSON)
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
 	}
 
-	if t.Name == "" || t.Address == "" || t.ProbeType == "" {
-		http.Error(w, "Missing required fields", http.StatusBadRequest)
+	target, err := s.db.GetTarget(id)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
 		return
 	}
-	if t.ProbeInterval == 0 {
-		t.ProbeInterval = 1.0
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(target)
+}
+
+// handleRollupTarget runs the target's rollups synchronously instead of waiting for
+// the next periodic pass, and reports how many aggregated windows were written.
+func (s *Server) handleRollupTarget(w http.ResponseWriter, r *http.Request) {
+	idStr := chi.URLParam(r, "id")
+	id, err := strconv.ParseInt(idStr, 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
 	}
-	if t.Timeout == 0 {
-		t.Timeout = 5.0
+	if s.scheduler == nil {
+		http.Error(w, "Rollups are not available", http.StatusServiceUnavailable)
+		return
 	}
 
-	if _, err := probe.GetConfig(t.ProbeType, t.Address); err != nil {
-		http.Error(w, "Invalid probe type", http.StatusBadRequest)
+	created, err := s.scheduler.RollupTarget(id)
+	if err != nil {
+		switch {
+		case errors.Is(err, sql.ErrNoRows):
+			http.Error(w, "Target not found", http.StatusNotFound)
+		case errors.Is(err, scheduler.ErrNoRetentionPolicies):
+			http.Error(w, "Target has no retention policies configured", http.StatusBadRequest)
+		default:
+			http.Error(w, "Rollup failed: "+err.Error(), http.StatusInternalServerError)
+		}
 		return
 	}
+	// Backfilled windows are older than the newest rollup, so the cache can't tell
+	s.results.invalidate(id)
 
-	// Detect removed retention policies and delete their data
-	oldPolicies, _ := scheduler.GetRetentionPolicies(*existingTarget)
-	newWindowSet := make(map[int]bool)
-	for _, p := range newPolicies {
-		newWindowSet[p.Window] = true
-	}
-	for _, oldP := range oldPolicies {
-		if !newWindowSet[oldP.Window] {
-			// This window was removed, delete its aggregated data
-			if oldP.Window == 0 {
-				// Raw data - we could delete it, but typically raw is retained if any policy exists
-				// For now, we skip raw data deletion on policy removal
-				continue
-			}
-			s.db.DeleteAggregatedResultsByWindow(id, oldP.Window)
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(map[string]int{"windows_created": created})
+}
+
+// handleClearTargetData deletes all of a target's results but keeps the target, so
+// it starts over with an empty history. It reports how many rows each table lost.
+func (s *Server) handleClearTargetData(w http.ResponseWriter, r *http.Request) {
+	idStr := chi.URLParam(r, "id")
+	id, err := strconv.ParseInt(idStr, 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+	if _, err := s.db.GetTarget(id); err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
 		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
 	}
 
-	if err := s.db.UpdateTarget(&t); err != nil {
+	cleared, err := s.db.ClearTargetData(id)
+	if err != nil {
 		http.Error(w, err.Error(), http.StatusInternalServerError)
 		return
 	}
+	s.results.invalidate(id)
 
-	// Update scheduler
-	if s.scheduler != nil {
-		s.scheduler.RemoveTarget(id)
-		s.scheduler.AddTarget(t)
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(cleared)
+}
+
+// writeProbeConfigError reports a probe.GetConfig or probe.ValidateProbeConfig
+// failure as a 400 response.
+func writeProbeConfigError(w http.ResponseWriter, err error) {
+	if errors.Is(err, probe.ErrInvalidConfig) {
+		http.Error(w, "Invalid probe config: "+err.Error(), http.StatusBadRequest)
+		return
 	}
+	http.Error(w, "Invalid probe type", http.StatusBadRequest)
+}
 
-	w.WriteHeader(http.StatusOK)
-	json.NewEncoder(w).Encode(t)
+// maxProbeTestTimeout caps the timeout of a test probe, so a request can't hold a
+// handler for long.
+const maxProbeTestTimeout = 10 * time.Second
+
+// APIProbeTest is a probe to run once, without saving a target. Timeout is in
+// seconds, like a target's, and defaults to 5. An empty SourceAddress uses the
+// server's default, like a target's.
+type APIProbeTest struct {
+	ProbeType     string  `json:"probe_type"`
+	Address       string  `json:"address"`
+	ProbeConfig   string  `json:"probe_config"`
+	AddressFamily string  `json:"address_family"`
+	SourceAddress string  `json:"source_address"`
+	Timeout       float64 `json:"timeout"`
 }
 
-func (s *Server) handleDashboard(w http.ResponseWriter, r *http.Request) {
-	if err := s.templates.ExecuteTemplate(w, "dashboard.html", nil); err != nil {
+// APIProbeTestResult is the outcome of a test probe. Error is set when it failed.
+type APIProbeTestResult struct {
+	OK         bool    `json:"ok"`
+	LatencyNS  float64 `json:"latency_ns"`
+	RemoteIP   string  `json:"remote_ip,omitempty"`
+	StatusCode int     `json:"status_code,omitempty"`
+	Error      string  `json:"error,omitempty"`
+}
+
+// handleProbeTest runs a single probe immediately and reports its latency or error.
+// Nothing is written to the database and the scheduler isn't involved, so it can
+// check a target before it is saved. A failed probe is still a 200.
+func (s *Server) handleProbeTest(w http.ResponseWriter, r *http.Request) {
+	var req APIProbeTest
+	if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	if req.ProbeType == "" || req.Address == "" {
+		http.Error(w, "Missing required fields", http.StatusBadRequest)
+		return
+	}
+	if !probe.ValidAddressFamily(req.AddressFamily) {
+		http.Error(w, "address_family must be auto, ipv4 or ipv6", http.StatusBadRequest)
+		return
+	}
+	if !probe.ValidSourceAddress(req.SourceAddress) {
+		http.Error(w, "source_address must be an IP address", http.StatusBadRequest)
+		return
+	}
+	if err := probe.ValidateProbeConfig(req.ProbeType, req.ProbeConfig); err != nil {
+		writeProbeConfigError(w, err)
+		return
+	}
+	cfg, err := probe.GetConfig(req.ProbeType, req.Address, req.ProbeConfig)
+	if err != nil {
+		writeProbeConfigError(w, err)
+		return
+	}
+	if err := probe.ValidateAddress(req.ProbeType, req.Address); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+
+	cfg.Timeout = 5 * time.Second
+	if req.Timeout > 0 {
+		cfg.Timeout = time.Duration(req.Timeout*1000) * time.Millisecond
+	}
+	if cfg.Timeout > maxProbeTestTimeout {
+		http.Error(w, fmt.Sprintf("timeout must be at most %g seconds", maxProbeTestTimeout.Seconds()), http.StatusBadRequest)
+		return
+	}
+	if err := probe.ValidateTimeout(req.ProbeType, req.ProbeConfig, cfg.Timeout); err != nil {
+		writeProbeConfigError(w, err)
+		return
+	}
+	cfg.AddressFamily = req.AddressFamily
+	cfg.SourceAddress = req.SourceAddress
+	if cfg.SourceAddress == "" {
+		cfg.SourceAddress = s.cfg.SourceAddress
+	}
+
+	var result APIProbeTestResult
+	res, err := probe.Run(cfg)
+	result.RemoteIP = res.RemoteIP
+	result.StatusCode = res.StatusCode
+	if err != nil {
+		result.Error = err.Error()
+	} else {
+		result.OK = true
+		result.LatencyNS = res.Latency
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(result)
+}
+
+func (s *Server) handleGetAlertRules(w http.ResponseWriter, r *http.Request) {
+	id, err := strconv.ParseInt(chi.URLParam(r, "id"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+
+	rules, err := s.db.GetAlertRulesForTarget(id)
+	if err != nil {
 		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(rules)
+}
+
+func (s *Server) handleCreateAlertRule(w http.ResponseWriter, r *http.Request) {
+	id, err := strconv.ParseInt(chi.URLParam(r, "id"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+	target, err := s.db.GetTarget(id)
+	if err != nil {
+		http.Error(w, "Target not found", http.StatusNotFound)
+		return
+	}
+
+	var rule db.AlertRule
+	if err := json.NewDecoder(r.Body).Decode(&rule); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	rule.TargetID = id
+	rule.Firing = false
+	rule.LastWindow = nil
+	if rule.Channel == "" {
+		rule.Channel = "webhook"
+	}
+
+	if err := scheduler.ValidateAlertRule(rule); err != nil {
+		http.Error(w, "Invalid alert rule: "+err.Error(), http.StatusBadRequest)
+		return
+	}
+	// Rules are evaluated against rollups, so the window must be one that is kept
+	policies, _ := scheduler.GetRetentionPolicies(*target)
+	windowConfigured := false
+	for _, p := range policies {
+		if p.Window == rule.WindowSeconds {
+			windowConfigured = true
+		}
+	}
+	if !windowConfigured {
+		http.Error(w, fmt.Sprintf("Invalid alert rule: target has no %ds retention window", rule.WindowSeconds), http.StatusBadRequest)
+		return
+	}
+
+	ruleID, err := s.db.AddAlertRule(&rule)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	rule.ID = ruleID
+
+	w.WriteHeader(http.StatusCreated)
+	json.NewEncoder(w).Encode(rule)
+}
+
+func (s *Server) handleDeleteAlertRule(w http.ResponseWriter, r *http.Request) {
+	id, err := strconv.ParseInt(chi.URLParam(r, "id"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+	ruleID, err := strconv.ParseInt(chi.URLParam(r, "ruleId"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid rule ID", http.StatusBadRequest)
+		return
+	}
+
+	if err := s.db.DeleteAlertRule(ruleID, id); err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Alert rule not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+
+	w.WriteHeader(http.StatusOK)
+}
+
+// renderTemplate executes a page template into a buffer before writing it, so a
+// template error is sent as a 500 rather than after a 200 and part of the page.
+func (s *Server) renderTemplate(w http.ResponseWriter, name string, data any) {
+	var buf bytes.Buffer
+	if err := s.templates.ExecuteTemplate(&buf, name, data); err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	w.Header().Set("Content-Type", "text/html; charset=utf-8")
+	buf.WriteTo(w)
+}
+
+func (s *Server) handleDashboard(w http.ResponseWriter, r *http.Request) {
+	s.renderTemplate(w, "dashboard.html", nil)
+}
+
+// handleGetTargets lists targets, optionally filtered by name_contains, probe_type
+// and tag, and paged with limit and offset. The body is the same array as without parameters;
+// the total number of matches is returned in X-Total-Count.
+func (s *Server) handleGetTargets(w http.ResponseWriter, r *http.Request) {
+	query := r.URL.Query()
+	q := db.TargetQuery{
+		NameContains: query.Get("name_contains"),
+		ProbeType:    query.Get("probe_type"),
+		Tag:          query.Get("tag"),
+	}
+	for name, dst := range map[string]*int{"limit": &q.Limit, "offset": &q.Offset} {
+		if v := query.Get(name); v != "" {
+			n, err := strconv.Atoi(v)
+			if err != nil || n < 0 {
+				http.Error(w, "Invalid "+name, http.StatusBadRequest)
+				return
+			}
+			*dst = n
+		}
+	}
+
+	targets, total, err := s.db.QueryTargets(q)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	w.Header().Set("Content-Type", "application/json")
+	w.Header().Set("X-Total-Count", strconv.Itoa(total))
+	json.NewEncoder(w).Encode(targets)
+}
+
+func (s *Server) handleGetTags(w http.ResponseWriter, r *http.Request) {
+	tags, err := s.db.GetTags()
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(tags)
+}
+
+// normalizeTags trims and de-duplicates tags, keeping their order. Commas are
+// rejected so tags can be listed in a single query parameter or CSV cell.
+func normalizeTags(tags []string) ([]string, error) {
+	seen := make(map[string]bool, len(tags))
+	var result []string
+	for _, tag := range tags {
+		tag = strings.TrimSpace(tag)
+		if tag == "" {
+			return nil, errors.New("tags cannot be empty")
+		}
+		if strings.ContainsRune(tag, ',') || strings.IndexFunc(tag, unicode.IsControl) >= 0 {
+			return nil, fmt.Errorf("invalid tag %q: tags cannot contain commas or control characters", tag)
+		}
+		if !seen[tag] {
+			seen[tag] = true
+			result = append(result, tag)
+		}
+	}
+	return result, nil
+}
+
+func (s *Server) handleGetTarget(w http.ResponseWriter, r *http.Request) {
+	idStr := chi.URLParam(r, "id")
+	id, err := strconv.ParseInt(idStr, 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+
+	target, err := s.db.GetTarget(id)
+	if err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(target)
+}
+
+type APIResult struct {
+	Time          time.Time
+	TargetID      int64
+	MinNS         int64
+	MaxNS         int64
+	AvgNS         int64
+	P0            float64
+	P1            float64
+	P25           float64
+	P50           float64
+	P75           float64
+	P99           float64
+	P999          float64 // 99.9th percentile, aggregated results only
+	P100          float64
+	Percentiles   []float64 // 0th, 5th, 10th... 100th
+	TimeoutCount  int64
+	ErrorCount    int64 // Probes that failed with an error other than a timeout
+	ProbeCount    int64 // Successful probes
+	AttemptCount  int64 // Probes sent, whatever their outcome; 0 means no data rather than down
+	WindowSeconds int
+	Loss          float64 // Packet loss fraction, raw multi-echo ping results only
+	TTL           int     // Reply TTL, raw ping results only; 0 when unknown
+	RemoteIP      string  // Address the probe reached, raw results only; empty when unknown
+	StatusCode    int     // HTTP status, raw http results only; 0 when unknown
+	JitterNS      float64 // Mean absolute difference between consecutive probes, aggregated results only
+}
+
+func sanitizeFloat(f float64) float64 {
+	if math.IsNaN(f) || math.IsInf(f, 0) {
+		return 0.0
+	}
+	return f
+}
+
+// APITargetStats counts a target's probe outcomes over a range of rollups.
+type APITargetStats struct {
+	TargetID      int64     `json:"target_id"`
+	Start         time.Time `json:"start"`
+	End           time.Time `json:"end"`
+	WindowSeconds int       `json:"window_seconds"`
+	Total         int64     `json:"total"`
+	Successes     int64     `json:"successes"`
+	Timeouts      int64     `json:"timeouts"`
+	Errors        int64     `json:"errors"`
+	// UptimePercent is the share of probes that succeeded, or 0 when there were none.
+	UptimePercent float64 `json:"uptime_percent"`
+}
+
+// parseStatsRange parses a ?range= value: a Go duration such as "90m" or "24h",
+// or a whole number of days such as "7d".
+func parseStatsRange(v string) (time.Duration, error) {
+	if days, ok := strings.CutSuffix(v, "d"); ok {
+		n, err := strconv.Atoi(days)
+		if err != nil {
+			return 0, err
+		}
+		return time.Duration(n) * 24 * time.Hour, nil
+	}
+	return time.ParseDuration(v)
+}
+
+// handleGetTargetStats counts successes, timeouts and errors from the target's
+// aggregated results, so long ranges only read a few hundred rows. Probes that
+// haven't been rolled up yet are not counted.
+func (s *Server) handleGetTargetStats(w http.ResponseWriter, r *http.Request) {
+	id, err := strconv.ParseInt(chi.URLParam(r, "id"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+
+	span := 24 * time.Hour
+	if v := r.URL.Query().Get("range"); v != "" {
+		span, err = parseStatsRange(v)
+		if err != nil || span <= 0 {
+			http.Error(w, "Invalid range", http.StatusBadRequest)
+			return
+		}
+	}
+
+	target, err := s.db.GetTarget(id)
+	if err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	policies, err := scheduler.GetRetentionPolicies(*target)
+	if err != nil {
+		http.Error(w, "Target has no retention policies configured", http.StatusInternalServerError)
+		return
+	}
+
+	end := time.Now().UTC()
+	stats := APITargetStats{
+		TargetID:      id,
+		Start:         end.Add(-span),
+		End:           end,
+		WindowSeconds: chooseWindow(policies, span),
+	}
+	results, err := s.db.GetAggregatedResults(id, stats.WindowSeconds, stats.Start, stats.End)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	stats.Successes, stats.Timeouts, stats.Errors, err = countOutcomes(results)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	stats.Total = stats.Successes + stats.Timeouts + stats.Errors
+	if stats.Total > 0 {
+		stats.UptimePercent = float64(stats.Successes) * 100 / float64(stats.Total)
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(stats)
+}
+
+// countOutcomes sums the successful, timed out and failed probes of aggregated results.
+func countOutcomes(results []db.AggregatedResult) (successes, timeouts, errs int64, err error) {
+	for _, res := range results {
+		td, err := db.DeserializeTDigest(res.TDigestData)
+		if err != nil {
+			return 0, 0, 0, fmt.Errorf("failed to read TDigest for window at %s: %v", res.Time.Format(time.RFC3339), err)
+		}
+		successes += int64(td.Count())
+		timeouts += res.TimeoutCount
+		errs += res.ErrorCount
+	}
+	return successes, timeouts, errs, nil
+}
+
+// sparklinePoints is about how many points a sparkline has.
+const sparklinePoints = 60
+
+// sparklineMetrics maps each metric a sparkline can plot to its quantile.
+var sparklineMetrics = map[string]float64{
+	"p50":  0.5,
+	"p90":  0.9,
+	"p95":  0.95,
+	"p99":  0.99,
+	"p999": 0.999,
+	"max":  1.0,
+}
+
+// APISparklinePoint is one point of a sparkline. Value is a latency in nanoseconds.
+type APISparklinePoint struct {
+	Time  time.Time `json:"time"`
+	Value float64   `json:"value"`
+}
+
+// handleGetTargetSparkline returns one latency metric of a target over a range, by
+// default p50 over the last hour, as about sparklinePoints points. The target's
+// rollups are merged into evenly sized buckets, and buckets without a successful
+// probe are left out.
+func (s *Server) handleGetTargetSparkline(w http.ResponseWriter, r *http.Request) {
+	id, err := strconv.ParseInt(chi.URLParam(r, "id"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+
+	q := r.URL.Query()
+	metric := q.Get("metric")
+	if metric == "" {
+		metric = "p50"
+	}
+	quantile, ok := sparklineMetrics[metric]
+	if !ok {
+		http.Error(w, "Invalid metric: must be p50, p90, p95, p99, p999 or max", http.StatusBadRequest)
+		return
+	}
+	span := time.Hour
+	if v := q.Get("range"); v != "" {
+		span, err = parseStatsRange(v)
+		if err != nil || span <= 0 {
+			http.Error(w, "Invalid range", http.StatusBadRequest)
+			return
+		}
+	}
+
+	target, err := s.db.GetTarget(id)
+	if err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	policies, err := scheduler.GetRetentionPolicies(*target)
+	if err != nil {
+		http.Error(w, "Target has no retention policies configured", http.StatusInternalServerError)
+		return
+	}
+
+	window := chooseWindowForPoints(rollupWindows(policies), span, sparklinePoints)
+	// Buckets are whole windows, so each rollup falls into exactly one
+	windowStep := time.Duration(window) * time.Second
+	bucket := (span/sparklinePoints + windowStep - 1) / windowStep * windowStep
+
+	end := time.Now().UTC()
+	results, err := s.db.GetAggregatedResults(id, window, end.Add(-span), end)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+
+	points := []APISparklinePoint{}
+	var bucketStart time.Time
+	var digests []*tdigest.TDigest
+	flush := func() error {
+		if len(digests) == 0 {
+			return nil
+		}
+		merged, err := db.MergeTDigests(0, digests)
+		if err != nil {
+			return err
+		}
+		digests = digests[:0]
+		if merged.Count() > 0 {
+			points = append(points, APISparklinePoint{Time: bucketStart, Value: sanitizeFloat(merged.Quantile(quantile))})
+		}
+		return nil
+	}
+	for _, res := range results {
+		if start := res.Time.Truncate(bucket); !start.Equal(bucketStart) {
+			if err := flush(); err != nil {
+				http.Error(w, err.Error(), http.StatusInternalServerError)
+				return
+			}
+			bucketStart = start
+		}
+		td, err := db.DeserializeTDigest(res.TDigestData)
+		if err != nil {
+			http.Error(w, fmt.Sprintf("failed to read TDigest for window at %s: %v", res.Time.Format(time.RFC3339), err), http.StatusInternalServerError)
+			return
+		}
+		digests = append(digests, td)
+	}
+	if err := flush(); err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+
+	w.Header().Set("X-Window-Seconds", strconv.Itoa(window))
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(points)
+}
+
+// defaultSLOObjective is the availability objective used when none is given.
+const defaultSLOObjective = 0.999
+
+// APITargetSLO compares a target's availability over a range to an objective.
+// Availability and the error budget are fractions, not percentages.
+type APITargetSLO struct {
+	TargetID      int64     `json:"target_id"`
+	Start         time.Time `json:"start"`
+	End           time.Time `json:"end"`
+	WindowSeconds int       `json:"window_seconds"`
+	Objective     float64   `json:"objective"`
+	Total         int64     `json:"total"`
+	Failed        int64     `json:"failed"`
+	// Availability is the share of probes that neither timed out nor failed, or 0
+	// when there were none.
+	Availability float64 `json:"availability"`
+	Met          bool    `json:"met"`
+	// ErrorBudgetRemaining is the share of the failures the objective allows that
+	// weren't used. It is negative once the objective is missed.
+	ErrorBudgetRemaining float64 `json:"error_budget_remaining"`
+}
+
+// handleGetTargetSLO reports whether a target met an availability objective between
+// start and end, by default over the last 30 days. Like handleGetTargetStats it
+// counts probes from the rollup window chooseWindow picks for the range.
+func (s *Server) handleGetTargetSLO(w http.ResponseWriter, r *http.Request) {
+	id, err := strconv.ParseInt(chi.URLParam(r, "id"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+
+	q := r.URL.Query()
+	slo := APITargetSLO{TargetID: id, End: time.Now().UTC(), Objective: defaultSLOObjective}
+	if v := q.Get("end"); v != "" {
+		if slo.End, err = time.Parse(time.RFC3339, v); err != nil {
+			http.Error(w, "Invalid end time", http.StatusBadRequest)
+			return
+		}
+	}
+	slo.Start = slo.End.Add(-30 * 24 * time.Hour)
+	if v := q.Get("start"); v != "" {
+		if slo.Start, err = time.Parse(time.RFC3339, v); err != nil {
+			http.Error(w, "Invalid start time", http.StatusBadRequest)
+			return
+		}
+	}
+	if !slo.Start.Before(slo.End) {
+		http.Error(w, "start must be before end", http.StatusBadRequest)
+		return
+	}
+	if v := q.Get("objective"); v != "" {
+		slo.Objective, err = strconv.ParseFloat(v, 64)
+		if err != nil || slo.Objective <= 0 || slo.Objective >= 1 {
+			http.Error(w, "Invalid objective: must be a fraction between 0 and 1, such as 0.999", http.StatusBadRequest)
+			return
+		}
+	}
+
+	target, err := s.db.GetTarget(id)
+	if err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	policies, err := scheduler.GetRetentionPolicies(*target)
+	if err != nil {
+		http.Error(w, "Target has no retention policies configured", http.StatusInternalServerError)
+		return
+	}
+
+	slo.WindowSeconds = chooseWindow(policies, slo.End.Sub(slo.Start))
+	results, err := s.db.GetAggregatedResults(id, slo.WindowSeconds, slo.Start, slo.End)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	successes, timeouts, errs, err := countOutcomes(results)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	slo.Total = successes + timeouts + errs
+	slo.Failed = timeouts + errs
+	slo.ErrorBudgetRemaining = 1
+	if slo.Total > 0 {
+		slo.Availability = float64(successes) / float64(slo.Total)
+		slo.Met = slo.Availability >= slo.Objective
+		allowed := (1 - slo.Objective) * float64(slo.Total)
+		slo.ErrorBudgetRemaining = 1 - float64(slo.Failed)/allowed
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(slo)
+}
+
+// chooseWindow picks the rollup window to read for a time range: the smallest
+// configured window that keeps the range under 1000 datapoints, or the largest
+// window when none does.
+func chooseWindow(policies []scheduler.RetentionPolicy, span time.Duration) int {
+	return chooseFromWindows(rollupWindows(policies), span)
+}
+
+// chooseFromWindows is chooseWindow for a sorted list of rollup windows.
+func chooseFromWindows(availableWindows []int, span time.Duration) int {
+	return chooseWindowForPoints(availableWindows, span, defaultResultPoints)
+}
+
+// Bounds of the points parameter of /api/results, the number of datapoints the
+// rollup window is chosen for.
+const (
+	defaultResultPoints = 1000
+	minResultPoints     = 10
+	maxResultPoints     = 5000
+)
+
+// chooseWindowForPoints picks the smallest of the sorted rollup windows that keeps
+// span under the given number of datapoints, or the largest window when none does.
+func chooseWindowForPoints(availableWindows []int, span time.Duration, points int) int {
+	desiredWindow := max(int(span.Seconds()/float64(points)), 1)
+
+	for _, w := range availableWindows {
+		if w >= desiredWindow {
+			return w
+		}
+	}
+	if len(availableWindows) > 0 {
+		return availableWindows[len(availableWindows)-1]
+	}
+	// No rollup windows configured, fall back to the default minute window
+	return 60
+}
+
+// rollupWindows returns the sorted rollup windows of policies, leaving out raw.
+func rollupWindows(policies []scheduler.RetentionPolicy) []int {
+	var windows []int
+	for _, p := range policies {
+		if p.Window > 0 {
+			windows = append(windows, p.Window)
+		}
+	}
+	sort.Ints(windows)
+	return windows
+}
+
+// resultTargetIDs parses the targets of a results request: the {id} path
+// parameter and any target_id query parameters, each a comma-separated list.
+func resultTargetIDs(r *http.Request) ([]int64, error) {
+	var lists []string
+	if v := chi.URLParam(r, "id"); v != "" {
+		lists = append(lists, v)
+	}
+	lists = append(lists, r.URL.Query()["target_id"]...)
+
+	var ids []int64
+	for _, list := range lists {
+		for _, v := range strings.Split(list, ",") {
+			id, err := strconv.ParseInt(strings.TrimSpace(v), 10, 64)
+			if err != nil {
+				return nil, err
+			}
+			if !slices.Contains(ids, id) {
+				ids = append(ids, id)
+			}
+		}
+	}
+	if len(ids) == 0 {
+		return nil, errors.New("no target ID")
+	}
+	return ids, nil
+}
+
+// handleGetResults returns a target's results between start and end, by default
+// from the rollup window chooseWindow picks for the range. The points parameter
+// picks the window for that many datapoints instead of 1000, and the window
+// parameter reads one of the target's other rollup windows. The window read is sent
+// in X-Window-Seconds, 0 for raw results, and the target's rollup windows in
+// X-Available-Windows.
+//
+// end defaults to now and start to an hour before end. A start after end is
+// rejected, and one older than the targets' longest retention is moved up to it,
+// since no data is kept from before then.
+//
+// Several targets can be compared by listing their IDs, comma-separated or in
+// repeated target_id parameters. Every target is then read at the same window,
+// chosen from the windows they all have, and the results are keyed by target ID.
+// A single target keeps the plain array response.
+func (s *Server) handleGetResults(w http.ResponseWriter, r *http.Request) {
+	ids, err := resultTargetIDs(r)
+	if err != nil {
+		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		return
+	}
+	s.serveResults(w, r, ids)
+}
+
+// serveResults writes the results of the targets ids as handleGetResults
+// describes. The targets are taken from ids alone, never from the request.
+func (s *Server) serveResults(w http.ResponseWriter, r *http.Request, ids []int64) {
+	startStr := r.URL.Query().Get("start")
+	endStr := r.URL.Query().Get("end")
+
+	var start, end time.Time
+	var window int
+	var err error
+	// Only windows every target has keep the series aligned
+	var available []int
+	// longest is the longest retention of any of the targets, in seconds, unless
+	// one of them keeps raw results forever
+	var longest int
+	var forever bool
+	for i, id := range ids {
+		// Fetch target to get retention policies
+		target, err := s.db.GetTarget(id)
+		if err != nil {
+			// If target not found, we can't really determine policies.
+			// Return 404 or just fail? The ID validation passed int parsing but DB check might fail.
+			http.Error(w, "Target not found: "+err.Error(), http.StatusNotFound)
+			return
+		}
+		policies, err := scheduler.GetRetentionPolicies(*target)
+		if err != nil {
+			http.Error(w, "Target has no retention policies configured", http.StatusInternalServerError)
+			return
+		}
+		for _, p := range policies {
+			longest = max(longest, p.Retention)
+			forever = forever || p.Retention == scheduler.RetainForever
+		}
+		if i == 0 {
+			available = rollupWindows(policies)
+		} else {
+			windows := rollupWindows(policies)
+			available = slices.DeleteFunc(available, func(aw int) bool { return !slices.Contains(windows, aw) })
+		}
+	}
+
+	// Default view (last hour), or the hour before end when only end is given
+	end = time.Now().UTC()
+	if endStr != "" {
+		end, err = time.Parse(time.RFC3339, endStr)
+		if err != nil {
+			http.Error(w, "Invalid end time", http.StatusBadRequest)
+			return
+		}
+	}
+	start = end.Add(-1 * time.Hour)
+	if startStr != "" {
+		start, err = time.Parse(time.RFC3339, startStr)
+		if err != nil {
+			http.Error(w, "Invalid start time", http.StatusBadRequest)
+			return
+		}
+	}
+	if !start.Before(end) {
+		http.Error(w, "Invalid time range: start must be before end", http.StatusBadRequest)
+		return
+	}
+	// Nothing older than the longest retention is kept, so don't let it widen the
+	// window chosen for the range
+	if longest > 0 && !forever {
+		if oldest := time.Now().UTC().Add(-time.Duration(longest) * time.Second); start.Before(oldest) && oldest.Before(end) {
+			start = oldest
+		}
+	}
+
+	points := defaultResultPoints
+	if v := r.URL.Query().Get("points"); v != "" {
+		points, err = strconv.Atoi(v)
+		if err != nil || points < minResultPoints || points > maxResultPoints {
+			http.Error(w, fmt.Sprintf("Invalid points: must be between %d and %d", minResultPoints, maxResultPoints), http.StatusBadRequest)
+			return
+		}
+	}
+
+	if v := r.URL.Query().Get("window"); v != "" {
+		window, err = strconv.Atoi(v)
+		if err != nil || !slices.Contains(available, window) {
+			http.Error(w, "Invalid window: must be one of the target's rollup windows", http.StatusBadRequest)
+			return
+		}
+	} else {
+		window = chooseWindowForPoints(available, end.Sub(start), points)
+	}
+	windowNames := make([]string, len(available))
+	for i, aw := range available {
+		windowNames[i] = strconv.Itoa(aw)
+	}
+	w.Header().Set("X-Available-Windows", strings.Join(windowNames, ","))
+
+	// raw=true returns the newest 1000 raw results in the range, while include_raw=true
+	// thins the whole range down to at most 1000 evenly spaced ones
+	includeRaw := r.URL.Query().Get("include_raw") == "true"
+	raw := r.URL.Query().Get("raw") == "true" || includeRaw
+
+	byTarget := make(map[int64][]APIResult, len(ids))
+	for _, id := range ids {
+		var apiResults []APIResult
+		if raw {
+			apiResults, err = s.rawAPIResults(id, start, end, includeRaw)
+		} else {
+			apiResults, err = s.aggregatedAPIResults(id, window, start, end)
+		}
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusInternalServerError)
+			return
+		}
+		byTarget[id] = apiResults
+	}
+
+	if raw {
+		w.Header().Set("X-Window-Seconds", "0")
+	} else {
+		w.Header().Set("X-Window-Seconds", strconv.Itoa(window))
+	}
+	w.Header().Set("Content-Type", "application/json")
+	if len(ids) == 1 {
+		json.NewEncoder(w).Encode(byTarget[ids[0]])
+		return
+	}
+	json.NewEncoder(w).Encode(byTarget)
+}
+
+// rawAPIResults returns up to 1000 of a target's raw results in [start, end): the
+// newest ones, or when downsample is set, ones spread over the whole range.
+func (s *Server) rawAPIResults(id int64, start, end time.Time, downsample bool) ([]APIResult, error) {
+	var rawResults []db.RawResult
+	var err error
+	if downsample {
+		rawResults, err = s.db.GetRawResultsDownsampled(id, start, end, 1000)
+	} else {
+		rawResults, err = s.db.GetRawResults(id, start, end, 1000)
+	}
+	if err != nil {
+		return nil, fmt.Errorf("failed to get raw results: %w", err)
+	}
+
+	var apiResults []APIResult
+	for _, rr := range rawResults {
+		// A failed probe has no latency to plot
+		if rr.Latency == db.LatencyTimeout {
+			apiResults = append(apiResults, APIResult{Time: rr.Time, TargetID: rr.TargetID, TimeoutCount: 1, AttemptCount: 1, Loss: rr.Loss})
+			continue
+		}
+		if rr.Latency == db.LatencyError {
+			apiResults = append(apiResults, APIResult{Time: rr.Time, TargetID: rr.TargetID, ErrorCount: 1, AttemptCount: 1, RemoteIP: rr.RemoteIP, StatusCode: rr.StatusCode})
+			continue
+		}
+		apiRes := APIResult{
+			Time:         rr.Time,
+			TargetID:     rr.TargetID,
+			ProbeCount:   1,
+			AttemptCount: 1,
+			MinNS:        int64(rr.Latency),
+			MaxNS:        int64(rr.Latency),
+			AvgNS:        int64(rr.Latency), // Set Avg to latency for simple display usually
+			P0:           rr.Latency,
+			P100:         rr.Latency,
+			P50:          rr.Latency, // Median is the value itself
+			Loss:         rr.Loss,
+			TTL:          rr.TTL,
+			RemoteIP:     rr.RemoteIP,
+			StatusCode:   rr.StatusCode,
+		}
+		apiResults = append(apiResults, apiRes)
+	}
+	return apiResults, nil
+}
+
+// aggregatedAPIResults returns a target's rollups of the given window in [start, end).
+// They are computed for the range widened to whole windows and cached, so a graph
+// refreshing over a range that moved by a few seconds reuses them.
+func (s *Server) aggregatedAPIResults(id int64, window int, start, end time.Time) ([]APIResult, error) {
+	if window <= 0 {
+		return s.computeAggregatedAPIResults(id, window, start, end)
+	}
+	step := time.Duration(window) * time.Second
+	key := resultsCacheKey{
+		targetID: id,
+		window:   window,
+		start:    start.Truncate(step).Unix(),
+		end:      end.Truncate(step).Add(step).Unix(),
+	}
+	latest, err := s.db.GetLastRollupTime(id, window)
+	if err != nil {
+		return nil, err
+	}
+	now := time.Now()
+	cached, ok := s.results.get(key, latest, now)
+	if !ok {
+		cached, err = s.computeAggregatedAPIResults(id, window, time.Unix(key.start, 0).UTC(), time.Unix(key.end, 0).UTC())
+		if err != nil {
+			return nil, err
+		}
+		s.results.put(key, cached, latest, now)
+	}
+
+	var apiResults []APIResult
+	for _, res := range cached {
+		if !res.Time.Before(start) && res.Time.Before(end) {
+			apiResults = append(apiResults, res)
+		}
+	}
+	return apiResults, nil
+}
+
+// computeAggregatedAPIResults reads a target's rollups of the given window in
+// [start, end) and computes their percentiles.
+func (s *Server) computeAggregatedAPIResults(id int64, window int, start, end time.Time) ([]APIResult, error) {
+	results, err := s.db.GetAggregatedResults(id, window, start, end)
+	if err != nil {
+		return nil, err
+	}
+
+	var apiResults []APIResult
+	for _, res := range results {
+		apiRes := APIResult{
+			Time:          res.Time,
+			TargetID:      res.TargetID,
+			TimeoutCount:  res.TimeoutCount,
+			ErrorCount:    res.ErrorCount,
+			ProbeCount:    0, // Will be populated from TDigest if available
+			AttemptCount:  res.Attempts(),
+			WindowSeconds: res.WindowSeconds,
+		}
+
+		if len(res.TDigestData) > 0 {
+			td, err := db.DeserializeTDigest(res.TDigestData)
+			if err == nil {
+				// Compute average from centroids
+				var totalMass, weightedSum float64
+				td.ForEachCentroid(func(mean float64, count uint64) bool {
+					totalMass += float64(count)
+					weightedSum += mean * float64(count)
+					return true
+				})
+				if totalMass > 0 {
+					apiRes.AvgNS = int64(weightedSum / totalMass)
+				}
+
+				apiRes.ProbeCount = int64(td.Count())
+				apiRes.P0 = sanitizeFloat(td.Quantile(0.0))
+				apiRes.P1 = sanitizeFloat(td.Quantile(0.01))
+				apiRes.P25 = sanitizeFloat(td.Quantile(0.25))
+				apiRes.P50 = sanitizeFloat(td.Quantile(0.5))
+				apiRes.P75 = sanitizeFloat(td.Quantile(0.75))
+				apiRes.P99 = sanitizeFloat(td.Quantile(0.99))
+				apiRes.P999 = sanitizeFloat(td.Quantile(0.999))
+				apiRes.P100 = sanitizeFloat(td.Quantile(1.0))
+
+				// Prefer the exact extremes over the TDigest's estimates when recorded
+				if res.MinNS != nil && res.MaxNS != nil {
+					apiRes.P0 = *res.MinNS
+					apiRes.P100 = *res.MaxNS
+				}
+				apiRes.MinNS = int64(apiRes.P0)
+				apiRes.MaxNS = int64(apiRes.P100)
+
+				// Calculate every 5th percentile
+				apiRes.Percentiles = make([]float64, 21)
+				for i := 0; i <= 20; i++ {
+					p := float64(i) * 0.05
+					apiRes.Percentiles[i] = sanitizeFloat(td.Quantile(p))
+				}
+				apiRes.Percentiles[0] = apiRes.P0
+				apiRes.Percentiles[20] = apiRes.P100
+			}
+		}
+		if res.JitterNS != nil {
+			apiRes.JitterNS = *res.JitterNS
+		}
+		apiResults = append(apiResults, apiRes)
+	}
+
+	return apiResults, nil
+}
+
+// handleExport streams a target's raw results for a time range as a CSV download
+// with columns time,target_id,latency_ns. Timeouts are written as "timeout" and
+// failed probes as "error".
+func (s *Server) handleExport(w http.ResponseWriter, r *http.Request) {
+	q := r.URL.Query()
+	id, err := strconv.ParseInt(q.Get("target_id"), 10, 64)
+	if err != nil {
+		http.Error(w, "Invalid target_id", http.StatusBadRequest)
+		return
+	}
+	if format := q.Get("format"); format != "" && format != "csv" {
+		http.Error(w, "Unsupported format: "+format, http.StatusBadRequest)
+		return
+	}
+
+	var start, end time.Time
+	if startStr, endStr := q.Get("start"), q.Get("end"); startStr != "" && endStr != "" {
+		start, err = time.Parse(time.RFC3339, startStr)
+		if err != nil {
+			http.Error(w, "Invalid start time", http.StatusBadRequest)
+			return
+		}
+		end, err = time.Parse(time.RFC3339, endStr)
+		if err != nil {
+			http.Error(w, "Invalid end time", http.StatusBadRequest)
+			return
+		}
+	} else {
+		// Default to the last hour, like /api/results
+		end = time.Now().UTC()
+		start = end.Add(-1 * time.Hour)
+	}
+
+	if _, err := s.db.GetTarget(id); err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+
+	w.Header().Set("Content-Type", "text/csv")
+	w.Header().Set("Content-Disposition", fmt.Sprintf(`attachment; filename="vaportrail-target-%d.csv"`, id))
+
+	cw := csv.NewWriter(w)
+	cw.Write([]string{"time", "target_id", "latency_ns"})
+	err = s.db.ForEachRawResult(id, start, end, func(rr db.RawResult) error {
+		var latency string
+		switch rr.Latency {
+		case db.LatencyTimeout:
+			latency = "timeout"
+		case db.LatencyError:
+			latency = "error"
+		default:
+			latency = strconv.FormatFloat(rr.Latency, 'f', -1, 64)
+		}
+		return cw.Write([]string{
+			rr.Time.UTC().Format(time.RFC3339Nano),
+			strconv.FormatInt(rr.TargetID, 10),
+			latency,
+		})
+	})
+	cw.Flush()
+	if err == nil {
+		err = cw.Error()
+	}
+	if err != nil {
+		// Headers are already sent, so the client just sees a truncated file
+		log.Printf("CSV export for target %d failed: %v", id, err)
+	}
+}
+
+// APIImport is the document accepted by POST /api/import. Targets are matched to
+// raw results by their ID in the document, which need not match the IDs they get.
+type APIImport struct {
+	Targets    []db.Target    `json:"targets"`
+	RawResults []db.RawResult `json:"raw_results"`
+}
+
+// APIImportReport counts what an import wrote.
+type APIImportReport struct {
+	TargetsCreated    int `json:"targets_created"`
+	TargetsExisting   int `json:"targets_existing"`
+	RawResultsAdded   int `json:"raw_results_added"`
+	RawResultsSkipped int `json:"raw_results_skipped"`
+}
+
+// handleImport restores targets and raw results, e.g. from a backup. A target whose
+// name already exists is reused rather than added again, and raw results already
+// stored for it at the same time are skipped, so importing the same document twice
+// doesn't duplicate anything. Every target is validated before anything is written.
+func (s *Server) handleImport(w http.ResponseWriter, r *http.Request) {
+	var doc APIImport
+	if err := json.NewDecoder(r.Body).Decode(&doc); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+
+	existing, err := s.db.GetTargets()
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	byName := make(map[string]db.Target, len(existing))
+	for _, t := range existing {
+		byName[t.Name] = t
+	}
+
+	seen := make(map[int64]bool, len(doc.Targets))
+	for i := range doc.Targets {
+		t := &doc.Targets[i]
+		if seen[t.ID] {
+			http.Error(w, fmt.Sprintf("Duplicate target ID %d", t.ID), http.StatusBadRequest)
+			return
+		}
+		seen[t.ID] = true
+		if err := prepareTarget(t); err != nil {
+			http.Error(w, fmt.Sprintf("Target %q: %v", t.Name, err), http.StatusBadRequest)
+			return
+		}
+	}
+	for _, rr := range doc.RawResults {
+		if !seen[rr.TargetID] {
+			http.Error(w, fmt.Sprintf("Raw result for unknown target ID %d", rr.TargetID), http.StatusBadRequest)
+			return
+		}
+	}
+	newNames := make(map[string]bool)
+	for _, t := range doc.Targets {
+		if _, ok := byName[t.Name]; !ok {
+			newNames[t.Name] = true
+		}
+	}
+	if err := s.checkTargetLimit(len(existing), len(newNames)); err != nil {
+		http.Error(w, err.Error(), http.StatusTooManyRequests)
+		return
+	}
+
+	var report APIImportReport
+	ids := make(map[int64]int64, len(doc.Targets)) // Document ID -> stored ID
+	var matched []int64
+	for _, t := range doc.Targets {
+		docID := t.ID
+		if current, ok := byName[t.Name]; ok {
+			ids[docID] = current.ID
+			matched = append(matched, current.ID)
+			report.TargetsExisting++
+			continue
+		}
+		t.ID = 0
+		id, err := s.db.AddTarget(&t)
+		if err != nil {
+			http.Error(w, fmt.Sprintf("Failed to add target %q: %v", t.Name, err), http.StatusInternalServerError)
+			return
+		}
+		t.ID = id
+		ids[docID] = id
+		byName[t.Name] = t
+		report.TargetsCreated++
+		if s.scheduler != nil {
+			s.scheduler.AddTarget(t)
+		}
+	}
+
+	results := make([]db.RawResult, 0, len(doc.RawResults))
+	for _, rr := range doc.RawResults {
+		rr.TargetID = ids[rr.TargetID]
+		results = append(results, rr)
+	}
+	for _, id := range matched {
+		results, err = s.skipStoredRawResults(id, results)
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusInternalServerError)
+			return
+		}
+	}
+	if err := s.db.AddRawResults(results); err != nil {
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	report.RawResultsAdded = len(results)
+	report.RawResultsSkipped = len(doc.RawResults) - len(results)
+
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(report)
+}
+
+// skipStoredRawResults drops the results for targetID whose time is already stored.
+func (s *Server) skipStoredRawResults(targetID int64, results []db.RawResult) ([]db.RawResult, error) {
+	var start, end time.Time
+	for _, rr := range results {
+		if rr.TargetID != targetID {
+			continue
+		}
+		if start.IsZero() || rr.Time.Before(start) {
+			start = rr.Time
+		}
+		if rr.Time.After(end) {
+			end = rr.Time
+		}
+	}
+	if start.IsZero() {
+		return results, nil
 	}
-}
 
-func (s *Server) handleGetTargets(w http.ResponseWriter, r *http.Request) {
-	targets, err := s.db.GetTargets()
+	stored := make(map[int64]bool)
+	err := s.db.ForEachRawResult(targetID, start, end.Add(time.Nanosecond), func(rr db.RawResult) error {
+		stored[rr.Time.UnixNano()] = true
+		return nil
+	})
 	if err != nil {
-		http.Error(w, err.Error(), http.StatusInternalServerError)
-		return
+		return nil, err
 	}
-	w.Header().Set("Content-Type", "application/json")
-	json.NewEncoder(w).Encode(targets)
+	kept := results[:0]
+	for _, rr := range results {
+		if rr.TargetID != targetID || !stored[rr.Time.UnixNano()] {
+			kept = append(kept, rr)
+		}
+	}
+	return kept, nil
 }
 
-type APIResult struct {
-	Time          time.Time
-	TargetID      int64
-	MinNS         int64
-	MaxNS         int64
-	AvgNS         int64
-	P0            float64
-	P1            float64
-	P25           float64
-	P50           float64
-	P75           float64
-	P99           float64
-	P100          float64
-	Percentiles   []float64 // 0th, 5th, 10th... 100th
-	TimeoutCount  int64
-	ProbeCount    int64
-	WindowSeconds int
+// APICentroid is one TDigest centroid: the mean latency of the samples it holds and
+// how many there are.
+type APICentroid struct {
+	MeanNS float64 `json:"mean_ns"`
+	Weight uint64  `json:"weight"`
 }
 
-func sanitizeFloat(f float64) float64 {
-	if math.IsNaN(f) || math.IsInf(f, 0) {
-		return 0.0
-	}
-	return f
+// APIHistogram is the latency distribution of one aggregated window.
+type APIHistogram struct {
+	Time          time.Time     `json:"time"`
+	WindowSeconds int           `json:"window_seconds"`
+	Centroids     []APICentroid `json:"centroids"`
 }
 
-func (s *Server) handleGetResults(w http.ResponseWriter, r *http.Request) {
-	idStr := chi.URLParam(r, "id")
-	id, err := strconv.ParseInt(idStr, 10, 64)
+// handleHistogram returns the TDigest centroids of each of a target's windows in a
+// time range, so clients can compute any quantile or plot the full distribution.
+func (s *Server) handleHistogram(w http.ResponseWriter, r *http.Request) {
+	q := r.URL.Query()
+	id, err := strconv.ParseInt(q.Get("target_id"), 10, 64)
 	if err != nil {
-		http.Error(w, "Invalid ID", http.StatusBadRequest)
+		http.Error(w, "Invalid target_id", http.StatusBadRequest)
 		return
 	}
-
-	startStr := r.URL.Query().Get("start")
-	endStr := r.URL.Query().Get("end")
-
-	var start, end time.Time
-	var window int
-	// Fetch target to get retention policies
-	target, err := s.db.GetTarget(id)
-	if err != nil {
-		// If target not found, we can't really determine policies.
-		// Return 404 or just fail? The ID validation passed int parsing but DB check might fail.
-		http.Error(w, "Target not found: "+err.Error(), http.StatusNotFound)
+	window, err := strconv.Atoi(q.Get("window"))
+	if err != nil || window <= 0 {
+		http.Error(w, "Invalid window", http.StatusBadRequest)
 		return
 	}
 
-	if startStr != "" && endStr != "" {
+	var start, end time.Time
+	if startStr, endStr := q.Get("start"), q.Get("end"); startStr != "" && endStr != "" {
 		start, err = time.Parse(time.RFC3339, startStr)
 		if err != nil {
 			http.Error(w, "Invalid start time", http.StatusBadRequest)
@@ -409,141 +1962,212 @@ func (s *Server) handleGetResults(w http.ResponseWriter, r *http.Request) {
 			return
 		}
 	} else {
-		// Default view (last hour)
+		// Default to the last hour, like /api/results
 		end = time.Now().UTC()
 		start = end.Add(-1 * time.Hour)
 	}
 
-	// Dynamic Window Selection
-	// Goal: < 1000 datapoints
-	durationSeconds := end.Sub(start).Seconds()
-	desiredWindow := max(int(durationSeconds/1000.0), 1)
+	if _, err := s.db.GetTarget(id); err != nil {
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
 
-	policies, err := scheduler.GetRetentionPolicies(*target)
+	results, err := s.db.GetAggregatedResults(id, window, start, end)
 	if err != nil {
-		http.Error(w, "Target has no retention policies configured", http.StatusInternalServerError)
+		http.Error(w, err.Error(), http.StatusInternalServerError)
 		return
 	}
 
-	// Collect available windows from policies (and 0 for raw if 0 exists)
-	// Actually policies usually define what we HAVE.
-	// We want to pick the smallest window >= desiredWindow
-	// availableWindows should include those defined in policies.
-
-	var availableWindows []int
-	for _, p := range policies {
-		if p.Window > 0 {
-			availableWindows = append(availableWindows, p.Window)
+	histograms := []APIHistogram{}
+	for _, res := range results {
+		h := APIHistogram{Time: res.Time, WindowSeconds: res.WindowSeconds, Centroids: []APICentroid{}}
+		if len(res.TDigestData) > 0 {
+			td, err := db.DeserializeTDigest(res.TDigestData)
+			if err != nil {
+				http.Error(w, fmt.Sprintf("Failed to read TDigest for window at %s: %v", res.Time.Format(time.RFC3339), err), http.StatusInternalServerError)
+				return
+			}
+			td.ForEachCentroid(func(mean float64, count uint64) bool {
+				h.Centroids = append(h.Centroids, APICentroid{MeanNS: mean, Weight: count})
+				return true
+			})
 		}
+		histograms = append(histograms, h)
 	}
-	sort.Ints(availableWindows)
 
-	// Pick best window
-	window = -1
-	for _, w := range availableWindows {
-		if w >= desiredWindow {
-			window = w
-			break
-		}
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(histograms)
+}
+
+// handleStream pushes each raw result to the client as a server-sent event once it
+// has been written. An optional ?target={id} restricts the stream to one target.
+func (s *Server) handleStream(w http.ResponseWriter, r *http.Request) {
+	if s.scheduler == nil {
+		http.Error(w, "Live results are not available", http.StatusServiceUnavailable)
+		return
 	}
 
-	// If no window found (all smaller than desired? Or desired is huge?)
-	// If window is still -1, it means desiredWindow > all available windows.
-	// Pick the largest one.
-	if window == -1 && len(availableWindows) > 0 {
-		window = availableWindows[len(availableWindows)-1]
+	var targetID int64
+	if v := r.URL.Query().Get("target"); v != "" {
+		id, err := strconv.ParseInt(v, 10, 64)
+		if err != nil {
+			http.Error(w, "Invalid target ID", http.StatusBadRequest)
+			return
+		}
+		targetID = id
 	}
 
-	// If we still don't have a window (e.g. policies empty?), default to 60
-	if window == -1 {
-		window = 60
+	results, cancel := s.scheduler.SubscribeResults(100)
+	defer cancel()
+
+	w.Header().Set("Content-Type", "text/event-stream")
+	w.Header().Set("Cache-Control", "no-cache")
+	w.Header().Set("Connection", "keep-alive")
+	w.WriteHeader(http.StatusOK)
+	rc := http.NewResponseController(w)
+	if err := rc.Flush(); err != nil {
+		return
 	}
 
-	var apiResults []APIResult
+	// Periodic comments keep proxies from closing an idle stream
+	keepalive := time.NewTicker(15 * time.Second)
+	defer keepalive.Stop()
 
-	if r.URL.Query().Get("raw") == "true" {
-		// User: "render the first 1000"
-		// Just pull 1000. DB query is ordered by time ASC, so this gives first 1000.
-		rawResults, err := s.db.GetRawResults(id, start, end, 1000)
-		if err != nil {
-			http.Error(w, "Failed to get raw results: "+err.Error(), http.StatusInternalServerError)
+	for {
+		select {
+		case <-r.Context().Done():
 			return
-		}
-		// No longer erroring on > 1000, just returning what we got (capped at 1000 by query limit)
-
-		for _, rr := range rawResults {
-			apiRes := APIResult{
-				Time:       rr.Time,
-				TargetID:   rr.TargetID,
-				ProbeCount: 1,
-				MinNS:      int64(rr.Latency),
-				MaxNS:      int64(rr.Latency),
-				AvgNS:      int64(rr.Latency), // Set Avg to latency for simple display usually
-				P0:         rr.Latency,
-				P100:       rr.Latency,
-				P50:        rr.Latency, // Median is the value itself
+		case <-s.shutdownCh:
+			return
+		case <-keepalive.C:
+			fmt.Fprint(w, ": keepalive\n\n")
+		case res, ok := <-results:
+			if !ok {
+				return // Scheduler stopped
+			}
+			if targetID != 0 && res.TargetID != targetID {
+				continue
+			}
+			data, err := json.Marshal(res)
+			if err != nil {
+				continue
 			}
-			apiResults = append(apiResults, apiRes)
+			fmt.Fprintf(w, "data: %s\n\n", data)
 		}
-		w.Header().Set("Content-Type", "application/json")
-		json.NewEncoder(w).Encode(apiResults)
+		if err := rc.Flush(); err != nil {
+			return
+		}
+	}
+}
+
+// wsSubscription is a message from a /ws client, changing which targets it follows.
+type wsSubscription struct {
+	Subscribe   []int64 `json:"subscribe"`
+	Unsubscribe []int64 `json:"unsubscribe"`
+}
+
+// handleWebSocket pushes raw results over a WebSocket like handleStream, but lets a
+// client change the targets it follows without reconnecting. The client sends
+// messages such as {"subscribe": [1, 2]} or {"unsubscribe": [2]}, each answered with
+// {"subscribed": [...]} listing every target it now follows, and receives each new
+// raw result of those targets in the same JSON as the event stream.
+func (s *Server) handleWebSocket(w http.ResponseWriter, r *http.Request) {
+	if s.scheduler == nil {
+		http.Error(w, "Live results are not available", http.StatusServiceUnavailable)
+		return
+	}
+	if !s.websocketOriginAllowed(r) {
+		http.Error(w, "Origin not allowed", http.StatusForbidden)
 		return
 	}
 
-	results, err := s.db.GetAggregatedResults(id, window, start, end)
+	ws, err := upgradeWebSocket(w, r)
 	if err != nil {
-		http.Error(w, err.Error(), http.StatusInternalServerError)
-		return
+		return // upgradeWebSocket has answered
 	}
+	results, cancel := s.scheduler.SubscribeResults(100)
+	defer cancel()
+	s.serveWebSocket(ws, results)
+}
 
-	for _, res := range results {
-		apiRes := APIResult{
-			Time:          res.Time,
-			TargetID:      res.TargetID,
-			TimeoutCount:  res.TimeoutCount,
-			ProbeCount:    0, // Will be populated from TDigest if available
-			WindowSeconds: res.WindowSeconds,
+// serveWebSocket runs a /ws connection until the client leaves, the server shuts
+// down or results is closed, then closes the connection.
+func (s *Server) serveWebSocket(ws *wsConn, results <-chan db.RawResult) {
+	requests := make(chan wsSubscription)
+	quit := make(chan struct{})
+	readerDone := make(chan struct{})
+	defer func() {
+		close(quit)
+		ws.Close()
+		<-readerDone
+	}()
+
+	go func() {
+		defer close(readerDone)
+		for {
+			msg, err := ws.readMessage()
+			if err != nil {
+				return
+			}
+			var sub wsSubscription
+			if err := json.Unmarshal(msg, &sub); err != nil {
+				ws.writeJSON(map[string]string{"error": "invalid message: " + err.Error()})
+				continue
+			}
+			select {
+			case requests <- sub:
+			case <-quit:
+				return
+			}
 		}
+	}()
 
-		if len(res.TDigestData) > 0 {
-			td, err := db.DeserializeTDigest(res.TDigestData)
-			if err == nil {
-				// Compute average from centroids
-				var totalMass, weightedSum float64
-				td.ForEachCentroid(func(mean float64, count uint64) bool {
-					totalMass += float64(count)
-					weightedSum += mean * float64(count)
-					return true
-				})
-				if totalMass > 0 {
-					apiRes.AvgNS = int64(weightedSum / totalMass)
-				}
-
-				apiRes.ProbeCount = int64(td.Count())
-				apiRes.P0 = sanitizeFloat(td.Quantile(0.0))
-				apiRes.P1 = sanitizeFloat(td.Quantile(0.01))
-				apiRes.P25 = sanitizeFloat(td.Quantile(0.25))
-				apiRes.P50 = sanitizeFloat(td.Quantile(0.5))
-				apiRes.P75 = sanitizeFloat(td.Quantile(0.75))
-				apiRes.P99 = sanitizeFloat(td.Quantile(0.99))
-				apiRes.P100 = sanitizeFloat(td.Quantile(1.0))
-
-				apiRes.MinNS = int64(apiRes.P0)
-				apiRes.MaxNS = int64(apiRes.P100)
+	// Pings keep proxies from closing an idle connection
+	keepalive := time.NewTicker(30 * time.Second)
+	defer keepalive.Stop()
 
-				// Calculate every 5th percentile
-				apiRes.Percentiles = make([]float64, 21)
-				for i := 0; i <= 20; i++ {
-					p := float64(i) * 0.05
-					apiRes.Percentiles[i] = sanitizeFloat(td.Quantile(p))
-				}
+	subscribed := make(map[int64]bool)
+	for {
+		var err error
+		select {
+		case <-readerDone:
+			return
+		case <-s.shutdownCh:
+			ws.closeWith(wsCloseGoingAway, "server shutting down")
+			return
+		case <-keepalive.C:
+			err = ws.writeFrame(wsPing, nil)
+		case sub := <-requests:
+			for _, id := range sub.Subscribe {
+				subscribed[id] = true
+			}
+			for _, id := range sub.Unsubscribe {
+				delete(subscribed, id)
+			}
+			ids := make([]int64, 0, len(subscribed))
+			for id := range subscribed {
+				ids = append(ids, id)
+			}
+			slices.Sort(ids)
+			err = ws.writeJSON(map[string][]int64{"subscribed": ids})
+		case res, ok := <-results:
+			if !ok {
+				ws.closeWith(wsCloseGoingAway, "scheduler stopped")
+				return
 			}
+			if !subscribed[res.TargetID] {
+				continue
+			}
+			err = ws.writeJSON(res)
+		}
+		if err != nil {
+			return
 		}
-		apiResults = append(apiResults, apiRes)
 	}
-
-	w.Header().Set("Content-Type", "application/json")
-	json.NewEncoder(w).Encode(apiResults)
 }
 
 func (s *Server) handleGraph(w http.ResponseWriter, r *http.Request) {
@@ -556,13 +2180,118 @@ func (s *Server) handleGraph(w http.ResponseWriter, r *http.Request) {
 
 	target, err := s.db.GetTarget(id)
 	if err != nil {
-		http.Error(w, "Target not found: "+err.Error(), http.StatusNotFound)
+		if errors.Is(err, sql.ErrNoRows) {
+			http.Error(w, "Target not found", http.StatusNotFound)
+			return
+		}
+		http.Error(w, err.Error(), http.StatusInternalServerError)
 		return
 	}
 
-	if err := s.templates.ExecuteTemplate(w, "graph.html", target); err != nil {
+	s.renderTemplate(w, "graph.html", target)
+}
+
+// handleMetrics exposes per-target latency data in the Prometheus text exposition format.
+// Everything is read from the database on each scrape, so no extra state is kept.
+// Scrapes can be split across Prometheus servers with the optional tag parameter and
+// the repeatable target parameter, which takes a target ID or name.
+func (s *Server) handleMetrics(w http.ResponseWriter, r *http.Request) {
+	query := r.URL.Query()
+	targets, _, err := s.db.QueryTargets(db.TargetQuery{Tag: query.Get("tag")})
+	if err != nil {
 		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+	if wanted := query["target"]; len(wanted) > 0 {
+		targets = slices.DeleteFunc(targets, func(t db.Target) bool {
+			return !slices.Contains(wanted, t.Name) && !slices.Contains(wanted, strconv.FormatInt(t.ID, 10))
+		})
+	}
+
+	var latency, timeouts, quantiles strings.Builder
+	for _, t := range targets {
+		labels := fmt.Sprintf(`target_id="%d",target_name="%s"`, t.ID, escapeMetricLabel(t.Name))
+
+		// p50/p99 and timeouts come from the newest bucket of the smallest rollup window
+		var p50 float64
+		haveDigest := false
+		if window := smallestRollupWindow(t); window > 0 {
+			total, err := s.db.GetTimeoutTotal(t.ID, window)
+			if err != nil {
+				http.Error(w, err.Error(), http.StatusInternalServerError)
+				return
+			}
+			fmt.Fprintf(&timeouts, "vaportrail_probe_timeouts_retained{%s} %d\n", labels, total)
+
+			latest, err := s.db.GetLatestAggregatedResult(t.ID, window)
+			if err != nil {
+				http.Error(w, err.Error(), http.StatusInternalServerError)
+				return
+			}
+			if latest != nil && len(latest.TDigestData) > 0 {
+				if td, err := db.DeserializeTDigest(latest.TDigestData); err == nil && td.Count() > 0 {
+					haveDigest = true
+					p50 = sanitizeFloat(td.Quantile(0.5))
+					for _, q := range []float64{0.5, 0.99} {
+						fmt.Fprintf(&quantiles, "vaportrail_probe_latency_window_seconds{%s,window_seconds=\"%d\",quantile=\"%s\"} %s\n",
+							labels, window, formatMetricValue(q), formatMetricValue(sanitizeFloat(td.Quantile(q))/1e9))
+					}
+				}
+			}
+		}
+
+		raw, err := s.db.GetLatestRawResult(t.ID)
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusInternalServerError)
+			return
+		}
+		switch {
+		case raw != nil:
+			fmt.Fprintf(&latency, "vaportrail_probe_latency_seconds{%s} %s\n", labels, formatMetricValue(raw.Latency/1e9))
+		case haveDigest:
+			fmt.Fprintf(&latency, "vaportrail_probe_latency_seconds{%s} %s\n", labels, formatMetricValue(p50/1e9))
+		}
+	}
+
+	w.Header().Set("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+	writeMetricFamily(w, "vaportrail_probe_latency_seconds", "gauge", "Most recent successful probe latency.", latency.String())
+	writeMetricFamily(w, "vaportrail_probe_timeouts_retained", "gauge", "Probe timeouts in the retained data of the smallest rollup window. Retention lowers it, so it is a gauge.", timeouts.String())
+	writeMetricFamily(w, "vaportrail_probe_latency_window_seconds", "gauge", "Latency quantiles of the newest bucket of the smallest rollup window.", quantiles.String())
+	if s.scheduler != nil {
+		stats := s.scheduler.WriteStats()
+		writeMetricFamily(w, "vaportrail_write_queue_depth", "gauge", "Probe results waiting for the batch writer.", fmt.Sprintf("vaportrail_write_queue_depth %d\n", stats.QueueDepth))
+		writeMetricFamily(w, "vaportrail_write_queue_capacity", "gauge", "Results the batch writer queue holds before probes wait.", fmt.Sprintf("vaportrail_write_queue_capacity %d\n", stats.QueueCapacity))
+		writeMetricFamily(w, "vaportrail_write_buffer_dropped_total", "counter", "Results dropped because the write buffer was full.", fmt.Sprintf("vaportrail_write_buffer_dropped_total %d\n", stats.Dropped))
+	}
+}
+
+func writeMetricFamily(w http.ResponseWriter, name, metricType, help, samples string) {
+	fmt.Fprintf(w, "# HELP %s %s\n# TYPE %s %s\n%s", name, help, name, metricType, samples)
+}
+
+// smallestRollupWindow returns the smallest non-raw retention window for a target, or 0 if it has none.
+func smallestRollupWindow(t db.Target) int {
+	policies, err := scheduler.GetRetentionPolicies(t)
+	if err != nil {
+		return 0
+	}
+	smallest := 0
+	for _, p := range policies {
+		if p.Window > 0 && (smallest == 0 || p.Window < smallest) {
+			smallest = p.Window
+		}
 	}
+	return smallest
+}
+
+var metricLabelEscaper = strings.NewReplacer(`\`, `\\`, `"`, `\"`, "\n", `\n`)
+
+func escapeMetricLabel(v string) string {
+	return metricLabelEscaper.Replace(v)
+}
+
+func formatMetricValue(v float64) string {
+	return strconv.FormatFloat(v, 'g', -1, 64)
 }
 
 type StatusPageTimings struct {
@@ -599,6 +2328,30 @@ func (s StatusPageData) DBSizeString() string {
 	return fmt.Sprintf("%.1f %ciB", float64(s.DBSize)/float64(div), "KMGTPE"[exp])
 }
 
+// handleHealthz is the liveness check: it succeeds whenever the server is serving.
+func (s *Server) handleHealthz(w http.ResponseWriter, r *http.Request) {
+	w.Write([]byte("ok\n"))
+}
+
+// handleReadyz is the readiness check: it succeeds once startup has finished, the
+// scheduler is running and the database answers queries.
+func (s *Server) handleReadyz(w http.ResponseWriter, r *http.Request) {
+	if !s.ready.Load() {
+		w.Header().Set("Retry-After", startupRetryAfter)
+		http.Error(w, "starting up", http.StatusServiceUnavailable)
+		return
+	}
+	if s.scheduler == nil || !s.scheduler.Running() {
+		http.Error(w, "scheduler not running", http.StatusServiceUnavailable)
+		return
+	}
+	if err := s.db.Ping(); err != nil {
+		http.Error(w, "database unavailable: "+err.Error(), http.StatusServiceUnavailable)
+		return
+	}
+	w.Write([]byte("ok\n"))
+}
+
 func (s *Server) handleStatus(w http.ResponseWriter, r *http.Request) {
 	data, err := s.statusPageData(nil)
 	if err != nil {
@@ -606,9 +2359,39 @@ func (s *Server) handleStatus(w http.ResponseWriter, r *http.Request) {
 		return
 	}
 
-	if err := s.templates.ExecuteTemplate(w, "status.html", data); err != nil {
+	s.renderTemplate(w, "status.html", data)
+}
+
+// APIStatus is the JSON form of the status page, for monitoring database growth.
+type APIStatus struct {
+	DBSizeBytes   int64            `json:"db_size_bytes"`
+	PageCount     int64            `json:"page_count"`
+	PageSize      int64            `json:"page_size"`
+	FreelistCount int64            `json:"freelist_count"`
+	TDigestStats  []db.TDigestStat `json:"tdigest_stats"`
+	RawStats      *db.RawStats     `json:"raw_stats"`
+}
+
+func (s *Server) handleAPIStatus(w http.ResponseWriter, r *http.Request) {
+	data, err := s.statusPageData(nil)
+	if err != nil {
 		http.Error(w, err.Error(), http.StatusInternalServerError)
+		return
+	}
+
+	tdStats := data.TDigestStats
+	if tdStats == nil {
+		tdStats = []db.TDigestStat{}
 	}
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(APIStatus{
+		DBSizeBytes:   data.DBSize,
+		PageCount:     data.PageCount,
+		PageSize:      data.PageSize,
+		FreelistCount: data.FreelistCount,
+		TDigestStats:  tdStats,
+		RawStats:      data.RawStats,
+	})
 }
 
 func (s *Server) handleStatusCleanupOrphanedData(w http.ResponseWriter, r *http.Request) {
@@ -640,9 +2423,69 @@ func (s *Server) handleStatusCleanupOrphanedData(w http.ResponseWriter, r *http.
 		}
 	}
 
-	if err := s.templates.ExecuteTemplate(w, "status.html", data); err != nil {
-		http.Error(w, err.Error(), http.StatusInternalServerError)
+	s.renderTemplate(w, "status.html", data)
+}
+
+// handleVacuum compacts the database file. It holds the write lock for as long as
+// the rewrite takes, so probe results queue up in the batch writer meanwhile.
+func (s *Server) handleVacuum(w http.ResponseWriter, r *http.Request) {
+	report, err := s.db.Vacuum()
+	if err != nil {
+		if isDatabaseBusyError(err) {
+			http.Error(w, "Database is busy; vacuum did not run. Try again in a moment.", http.StatusServiceUnavailable)
+			return
+		}
+		http.Error(w, "Failed to vacuum database: "+err.Error(), http.StatusInternalServerError)
+		return
+	}
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(report)
+}
+
+// handleIntegrityCheck reports whether SQLite finds the database file intact, e.g.
+// after a crash. The check reads the whole file and is abandoned if the client leaves.
+func (s *Server) handleIntegrityCheck(w http.ResponseWriter, r *http.Request) {
+	report, err := s.db.IntegrityCheck(r.Context())
+	if err != nil {
+		http.Error(w, "Failed to check database integrity: "+err.Error(), http.StatusInternalServerError)
+		return
+	}
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(report)
+}
+
+// handleFlush writes the raw results waiting in the scheduler's batch writer now,
+// so they can be queried without waiting for its next flush.
+func (s *Server) handleFlush(w http.ResponseWriter, r *http.Request) {
+	if s.scheduler == nil {
+		http.Error(w, "Scheduler is not available", http.StatusServiceUnavailable)
+		return
+	}
+	written, err := s.scheduler.Flush()
+	if err != nil {
+		if errors.Is(err, scheduler.ErrNotRunning) {
+			http.Error(w, "Scheduler is not running", http.StatusServiceUnavailable)
+			return
+		}
+		http.Error(w, "Failed to flush raw results: "+err.Error(), http.StatusInternalServerError)
+		return
+	}
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(map[string]int{"rows_written": written})
+}
+
+// handleReloadScheduler syncs the running probe loops with the targets table, the
+// same as SIGHUP. Use it after changing targets behind the API's back.
+func (s *Server) handleReloadScheduler(w http.ResponseWriter, r *http.Request) {
+	if s.scheduler == nil {
+		http.Error(w, "Scheduler is not available", http.StatusServiceUnavailable)
+		return
+	}
+	if err := s.scheduler.Reload(); err != nil {
+		http.Error(w, "Failed to reload targets: "+err.Error(), http.StatusInternalServerError)
+		return
 	}
+	w.WriteHeader(http.StatusNoContent)
 }
 
 func isDatabaseBusyError(err error) bool {
@@ -997,15 +2840,11 @@ func (s *Server) handleDeleteDashboardGraph(w http.ResponseWriter, r *http.Reque
 // Dashboard page handlers
 
 func (s *Server) handleDashboardCreatePage(w http.ResponseWriter, r *http.Request) {
-	if err := s.templates.ExecuteTemplate(w, "dashboard_create.html", nil); err != nil {
-		http.Error(w, err.Error(), http.StatusInternalServerError)
-	}
+	s.renderTemplate(w, "dashboard_create.html", nil)
 }
 
 func (s *Server) handleDashboardViewPage(w http.ResponseWriter, r *http.Request) {
-	if err := s.templates.ExecuteTemplate(w, "dashboard_view.html", nil); err != nil {
-		http.Error(w, err.Error(), http.StatusInternalServerError)
-	}
+	s.renderTemplate(w, "dashboard_view.html", nil)
 }
 
 // Public dashboard handlers
@@ -1023,9 +2862,7 @@ func (s *Server) handlePublicDashboard(w http.ResponseWriter, r *http.Request) {
 		return
 	}
 
-	if err := s.templates.ExecuteTemplate(w, "dashboard_public.html", dash); err != nil {
-		http.Error(w, err.Error(), http.StatusInternalServerError)
-	}
+	s.renderTemplate(w, "dashboard_public.html", dash)
 }
 
 func (s *Server) handleRegenerateDashboardSlug(w http.ResponseWriter, r *http.Request) {
@@ -1116,12 +2953,7 @@ func (s *Server) handlePublicDashboardResults(w http.ResponseWriter, r *http.Req
 		return
 	}
 
-	// Reuse existing results logic - pass the request to handleGetResults
-	// But we need to set the URL param for the ID
-	// Create a new request context with the target ID
-	rctx := chi.NewRouteContext()
-	rctx.URLParams.Add("id", targetIdStr)
-	r = r.WithContext(context.WithValue(r.Context(), chi.RouteCtxKey, rctx))
-
-	s.handleGetResults(w, r)
+	// Only the checked target is read; target_id parameters would name targets
+	// that aren't on the dashboard
+	s.serveResults(w, r, []int64{targetId})
 }
@@ -388,6 +388,7 @@ const VaporTrail = (function () {
                 content += `<hr style="border: 0; border-top: 1px solid #555; margin: 5px 0;">`;
                 content += `<div>Success: ${originalData.ProbeCount}</div>`;
                 content += `<div>Timeout: ${originalData.TimeoutCount}</div>`;
+                content += `<div>Error: ${originalData.ErrorCount || 0}</div>`;
 
                 tooltipEl.innerHTML = content;
             }
@@ -444,7 +445,7 @@ const VaporTrail = (function () {
                     content += `<div>  Max: ${(d.P100 / 1e6).toFixed(2)} ms</div>`;
                     content += `<div>  Median: ${(d.P50 / 1e6).toFixed(2)} ms</div>`;
                     content += `<div>  Min: ${(d.P0 / 1e6).toFixed(2)} ms</div>`;
-                    content += `<div>  Success: ${d.ProbeCount} | Timeout: ${d.TimeoutCount}</div>`;
+                    content += `<div>  Success: ${d.ProbeCount} | Timeout: ${d.TimeoutCount} | Error: ${d.ErrorCount || 0}</div>`;
                 }
 
                 tooltipEl.innerHTML = content;
@@ -754,7 +755,8 @@ const VaporTrail = (function () {
                             `  Median: ${(d.P50 / 1e6).toFixed(2)} ms`,
                             `  Min: ${(d.P0 / 1e6).toFixed(2)} ms`,
                             `  Success: ${d.ProbeCount}`,
-                            `  Timeout: ${d.TimeoutCount}`
+                            `  Timeout: ${d.TimeoutCount}`,
+                            `  Error: ${d.ErrorCount || 0}`
                         ];
                     }
                 }